@@ -4,12 +4,10 @@
 #[macro_use]
 extern crate libax;
 
-use core::str::FromStr;
-
 use libax::io::{self, prelude::*};
-use libax::net::{IpAddr, TcpStream};
+use libax::net::TcpStream;
 
-const DEST_IP: &str = "49.12.234.183"; // ident.me
+const DEST: &str = "ident.me:80";
 const REQUEST: &str = "\
 GET / HTTP/1.1\r\n\
 Host: ident.me\r\n\
@@ -17,8 +15,8 @@ Accept: */*\r\n\
 \r\n";
 
 fn client() -> io::Result {
-    let (addr, port) = (IpAddr::from_str(DEST_IP).unwrap(), 80);
-    let mut stream = TcpStream::connect((addr, port).into())?;
+    let mut stream = TcpStream::connect(DEST)?;
+    println!("connected to {}", stream.peer_addr()?);
     stream.write(REQUEST.as_bytes())?;
 
     let mut buf = [0; 1024];
@@ -0,0 +1,77 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate libax;
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use libax::io::{self, prelude::*};
+use libax::net::{TcpListener, TcpStream, UdpSocket};
+use libax::task;
+
+const TCP_ADDR: &str = "127.0.0.1:5555";
+const UDP_ADDR: &str = "127.0.0.1:5556";
+const TCP_ADDR_V6: &str = "[::1]:5557";
+const UDP_ADDR_V6: &str = "[::1]:5558";
+const MESSAGE: &[u8] = b"Hello, loopback!";
+
+static LISTENING: AtomicBool = AtomicBool::new(false);
+
+fn tcp_server(addr: &str) -> io::Result {
+    let mut listener = TcpListener::bind(addr)?;
+    LISTENING.store(true, Ordering::Release);
+    let (mut stream, addr) = listener.accept()?;
+    println!("TCP server: accepted {}", addr);
+
+    let mut buf = [0; 64];
+    let n = stream.read(&mut buf)?;
+    stream.write_all(&buf[..n])?;
+    Ok(())
+}
+
+fn test_tcp(addr: &'static str) -> io::Result {
+    LISTENING.store(false, Ordering::Release);
+    task::spawn(move || tcp_server(addr).expect("TCP server failed"));
+    while !LISTENING.load(Ordering::Acquire) {
+        task::yield_now();
+    }
+
+    let mut stream = TcpStream::connect(addr)?;
+    println!("TCP client: connected to {}", stream.peer_addr()?);
+    stream.write_all(MESSAGE)?;
+    let mut buf = [0; 64];
+    let n = stream.read(&mut buf)?;
+    assert_eq!(&buf[..n], MESSAGE);
+    Ok(())
+}
+
+fn test_udp(server_addr: &str, client_addr: &str) -> io::Result {
+    let server = UdpSocket::bind(server_addr)?;
+    let client = UdpSocket::bind(client_addr)?;
+    client.send_to(MESSAGE, server_addr)?;
+
+    let mut buf = [0; 64];
+    let (n, from) = server.recv_from(&mut buf)?;
+    assert_eq!(&buf[..n], MESSAGE);
+    assert_eq!(from, client.local_addr()?);
+    server.send_to(&buf[..n], from)?;
+
+    let (n, from) = client.recv_from(&mut buf)?;
+    assert_eq!(&buf[..n], MESSAGE);
+    assert_eq!(from, server.local_addr()?);
+    Ok(())
+}
+
+#[no_mangle]
+fn main() {
+    println!("Hello, loopback test!");
+    test_tcp(TCP_ADDR).expect("test TCP over loopback failed");
+    println!("TCP over loopback OK");
+    test_udp(UDP_ADDR, "127.0.0.1:0").expect("test UDP over loopback failed");
+    println!("UDP over loopback OK");
+    test_tcp(TCP_ADDR_V6).expect("test TCP over IPv6 loopback failed");
+    println!("TCP over IPv6 loopback OK");
+    test_udp(UDP_ADDR_V6, "[::1]:0").expect("test UDP over IPv6 loopback failed");
+    println!("UDP over IPv6 loopback OK");
+}
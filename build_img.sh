@@ -5,6 +5,9 @@ mkdir -p mnt
 sudo mount disk.img mnt
 # 此处生成的是初赛的测例
 sudo cp -r ./testcases/junior/* ./mnt/
+# 仓库中的用户态测例
+make -C ./testcases/unix CC=riscv64-linux-musl-gcc
+sudo cp ./testcases/unix/unix_socket ./mnt/
 sudo umount mnt
 rm -rf mnt
 sudo chmod 777 disk.img
\ No newline at end of file
@@ -34,10 +34,20 @@ pub enum AxError {
     BadAddress,
     /// Bad internal state.
     BadState,
+    /// The operation failed because the other end of a pipe or socket was closed.
+    BrokenPipe,
     /// The connection was refused by the remote server,
     ConnectionRefused,
+    /// The operation needs to move an entry from one filesystem to another.
+    CrossesDevices,
+    /// Waiting for the resource would deadlock, e.g. two processes waiting
+    /// for the record locks held by each other.
+    Deadlock,
     /// A non-empty directory was specified where an empty directory was expected.
     DirectoryNotEmpty,
+    /// The operation was started and goes on in the background, e.g. a
+    /// non-blocking connect.
+    InProgress,
     /// Data not valid for the operation were encountered.
     ///
     /// Unlike [`InvalidInput`], this typically means that the operation
@@ -55,6 +65,9 @@ pub enum AxError {
     Io,
     /// The filesystem object is, unexpectedly, a directory.
     IsADirectory,
+    /// A loop, or nesting that is too deep, was encountered, e.g. of symbolic
+    /// links or of epoll instances.
+    Loop,
     /// Not enough space/cannot allocate memory.
     NoMemory,
     /// A filesystem object is, unexpectedly, not a directory.
@@ -74,6 +87,9 @@ pub enum AxError {
     UnexpectedEof,
     /// This operation is unsupported or unimplemented.
     Unsupported,
+    /// The operation needs to block to complete, but the blocking operation was
+    /// requested to not occur.
+    WouldBlock,
     /// An error returned when an operation could not be completed because a
     /// call to `write()` returned [`Ok(0)`](Ok).
     WriteZero,
@@ -171,13 +187,18 @@ impl From<AxError> for LinuxError {
         use AxError::*;
         match e {
             AlreadyExists => LinuxError::EEXIST,
-            Again => LinuxError::EAGAIN,
+            Again | WouldBlock => LinuxError::EAGAIN,
             BadAddress | BadState => LinuxError::EFAULT,
+            BrokenPipe => LinuxError::EPIPE,
             ConnectionRefused => LinuxError::ECONNREFUSED,
+            CrossesDevices => LinuxError::EXDEV,
+            Deadlock => LinuxError::EDEADLK,
             DirectoryNotEmpty => LinuxError::ENOTEMPTY,
+            InProgress => LinuxError::EINPROGRESS,
             InvalidInput | InvalidData => LinuxError::EINVAL,
             Io => LinuxError::EIO,
             IsADirectory => LinuxError::EISDIR,
+            Loop => LinuxError::ELOOP,
             NoMemory => LinuxError::ENOMEM,
             NotADirectory => LinuxError::ENOTDIR,
             NotConnected => LinuxError::ENOTCONN,
@@ -1,4 +1,5 @@
 use alloc::collections::BTreeMap;
+use alloc::string::String;
 use alloc::sync::{Arc, Weak};
 use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType};
 use axfs_vfs::{VfsError, VfsResult};
@@ -9,7 +10,7 @@ use spin::RwLock;
 /// It implements [`axfs_vfs::VfsNodeOps`].
 pub struct DirNode {
     parent: RwLock<Weak<dyn VfsNodeOps>>,
-    children: RwLock<BTreeMap<&'static str, VfsNodeRef>>,
+    children: RwLock<BTreeMap<String, VfsNodeRef>>,
 }
 
 impl DirNode {
@@ -26,16 +27,32 @@ impl DirNode {
     }
 
     /// Create a subdirectory at this directory.
-    pub fn mkdir(self: &Arc<Self>, name: &'static str) -> Arc<Self> {
+    pub fn mkdir(self: &Arc<Self>, name: &str) -> Arc<Self> {
         let parent = self.clone() as VfsNodeRef;
         let node = Self::new(Some(&parent));
-        self.children.write().insert(name, node.clone());
+        self.children.write().insert(name.into(), node.clone());
         node
     }
 
     /// Add a node to this directory.
-    pub fn add(&self, name: &'static str, node: VfsNodeRef) {
-        self.children.write().insert(name, node);
+    pub fn add(&self, name: &str, node: VfsNodeRef) {
+        self.children.write().insert(name.into(), node);
+    }
+
+    /// Add a node to this directory unless one named `name` already exists.
+    /// Returns whether the node was added.
+    pub fn try_add(&self, name: &str, node: VfsNodeRef) -> bool {
+        let mut children = self.children.write();
+        if children.contains_key(name) {
+            return false;
+        }
+        children.insert(name.into(), node);
+        true
+    }
+
+    /// Detach the node named `name` from this directory, returns the removed node.
+    pub fn remove_child(&self, name: &str) -> Option<VfsNodeRef> {
+        self.children.write().remove(name)
     }
 }
 
@@ -8,6 +8,7 @@ extern crate alloc;
 
 mod dir;
 mod null;
+mod random;
 mod zero;
 
 #[cfg(test)]
@@ -15,6 +16,7 @@ mod tests;
 
 pub use self::dir::DirNode;
 pub use self::null::NullDev;
+pub use self::random::RandomDev;
 pub use self::zero::ZeroDev;
 
 use alloc::sync::Arc;
@@ -37,16 +39,27 @@ impl DeviceFileSystem {
     }
 
     /// Create a subdirectory at the root directory.
-    pub fn mkdir(&self, name: &'static str) -> Arc<DirNode> {
+    pub fn mkdir(&self, name: &str) -> Arc<DirNode> {
         self.root.mkdir(name)
     }
 
     /// Add a node to the root directory.
     ///
     /// The node must implement [`axfs_vfs::VfsNodeOps`], and be wrapped in [`Arc`].
-    pub fn add(&self, name: &'static str, node: VfsNodeRef) {
+    pub fn add(&self, name: &str, node: VfsNodeRef) {
         self.root.add(name, node);
     }
+
+    /// Add a node to the root directory unless one named `name` already
+    /// exists. Returns whether the node was added.
+    pub fn try_add(&self, name: &str, node: VfsNodeRef) -> bool {
+        self.root.try_add(name, node)
+    }
+
+    /// Remove a node from the root directory, returns the removed node.
+    pub fn remove_child(&self, name: &str) -> Option<VfsNodeRef> {
+        self.root.remove_child(name)
+    }
 }
 
 impl VfsOps for DeviceFileSystem {
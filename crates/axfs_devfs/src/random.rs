@@ -0,0 +1,164 @@
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
+use spin::Mutex;
+
+const CHACHA_BLOCK_SIZE: usize = 64;
+const CHACHA_CONSTANTS: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+
+/// A random device behaves like `/dev/random` and `/dev/urandom`.
+///
+/// The bytes are produced by a ChaCha20-based CSPRNG. The key is replaced
+/// after every read (fast key erasure), and data written to the device is
+/// mixed into the key as additional entropy.
+pub struct RandomDev {
+    rng: Mutex<ChaChaRng>,
+}
+
+struct ChaChaRng {
+    key: [u32; 8],
+    counter: u64,
+    buf: [u8; CHACHA_BLOCK_SIZE],
+    pos: usize,
+}
+
+impl RandomDev {
+    /// Create a new random device with the given 256-bit seed.
+    pub fn new(seed: [u8; 32]) -> Self {
+        Self {
+            rng: Mutex::new(ChaChaRng::new(seed)),
+        }
+    }
+
+    /// Fill `buf` with random bytes.
+    pub fn fill_bytes(&self, buf: &mut [u8]) {
+        self.rng.lock().fill_bytes(buf);
+    }
+
+    /// Mix `data` into the generator state.
+    pub fn add_entropy(&self, data: &[u8]) {
+        self.rng.lock().add_entropy(data);
+    }
+}
+
+impl VfsNodeOps for RandomDev {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::default_file(),
+            VfsNodeType::CharDevice,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.fill_bytes(buf);
+        Ok(buf.len())
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.add_entropy(buf);
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+impl ChaChaRng {
+    fn new(seed: [u8; 32]) -> Self {
+        let mut key = [0; 8];
+        for (k, chunk) in key.iter_mut().zip(seed.chunks_exact(4)) {
+            *k = u32::from_le_bytes(chunk.try_into().unwrap());
+        }
+        Self {
+            key,
+            counter: 0,
+            buf: [0; CHACHA_BLOCK_SIZE],
+            pos: CHACHA_BLOCK_SIZE,
+        }
+    }
+
+    fn next_block(&mut self) -> [u8; CHACHA_BLOCK_SIZE] {
+        let block = chacha20_block(&self.key, self.counter);
+        self.counter = self.counter.wrapping_add(1);
+        block
+    }
+
+    /// Replace the key with fresh keystream, so that earlier outputs cannot
+    /// be recovered from the current state.
+    fn rekey(&mut self, extra: &[u8]) {
+        let block = self.next_block();
+        for (i, k) in self.key.iter_mut().enumerate() {
+            let mut word = [0; 4];
+            word.copy_from_slice(&block[i * 4..i * 4 + 4]);
+            for (j, b) in word.iter_mut().enumerate() {
+                *b ^= extra.get(i * 4 + j).copied().unwrap_or(0);
+            }
+            *k = u32::from_le_bytes(word);
+        }
+        self.pos = CHACHA_BLOCK_SIZE;
+    }
+
+    fn fill_bytes(&mut self, buf: &mut [u8]) {
+        let mut filled = 0;
+        while filled < buf.len() {
+            if self.pos == CHACHA_BLOCK_SIZE {
+                self.buf = self.next_block();
+                self.pos = 0;
+            }
+            let n = (buf.len() - filled).min(CHACHA_BLOCK_SIZE - self.pos);
+            buf[filled..filled + n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+            self.pos += n;
+            filled += n;
+        }
+        self.rekey(&[]);
+    }
+
+    fn add_entropy(&mut self, data: &[u8]) {
+        for chunk in data.chunks(32) {
+            self.rekey(chunk);
+        }
+    }
+}
+
+#[inline]
+fn quarter_round(s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(16);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(12);
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(8);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(7);
+}
+
+/// The ChaCha20 block function with a 64-bit counter and a zero nonce.
+fn chacha20_block(key: &[u32; 8], counter: u64) -> [u8; CHACHA_BLOCK_SIZE] {
+    let mut init = [0u32; 16];
+    init[..4].copy_from_slice(&CHACHA_CONSTANTS);
+    init[4..12].copy_from_slice(key);
+    init[12] = counter as u32;
+    init[13] = (counter >> 32) as u32;
+
+    let mut s = init;
+    for _ in 0..10 {
+        quarter_round(&mut s, 0, 4, 8, 12);
+        quarter_round(&mut s, 1, 5, 9, 13);
+        quarter_round(&mut s, 2, 6, 10, 14);
+        quarter_round(&mut s, 3, 7, 11, 15);
+        quarter_round(&mut s, 0, 5, 10, 15);
+        quarter_round(&mut s, 1, 6, 11, 12);
+        quarter_round(&mut s, 2, 7, 8, 13);
+        quarter_round(&mut s, 3, 4, 9, 14);
+    }
+
+    let mut out = [0; CHACHA_BLOCK_SIZE];
+    for (i, word) in s.iter().enumerate() {
+        let word = word.wrapping_add(init[i]);
+        out[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+    }
+    out
+}
@@ -91,6 +91,54 @@ fn test_get_parent(devfs: &DeviceFileSystem) -> VfsResult {
     Ok(())
 }
 
+fn test_random_dev(devfs: &DeviceFileSystem) -> VfsResult {
+    const N: usize = 32;
+    let (mut buf1, mut buf2) = ([0; N], [0; N]);
+
+    let node = devfs.root_dir().lookup("urandom")?;
+    assert_eq!(node.get_attr()?.file_type(), VfsNodeType::CharDevice);
+    assert_eq!(node.get_attr()?.size(), 0);
+    assert_eq!(node.read_at(0, &mut buf1)?, N);
+    assert_eq!(node.read_at(0, &mut buf2)?, N);
+    assert_ne!(buf1, buf2);
+    assert_eq!(node.write_at(0, b"entropy")?, 7);
+
+    // ChaCha20 keystream of the all-zero key and nonce.
+    let mut buf = [0; N];
+    RandomDev::new([0; 32]).fill_bytes(&mut buf);
+    assert_eq!(
+        buf,
+        [
+            0x76, 0xb8, 0xe0, 0xad, 0xa0, 0xf1, 0x3d, 0x90, 0x40, 0x5d, 0x6a, 0xe5, 0x53, 0x86,
+            0xbd, 0x28, 0xbd, 0xd2, 0x19, 0xb8, 0xa0, 0x8d, 0xed, 0x1a, 0xa8, 0x36, 0xef, 0xcc,
+            0x8b, 0x77, 0x0d, 0xc7,
+        ]
+    );
+
+    Ok(())
+}
+
+fn test_add_remove(devfs: &DeviceFileSystem) -> VfsResult {
+    let root = devfs.root_dir();
+    assert_eq!(root.clone().lookup("vda").err(), Some(VfsError::NotFound));
+
+    devfs.add("vda", Arc::new(ZeroDev));
+    assert_eq!(
+        root.clone().lookup("vda")?.get_attr()?.file_type(),
+        VfsNodeType::CharDevice
+    );
+    // an existing node is not replaced
+    assert!(!devfs.try_add("vda", Arc::new(NullDev)));
+    assert_eq!(root.clone().lookup("vda")?.read_at(0, &mut [1])?, 1);
+    assert!(devfs.remove_child("vda").is_some());
+    assert!(devfs.remove_child("vda").is_none());
+    assert_eq!(root.clone().lookup("vda").err(), Some(VfsError::NotFound));
+    assert!(devfs.try_add("vda", Arc::new(NullDev)));
+    assert!(devfs.remove_child("vda").is_some());
+
+    Ok(())
+}
+
 #[test]
 fn test_devfs() {
     // .
@@ -112,4 +160,8 @@ fn test_devfs() {
 
     test_devfs_ops(&devfs).unwrap();
     test_get_parent(&devfs).unwrap();
+    test_add_remove(&devfs).unwrap();
+
+    devfs.add("urandom", Arc::new(RandomDev::new([0xa5; 32])));
+    test_random_dev(&devfs).unwrap();
 }
@@ -0,0 +1,122 @@
+//! Unpacking of `newc` format cpio archives, as used by Linux initramfs.
+//!
+//! Each entry is a 110-byte ASCII header, the NUL-terminated path name and
+//! the file data, with the header plus name and the data each padded to a
+//! multiple of 4 bytes. The archive ends with an entry named `TRAILER!!!`.
+
+use alloc::sync::Arc;
+use axfs_vfs::{VfsError, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsResult};
+
+use crate::DirNode;
+
+const HEADER_LEN: usize = 110;
+const MAGIC_NEWC: &[u8] = b"070701";
+/// The `newc` variant with checksums, the checksum is not verified.
+const MAGIC_NEWC_CRC: &[u8] = b"070702";
+const TRAILER: &str = "TRAILER!!!";
+
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+
+const fn align4(x: usize) -> usize {
+    (x + 3) & !3
+}
+
+/// Parses the `index`-th 8-digit hexadecimal field of the header.
+fn header_field(header: &[u8], index: usize) -> VfsResult<u32> {
+    let start = 6 + index * 8;
+    let digits =
+        core::str::from_utf8(&header[start..start + 8]).map_err(|_| VfsError::InvalidData)?;
+    u32::from_str_radix(digits, 16).map_err(|_| VfsError::InvalidData)
+}
+
+/// Creates the directory `path` and all of its missing ancestors.
+fn create_dir_all(root: &Arc<DirNode>, path: &str) -> VfsResult {
+    let ancestors = path.match_indices('/').map(|(n, _)| &path[..n]);
+    for dir in ancestors.chain(core::iter::once(path)) {
+        if dir.is_empty() {
+            continue;
+        }
+        match root.clone().lookup(dir) {
+            Ok(node) if node.get_attr()?.is_dir() => {}
+            Ok(_) => return Err(VfsError::NotADirectory),
+            Err(VfsError::NotFound) => root.create(dir, VfsNodeType::Dir)?,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Unpacks `archive` into the directory `root`, returns the number of files
+/// and directories unpacked.
+///
+/// The permission mode and owner of each entry are kept. Missing parent
+/// directories are created with the default permission. Entries other than regular files
+/// and directories (symbolic links, device nodes and so on) are skipped, and
+/// an existing file is overwritten by a later entry with the same name.
+pub(crate) fn unpack(root: &Arc<DirNode>, archive: &[u8]) -> VfsResult<usize> {
+    let mut pos = 0;
+    let mut count = 0;
+    loop {
+        let header = archive
+            .get(pos..pos + HEADER_LEN)
+            .ok_or(VfsError::UnexpectedEof)?;
+        if &header[..6] != MAGIC_NEWC && &header[..6] != MAGIC_NEWC_CRC {
+            return Err(VfsError::InvalidData);
+        }
+        let mode = header_field(header, 1)?;
+        let uid = header_field(header, 2)?;
+        let gid = header_field(header, 3)?;
+        let file_size = header_field(header, 6)? as usize;
+        let name_size = header_field(header, 11)? as usize;
+
+        let name_start = pos + HEADER_LEN;
+        let name = archive
+            .get(name_start..name_start + name_size)
+            .ok_or(VfsError::UnexpectedEof)?;
+        let name = core::str::from_utf8(name.strip_suffix(b"\0").unwrap_or(name))
+            .map_err(|_| VfsError::InvalidData)?;
+        let data_start = align4(name_start + name_size);
+        let data = archive
+            .get(data_start..data_start + file_size)
+            .ok_or(VfsError::UnexpectedEof)?;
+        pos = align4(data_start + file_size);
+
+        if name == TRAILER {
+            return Ok(count);
+        }
+        let path = name.trim_start_matches("./").trim_matches('/');
+        if path.is_empty() || path == "." {
+            continue;
+        }
+        let (parent, _) = path.rsplit_once('/').unwrap_or(("", path));
+        let node: VfsNodeRef = match mode & S_IFMT {
+            S_IFDIR => {
+                create_dir_all(root, path)?;
+                root.clone().lookup(path)?
+            }
+            S_IFREG => {
+                create_dir_all(root, parent)?;
+                let node = match root.clone().lookup(path) {
+                    Ok(node) => node,
+                    Err(VfsError::NotFound) => {
+                        root.create(path, VfsNodeType::File)?;
+                        root.clone().lookup(path)?
+                    }
+                    Err(e) => return Err(e),
+                };
+                node.truncate(0)?;
+                node.write_at(0, data)?;
+                node
+            }
+            _ => {
+                log::warn!("cpio: skip {} with unsupported mode {:#o}", path, mode);
+                continue;
+            }
+        };
+        node.set_perm(VfsNodePerm::from_bits_truncate((mode & 0o7777) as u16))?;
+        node.set_owner(Some(uid), Some(gid))?;
+        count += 1;
+    }
+}
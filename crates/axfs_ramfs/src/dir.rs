@@ -2,7 +2,7 @@ use alloc::collections::BTreeMap;
 use alloc::sync::{Arc, Weak};
 use alloc::{string::String, vec::Vec};
 
-use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType};
+use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType};
 use axfs_vfs::{VfsError, VfsResult};
 use spin::RwLock;
 
@@ -15,6 +15,9 @@ pub struct DirNode {
     this: Weak<DirNode>,
     parent: RwLock<Weak<dyn VfsNodeOps>>,
     children: RwLock<BTreeMap<String, VfsNodeRef>>,
+    perm: RwLock<VfsNodePerm>,
+    /// User and group IDs of the owner.
+    owner: RwLock<(u32, u32)>,
 }
 
 impl DirNode {
@@ -23,6 +26,8 @@ impl DirNode {
             this: this.clone(),
             parent: RwLock::new(parent.unwrap_or_else(|| Weak::<Self>::new())),
             children: RwLock::new(BTreeMap::new()),
+            perm: RwLock::new(VfsNodePerm::default_dir()),
+            owner: RwLock::new((0, 0)),
         })
     }
 
@@ -67,11 +72,58 @@ impl DirNode {
         children.remove(name);
         Ok(())
     }
+
+    /// Returns the parent directory of `path` and the last component of it.
+    fn lookup_parent(&self, path: &str) -> VfsResult<(Arc<DirNode>, String)> {
+        let path = path.trim_end_matches('/');
+        let (parent, name) = match path.rfind('/') {
+            Some(n) => (&path[..n], &path[n + 1..]),
+            None => ("", path),
+        };
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(VfsError::InvalidInput);
+        }
+        let this = self.this.upgrade().ok_or(VfsError::NotFound)?;
+        let parent = this.lookup(parent)?;
+        let parent = parent
+            .as_any()
+            .downcast_ref::<DirNode>()
+            .ok_or(VfsError::NotADirectory)?
+            .this
+            .upgrade()
+            .ok_or(VfsError::NotFound)?;
+        Ok((parent, name.into()))
+    }
+
+    /// Checks whether `node` is this directory or one of its ancestors.
+    fn is_descendant_of(&self, node: &VfsNodeRef) -> bool {
+        let target = Arc::as_ptr(node) as *const ();
+        let mut cur = self.this.upgrade().map(|dir| dir as VfsNodeRef);
+        while let Some(dir) = cur {
+            if Arc::as_ptr(&dir) as *const () == target {
+                return true;
+            }
+            cur = dir.parent();
+        }
+        false
+    }
 }
 
 impl VfsNodeOps for DirNode {
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        Ok(VfsNodeAttr::new_dir(4096, 0))
+        let (uid, gid) = *self.owner.read();
+        Ok(VfsNodeAttr::new(*self.perm.read(), VfsNodeType::Dir, 4096, 0).with_owner(uid, gid))
+    }
+
+    fn set_perm(&self, perm: VfsNodePerm) -> VfsResult {
+        *self.perm.write() = perm;
+        Ok(())
+    }
+
+    fn set_owner(&self, uid: Option<u32>, gid: Option<u32>) -> VfsResult {
+        let mut owner = self.owner.write();
+        *owner = (uid.unwrap_or(owner.0), gid.unwrap_or(owner.1));
+        Ok(())
     }
 
     fn parent(&self) -> Option<VfsNodeRef> {
@@ -165,6 +217,40 @@ impl VfsNodeOps for DirNode {
         }
     }
 
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        log::debug!("rename at ramfs: {} -> {}", src_path, dst_path);
+        let (src_dir, src_name) = self.lookup_parent(src_path)?;
+        let (dst_dir, dst_name) = self.lookup_parent(dst_path)?;
+        let node = src_dir
+            .children
+            .read()
+            .get(&src_name)
+            .cloned()
+            .ok_or(VfsError::NotFound)?;
+        let is_dir = node.get_attr()?.is_dir();
+        if is_dir && dst_dir.is_descendant_of(&node) {
+            return Err(VfsError::InvalidInput);
+        }
+        let old = dst_dir.children.read().get(&dst_name).cloned();
+        if let Some(old) = old {
+            if Arc::ptr_eq(&old, &node) {
+                return Ok(());
+            }
+            match (is_dir, old.get_attr()?.is_dir()) {
+                (true, false) => return Err(VfsError::NotADirectory),
+                (false, true) => return Err(VfsError::IsADirectory),
+                _ => {}
+            }
+            dst_dir.remove_node(&dst_name)?;
+        }
+        src_dir.children.write().remove(&src_name);
+        if let Some(dir) = node.as_any().downcast_ref::<DirNode>() {
+            dir.set_parent(Some(&(dst_dir.clone() as VfsNodeRef)));
+        }
+        dst_dir.children.write().insert(dst_name, node);
+        Ok(())
+    }
+
     axfs_vfs::impl_vfs_dir_default! {}
 }
 
@@ -1,5 +1,7 @@
 use alloc::vec::Vec;
-use axfs_vfs::{impl_vfs_non_dir_default, VfsNodeAttr, VfsNodeOps, VfsResult};
+use axfs_vfs::{
+    impl_vfs_non_dir_default, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult,
+};
 use spin::RwLock;
 
 /// The file node in the RAM filesystem.
@@ -7,19 +9,37 @@ use spin::RwLock;
 /// It implements [`axfs_vfs::VfsNodeOps`].
 pub struct FileNode {
     content: RwLock<Vec<u8>>,
+    perm: RwLock<VfsNodePerm>,
+    /// User and group IDs of the owner.
+    owner: RwLock<(u32, u32)>,
 }
 
 impl FileNode {
     pub(super) const fn new() -> Self {
         Self {
             content: RwLock::new(Vec::new()),
+            perm: RwLock::new(VfsNodePerm::default_file()),
+            owner: RwLock::new((0, 0)),
         }
     }
 }
 
 impl VfsNodeOps for FileNode {
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        Ok(VfsNodeAttr::new_file(self.content.read().len() as _, 0))
+        let (uid, gid) = *self.owner.read();
+        let size = self.content.read().len() as _;
+        Ok(VfsNodeAttr::new(*self.perm.read(), VfsNodeType::File, size, 0).with_owner(uid, gid))
+    }
+
+    fn set_perm(&self, perm: VfsNodePerm) -> VfsResult {
+        *self.perm.write() = perm;
+        Ok(())
+    }
+
+    fn set_owner(&self, uid: Option<u32>, gid: Option<u32>) -> VfsResult {
+        let mut owner = self.owner.write();
+        *owner = (uid.unwrap_or(owner.0), gid.unwrap_or(owner.1));
+        Ok(())
     }
 
     fn truncate(&self, size: u64) -> VfsResult {
@@ -6,6 +6,7 @@
 
 extern crate alloc;
 
+mod cpio;
 mod dir;
 mod file;
 
@@ -38,6 +39,15 @@ impl RamFileSystem {
     pub fn root_dir_node(&self) -> Arc<DirNode> {
         self.root.clone()
     }
+
+    /// Unpacks a `newc` format cpio archive (e.g. an initramfs) into the
+    /// filesystem, returns the number of files and directories unpacked.
+    ///
+    /// Only regular files and directories are supported, other entries are
+    /// skipped.
+    pub fn unpack_cpio(&self, archive: &[u8]) -> VfsResult<usize> {
+        cpio::unpack(&self.root, archive)
+    }
 }
 
 impl VfsOps for RamFileSystem {
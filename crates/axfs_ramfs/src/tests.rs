@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use axfs_vfs::{VfsError, VfsNodeType, VfsResult};
+use axfs_vfs::{VfsError, VfsNodePerm, VfsNodeType, VfsResult};
 
 use crate::*;
 
@@ -88,6 +88,47 @@ fn test_get_parent(devfs: &RamFileSystem) -> VfsResult {
     Ok(())
 }
 
+fn test_rename(devfs: &RamFileSystem) -> VfsResult {
+    let root = devfs.root_dir();
+    let f1 = root.clone().lookup("f1")?;
+    root.rename("f1", "foo/f5")?;
+    assert_eq!(root.clone().lookup("f1").err(), Some(VfsError::NotFound));
+    assert!(Arc::ptr_eq(&root.clone().lookup("foo/f5")?, &f1));
+
+    // replace an existing file
+    root.rename("foo/f5", "f2")?;
+    assert!(Arc::ptr_eq(&root.clone().lookup("f2")?, &f1));
+    assert_eq!(
+        root.rename("f2", "foo/bar").err(),
+        Some(VfsError::IsADirectory)
+    );
+    assert_eq!(
+        root.rename("foo/bar", "f2").err(),
+        Some(VfsError::NotADirectory)
+    );
+    assert_eq!(
+        root.rename("foo", "foo/bar/baz").err(),
+        Some(VfsError::InvalidInput)
+    );
+    assert_eq!(
+        root.rename("nothing", "f1").err(),
+        Some(VfsError::NotFound)
+    );
+
+    // move a directory and check its parent
+    root.rename("foo/bar", "baz")?;
+    let baz = root.clone().lookup("baz")?;
+    assert!(Arc::ptr_eq(&baz.parent().unwrap(), &root.clone().lookup(".")?));
+    assert!(baz.clone().lookup("f4").is_ok());
+    root.rename("baz", "foo/bar")?;
+    assert!(Arc::ptr_eq(&root.clone().lookup("foo/bar")?, &baz));
+    assert!(Arc::ptr_eq(&baz.parent().unwrap(), &root.clone().lookup("foo")?));
+
+    root.rename("f2", "f1")?;
+    root.create("f2", VfsNodeType::File)?;
+    Ok(())
+}
+
 #[test]
 fn test_ramfs() {
     // .
@@ -117,6 +158,7 @@ fn test_ramfs() {
 
     test_ramfs_ops(&ramfs).unwrap();
     test_get_parent(&ramfs).unwrap();
+    test_rename(&ramfs).unwrap();
 
     let root = ramfs.root_dir();
     assert_eq!(root.remove("f1"), Ok(()));
@@ -134,3 +176,76 @@ fn test_ramfs() {
     assert_eq!(root.remove("./foo"), Ok(()));
     assert!(ramfs.root_dir_node().get_entries().is_empty());
 }
+
+#[test]
+fn test_perm_and_owner() {
+    let ramfs = RamFileSystem::new();
+    let root = ramfs.root_dir();
+    root.create("dir/file", VfsNodeType::File).unwrap_err();
+    root.create("dir", VfsNodeType::Dir).unwrap();
+    root.create("dir/file", VfsNodeType::File).unwrap();
+    let dir = root.clone().lookup("dir").unwrap();
+    let file = root.clone().lookup("dir/file").unwrap();
+
+    let attr = file.get_attr().unwrap();
+    assert_eq!((attr.perm().bits(), attr.uid(), attr.gid()), (0o666, 0, 0));
+    assert_eq!(dir.get_attr().unwrap().perm().bits(), 0o755);
+
+    file.set_perm(VfsNodePerm::from_bits_truncate(0o4750)).unwrap();
+    file.set_owner(Some(1000), Some(100)).unwrap();
+    // only change the group
+    dir.set_owner(None, Some(100)).unwrap();
+    let attr = file.get_attr().unwrap();
+    assert_eq!((attr.perm().bits(), attr.uid(), attr.gid()), (0o4750, 1000, 100));
+    assert!(attr.perm().contains(VfsNodePerm::SET_UID));
+    let attr = dir.get_attr().unwrap();
+    assert_eq!((attr.uid(), attr.gid()), (0, 100));
+}
+
+/// Appends a `newc` cpio entry to `archive`.
+fn push_cpio_entry(archive: &mut Vec<u8>, name: &str, mode: u32, data: &[u8]) {
+    let fields = [0, mode, 0, 0, 1, 0, data.len() as u32, 0, 0, 0, 0, name.len() as u32 + 1, 0];
+    archive.extend_from_slice(b"070701");
+    for field in fields {
+        archive.extend_from_slice(format!("{:08X}", field).as_bytes());
+    }
+    archive.extend_from_slice(name.as_bytes());
+    archive.push(0);
+    archive.resize((archive.len() + 3) & !3, 0);
+    archive.extend_from_slice(data);
+    archive.resize((archive.len() + 3) & !3, 0);
+}
+
+#[test]
+fn test_unpack_cpio() {
+    let mut archive = Vec::new();
+    push_cpio_entry(&mut archive, ".", 0o040755, b"");
+    push_cpio_entry(&mut archive, "bin", 0o040755, b"");
+    push_cpio_entry(&mut archive, "bin/init", 0o100755, b"\x7fELF");
+    // the parent directories of this file are not in the archive
+    push_cpio_entry(&mut archive, "./etc/conf.d/hostname", 0o100644, b"arceos\n");
+    push_cpio_entry(&mut archive, "bin/sh", 0o120777, b"init");
+    push_cpio_entry(&mut archive, "TRAILER!!!", 0, b"");
+
+    let ramfs = RamFileSystem::new();
+    assert_eq!(ramfs.unpack_cpio(&archive), Ok(3));
+    let root = ramfs.root_dir();
+    let mut buf = [0; 16];
+    let init = root.clone().lookup("bin/init").unwrap();
+    assert_eq!(init.read_at(0, &mut buf), Ok(4));
+    assert_eq!(&buf[..4], b"\x7fELF");
+    let hostname = root.clone().lookup("/etc/conf.d/hostname").unwrap();
+    assert_eq!(hostname.get_attr().unwrap().size(), 7);
+    assert_eq!(init.get_attr().unwrap().perm().bits(), 0o755);
+    assert_eq!(hostname.get_attr().unwrap().perm().bits(), 0o644);
+    assert!(root.clone().lookup("etc").unwrap().get_attr().unwrap().is_dir());
+    // symbolic links are not supported
+    assert_eq!(root.clone().lookup("bin/sh").err(), Some(VfsError::NotFound));
+
+    // a truncated archive
+    archive.truncate(archive.len() - 8);
+    assert_eq!(
+        RamFileSystem::new().unpack_cpio(&archive),
+        Err(VfsError::UnexpectedEof)
+    );
+}
@@ -10,6 +10,7 @@
 //!
 //! - [`mount()`](VfsOps::mount): Do something when the filesystem is mounted.
 //! - [`umount()`](VfsOps::umount): Do something when the filesystem is unmounted.
+//! - [`sync()`](VfsOps::sync): Write back cached data to the storage.
 //! - [`format()`](VfsOps::format): Format the filesystem.
 //! - [`statfs()`](VfsOps::statfs): Get the attributes of the filesystem.
 //! - [`root_dir()`](VfsOps::root_dir): Get root directory of the filesystem.
@@ -26,10 +27,12 @@
 //! | [`write_at()`](VfsNodeOps::write_at) | Write data to the file | file |
 //! | [`fsync()`](VfsNodeOps::fsync) | Synchronize the file data to disk | file |
 //! | [`truncate()`](VfsNodeOps::truncate) | Truncate the file | file |
+//! | [`set_times()`](VfsNodeOps::set_times) | Set the access and modification times | file |
 //! | [`parent()`](VfsNodeOps::parent) | Get the parent directory | directory |
 //! | [`lookup()`](VfsNodeOps::lookup) | Lookup the node with the given path | directory |
 //! | [`create()`](VfsNodeOps::create) | Create a new node with the given path | directory |
 //! | [`remove()`](VfsNodeOps::remove) | Remove the node with the given path | directory |
+//! | [`rename()`](VfsNodeOps::rename) | Rename or move the node with the given path | directory |
 //! | [`read_dir()`](VfsNodeOps::read_dir) | Read directory entries | directory |
 //!
 //! [inodes]: https://en.wikipedia.org/wiki/Inode
@@ -44,6 +47,7 @@ mod structs;
 pub mod path;
 
 use alloc::sync::Arc;
+use core::time::Duration;
 use axerrno::{ax_err, AxError, AxResult};
 
 pub use self::structs::{FileSystemInfo, VfsDirEntry, VfsNodeAttr, VfsNodePerm, VfsNodeType};
@@ -69,6 +73,11 @@ pub trait VfsOps: Send + Sync {
         Ok(())
     }
 
+    /// Write back all cached data of the filesystem to the storage.
+    fn sync(&self) -> VfsResult {
+        Ok(())
+    }
+
     /// Format the filesystem.
     fn format(&self) -> VfsResult {
         ax_err!(Unsupported)
@@ -122,6 +131,26 @@ pub trait VfsNodeOps: Send + Sync {
         ax_err!(InvalidInput)
     }
 
+    /// Set the last access and modification times of the file, as durations
+    /// since the Unix epoch.
+    ///
+    /// A `None` leaves the corresponding time unchanged.
+    fn set_times(&self, _atime: Option<Duration>, _mtime: Option<Duration>) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
+    /// Set the permission mode of the node.
+    fn set_perm(&self, _perm: VfsNodePerm) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
+    /// Set the user and group IDs of the owner of the node.
+    ///
+    /// A `None` leaves the corresponding ID unchanged.
+    fn set_owner(&self, _uid: Option<u32>, _gid: Option<u32>) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
     // directory operations:
 
     /// Get the parent directory of this directory.
@@ -150,6 +179,16 @@ pub trait VfsNodeOps: Send + Sync {
         ax_err!(Unsupported)
     }
 
+    /// Rename the node at `src_path` to `dst_path`, both relative to this
+    /// directory.
+    ///
+    /// If `dst_path` already exists, it is replaced: a file can only replace a
+    /// file, and a directory can only replace an empty directory. Moving a directory into its own
+    /// subtree fails with [`InvalidInput`](AxError::InvalidInput).
+    fn rename(&self, _src_path: &str, _dst_path: &str) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
     /// Read directory entries into `dirents`, starting from `start_idx`.
     fn read_dir(&self, _start_idx: usize, _dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
         ax_err!(Unsupported)
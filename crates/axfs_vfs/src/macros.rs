@@ -50,6 +50,10 @@ macro_rules! impl_vfs_non_dir_default {
             $crate::__priv::ax_err!(NotADirectory)
         }
 
+        fn rename(&self, _src_path: &str, _dst_path: &str) -> $crate::VfsResult {
+            $crate::__priv::ax_err!(NotADirectory)
+        }
+
         fn read_dir(
             &self,
             _start_idx: usize,
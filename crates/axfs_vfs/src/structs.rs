@@ -1,3 +1,5 @@
+use core::time::Duration;
+
 /// Filesystem attributes.
 ///
 /// Currently not used.
@@ -10,12 +12,22 @@ pub struct FileSystemInfo;
 pub struct VfsNodeAttr {
     /// File permission mode.
     mode: VfsNodePerm,
+    /// User ID of the owner.
+    uid: u32,
+    /// Group ID of the owner.
+    gid: u32,
     /// File type.
     ty: VfsNodeType,
     /// Total size, in bytes.
     size: u64,
     /// Number of 512B blocks allocated.
     blocks: u64,
+    /// Time of last access, since the Unix epoch.
+    atime: Duration,
+    /// Time of last modification, since the Unix epoch.
+    mtime: Duration,
+    /// Time of last status change, since the Unix epoch.
+    ctime: Duration,
 }
 
 bitflags::bitflags! {
@@ -42,6 +54,14 @@ bitflags::bitflags! {
         const OTHER_WRITE = 0o2;
         /// Others have execute permission.
         const OTHER_EXEC = 0o1;
+
+        /// Set user ID on execution.
+        const SET_UID = 0o4000;
+        /// Set group ID on execution.
+        const SET_GID = 0o2000;
+        /// Restricted deletion in a directory: only the owners of an entry
+        /// or of the directory can remove the entry.
+        const STICKY = 0o1000;
     }
 }
 
@@ -171,9 +191,14 @@ impl VfsNodeAttr {
     pub const fn new(mode: VfsNodePerm, ty: VfsNodeType, size: u64, blocks: u64) -> Self {
         Self {
             mode,
+            uid: 0,
+            gid: 0,
             ty,
             size,
             blocks,
+            atime: Duration::ZERO,
+            mtime: Duration::ZERO,
+            ctime: Duration::ZERO,
         }
     }
 
@@ -181,9 +206,14 @@ impl VfsNodeAttr {
     pub const fn new_file(size: u64, blocks: u64) -> Self {
         Self {
             mode: VfsNodePerm::default_file(),
+            uid: 0,
+            gid: 0,
             ty: VfsNodeType::File,
             size,
             blocks,
+            atime: Duration::ZERO,
+            mtime: Duration::ZERO,
+            ctime: Duration::ZERO,
         }
     }
 
@@ -192,12 +222,37 @@ impl VfsNodeAttr {
     pub const fn new_dir(size: u64, blocks: u64) -> Self {
         Self {
             mode: VfsNodePerm::default_dir(),
+            uid: 0,
+            gid: 0,
             ty: VfsNodeType::Dir,
             size,
             blocks,
+            atime: Duration::ZERO,
+            mtime: Duration::ZERO,
+            ctime: Duration::ZERO,
         }
     }
 
+    /// Sets the last access, modification and status change times of the
+    /// node, as durations since the Unix epoch.
+    ///
+    /// Nodes created by the other constructors have all times set to zero.
+    pub const fn with_times(mut self, atime: Duration, mtime: Duration, ctime: Duration) -> Self {
+        self.atime = atime;
+        self.mtime = mtime;
+        self.ctime = ctime;
+        self
+    }
+
+    /// Sets the user and group IDs of the owner of the node.
+    ///
+    /// Nodes created by the other constructors are owned by root (`0:0`).
+    pub const fn with_owner(mut self, uid: u32, gid: u32) -> Self {
+        self.uid = uid;
+        self.gid = gid;
+        self
+    }
+
     /// Returns the size of the node.
     pub const fn size(&self) -> u64 {
         self.size
@@ -218,6 +273,16 @@ impl VfsNodeAttr {
         self.mode = perm
     }
 
+    /// Returns the user ID of the owner of the node.
+    pub const fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    /// Returns the group ID of the owner of the node.
+    pub const fn gid(&self) -> u32 {
+        self.gid
+    }
+
     /// Returns the type of the node.
     pub const fn file_type(&self) -> VfsNodeType {
         self.ty
@@ -232,6 +297,21 @@ impl VfsNodeAttr {
     pub const fn is_dir(&self) -> bool {
         self.ty.is_dir()
     }
+
+    /// Returns the time of last access.
+    pub const fn atime(&self) -> Duration {
+        self.atime
+    }
+
+    /// Returns the time of last modification.
+    pub const fn mtime(&self) -> Duration {
+        self.mtime
+    }
+
+    /// Returns the time of last status change.
+    pub const fn ctime(&self) -> Duration {
+        self.ctime
+    }
 }
 
 impl VfsDirEntry {
@@ -181,6 +181,15 @@ pub enum SeekFrom {
     Current(i64),
 }
 
+/// I/O poll results.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PollState {
+    /// Object can be read now.
+    pub readable: bool,
+    /// Object can be written now.
+    pub writable: bool,
+}
+
 /// A `BufRead` is a type of `Read`er which has an internal buffer, allowing it
 /// to perform extra ways of reading.
 pub trait BufRead: Read {
@@ -205,7 +214,7 @@ pub trait BufRead: Read {
             let (done, used) = {
                 let available = match self.fill_buf() {
                     Ok(n) => n,
-                    Err(Error::Again) => continue,
+                    Err(Error::Again | Error::WouldBlock) => continue,
                     Err(e) => return Err(e),
                 };
                 match available.iter().position(|&b| b == byte) {
@@ -30,7 +30,7 @@
 
 bitflags::bitflags! {
     /// Capabilities (access rights).
-    #[derive(Default, Debug, Clone, Copy)]
+    #[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
     pub struct Cap: u32 {
         /// Readable access.
         const READ = 1 << 0;
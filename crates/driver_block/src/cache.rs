@@ -0,0 +1,380 @@
+//! A write-back block cache that sits in front of a block device.
+//!
+//! [`BlockCache`] implements [`BlockDriverOps`] itself, so it can replace the
+//! raw device anywhere one is expected. Reads are served from memory when
+//! possible, and a miss that continues a sequential run also loads the
+//! following blocks. Writes only mark the cached copy dirty; dirty blocks go
+//! to the device when they are evicted or when [`flush`] is called.
+//!
+//! [`flush`]: BlockDriverOps::flush
+
+use alloc::{boxed::Box, collections::BTreeMap, vec};
+
+use crate::BlockDriverOps;
+use driver_common::{BaseDriverOps, DevError, DevResult, DeviceType};
+
+/// Counters describing how well a [`BlockCache`] performs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Block reads served from the cache.
+    pub hits: u64,
+    /// Block reads that had to go to the device.
+    pub misses: u64,
+    /// Blocks loaded in advance by sequential read-ahead.
+    pub read_ahead: u64,
+    /// Dirty blocks written back to the device.
+    pub write_backs: u64,
+}
+
+struct CachedBlock {
+    data: Box<[u8]>,
+    dirty: bool,
+    /// Value of the LRU clock at the last access.
+    last_use: u64,
+}
+
+/// A block cache with LRU eviction, dirty tracking and read-ahead.
+pub struct BlockCache<D> {
+    dev: D,
+    capacity: usize,
+    read_ahead: usize,
+    blocks: BTreeMap<u64, CachedBlock>,
+    /// Maps the last access time to the block ID, oldest first.
+    lru: BTreeMap<u64, u64>,
+    clock: u64,
+    /// The block a sequential reader would ask for next.
+    next_sequential: Option<u64>,
+    stats: CacheStats,
+}
+
+impl<D: BlockDriverOps> BlockCache<D> {
+    /// Creates a cache holding at most `capacity` blocks of `dev`.
+    ///
+    /// On a sequential miss, up to `read_ahead` blocks after the requested
+    /// one are loaded with the same device request.
+    pub fn new(dev: D, capacity: usize, read_ahead: usize) -> Self {
+        assert!(capacity > 0, "block cache capacity must not be zero");
+        Self {
+            dev,
+            capacity,
+            read_ahead: read_ahead.min(capacity - 1),
+            blocks: BTreeMap::new(),
+            lru: BTreeMap::new(),
+            clock: 0,
+            next_sequential: None,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Returns the hit/miss counters collected so far.
+    pub const fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Returns the number of blocks currently cached.
+    pub fn cached_blocks(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Returns the number of cached blocks not yet written to the device.
+    pub fn dirty_blocks(&self) -> usize {
+        self.blocks.values().filter(|b| b.dirty).count()
+    }
+
+    /// Returns a reference to the underlying device.
+    pub const fn inner(&self) -> &D {
+        &self.dev
+    }
+
+    fn check_range(&self, block_id: u64, len: usize) -> DevResult {
+        let block_size = self.dev.block_size();
+        if len % block_size != 0 {
+            return Err(DevError::InvalidParam);
+        }
+        match block_id.checked_add((len / block_size) as u64) {
+            Some(end) if end <= self.dev.num_blocks() => Ok(()),
+            _ => Err(DevError::Io),
+        }
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// Marks a cached block as the most recently used one.
+    fn touch(&mut self, block_id: u64) {
+        let now = self.tick();
+        let block = self.blocks.get_mut(&block_id).unwrap();
+        self.lru.remove(&block.last_use);
+        block.last_use = now;
+        self.lru.insert(now, block_id);
+    }
+
+    /// Drops the least recently used block, writing it back if dirty.
+    fn evict_one(&mut self) -> DevResult {
+        let Some((&last_use, &block_id)) = self.lru.first_key_value() else {
+            return Ok(());
+        };
+        let block = &self.blocks[&block_id];
+        if block.dirty {
+            self.dev.write_block(block_id, &block.data)?;
+            self.stats.write_backs += 1;
+        }
+        self.lru.remove(&last_use);
+        self.blocks.remove(&block_id);
+        Ok(())
+    }
+
+    fn insert(&mut self, block_id: u64, data: Box<[u8]>, dirty: bool) -> DevResult {
+        while self.blocks.len() >= self.capacity {
+            self.evict_one()?;
+        }
+        let now = self.tick();
+        self.lru.insert(now, block_id);
+        let block = CachedBlock {
+            data,
+            dirty,
+            last_use: now,
+        };
+        self.blocks.insert(block_id, block);
+        Ok(())
+    }
+
+    /// Reads `block_id` from the device, together with the blocks after it
+    /// if the access continues a sequential run.
+    fn fill(&mut self, block_id: u64) -> DevResult {
+        let block_size = self.dev.block_size();
+        let mut count = 1;
+        if self.next_sequential == Some(block_id) {
+            let limit = (self.dev.num_blocks() - block_id).min(self.read_ahead as u64 + 1);
+            // stop at the first cached block so newer dirty data is kept
+            while count < limit && !self.blocks.contains_key(&(block_id + count)) {
+                count += 1;
+            }
+        }
+        let mut buf = vec![0u8; block_size * count as usize];
+        self.dev.read_block(block_id, &mut buf)?;
+        // insert the requested block last so it is the most recently used
+        for (i, data) in buf.chunks_exact(block_size).enumerate().rev() {
+            self.insert(block_id + i as u64, data.into(), false)?;
+        }
+        self.stats.read_ahead += count - 1;
+        Ok(())
+    }
+
+    /// Makes sure `block_id` is in the cache.
+    fn load(&mut self, block_id: u64) -> DevResult {
+        if self.blocks.contains_key(&block_id) {
+            self.stats.hits += 1;
+            self.touch(block_id);
+        } else {
+            self.stats.misses += 1;
+            self.fill(block_id)?;
+        }
+        self.next_sequential = Some(block_id + 1);
+        Ok(())
+    }
+}
+
+impl<D: BlockDriverOps> BaseDriverOps for BlockCache<D> {
+    fn device_type(&self) -> DeviceType {
+        self.dev.device_type()
+    }
+
+    fn device_name(&self) -> &str {
+        self.dev.device_name()
+    }
+}
+
+impl<D: BlockDriverOps> BlockDriverOps for BlockCache<D> {
+    #[inline]
+    fn num_blocks(&self) -> u64 {
+        self.dev.num_blocks()
+    }
+
+    #[inline]
+    fn block_size(&self) -> usize {
+        self.dev.block_size()
+    }
+
+    fn read_block(&mut self, block_id: u64, buf: &mut [u8]) -> DevResult {
+        self.check_range(block_id, buf.len())?;
+        let block_size = self.dev.block_size();
+        for (i, data) in buf.chunks_exact_mut(block_size).enumerate() {
+            let id = block_id + i as u64;
+            self.load(id)?;
+            data.copy_from_slice(&self.blocks[&id].data);
+        }
+        Ok(())
+    }
+
+    fn write_block(&mut self, block_id: u64, buf: &[u8]) -> DevResult {
+        self.check_range(block_id, buf.len())?;
+        let block_size = self.dev.block_size();
+        for (i, data) in buf.chunks_exact(block_size).enumerate() {
+            let id = block_id + i as u64;
+            if let Some(block) = self.blocks.get_mut(&id) {
+                block.data.copy_from_slice(data);
+                block.dirty = true;
+                self.touch(id);
+            } else {
+                // whole blocks are overwritten, no need to read them first
+                self.insert(id, data.into(), true)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> DevResult {
+        for (&id, block) in self.blocks.iter_mut().filter(|(_, b)| b.dirty) {
+            self.dev.write_block(id, &block.data)?;
+            block.dirty = false;
+            self.stats.write_backs += 1;
+        }
+        self.dev.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    const BLOCK_SIZE: usize = 512;
+
+    /// A RAM-backed device that counts the requests it receives.
+    struct CountingDisk {
+        data: Vec<u8>,
+        reads: usize,
+        writes: usize,
+    }
+
+    impl CountingDisk {
+        fn new(num_blocks: usize) -> Self {
+            Self {
+                data: vec![0; num_blocks * BLOCK_SIZE],
+                reads: 0,
+                writes: 0,
+            }
+        }
+    }
+
+    impl BaseDriverOps for CountingDisk {
+        fn device_type(&self) -> DeviceType {
+            DeviceType::Block
+        }
+
+        fn device_name(&self) -> &str {
+            "counting-disk"
+        }
+    }
+
+    impl BlockDriverOps for CountingDisk {
+        fn num_blocks(&self) -> u64 {
+            (self.data.len() / BLOCK_SIZE) as u64
+        }
+
+        fn block_size(&self) -> usize {
+            BLOCK_SIZE
+        }
+
+        fn read_block(&mut self, block_id: u64, buf: &mut [u8]) -> DevResult {
+            let offset = block_id as usize * BLOCK_SIZE;
+            buf.copy_from_slice(&self.data[offset..offset + buf.len()]);
+            self.reads += 1;
+            Ok(())
+        }
+
+        fn write_block(&mut self, block_id: u64, buf: &[u8]) -> DevResult {
+            let offset = block_id as usize * BLOCK_SIZE;
+            self.data[offset..offset + buf.len()].copy_from_slice(buf);
+            self.writes += 1;
+            Ok(())
+        }
+
+        fn flush(&mut self) -> DevResult {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_hit_and_write_back() {
+        let mut cache = BlockCache::new(CountingDisk::new(16), 4, 0);
+        let mut buf = [0u8; BLOCK_SIZE];
+
+        cache.write_block(3, &[0xaa; BLOCK_SIZE]).unwrap();
+        cache.read_block(3, &mut buf).unwrap();
+        assert_eq!(buf, [0xaa; BLOCK_SIZE]);
+        assert_eq!(cache.inner().reads, 0);
+        assert_eq!(cache.inner().writes, 0);
+        assert_eq!(cache.dirty_blocks(), 1);
+        assert_eq!(cache.stats().hits, 1);
+
+        cache.flush().unwrap();
+        assert_eq!(cache.inner().writes, 1);
+        assert_eq!(cache.inner().data[3 * BLOCK_SIZE], 0xaa);
+        assert_eq!(cache.dirty_blocks(), 0);
+
+        cache.flush().unwrap();
+        assert_eq!(cache.stats().write_backs, 1);
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let mut cache = BlockCache::new(CountingDisk::new(16), 2, 0);
+        let mut buf = [0u8; BLOCK_SIZE];
+
+        cache.write_block(0, &[1; BLOCK_SIZE]).unwrap();
+        cache.read_block(5, &mut buf).unwrap();
+        cache.read_block(0, &mut buf).unwrap(); // block 5 is now the oldest
+        cache.read_block(9, &mut buf).unwrap();
+        assert_eq!(cache.cached_blocks(), 2);
+        assert_eq!(cache.inner().writes, 0);
+
+        cache.read_block(5, &mut buf).unwrap(); // evicts the dirty block 0
+        assert_eq!(cache.inner().writes, 1);
+        assert_eq!(cache.inner().data[0], 1);
+        assert_eq!(cache.stats().misses, 3);
+        assert_eq!(cache.stats().hits, 1);
+    }
+
+    #[test]
+    fn test_read_ahead() {
+        let mut disk = CountingDisk::new(16);
+        for (i, block) in disk.data.chunks_exact_mut(BLOCK_SIZE).enumerate() {
+            block.fill(i as u8);
+        }
+        let mut cache = BlockCache::new(disk, 8, 4);
+        let mut buf = [0u8; BLOCK_SIZE];
+
+        cache.read_block(0, &mut buf).unwrap(); // random access, no read-ahead
+        cache.write_block(3, &[0xff; BLOCK_SIZE]).unwrap();
+        cache.read_block(1, &mut buf).unwrap(); // loads 1 and 2, stops at 3
+        assert_eq!(buf[0], 1);
+        assert_eq!(cache.inner().reads, 2);
+        assert_eq!(cache.stats().read_ahead, 1);
+
+        for id in 2..4 {
+            cache.read_block(id, &mut buf).unwrap();
+        }
+        assert_eq!(buf[0], 0xff);
+        assert_eq!(cache.inner().reads, 2);
+
+        cache.read_block(4, &mut buf).unwrap(); // loads 4..=8
+        for id in 5..9 {
+            cache.read_block(id, &mut buf).unwrap();
+            assert_eq!(buf[0], id as u8);
+        }
+        assert_eq!(cache.inner().reads, 3);
+        assert_eq!(cache.stats().read_ahead, 5);
+
+        // reads never go past the end of the device
+        let mut big = [0u8; 2 * BLOCK_SIZE];
+        assert!(matches!(cache.read_block(15, &mut big), Err(DevError::Io)));
+        assert!(matches!(
+            cache.read_block(14, &mut big[..100]),
+            Err(DevError::InvalidParam)
+        ));
+    }
+}
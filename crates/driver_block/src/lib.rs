@@ -1,9 +1,13 @@
 //! Common traits and types for block storage device drivers (i.e. disk).
 
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 #![feature(doc_auto_cfg)]
 #![feature(const_trait_impl)]
 
+extern crate alloc;
+
+pub mod cache;
+pub mod partition;
 #[cfg(feature = "ramdisk")]
 pub mod ramdisk;
 
@@ -0,0 +1,349 @@
+//! Parsing of MBR and GPT partition tables.
+//!
+//! [`parse_partitions`] reads the partition table of a block device and
+//! returns the location of each partition in device blocks. Partitions are
+//! numbered the way Linux does: primary MBR partitions are `1..=4`, logical
+//! partitions inside an extended partition start from `5`, and a GPT entry in
+//! slot `i` is partition `i + 1`.
+//!
+//! Block addresses in the tables are interpreted in units of the device's own
+//! block size, which must be at least 512 bytes.
+
+use alloc::{vec, vec::Vec};
+
+use crate::BlockDriverOps;
+use driver_common::{DevError, DevResult};
+
+const MBR_SIGNATURE: [u8; 2] = [0x55, 0xaa];
+const MBR_TABLE_OFFSET: usize = 446;
+const MBR_ENTRY_SIZE: usize = 16;
+const MBR_TYPE_EMPTY: u8 = 0x00;
+const MBR_TYPE_GPT_PROTECTIVE: u8 = 0xee;
+const MBR_TYPES_EXTENDED: [u8; 3] = [0x05, 0x0f, 0x85];
+/// Upper bound on the length of an EBR chain, guards against loops.
+const MAX_LOGICAL_PARTITIONS: usize = 128;
+
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+/// Upper bound on the number of GPT entries that are examined.
+const MAX_GPT_ENTRIES: u32 = 1024;
+
+/// The location of one partition on a block device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartitionInfo {
+    /// The partition number, starting from 1.
+    pub index: usize,
+    /// The first block of the partition.
+    pub start_block: u64,
+    /// The number of blocks in the partition.
+    pub num_blocks: u64,
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+/// A raw MBR partition entry.
+struct MbrEntry {
+    kind: u8,
+    start: u64,
+    count: u64,
+}
+
+fn mbr_entries(sector: &[u8]) -> impl Iterator<Item = MbrEntry> + '_ {
+    (0..4).map(move |i| {
+        let entry = &sector[MBR_TABLE_OFFSET + i * MBR_ENTRY_SIZE..][..MBR_ENTRY_SIZE];
+        MbrEntry {
+            kind: entry[4],
+            start: read_u32(entry, 8) as u64,
+            count: read_u32(entry, 12) as u64,
+        }
+    })
+}
+
+/// Reads the partition table of `dev`.
+///
+/// Returns an empty list if the device has neither an MBR nor a GPT, so that
+/// the caller can use the whole device instead. Partitions that do not fit in
+/// the device are skipped.
+pub fn parse_partitions(dev: &mut dyn BlockDriverOps) -> DevResult<Vec<PartitionInfo>> {
+    let block_size = dev.block_size();
+    if block_size < 512 {
+        return Err(DevError::Unsupported);
+    }
+    let mut sector = vec![0; block_size];
+    dev.read_block(0, &mut sector)?;
+    if !is_mbr(&sector) {
+        return Ok(Vec::new());
+    }
+    if mbr_entries(&sector).any(|e| e.kind == MBR_TYPE_GPT_PROTECTIVE) {
+        if let Some(parts) = parse_gpt(dev)? {
+            return Ok(parts);
+        }
+    }
+    parse_mbr(dev, &sector)
+}
+
+/// Checks whether `sector` holds a partition table rather than the boot
+/// sector of a filesystem spanning the whole device.
+///
+/// A FAT boot sector also ends with the `0x55AA` signature, so like Linux we
+/// reject sectors whose boot indicators are invalid or that carry a FAT
+/// filesystem type string.
+fn is_mbr(sector: &[u8]) -> bool {
+    if sector[510..512] != MBR_SIGNATURE {
+        return false;
+    }
+    let boot_flags_valid = (0..4)
+        .map(|i| sector[MBR_TABLE_OFFSET + i * MBR_ENTRY_SIZE])
+        .all(|flag| flag == 0x00 || flag == 0x80);
+    let fat_boot_sector = matches!(sector[0], 0xeb | 0xe9)
+        && (sector[54..57] == *b"FAT" || sector[82..87] == *b"FAT32");
+    boot_flags_valid && !fat_boot_sector
+}
+
+fn push_checked(parts: &mut Vec<PartitionInfo>, total: u64, info: PartitionInfo) {
+    if info.num_blocks > 0 && info.start_block < total && info.num_blocks <= total - info.start_block
+    {
+        parts.push(info);
+    }
+}
+
+fn parse_mbr(dev: &mut dyn BlockDriverOps, sector: &[u8]) -> DevResult<Vec<PartitionInfo>> {
+    let total = dev.num_blocks();
+    let mut parts = Vec::new();
+    let mut extended = None;
+    for (i, entry) in mbr_entries(sector).enumerate() {
+        match entry.kind {
+            MBR_TYPE_EMPTY => {}
+            kind if MBR_TYPES_EXTENDED.contains(&kind) => {
+                extended.get_or_insert(entry.start);
+            }
+            _ => push_checked(
+                &mut parts,
+                total,
+                PartitionInfo {
+                    index: i + 1,
+                    start_block: entry.start,
+                    num_blocks: entry.count,
+                },
+            ),
+        }
+    }
+
+    if let Some(ext_start) = extended {
+        // Each EBR describes one logical partition relative to itself, and
+        // links to the next EBR relative to the start of the extended partition.
+        let mut ebr = vec![0; dev.block_size()];
+        let mut ebr_lba = ext_start;
+        let mut index = 5;
+        for _ in 0..MAX_LOGICAL_PARTITIONS {
+            if ebr_lba >= total {
+                break;
+            }
+            dev.read_block(ebr_lba, &mut ebr)?;
+            if ebr[510..512] != MBR_SIGNATURE {
+                break;
+            }
+            let mut entries = mbr_entries(&ebr);
+            let logical = entries.next().unwrap();
+            let next = entries.next().unwrap();
+            if logical.kind != MBR_TYPE_EMPTY {
+                push_checked(
+                    &mut parts,
+                    total,
+                    PartitionInfo {
+                        index,
+                        start_block: ebr_lba + logical.start,
+                        num_blocks: logical.count,
+                    },
+                );
+                index += 1;
+            }
+            if next.kind == MBR_TYPE_EMPTY || next.start == 0 {
+                break;
+            }
+            ebr_lba = ext_start + next.start;
+        }
+    }
+    Ok(parts)
+}
+
+/// Returns `None` if the GPT header is missing, so the caller falls back to
+/// the protective MBR.
+fn parse_gpt(dev: &mut dyn BlockDriverOps) -> DevResult<Option<Vec<PartitionInfo>>> {
+    let block_size = dev.block_size();
+    let total = dev.num_blocks();
+    let mut header = vec![0; block_size];
+    dev.read_block(1, &mut header)?;
+    if &header[..8] != GPT_SIGNATURE {
+        return Ok(None);
+    }
+    let entries_lba = read_u64(&header, 72);
+    let num_entries = read_u32(&header, 80).min(MAX_GPT_ENTRIES) as usize;
+    let entry_size = read_u32(&header, 84) as usize;
+    if entry_size < 128 || entry_size > block_size || block_size % entry_size != 0 {
+        return Err(DevError::Io);
+    }
+
+    let per_block = block_size / entry_size;
+    let mut parts = Vec::new();
+    let mut block = vec![0; block_size];
+    for slot in 0..num_entries {
+        if slot % per_block == 0 {
+            dev.read_block(entries_lba + (slot / per_block) as u64, &mut block)?;
+        }
+        let entry = &block[(slot % per_block) * entry_size..][..entry_size];
+        // an all-zero partition type GUID marks an unused entry
+        if entry[..16].iter().all(|&b| b == 0) {
+            continue;
+        }
+        let first = read_u64(entry, 32);
+        let last = read_u64(entry, 40);
+        if last < first {
+            continue;
+        }
+        push_checked(
+            &mut parts,
+            total,
+            PartitionInfo {
+                index: slot + 1,
+                start_block: first,
+                num_blocks: last - first + 1,
+            },
+        );
+    }
+    Ok(Some(parts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use driver_common::{BaseDriverOps, DeviceType};
+
+    const BLOCK_SIZE: usize = 512;
+
+    struct MemDisk(Vec<u8>);
+
+    impl MemDisk {
+        fn new(num_blocks: usize) -> Self {
+            Self(vec![0; num_blocks * BLOCK_SIZE])
+        }
+
+        fn block(&mut self, id: usize) -> &mut [u8] {
+            &mut self.0[id * BLOCK_SIZE..][..BLOCK_SIZE]
+        }
+
+        /// Writes a partition table sector with the given `(type, start, count)` entries.
+        fn write_table(&mut self, id: usize, entries: &[(u8, u32, u32)]) {
+            let sector = self.block(id);
+            for (i, &(kind, start, count)) in entries.iter().enumerate() {
+                let entry = &mut sector[MBR_TABLE_OFFSET + i * MBR_ENTRY_SIZE..];
+                entry[4] = kind;
+                entry[8..12].copy_from_slice(&start.to_le_bytes());
+                entry[12..16].copy_from_slice(&count.to_le_bytes());
+            }
+            sector[510..512].copy_from_slice(&MBR_SIGNATURE);
+        }
+    }
+
+    impl BaseDriverOps for MemDisk {
+        fn device_type(&self) -> DeviceType {
+            DeviceType::Block
+        }
+
+        fn device_name(&self) -> &str {
+            "mem-disk"
+        }
+    }
+
+    impl BlockDriverOps for MemDisk {
+        fn num_blocks(&self) -> u64 {
+            (self.0.len() / BLOCK_SIZE) as u64
+        }
+
+        fn block_size(&self) -> usize {
+            BLOCK_SIZE
+        }
+
+        fn read_block(&mut self, block_id: u64, buf: &mut [u8]) -> DevResult {
+            let start = block_id as usize * BLOCK_SIZE;
+            buf.copy_from_slice(&self.0[start..start + buf.len()]);
+            Ok(())
+        }
+
+        fn write_block(&mut self, block_id: u64, buf: &[u8]) -> DevResult {
+            let start = block_id as usize * BLOCK_SIZE;
+            self.0[start..start + buf.len()].copy_from_slice(buf);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> DevResult {
+            Ok(())
+        }
+    }
+
+    fn part(index: usize, start_block: u64, num_blocks: u64) -> PartitionInfo {
+        PartitionInfo {
+            index,
+            start_block,
+            num_blocks,
+        }
+    }
+
+    #[test]
+    fn test_no_table() {
+        let mut disk = MemDisk::new(16);
+        assert!(parse_partitions(&mut disk).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_fat_boot_sector() {
+        let mut disk = MemDisk::new(16);
+        disk.write_table(0, &[(0x83, 1, 8)]);
+        let sector = disk.block(0);
+        sector[..3].copy_from_slice(&[0xeb, 0x58, 0x90]);
+        sector[82..90].copy_from_slice(b"FAT32   ");
+        assert!(parse_partitions(&mut disk).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_mbr_with_logical() {
+        let mut disk = MemDisk::new(256);
+        disk.write_table(
+            0,
+            &[(0x83, 8, 32), (0x00, 0, 0), (0x05, 64, 128), (0x83, 240, 100)],
+        );
+        // first EBR at 64: logical at 64+2, next EBR at 64+32
+        disk.write_table(64, &[(0x83, 2, 16), (0x05, 32, 64)]);
+        // second EBR at 96: logical at 96+4, end of chain
+        disk.write_table(96, &[(0x0c, 4, 20)]);
+
+        let parts = parse_partitions(&mut disk).unwrap();
+        // the fourth primary partition runs past the end of the disk
+        assert_eq!(parts, [part(1, 8, 32), part(5, 66, 16), part(6, 100, 20)]);
+    }
+
+    #[test]
+    fn test_gpt() {
+        let mut disk = MemDisk::new(128);
+        disk.write_table(0, &[(MBR_TYPE_GPT_PROTECTIVE, 1, 127)]);
+        let header = disk.block(1);
+        header[..8].copy_from_slice(GPT_SIGNATURE);
+        header[72..80].copy_from_slice(&2u64.to_le_bytes());
+        header[80..84].copy_from_slice(&8u32.to_le_bytes());
+        header[84..88].copy_from_slice(&128u32.to_le_bytes());
+        for (slot, first, last) in [(0usize, 34u64, 63u64), (2, 64, 100)] {
+            let entry = &mut disk.0[2 * BLOCK_SIZE + slot * 128..][..128];
+            entry[..16].fill(0xaa);
+            entry[32..40].copy_from_slice(&first.to_le_bytes());
+            entry[40..48].copy_from_slice(&last.to_le_bytes());
+        }
+
+        let parts = parse_partitions(&mut disk).unwrap();
+        assert_eq!(parts, [part(1, 34, 30), part(3, 64, 37)]);
+    }
+}
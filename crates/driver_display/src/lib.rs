@@ -22,7 +22,7 @@ pub struct DisplayInfo {
 ///
 /// It's a special memory buffer that mapped from the device memory.
 pub struct FrameBuffer<'a> {
-    _raw: &'a mut [u8],
+    raw: &'a mut [u8],
 }
 
 impl<'a> FrameBuffer<'a> {
@@ -33,13 +33,33 @@ impl<'a> FrameBuffer<'a> {
     /// Caller must insure that the given memory region is valid and accessible.
     pub unsafe fn from_raw_parts_mut(ptr: *mut u8, len: usize) -> Self {
         Self {
-            _raw: core::slice::from_raw_parts_mut(ptr, len),
+            raw: core::slice::from_raw_parts_mut(ptr, len),
         }
     }
 
     /// Use the given slice as the framebuffer.
     pub fn from_slice(slice: &'a mut [u8]) -> Self {
-        Self { _raw: slice }
+        Self { raw: slice }
+    }
+
+    /// The size of the framebuffer in bytes.
+    pub fn len(&self) -> usize {
+        self.raw.len()
+    }
+
+    /// Whether the framebuffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.raw.is_empty()
+    }
+
+    /// Get the framebuffer as a byte slice.
+    pub fn as_slice(&self) -> &[u8] {
+        self.raw
+    }
+
+    /// Get the framebuffer as a mutable byte slice.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        self.raw
     }
 }
 
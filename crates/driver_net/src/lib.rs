@@ -28,6 +28,14 @@ pub trait NetDriverOps: BaseDriverOps {
     /// The ethernet address of the NIC.
     fn mac_address(&self) -> EthernetAddress;
 
+    /// The IRQ number of the NIC, or [`None`] if it does not raise
+    /// interrupts.
+    fn irq_num(&self) -> Option<usize>;
+
+    /// Acknowledges the interrupt raised by the NIC, returns whether there
+    /// was one pending.
+    fn ack_interrupt(&mut self) -> bool;
+
     /// Whether can send data.
     fn can_send(&self) -> bool;
 
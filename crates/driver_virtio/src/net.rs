@@ -9,6 +9,7 @@ use virtio_drivers::{transport::Transport, Hal};
 /// `QS` is the VirtIO queue size.
 pub struct VirtIoNetDev<H: Hal, T: Transport, const QS: usize> {
     inner: InnerDev<H, T, QS>,
+    irq_num: Option<usize>,
 }
 
 unsafe impl<H: Hal, T: Transport, const QS: usize> Send for VirtIoNetDev<H, T, QS> {}
@@ -55,10 +56,12 @@ impl<H: Hal, T: Transport, const QS: usize> VirtIoNetDev<H, T, QS> {
     /// Creates a new driver instance and initializes the device, or returns
     /// an error if any step fails.
     ///
-    /// `buf_len` is the length of the transmit and receive buffer.
-    pub fn try_new(transport: T, buf_len: usize) -> DevResult<Self> {
+    /// `buf_len` is the length of the transmit and receive buffer, and
+    /// `irq_num` is the IRQ of the transport, if it is connected to one.
+    pub fn try_new(transport: T, buf_len: usize, irq_num: Option<usize>) -> DevResult<Self> {
         Ok(Self {
             inner: InnerDev::new(transport, buf_len).map_err(as_dev_err)?,
+            irq_num,
         })
     }
 }
@@ -81,6 +84,14 @@ impl<H: Hal, T: Transport, const QS: usize> NetDriverOps for VirtIoNetDev<H, T,
         EthernetAddress(self.inner.mac_address())
     }
 
+    fn irq_num(&self) -> Option<usize> {
+        self.irq_num
+    }
+
+    fn ack_interrupt(&mut self) -> bool {
+        self.inner.ack_interrupt()
+    }
+
     fn can_send(&self) -> bool {
         self.inner.can_send()
     }
@@ -0,0 +1,66 @@
+use alloc::sync::Arc;
+
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
+use driver_display::DisplayDriverOps;
+
+use crate::DISPLAYS;
+
+/// The framebuffer device `/dev/fb0`.
+///
+/// Reads and writes go directly to the framebuffer of the first display,
+/// writes are flushed to the screen if the device requires it.
+pub struct FrameBufferDev;
+
+impl VfsNodeOps for FrameBufferDev {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let size = DISPLAYS.0.lock().0.info().fb_size as u64;
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::default_file(),
+            VfsNodeType::CharDevice,
+            size,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let device = DISPLAYS.0.lock();
+        let fb = device.0.fb();
+        let src = fb.as_slice().get(offset as usize..).unwrap_or(&[]);
+        let len = src.len().min(buf.len());
+        buf[..len].copy_from_slice(&src[..len]);
+        Ok(len)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let mut device = DISPLAYS.0.lock();
+        let len = {
+            let mut fb = device.0.fb();
+            let dst = fb.as_mut_slice().get_mut(offset as usize..).unwrap_or(&mut []);
+            let len = dst.len().min(buf.len());
+            dst[..len].copy_from_slice(&buf[..len]);
+            len
+        };
+        if device.0.need_flush() {
+            device.0.flush().ok();
+        }
+        Ok(len)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        DISPLAYS.0.lock().0.flush().ok();
+        Ok(())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// Publish the framebuffer of the first display as `/dev/fb0`.
+pub(crate) fn register_fbdev() {
+    if let Err(e) = axfs::devfs::register_device("fb0", Arc::new(FrameBufferDev)) {
+        warn!("failed to register /dev/fb0: {:?}", e);
+    }
+}
@@ -2,6 +2,11 @@
 
 #[macro_use]
 extern crate log;
+#[cfg(feature = "devfs")]
+extern crate alloc;
+
+#[cfg(feature = "devfs")]
+mod fbdev;
 
 pub use driver_display::{DisplayDriverOps, DisplayInfo};
 
@@ -18,6 +23,9 @@ pub fn init_display(display_devs: DisplayDevices) {
 
     info!("number of Displays: {}", display_devs.len());
     DISPLAYS.init_by(DisplayDevicesWrapper(Mutex::new(display_devs)));
+
+    #[cfg(feature = "devfs")]
+    fbdev::register_fbdev();
 }
 
 pub fn framebuffer_info() -> DisplayInfo {
@@ -24,9 +24,11 @@ pub struct BlockDevices(
     // e.g. #[cfg(feature = "nvme")] pub nvme::NVMeDev,
 );
 
+/// The NICs, [`None`] for a NIC that is not present: networking still works
+/// over the loopback interface without any.
 #[derive(TupleForEach)]
 pub struct NetDevices(
-    #[cfg(feature = "virtio-net")] pub VirtIoNetDev,
+    #[cfg(feature = "virtio-net")] pub Option<VirtIoNetDev>,
     // e.g. #[cfg(feature = "e1000")] pub e1000::E1000Dev,
 );
 
@@ -50,7 +52,7 @@ impl AllDevices {
             ),
             net: NetDevices(
                 #[cfg(feature = "virtio-net")]
-                Self::probe_virtio_net().expect("no virtio-net device found"),
+                Self::probe_virtio_net(),
             ),
             display: DisplayDevices(
                 #[cfg(feature = "virtio-gpu")]
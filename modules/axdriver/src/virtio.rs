@@ -75,16 +75,16 @@ impl AllDevices {
     fn probe_devices_common<D, F>(dev_type: DeviceType, ret: F) -> Option<D>
     where
         D: BaseDriverOps,
-        F: FnOnce(VirtIoTransport) -> Option<D>,
+        F: FnOnce(VirtIoTransport, usize) -> Option<D>,
     {
         // TODO: parse device tree
-        for reg in axconfig::VIRTIO_MMIO_REGIONS {
+        for (i, reg) in axconfig::VIRTIO_MMIO_REGIONS.iter().enumerate() {
             if let Some(transport) = driver_virtio::probe_mmio_device(
                 phys_to_virt(reg.0.into()).as_mut_ptr(),
                 reg.1,
                 Some(dev_type),
             ) {
-                let dev = ret(transport)?;
+                let dev = ret(transport, axconfig::VIRTIO_MMIO_IRQ_BASE + i)?;
                 info!(
                     "created a new {:?} device: {:?}",
                     dev.device_type(),
@@ -98,18 +98,18 @@ impl AllDevices {
 
     #[cfg(feature = "virtio-blk")]
     pub(crate) fn probe_virtio_blk() -> Option<VirtIoBlockDev> {
-        Self::probe_devices_common(DeviceType::Block, |t| VirtIoBlockDev::try_new(t).ok())
+        Self::probe_devices_common(DeviceType::Block, |t, _| VirtIoBlockDev::try_new(t).ok())
     }
 
     #[cfg(feature = "virtio-net")]
     pub(crate) fn probe_virtio_net() -> Option<VirtIoNetDev> {
-        Self::probe_devices_common(DeviceType::Net, |t| {
-            VirtIoNetDev::try_new(t, NET_BUFFER_SIZE).ok()
+        Self::probe_devices_common(DeviceType::Net, |t, irq_num| {
+            VirtIoNetDev::try_new(t, NET_BUFFER_SIZE, Some(irq_num)).ok()
         })
     }
 
     #[cfg(feature = "virtio-gpu")]
     pub(crate) fn probe_virtio_display() -> Option<VirtIoGpuDev> {
-        Self::probe_devices_common(DeviceType::Display, |t| VirtIoGpuDev::try_new(t).ok())
+        Self::probe_devices_common(DeviceType::Display, |t, _| VirtIoGpuDev::try_new(t).ok())
     }
 }
@@ -1,7 +1,9 @@
+use alloc::sync::Arc;
 use axio::{prelude::*, Result, SeekFrom};
 use core::fmt;
 
 use crate::fops;
+use crate::page_cache::PageCache;
 
 /// A structure representing a type of file with accessors for each file type.
 /// It is returned by [`Metadata::file_type`] method.
@@ -101,6 +103,16 @@ impl Metadata {
         self.0.perm()
     }
 
+    /// Returns the user ID of the owner of this file.
+    pub const fn uid(&self) -> u32 {
+        self.0.uid()
+    }
+
+    /// Returns the group ID of the owner of this file.
+    pub const fn gid(&self) -> u32 {
+        self.0.gid()
+    }
+
     pub const fn raw_metadata(&self) -> &fops::FileAttr {
         &self.0
     }
@@ -151,6 +163,17 @@ impl File {
         self.inner.truncate(size)
     }
 
+    /// Attempts to sync all data and metadata of the file to disk.
+    pub fn sync_all(&self) -> Result<()> {
+        self.inner.flush()
+    }
+
+    /// Returns the page cache of the file, if it is a regular file opened by
+    /// path.
+    pub fn page_cache(&self) -> Option<Arc<PageCache>> {
+        self.inner.page_cache()
+    }
+
     /// Queries metadata about the underlying file.
     pub fn metadata(&self) -> Result<Metadata> {
         self.inner.get_attr().map(Metadata)
@@ -6,8 +6,10 @@ mod link;
 
 pub use self::dir::{DirBuilder, DirEntry, ReadDir};
 pub use self::file::{File, FileType, Metadata, OpenOptions, Permissions};
+pub use crate::page_cache::{CachedPage, PageCache};
 
 use alloc::{string::String, vec::Vec};
+use core::time::Duration;
 use axio::{self as io, prelude::*};
 
 /// Returns an iterator over the entries within a directory.
@@ -78,7 +80,42 @@ pub fn remove_dir(path: &str) -> io::Result<()> {
 
 /// Removes a file from the filesystem.
 pub fn remove_file(path: &str) -> io::Result<()> {
-    crate::root::remove_file(None, path)
+    crate::root::remove_file(None, path, &crate::root::absolute_path(path)?)
+}
+
+/// Rename a file or directory to a new name, replacing the original file if
+/// `to` already exists.
+pub fn rename(from: &str, to: &str) -> io::Result<()> {
+    crate::root::rename(from, to)
+}
+
+/// Changes the last access and modification times of a file or directory.
+///
+/// A `None` leaves the corresponding time unchanged.
+pub fn set_times(path: &str, atime: Option<Duration>, mtime: Option<Duration>) -> io::Result<()> {
+    crate::root::set_times(path, atime, mtime)
+}
+
+/// Changes the permissions found on a file or a directory.
+pub fn set_permissions(path: &str, perm: Permissions) -> io::Result<()> {
+    crate::root::set_perm(path, perm)
+}
+
+/// Changes the owner and group of a file or a directory.
+///
+/// A `None` leaves the corresponding ID unchanged.
+pub fn set_owner(path: &str, uid: Option<u32>, gid: Option<u32>) -> io::Result<()> {
+    crate::root::set_owner(path, uid, gid)
+}
+
+/// Writes back the cached data of all mounted filesystems to the storage.
+pub fn sync() -> io::Result<()> {
+    crate::root::sync()
+}
+
+/// Whether two paths are on the same filesystem. The paths need not exist.
+pub fn same_filesystem(path1: &str, path2: &str) -> io::Result<bool> {
+    crate::root::same_fs(path1, path2)
 }
 
 /// Check if a path exists.
@@ -86,12 +123,19 @@ pub fn path_exists(path: &str) -> bool {
     crate::root::lookup(None, path).is_ok()
 }
 
-// /// Mount a filesystem
-// pub fn mount_fs(device: &str, mountpoint: &str) -> io::Result<()> {
-//     crate::root::mount_fs(device, mountpoint)
-// }
-//
-// /// Unmount a filesystem
-// pub fn unmount_fs(mountpoint: &str) -> io::Result<()> {
-//     crate::root::unmount_(mountpoint)
-// }
+/// Mount the FAT filesystem on a block device such as `/dev/vda2`, or in an
+/// image file, at the directory `target`.
+///
+/// An image file is attached to a free loop device, which is released again
+/// when the filesystem is unmounted.
+pub fn mount(source: &str, target: &str) -> io::Result<()> {
+    crate::root::mount(source, target)
+}
+
+/// Unmount the filesystem mounted at `target` by [`mount`].
+///
+/// Fails with `ResourceBusy` while files or directories in it are still open,
+/// or the current directory is in it.
+pub fn umount(target: &str) -> io::Result<()> {
+    crate::root::umount(target)
+}
@@ -3,22 +3,30 @@
 //! 所以这是一个抽象出来的表示块设备的结构,通过它可以统一地访问不同类型的块设备(如virtio块设备、ramdisk等)。这属于操作系统中设备管理方面的内容。
 
 
-use crate::BlockDevice;
+use crate::BlockDeviceRef;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use axsync::Mutex;
+use core::ops::Range;
 use driver_block::BlockDriverOps;
-use driver_common::DevResult;
+use driver_common::{BaseDriverOps, DevError, DevResult, DeviceType};
 
 const BLOCK_SIZE: usize = 512;  // 块大小为512字节
 
+/// 所有分区, 以及它们所在的磁盘和在磁盘上的块范围
+static PARTITIONS: Mutex<Vec<(BlockDeviceRef, BlockDeviceRef, Range<u64>)>> =
+    Mutex::new(Vec::new());
+
 pub struct Disk {
     block_id: u64,   // 当前块ID
     offset: usize,   // 块内偏移
-    dev: BlockDevice,  // 块设备
+    dev: BlockDeviceRef,  // 块设备, 与`/dev`下的设备节点共享
 }
 
 impl Disk {
     /// Create a new disk.
-    pub fn new(dev: BlockDevice) -> Self {
-        assert_eq!(BLOCK_SIZE, dev.block_size());
+    pub fn new(dev: BlockDeviceRef) -> Self {
+        assert_eq!(BLOCK_SIZE, dev.lock().block_size());
         Self {
             block_id: 0,
             offset: 0,
@@ -26,9 +34,19 @@ impl Disk {
         }
     }
 
+    /// Get the underlying block device.
+    pub fn device(&self) -> BlockDeviceRef {
+        self.dev.clone()
+    }
+
+    /// Write back the data cached in the block device.
+    pub fn flush(&mut self) -> DevResult {
+        self.dev.lock().flush()
+    }
+
     /// Get the size of the disk.
     pub fn size(&self) -> u64 {
-        self.dev.num_blocks() * BLOCK_SIZE as u64
+        self.dev.lock().num_blocks() * BLOCK_SIZE as u64
     }
 
     /// Get the position of the cursor.
@@ -47,6 +65,7 @@ impl Disk {
         let read_size = if self.offset == 0 && buf.len() >= BLOCK_SIZE {
             // whole block
             self.dev
+                .lock()
                 .read_block(self.block_id, &mut buf[0..BLOCK_SIZE])?;
             self.block_id += 1;
             BLOCK_SIZE
@@ -56,7 +75,7 @@ impl Disk {
             let start = self.offset;
             let count = buf.len().min(BLOCK_SIZE - self.offset);
 
-            self.dev.read_block(self.block_id, &mut data)?;
+            self.dev.lock().read_block(self.block_id, &mut data)?;
             buf[..count].copy_from_slice(&data[start..start + count]);
 
             self.offset += count;
@@ -73,7 +92,7 @@ impl Disk {
     pub fn write_one(&mut self, buf: &[u8]) -> DevResult<usize> {
         let write_size = if self.offset == 0 && buf.len() >= BLOCK_SIZE {
             // whole block
-            self.dev.write_block(self.block_id, &buf[0..BLOCK_SIZE])?;
+            self.dev.lock().write_block(self.block_id, &buf[0..BLOCK_SIZE])?;
             self.block_id += 1;
             BLOCK_SIZE
         } else {
@@ -82,9 +101,9 @@ impl Disk {
             let start = self.offset;
             let count = buf.len().min(BLOCK_SIZE - self.offset);
 
-            self.dev.read_block(self.block_id, &mut data)?;
+            self.dev.lock().read_block(self.block_id, &mut data)?;
             data[start..start + count].copy_from_slice(&buf[..count]);
-            self.dev.write_block(self.block_id, &data)?;
+            self.dev.lock().write_block(self.block_id, &data)?;
 
             self.offset += count;
             if self.offset >= BLOCK_SIZE {
@@ -96,3 +115,101 @@ impl Disk {
         Ok(write_size)
     }
 }
+
+/// 磁盘上的一个分区, 作为独立的块设备使用
+///
+/// 块号相对于分区的起始位置, 读写时加上偏移后交给所在的磁盘, 超出分区范围的访问返回`InvalidParam`。
+pub struct Partition {
+    dev: BlockDeviceRef,
+    start_block: u64,
+    num_blocks: u64,
+}
+
+impl Partition {
+    /// 以磁盘`dev`上从`start_block`开始的`num_blocks`个块创建分区
+    pub fn new(dev: BlockDeviceRef, start_block: u64, num_blocks: u64) -> Self {
+        Self {
+            dev,
+            start_block,
+            num_blocks,
+        }
+    }
+
+    /// 以磁盘`dev`上从`start_block`开始的`num_blocks`个块创建分区, 并记录它在磁盘上的位置,
+    /// 以便[`overlaps`]判断它与磁盘和其它分区是否重叠
+    pub fn new_ref(dev: BlockDeviceRef, start_block: u64, num_blocks: u64) -> BlockDeviceRef {
+        let part: BlockDeviceRef =
+            Arc::new(Mutex::new(Self::new(dev.clone(), start_block, num_blocks)));
+        let range = start_block..start_block + num_blocks;
+        PARTITIONS.lock().push((part.clone(), dev, range));
+        part
+    }
+
+    /// 检查从`block_id`开始、长度为`len`字节的访问是否在分区内, 返回在磁盘上的块号
+    fn translate(&self, block_id: u64, len: usize, block_size: usize) -> DevResult<u64> {
+        let count = (len / block_size) as u64;
+        if len % block_size != 0 || block_id > self.num_blocks || count > self.num_blocks - block_id
+        {
+            return Err(DevError::InvalidParam);
+        }
+        Ok(self.start_block + block_id)
+    }
+}
+
+impl BaseDriverOps for Partition {
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Block
+    }
+
+    fn device_name(&self) -> &str {
+        "partition"
+    }
+}
+
+impl BlockDriverOps for Partition {
+    fn num_blocks(&self) -> u64 {
+        self.num_blocks
+    }
+
+    fn block_size(&self) -> usize {
+        self.dev.lock().block_size()
+    }
+
+    fn read_block(&mut self, block_id: u64, buf: &mut [u8]) -> DevResult {
+        let mut dev = self.dev.lock();
+        let block_id = self.translate(block_id, buf.len(), dev.block_size())?;
+        dev.read_block(block_id, buf)
+    }
+
+    fn write_block(&mut self, block_id: u64, buf: &[u8]) -> DevResult {
+        let mut dev = self.dev.lock();
+        let block_id = self.translate(block_id, buf.len(), dev.block_size())?;
+        dev.write_block(block_id, buf)
+    }
+
+    fn flush(&mut self) -> DevResult {
+        self.dev.lock().flush()
+    }
+}
+
+/// 块设备所在的磁盘和在磁盘上的块范围, 不是分区的设备占据自身的全部
+fn extent(dev: &BlockDeviceRef) -> (*const (), Range<u64>) {
+    let partitions = PARTITIONS.lock();
+    let partition = partitions.iter().find(|(part, ..)| same_device(part, dev));
+    match partition {
+        Some((_, disk, range)) => (Arc::as_ptr(disk) as *const (), range.clone()),
+        None => (Arc::as_ptr(dev) as *const (), 0..u64::MAX),
+    }
+}
+
+/// `a`和`b`是否为同一个块设备
+pub(crate) fn same_device(a: &BlockDeviceRef, b: &BlockDeviceRef) -> bool {
+    Arc::as_ptr(a) as *const () == Arc::as_ptr(b) as *const ()
+}
+
+/// `a`和`b`是否共享磁盘上的块, 如同一个设备、磁盘和它的分区、重叠的两个分区
+pub(crate) fn overlaps(a: &BlockDeviceRef, b: &BlockDeviceRef) -> bool {
+    let (disk_a, range_a) = extent(a);
+    let (disk_b, range_b) = extent(b);
+    disk_a == disk_b && range_a.start < range_b.end && range_b.start < range_a.end
+}
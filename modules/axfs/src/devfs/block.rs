@@ -0,0 +1,102 @@
+use alloc::vec;
+
+use axfs_vfs::{VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
+use driver_common::DevError;
+
+use crate::BlockDeviceRef;
+
+/// 块设备节点, 对应`/dev/vda`等
+///
+/// 支持任意偏移和长度的读写, 不足一个块的部分通过读-改-写完成。
+pub struct BlockDev {
+    dev: BlockDeviceRef,
+}
+
+impl BlockDev {
+    /// 以共享的块设备创建设备节点
+    pub fn new(dev: BlockDeviceRef) -> Self {
+        Self { dev }
+    }
+}
+
+impl VfsNodeOps for BlockDev {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let dev = self.dev.lock();
+        let size = dev.num_blocks() * dev.block_size() as u64;
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::default_file(),
+            VfsNodeType::BlockDevice,
+            size,
+            size / 512,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut dev = self.dev.lock();
+        let block_size = dev.block_size();
+        let end = (offset + buf.len() as u64).min(dev.num_blocks() * block_size as u64);
+        let mut block = vec![0u8; block_size];
+        let mut pos = offset;
+        while pos < end {
+            let block_id = pos / block_size as u64;
+            let start = (pos % block_size as u64) as usize;
+            let count = (block_size - start).min((end - pos) as usize);
+            let dst = &mut buf[(pos - offset) as usize..][..count];
+            if count == block_size {
+                dev.read_block(block_id, dst).map_err(as_vfs_err)?;
+            } else {
+                dev.read_block(block_id, &mut block).map_err(as_vfs_err)?;
+                dst.copy_from_slice(&block[start..start + count]);
+            }
+            pos += count as u64;
+        }
+        Ok(end.saturating_sub(offset) as usize)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let mut dev = self.dev.lock();
+        let block_size = dev.block_size();
+        let end = (offset + buf.len() as u64).min(dev.num_blocks() * block_size as u64);
+        let mut block = vec![0u8; block_size];
+        let mut pos = offset;
+        while pos < end {
+            let block_id = pos / block_size as u64;
+            let start = (pos % block_size as u64) as usize;
+            let count = (block_size - start).min((end - pos) as usize);
+            let src = &buf[(pos - offset) as usize..][..count];
+            if count == block_size {
+                dev.write_block(block_id, src).map_err(as_vfs_err)?;
+            } else {
+                dev.read_block(block_id, &mut block).map_err(as_vfs_err)?;
+                block[start..start + count].copy_from_slice(src);
+                dev.write_block(block_id, &block).map_err(as_vfs_err)?;
+            }
+            pos += count as u64;
+        }
+        Ok(end.saturating_sub(offset) as usize)
+    }
+
+    fn fsync(&self) -> VfsResult {
+        self.dev.lock().flush().map_err(as_vfs_err)
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(()) // 块设备的大小是固定的, 和Linux一样忽略O_TRUNC
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// 将驱动的DevError映射为VfsError
+pub(crate) fn as_vfs_err(err: DevError) -> VfsError {
+    match err {
+        DevError::AlreadyExists => VfsError::AlreadyExists,
+        DevError::Again => VfsError::Again,
+        DevError::BadState => VfsError::BadState,
+        DevError::InvalidParam => VfsError::InvalidInput,
+        DevError::Io => VfsError::Io,
+        DevError::NoMemory => VfsError::NoMemory,
+        DevError::ResourceBusy => VfsError::ResourceBusy,
+        DevError::Unsupported => VfsError::Unsupported,
+    }
+}
@@ -0,0 +1,44 @@
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
+use axhal::console::{getchar, write_bytes};
+
+/// 控制台设备, 对应`/dev/tty`和`/dev/console`
+///
+/// 读操作至少等待一个字符到达, 之后只返回已经到达的字符; 写操作直接输出到控制台。
+pub struct ConsoleDev;
+
+impl VfsNodeOps for ConsoleDev {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::default_file(),
+            VfsNodeType::CharDevice,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut read_len = 0;
+        while read_len < buf.len() {
+            match getchar() {
+                Some(c) => {
+                    buf[read_len] = c;
+                    read_len += 1;
+                }
+                None if read_len > 0 => break,
+                None => axtask::yield_now(),
+            }
+        }
+        Ok(read_len)
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        write_bytes(buf);
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
@@ -0,0 +1,151 @@
+//! 设备文件系统(`/dev`)的设备注册接口。
+//!
+//! 内核在 `init_rootfs()` 时创建 devfs, 并注册 `null`、`zero`、`tty`、`console`、
+//! `random`、`urandom` 等基本设备。其余设备由各自的驱动或子系统通过
+//! [`register_device`] 发布自己的设备节点; 块设备通过 [`register_block_device`]
+//! 注册, 按注册顺序依次命名为 `vda`、`vdb`……, 磁盘上的分区再以
+//! [`register_named_block_device`] 注册为 `vda1`、`vda2`……
+//!
+//! 注册过的块设备可以通过 [`block_device`] 按名字取出, 用于挂载文件系统。
+
+mod block;
+mod console;
+
+pub use self::block::BlockDev;
+pub use self::console::ConsoleDev;
+
+use alloc::{collections::BTreeMap, format, string::String, sync::Arc};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axerrno::{ax_err, ax_err_type, AxError, AxResult};
+use axfs_vfs::VfsNodeRef;
+use axsync::Mutex;
+use lazy_init::LazyInit;
+
+use crate::fs::devfs::{DeviceFileSystem, NullDev, RandomDev, ZeroDev};
+use crate::BlockDeviceRef;
+
+/// 块设备名称的最大个数, 即 `vda` 到 `vdz`
+const MAX_BLOCK_DEVICES: usize = 26;
+
+static DEVFS: LazyInit<Arc<DeviceFileSystem>> = LazyInit::new();
+/// `/dev/random`和`/dev/urandom`背后的CSPRNG, 内核中的其它模块通过[`fill_random`]使用
+static RANDOM: LazyInit<Arc<RandomDev>> = LazyInit::new();
+static NEXT_BLOCK_ID: AtomicUsize = AtomicUsize::new(0);
+/// 已注册的块设备, 以设备名为键
+static BLOCK_DEVICES: Mutex<BTreeMap<String, BlockDeviceRef>> = Mutex::new(BTreeMap::new());
+
+/// 创建devfs并注册内核自带的设备, 返回待挂载到`/dev`的文件系统
+pub(crate) fn init_devfs() -> Arc<DeviceFileSystem> {
+    let devfs = Arc::new(DeviceFileSystem::new());
+    devfs.add("null", Arc::new(NullDev));
+    devfs.add("zero", Arc::new(ZeroDev));
+    let foo_dir = devfs.mkdir("foo");
+    foo_dir.add("bar", Arc::new(ZeroDev));
+
+    // tty和console都指向同一个控制台
+    let console = Arc::new(ConsoleDev);
+    devfs.add("tty", console.clone());
+    devfs.add("console", console);
+
+    // random和urandom共享同一个CSPRNG
+    let random = Arc::new(RandomDev::new(boot_seed()));
+    devfs.add("random", random.clone());
+    devfs.add("urandom", random.clone());
+    RANDOM.init_by(random);
+
+    DEVFS.init_by(devfs.clone());
+    devfs
+}
+
+/// 用内核的CSPRNG产生的随机字节填满`buf`, 与读`/dev/urandom`相同
+///
+/// 必须在devfs初始化之后调用。
+pub fn fill_random(buf: &mut [u8]) {
+    RANDOM.fill_bytes(buf);
+}
+
+/// 在`/dev`下发布名为`name`的设备节点
+///
+/// - name: 设备名, 不能包含`/`
+/// - node: 设备节点, 需要实现[`axfs_vfs::VfsNodeOps`]
+///
+/// 若devfs尚未初始化, 返回`BadState`; 若同名设备已存在, 返回`AlreadyExists`。
+pub fn register_device(name: &str, node: VfsNodeRef) -> AxResult {
+    let devfs = DEVFS
+        .try_get()
+        .ok_or_else(|| ax_err_type!(BadState, "devfs is not initialized"))?;
+    if name.is_empty() || name.contains('/') {
+        return ax_err!(InvalidInput, "invalid device name");
+    }
+    if !devfs.try_add(name, node) {
+        return ax_err!(AlreadyExists);
+    }
+    info!("  register device /dev/{}", name);
+    Ok(())
+}
+
+/// 从`/dev`下移除名为`name`的设备节点, 已打开的文件不受影响
+pub fn unregister_device(name: &str) -> AxResult {
+    let devfs = DEVFS
+        .try_get()
+        .ok_or_else(|| ax_err_type!(BadState, "devfs is not initialized"))?;
+    if devfs.remove_child(name).is_none() {
+        return ax_err!(NotFound);
+    }
+    BLOCK_DEVICES.lock().remove(name);
+    info!("  unregister device /dev/{}", name);
+    Ok(())
+}
+
+/// 将块设备注册为`/dev/vdX`, 返回分配的设备名
+///
+/// 从下一个未分配的编号开始尝试, 名字已被占用时换下一个, 只有注册成功才占用编号。
+pub fn register_block_device(dev: BlockDeviceRef) -> AxResult<String> {
+    let mut id = NEXT_BLOCK_ID.load(Ordering::Acquire);
+    loop {
+        if id >= MAX_BLOCK_DEVICES {
+            return ax_err!(StorageFull, "too many block devices");
+        }
+        let name = format!("vd{}", (b'a' + id as u8) as char);
+        match register_named_block_device(&name, dev.clone()) {
+            Ok(()) => {
+                NEXT_BLOCK_ID.fetch_max(id + 1, Ordering::AcqRel);
+                return Ok(name);
+            }
+            Err(AxError::AlreadyExists) => id += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// 将块设备以指定的名字`name`注册到`/dev`下, 用于分区等有固定命名规则的设备
+pub fn register_named_block_device(name: &str, dev: BlockDeviceRef) -> AxResult {
+    register_device(name, Arc::new(BlockDev::new(dev.clone())))?;
+    BLOCK_DEVICES.lock().insert(name.into(), dev);
+    Ok(())
+}
+
+/// 获取以`name`注册的块设备, 如`vda2`
+pub fn block_device(name: &str) -> Option<BlockDeviceRef> {
+    BLOCK_DEVICES.lock().get(name).cloned()
+}
+
+/// 生成CSPRNG的初始种子
+///
+/// 目前没有硬件随机数源, 只能混合启动时刻的时钟和栈地址。写入`/dev/random`
+/// 的数据会被继续混入随机数发生器的状态。
+fn boot_seed() -> [u8; 32] {
+    let mut seed = [0u8; 32];
+    let stack_addr = &seed as *const _ as u64;
+    let mut x = axhal::time::current_time_nanos() ^ stack_addr.rotate_left(32);
+    for chunk in seed.chunks_exact_mut(8) {
+        // splitmix64
+        x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = x;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        chunk.copy_from_slice(&(z ^ (z >> 31)).to_le_bytes());
+    }
+    seed
+}
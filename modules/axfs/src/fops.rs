@@ -4,12 +4,16 @@
 //! WithCap在每次方法调用时带入访问权限,使得对底层节点的操作始终带有正确的权限检查。
 //! 整体来说,这个接口定义提供了统一和安全地访问文件系统的方法。通过权限检查和偏移量维护,可以避免许多低级错误。
 
+use alloc::string::String;
+use alloc::sync::Arc;
 use axerrno::{ax_err, ax_err_type, AxResult};
-use axfs_vfs::{VfsError, VfsNodeRef};
+use axfs_vfs::{VfsError, VfsNodeRef, VfsNodeType};
 use axio::SeekFrom;
 use capability::{Cap, WithCap};
 use core::fmt;
 
+use crate::page_cache::{self, PageCache};
+
 pub type FileType = axfs_vfs::VfsNodeType; // 文件类型
 pub type DirEntry = axfs_vfs::VfsDirEntry; // 目录项
 pub type FileAttr = axfs_vfs::VfsNodeAttr; // 文件属性
@@ -20,12 +24,14 @@ pub struct File {
     node: WithCap<VfsNodeRef>, // 包含访问权限的文件节点引用(Inner+Cap,Cap就是三种权限的bitflag)
     is_append: bool,           // 是否以追加模式打开
     offset: u64,
+    cache: Option<Arc<PageCache>>, // 普通文件的页缓存, 读写都经过它
 }
 
 /// Directory operations. 打开的目录
 pub struct Directory {
     node: WithCap<VfsNodeRef>, // 包含访问权限的节点引用
     entry_idx: usize,          // 目录项索引
+    path: String,              // 目录的绝对路径, 用于解析相对于它的路径
 }
 
 #[derive(Clone)]
@@ -98,7 +104,13 @@ impl OpenOptions {
 }
 
 impl File {
-    fn _open_at(dir: Option<&VfsNodeRef>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
+    /// `abs_path`是`path`对应的绝对路径, 页缓存以它为键
+    fn _open_at(
+        dir: Option<&VfsNodeRef>,
+        path: &str,
+        abs_path: String,
+        opts: &OpenOptions,
+    ) -> AxResult<Self> {
         debug!("open file: {} {:?}", path, opts);
         if !opts.is_valid() {
             debug!("invalid open options: {:?}", opts);
@@ -134,23 +146,34 @@ impl File {
             return ax_err!(PermissionDenied);
         }
         node.open()?;
+        let cache = match attr.file_type() {
+            VfsNodeType::File => Some(page_cache::get_or_create(abs_path, node.clone())),
+            _ => None,
+        };
         if opts.truncate {
-            node.truncate(0)?;
+            match &cache {
+                Some(cache) => cache.truncate(0)?,
+                None => node.truncate(0)?,
+            }
         }
         Ok(Self {
             node: WithCap::new(node, access_cap),
             is_append: opts.append,
             offset: 0,
+            cache,
         })
     }
     /// 以相对/绝对路径打开文件
     pub fn open(path: &str, opts: &OpenOptions) -> AxResult<Self> {
-        Self::_open_at(None, path, opts)
+        Self::_open_at(None, path, crate::root::absolute_path(path)?, opts)
     }
     /// 截断文件到指定大小
     pub fn truncate(&self, size: u64) -> AxResult {
-        self.node.access(Cap::WRITE)?.truncate(size)?;
-        Ok(())
+        let node = self.node.access(Cap::WRITE)?;
+        match &self.cache {
+            Some(cache) => cache.truncate(size),
+            None => node.truncate(size),
+        }
     }
     /// 读文件, 返回读取的字节数
     pub fn read(&mut self, buf: &mut [u8]) -> AxResult<usize> {
@@ -158,7 +181,10 @@ impl File {
         // let buf_str = String::from_utf8_lossy(buf);
         // debug!("buf_str!!: {}", buf_str);
         let node = self.node.access(Cap::READ)?;
-        let read_len = node.read_at(self.offset, buf)?;
+        let read_len = match &self.cache {
+            Some(cache) => cache.read_at(self.offset, buf)?,
+            None => node.read_at(self.offset, buf)?,
+        };
         self.offset += read_len as u64;
         // debug!("read_len!!: {}", read_len);
         Ok(read_len)
@@ -169,13 +195,20 @@ impl File {
         if self.is_append {
             self.offset = self.get_attr()?.size(); // 如果是追加模式, 则会将文件指针移动到文件末尾
         };
-        let write_len = node.write_at(self.offset, buf)?;
+        let write_len = match &self.cache {
+            Some(cache) => cache.write_at(self.offset, buf)?,
+            None => node.write_at(self.offset, buf)?,
+        };
         self.offset += write_len as u64;
         Ok(write_len)
     }
     /// 清空缓冲区, 将缓冲区中的数据写入磁盘
     pub fn flush(&self) -> AxResult {
-        self.node.access(Cap::WRITE)?.fsync()?;
+        let node = self.node.access(Cap::WRITE)?;
+        match &self.cache {
+            Some(cache) => cache.sync()?,
+            None => node.fsync()?,
+        }
         Ok(())
     }
     /// 设置文件指针位置
@@ -191,13 +224,28 @@ impl File {
         Ok(new_offset)
     }
     /// 获取文件属性
+    ///
+    /// 有页缓存时从页缓存的节点获取, 它在重命名后会重新打开, 大小也与通过页缓存的写入一致。
     pub fn get_attr(&self) -> AxResult<FileAttr> {
-        self.node.access(Cap::empty())?.get_attr()
+        let node = self.node.access(Cap::empty())?;
+        match &self.cache {
+            Some(cache) => cache.node().get_attr(),
+            None => node.get_attr(),
+        }
+    }
+    /// 获取文件的页缓存, 用于共享映射
+    pub fn page_cache(&self) -> Option<Arc<PageCache>> {
+        self.cache.clone()
     }
 }
 
 impl Directory {
-    fn _open_dir_at(dir: Option<&VfsNodeRef>, path: &str, opts: &OpenOptions) -> AxResult<Self> {
+    fn _open_dir_at(
+        dir: Option<&VfsNodeRef>,
+        path: &str,
+        abs_path: String,
+        opts: &OpenOptions,
+    ) -> AxResult<Self> {
         debug!("open dir: {}", path);
         if !opts.read {
             return ax_err!(InvalidInput);
@@ -220,6 +268,7 @@ impl Directory {
         Ok(Self {
             node: WithCap::new(node, access_cap),
             entry_idx: 0,
+            path: abs_path,
         })
     }
     /// 获取目录项
@@ -230,17 +279,21 @@ impl Directory {
             Ok(Some(self.node.access(Cap::EXECUTE)?))
         }
     }
+    /// 相对于该目录的路径对应的绝对路径
+    fn path_at(&self, path: &str) -> AxResult<String> {
+        crate::root::absolute_path_at(Some(&self.path), path)
+    }
     /// 以相对/绝对路径打开目录
     pub fn open_dir(path: &str, opts: &OpenOptions) -> AxResult<Self> {
-        Self::_open_dir_at(None, path, opts)
+        Self::_open_dir_at(None, path, crate::root::absolute_path(path)?, opts)
     }
     /// 打开目录项
     pub fn open_dir_at(&self, path: &str, opts: &OpenOptions) -> AxResult<Self> {
-        Self::_open_dir_at(self.access_at(path)?, path, opts)
+        Self::_open_dir_at(self.access_at(path)?, path, self.path_at(path)?, opts)
     }
     /// 打开目录项的文件
     pub fn open_file_at(&self, path: &str, opts: &OpenOptions) -> AxResult<File> {
-        File::_open_at(self.access_at(path)?, path, opts)
+        File::_open_at(self.access_at(path)?, path, self.path_at(path)?, opts)
     }
     /// 创建文件
     pub fn create_file(&self, path: &str) -> AxResult<VfsNodeRef> {
@@ -252,7 +305,7 @@ impl Directory {
     }
     /// 删除文件
     pub fn remove_file(&self, path: &str) -> AxResult {
-        crate::root::remove_file(self.access_at(path)?, path)
+        crate::root::remove_file(self.access_at(path)?, path, &self.path_at(path)?)
     }
     /// 删除子目录
     pub fn remove_dir(&self, path: &str) -> AxResult {
@@ -271,6 +324,10 @@ impl Directory {
 
 impl Drop for File {
     fn drop(&mut self) {
+        // 关闭文件时写回共享映射中可能被修改的页
+        if let Some(cache) = &self.cache {
+            cache.sync_pages(0, u64::MAX).ok();
+        }
         unsafe { self.node.access_unchecked().release().ok() };
     }
 }
@@ -3,38 +3,93 @@
 
 use alloc::sync::Arc;
 use core::cell::UnsafeCell;
+use core::mem::ManuallyDrop;
+use core::time::Duration;
 
 use axfs_vfs::{VfsDirEntry, VfsError, VfsNodePerm, VfsResult};
 use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps};
+use axhal::time::wall_time;
 use axsync::Mutex;
-use fatfs::{Dir, File, LossyOemCpConverter, NullTimeProvider, Read, Seek, SeekFrom, Write};
+use fatfs::{Date, DateTime, Time, TimeProvider};
+use fatfs::{Dir, File, LossyOemCpConverter, Read, Seek, SeekFrom, Write};
 // LossyOemCpConverter表示OEM代码页到UTF-16的不可逆转换。这用于fatfs库将FAT32文件系统中的文件名从OEM代码页转换为UTF-16。
-// NullTimeProvider表示不提供时间相关信息。fatfs库在某些操作中需要时间相关信息,此处通过NullTimeProvider表示不提供。
+// fatfs库在创建、修改文件时需要当前时间,由KernelTimeProvider提供。
 // Read、Write和Seek trait分别表示读、写和寻址操作。fatfs库通过这些trait来对抽象的Io设备进行操作。
 
 
 use crate::dev::Disk;
+use crate::BlockDeviceRef;
 
 const BLOCK_SIZE: usize = 512;
 
 /// FAT文件系统结构体,封装fatfs库的FileSystem。
+///
+/// fatfs库的File和Dir借用着FileSystem, 所以FileSystem放在堆上的[`FatFsInner`]中,
+/// 每个节点都持有一份引用, 最后一个引用释放时才释放FileSystem。
 pub struct FatFileSystem {
     // fatfs库的FileSystem
-    inner: fatfs::FileSystem<Disk, NullTimeProvider, LossyOemCpConverter>,
+    inner: Arc<FatFsInner>,
     // FAT文件系统的根目录
     root_dir: UnsafeCell<Option<VfsNodeRef>>,
 }
 
+/// 堆上的fatfs库的FileSystem和它所在的块设备
+struct FatFsInner {
+    fs: ManuallyDrop<fatfs::FileSystem<Disk, KernelTimeProvider, LossyOemCpConverter>>,
+    // 底层的块设备, 同步时写回其中缓存的块
+    dev: BlockDeviceRef,
+}
+
 /// 封装fatfs库的File,实现Send和Sync以用于多线程环境。
-pub struct FileWrapper<'a>(Mutex<File<'a, Disk, NullTimeProvider, LossyOemCpConverter>>);
+///
+/// fatfs库的File不提供读取目录项中时间的接口,所以在打开时从目录项读出时间,
+/// 之后写入文件时与fatfs库同步更新。最后一个字段使借用的FileSystem在File释放之后才释放。
+pub struct FileWrapper<'a>(
+    Mutex<File<'a, Disk, KernelTimeProvider, LossyOemCpConverter>>,
+    Mutex<FatTimes>,
+    Arc<FatFsInner>,
+);
+
+/// 封装fatfs库的Dir,实现Send和Sync以用于多线程环境。最后一个字段的作用与[`FileWrapper`]相同
+pub struct DirWrapper<'a>(
+    Dir<'a, Disk, KernelTimeProvider, LossyOemCpConverter>,
+    FatTimes,
+    Arc<FatFsInner>,
+);
+
+/// 以axhal提供的墙上时间作为fatfs库的时间来源
+///
+/// FAT记录的是本地时间,这里不区分时区,直接使用UTC。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KernelTimeProvider;
+
+impl TimeProvider for KernelTimeProvider {
+    fn get_current_date(&self) -> Date {
+        self.get_current_date_time().date
+    }
 
-/// 封装fatfs库的Dir,实现Send和Sync以用于多线程环境。
-pub struct DirWrapper<'a>(Dir<'a, Disk, NullTimeProvider, LossyOemCpConverter>);
+    fn get_current_date_time(&self) -> DateTime {
+        fat_date_time(wall_time())
+    }
+}
+
+/// 目录项中记录的时间,以距Unix纪元的时长表示
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FatTimes {
+    /// 最后访问时间,FAT只记录日期
+    atime: Duration,
+    /// 最后修改时间
+    mtime: Duration,
+}
 
 unsafe impl Sync for FatFileSystem {}
 
 unsafe impl Send for FatFileSystem {}
 
+unsafe impl Sync for FatFsInner {}
+
+unsafe impl Send for FatFsInner {}
+
 unsafe impl<'a> Send for FileWrapper<'a> {}
 
 unsafe impl<'a> Sync for FileWrapper<'a> {}
@@ -46,28 +101,68 @@ unsafe impl<'a> Sync for DirWrapper<'a> {}
 impl FatFileSystem {
     /// 初始化一个FatFileSystem
     pub fn new(disk: Disk) -> Self {
-        let inner = fatfs::FileSystem::new(disk, fatfs::FsOptions::new())
-            .expect("failed to initialize FAT filesystem");
-        Self {
-            inner,
+        Self::try_new(disk).expect("failed to initialize FAT filesystem")
+    }
+
+    /// 在`disk`上打开FAT文件系统, 磁盘上不是有效的FAT文件系统时返回错误
+    pub fn try_new(disk: Disk) -> VfsResult<Self> {
+        let dev = disk.device();
+        let options = fatfs::FsOptions::new().time_provider(KernelTimeProvider);
+        let fs = fatfs::FileSystem::new(disk, options).map_err(as_vfs_err)?;
+        Ok(Self {
+            inner: Arc::new(FatFsInner {
+                fs: ManuallyDrop::new(fs),
+                dev,
+            }),
             root_dir: UnsafeCell::new(None),
-        }
+        })
+    }
+
+    /// 是否还有根目录之外的节点, 或者根目录被文件系统之外引用着
+    pub fn busy(&self) -> bool {
+        let root_dir = unsafe { (*self.root_dir.get()).as_ref() };
+        // 文件系统本身和根目录各持有一份
+        Arc::strong_count(&self.inner) > 2
+            || root_dir.map_or(false, |dir| Arc::strong_count(dir) > 1)
     }
 
     /// 设置root_dir,必须在其他操作前调用
-    pub fn init(&'static self) {
+    pub fn init(&self) {
         // must be called before later operations
-        unsafe { *self.root_dir.get() = Some(Self::new_dir(self.inner.root_dir())) }
+        // 节点持有`inner`的引用, FileSystem在所有借用它的节点释放之后才释放, 地址也不会改变
+        let fs: &'static fatfs::FileSystem<Disk, KernelTimeProvider, LossyOemCpConverter> =
+            unsafe { &*(&*self.inner.fs as *const _) };
+        // 根目录没有目录项,也就没有时间
+        let root_dir = Self::new_dir(fs.root_dir(), FatTimes::default(), self.inner.clone());
+        unsafe { *self.root_dir.get() = Some(root_dir) }
     }
 
     /// 从fatfs库的File创建一个FileWrapper
-    fn new_file(file: File<'_, Disk, NullTimeProvider, LossyOemCpConverter>) -> Arc<FileWrapper> {
-        Arc::new(FileWrapper(Mutex::new(file)))
+    fn new_file(
+        file: File<'static, Disk, KernelTimeProvider, LossyOemCpConverter>,
+        times: FatTimes,
+        inner: Arc<FatFsInner>,
+    ) -> Arc<FileWrapper<'static>> {
+        Arc::new(FileWrapper(Mutex::new(file), Mutex::new(times), inner))
     }
 
     /// 从fatfs库的Dir创建一个DirWrapper
-    fn new_dir(dir: Dir<'_, Disk, NullTimeProvider, LossyOemCpConverter>) -> Arc<DirWrapper> {
-        Arc::new(DirWrapper(dir))
+    fn new_dir(
+        dir: Dir<'static, Disk, KernelTimeProvider, LossyOemCpConverter>,
+        times: FatTimes,
+        inner: Arc<FatFsInner>,
+    ) -> Arc<DirWrapper<'static>> {
+        Arc::new(DirWrapper(dir, times, inner))
+    }
+}
+
+impl Drop for FatFsInner {
+    /// fatfs库的FileSystem释放时会写回FSInfo扇区等元数据, 之后再把块缓存写回设备
+    fn drop(&mut self) {
+        unsafe { ManuallyDrop::drop(&mut self.fs) };
+        if self.dev.lock().flush().is_err() {
+            warn!("failed to write back the FAT filesystem");
+        }
     }
 }
 
@@ -80,7 +175,13 @@ impl VfsNodeOps for FileWrapper<'static> {
         let blocks = (size + BLOCK_SIZE as u64 - 1) / BLOCK_SIZE as u64;
         // FAT fs doesn't support permissions, we just set everything to 755
         let perm = VfsNodePerm::from_bits_truncate(0o755);
-        Ok(VfsNodeAttr::new(perm, VfsNodeType::File, size, blocks))
+        let times = *self.1.lock();
+        // FAT没有记录属性修改时间,与Linux的vfat一致,用修改时间代替
+        Ok(VfsNodeAttr::new(perm, VfsNodeType::File, size, blocks).with_times(
+            times.atime,
+            times.mtime,
+            times.mtime,
+        ))
     }
 
     fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
@@ -92,16 +193,67 @@ impl VfsNodeOps for FileWrapper<'static> {
     fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
         let mut file = self.0.lock();
         file.seek(SeekFrom::Start(offset)).map_err(as_vfs_err)?; // TODO: more efficient
-        file.write(buf).map_err(as_vfs_err)
+        let len = file.write(buf).map_err(as_vfs_err)?;
+        // fatfs库在写入时会把目录项的修改时间设为当前时间
+        self.1.lock().mtime = wall_time();
+        Ok(len)
     }
 
     fn truncate(&self, size: u64) -> VfsResult {
         let mut file = self.0.lock();
         file.seek(SeekFrom::Start(size)).map_err(as_vfs_err)?; // TODO: more efficient
-        file.truncate().map_err(as_vfs_err)
+        file.truncate().map_err(as_vfs_err)?;
+        self.1.lock().mtime = wall_time();
+        Ok(())
+    }
+
+    /// 将文件的目录项(大小、时间等)写回磁盘
+    fn fsync(&self) -> VfsResult {
+        self.0.lock().flush().map_err(as_vfs_err)
+    }
+
+    /// 设置访问时间和修改时间, FAT的访问时间只精确到日期
+    fn set_times(&self, atime: Option<Duration>, mtime: Option<Duration>) -> VfsResult {
+        let mut file = self.0.lock();
+        let mut times = self.1.lock();
+        if let Some(atime) = atime {
+            let date = fat_date_time(atime).date;
+            file.set_accessed(date);
+            times.atime = unix_time(DateTime::new(date, Time::new(0, 0, 0, 0)));
+        }
+        if let Some(mtime) = mtime {
+            let date_time = fat_date_time(mtime);
+            file.set_modified(date_time);
+            times.mtime = unix_time(date_time);
+        }
+        file.flush().map_err(as_vfs_err)
     }
 }
 
+impl DirWrapper<'static> {
+    /// 读出路径`path`对应的目录项中记录的时间
+    fn entry_times(&self, path: &str) -> Option<FatTimes> {
+        match path.rfind('/') {
+            Some(n) => find_entry_times(&self.0.open_dir(&path[..n]).ok()?, &path[n + 1..]),
+            None => find_entry_times(&self.0, path),
+        }
+    }
+}
+
+/// 在目录`dir`中查找名为`name`的目录项,返回其中记录的时间
+fn find_entry_times(
+    dir: &Dir<'_, Disk, KernelTimeProvider, LossyOemCpConverter>,
+    name: &str,
+) -> Option<FatTimes> {
+    dir.iter()
+        .filter_map(|entry| entry.ok())
+        .find(|entry| entry.file_name().eq_ignore_ascii_case(name))
+        .map(|entry| FatTimes {
+            atime: unix_time(DateTime::new(entry.accessed(), Time::new(0, 0, 0, 0))),
+            mtime: unix_time(entry.modified()),
+        })
+}
+
 /// 实现VfsNodeOps trait以提供目录相关操作的抽象接口
 impl VfsNodeOps for DirWrapper<'static> {
     axfs_vfs::impl_vfs_dir_default! {}  //实现默认的目录操作
@@ -116,14 +268,18 @@ impl VfsNodeOps for DirWrapper<'static> {
             VfsNodeType::Dir,
             BLOCK_SIZE as u64,
             1,
-        ))
+        )
+        .with_times(self.1.atime, self.1.mtime, self.1.mtime))
     }
 
     /// 获取父目录
     fn parent(&self) -> Option<VfsNodeRef> {
         self.0
             .open_dir("..")
-            .map_or(None, |dir| Some(FatFileSystem::new_dir(dir)))
+            .map_or(None, |dir| {
+                let times = self.entry_times("..").unwrap_or_default();
+                Some(FatFileSystem::new_dir(dir, times, self.2.clone()))
+            })
     }
 
     /// 查找目录中的文件或子目录
@@ -139,9 +295,11 @@ impl VfsNodeOps for DirWrapper<'static> {
 
         // TODO: use `fatfs::Dir::find_entry`, but it's not public.
         if let Ok(file) = self.0.open_file(path) {
-            Ok(FatFileSystem::new_file(file))
+            let times = self.entry_times(path).unwrap_or_default();
+            Ok(FatFileSystem::new_file(file, times, self.2.clone()))
         } else if let Ok(dir) = self.0.open_dir(path) {
-            Ok(FatFileSystem::new_dir(dir))
+            let times = self.entry_times(path).unwrap_or_default();
+            Ok(FatFileSystem::new_dir(dir, times, self.2.clone()))
         } else {
             Err(VfsError::NotFound)
         }
@@ -182,6 +340,45 @@ impl VfsNodeOps for DirWrapper<'static> {
         self.0.remove(path).map_err(as_vfs_err)
     }
 
+    /// 重命名或移动目录中的文件或子目录, 目标已存在时将其替换
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        debug!("rename at fatfs: {} -> {}", src_path, dst_path);
+        let src_path = src_path.trim_matches('/');
+        let dst_path = dst_path.trim_matches('/');
+        let src_is_dir = if self.0.open_file(src_path).is_ok() {
+            false
+        } else if self.0.open_dir(src_path).is_ok() {
+            true
+        } else {
+            return Err(VfsError::NotFound);
+        };
+        if src_path == dst_path {
+            return Ok(());
+        }
+        // 不能把目录移动到它自己的子目录下
+        if src_is_dir
+            && dst_path.starts_with(src_path)
+            && dst_path[src_path.len()..].starts_with('/')
+        {
+            return Err(VfsError::InvalidInput);
+        }
+        // fatfs的rename要求目标不存在, 先删除被替换的目标
+        if self.0.open_file(dst_path).is_ok() {
+            if src_is_dir {
+                return Err(VfsError::NotADirectory);
+            }
+            self.0.remove(dst_path).map_err(as_vfs_err)?;
+        } else if self.0.open_dir(dst_path).is_ok() {
+            if !src_is_dir {
+                return Err(VfsError::IsADirectory);
+            }
+            self.0.remove(dst_path).map_err(as_vfs_err)?;
+        }
+        self.0
+            .rename(src_path, &self.0, dst_path)
+            .map_err(as_vfs_err)
+    }
+
     /// 读取目录项
     fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
         let mut iter = self.0.iter().skip(start_idx);
@@ -206,6 +403,15 @@ impl VfsNodeOps for DirWrapper<'static> {
 }
 
 impl VfsOps for FatFileSystem {
+    /// 写回块缓存中的脏块
+    fn sync(&self) -> VfsResult {
+        self.inner.dev.lock().flush().map_err(|_| VfsError::Io)
+    }
+
+    fn umount(&self) -> VfsResult {
+        self.sync()
+    }
+
     /// 根目录
     fn root_dir(&self) -> VfsNodeRef {
         let root_dir = unsafe { (*self.root_dir.get()).as_ref().unwrap() };
@@ -252,7 +458,7 @@ impl Write for Disk {
         Ok(write_len)
     }
     fn flush(&mut self) -> Result<(), Self::Error> {
-        Ok(())
+        Disk::flush(self).map_err(|_| ())
     }
 }
 
@@ -273,6 +479,56 @@ impl Seek for Disk {
     }
 }
 
+/// 将Unix时间戳转换为FAT的日期时间
+///
+/// FAT只能表示1980年到2107年之间的时间, 超出范围时取边界值。
+fn fat_date_time(time: Duration) -> DateTime {
+    let secs = time.as_secs();
+    let (days, rem) = (secs / 86400, secs % 86400);
+    // 由1970-01-01起的天数计算公历日期, 以3月为一年的开始, 使闰日位于年末
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    if year < 1980 {
+        DateTime::new(Date::new(1980, 1, 1), Time::new(0, 0, 0, 0))
+    } else if year > 2107 {
+        DateTime::new(Date::new(2107, 12, 31), Time::new(23, 59, 59, 999))
+    } else {
+        DateTime::new(
+            Date::new(year as u16, month as u16, day as u16),
+            Time::new(
+                (rem / 3600) as u16,
+                (rem % 3600 / 60) as u16,
+                (rem % 60) as u16,
+                time.subsec_millis() as u16,
+            ),
+        )
+    }
+}
+
+/// 将FAT的日期时间转换为Unix时间戳
+fn unix_time(date_time: DateTime) -> Duration {
+    let (date, time) = (date_time.date, date_time.time);
+    let (month, day) = (date.month as u64, date.day as u64);
+    // 以3月为一年的开始计算距1970-01-01的天数, 与fat_date_time相反
+    let year = date.year as u64 - if month <= 2 { 1 } else { 0 };
+    let era = year / 400;
+    let yoe = year - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    let secs = days * 86400 + time.hour as u64 * 3600 + time.min as u64 * 60 + time.sec as u64;
+    Duration::new(secs, time.millis as u32 * 1_000_000)
+}
+
 /// 将fatfs库的Error映射为VfsError
 const fn as_vfs_err(err: fatfs::Error<()>) -> VfsError {
     use fatfs::Error::*;
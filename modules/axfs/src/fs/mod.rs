@@ -3,3 +3,6 @@ pub mod fatfs;
 
 #[cfg(feature = "devfs")]
 pub use axfs_devfs as devfs;
+
+#[cfg(feature = "ramfs")]
+pub use axfs_ramfs as ramfs;
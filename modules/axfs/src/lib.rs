@@ -8,10 +8,25 @@ mod dev;
 mod fs;
 mod root;
 
+pub mod page_cache;
 pub mod api;
+#[cfg(feature = "devfs")]
+pub mod devfs;
+#[cfg(feature = "devfs")]
+pub mod loopdev;
 pub mod fops;
+pub mod lock;
+pub mod perm;
 
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use axsync::Mutex;
+use core::time::Duration;
+use driver_block::cache::{BlockCache, CacheStats};
+use driver_block::partition::{parse_partitions, PartitionInfo};
+use driver_block::BlockDriverOps;
 use driver_common::BaseDriverOps;
+use lazy_init::LazyInit;
 
 cfg_if::cfg_if! {
     if #[cfg(feature = "use-virtio-blk")] {
@@ -21,10 +36,153 @@ cfg_if::cfg_if! {
     }
 }
 
+/// 块设备的共享引用, 文件系统和`/dev`下的设备节点访问的是同一个设备
+pub type BlockDeviceRef = Arc<Mutex<dyn BlockDriverOps>>;
+
+/// 块缓存最多缓存的块数, 共512KiB
+const BLOCK_CACHE_CAPACITY: usize = 1024;
+/// 顺序读缺失时额外预读的块数
+const BLOCK_CACHE_READ_AHEAD: usize = 16;
+/// 写回任务每隔这么久写回一次块缓存中的脏块
+const WRITEBACK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 根块设备之上的块缓存, 所有文件系统和设备节点都经由它访问磁盘
+static BLOCK_CACHE: LazyInit<Arc<Mutex<BlockCache<BlockDevice>>>> = LazyInit::new();
+
+/// 根块设备在`/dev`下的名字, 它总是第一个注册的块设备
+#[cfg(not(feature = "initramfs"))]
+const ROOT_DISK_NAME: &str = "vda";
+
+/// 初始化文件系统
+///
+/// 读取`blk_dev`上的分区表, 每个分区作为独立的块设备注册为`/dev/vda1`、`/dev/vda2`……
+/// 根文件系统所在的设备由内核命令行的`root=`参数指定, 如`root=/dev/vda2`; 未指定时,
+/// 有分区表则使用第一个分区, 否则使用整个磁盘。
+///
+/// 启用`initramfs`特征时根文件系统是解包了initramfs的ramfs, 磁盘和分区只注册到`/dev`下。
 pub fn init_filesystems(blk_dev: BlockDevice) {
     info!("Initialize filesystems...");
     info!("  use block device: {:?}", blk_dev.device_name());
 
-    let disk = self::dev::Disk::new(blk_dev);
-    self::root::init_rootfs(disk);
+    let cache = BlockCache::new(blk_dev, BLOCK_CACHE_CAPACITY, BLOCK_CACHE_READ_AHEAD);
+    BLOCK_CACHE.init_by(Arc::new(Mutex::new(cache)));
+    let blk_dev: BlockDeviceRef = BLOCK_CACHE.clone();
+    axhal::misc::set_terminate_hook(flush_block_cache);
+    axtask::spawn(writeback_task);
+
+    let partitions: Vec<(PartitionInfo, BlockDeviceRef)> = parse_partitions(&mut *blk_dev.lock())
+        .unwrap_or_else(|e| {
+            warn!("  failed to read the partition table: {:?}", e);
+            Vec::new()
+        })
+        .into_iter()
+        .map(|info| {
+            info!(
+                "  partition {}: start block {}, {} blocks",
+                info.index, info.start_block, info.num_blocks
+            );
+            let part =
+                self::dev::Partition::new_ref(blk_dev.clone(), info.start_block, info.num_blocks);
+            (info, part)
+        })
+        .collect();
+
+    #[cfg(feature = "initramfs")]
+    self::root::init_rootfs_from_initramfs();
+    #[cfg(not(feature = "initramfs"))]
+    {
+        let root_dev = select_root_device(axhal::cmdline::get("root"), &blk_dev, &partitions);
+        let disk = self::dev::Disk::new(root_dev);
+        self::root::init_rootfs(disk);
+    }
+
+    #[cfg(feature = "devfs")]
+    {
+        let name = self::devfs::register_block_device(blk_dev)
+            .expect("failed to register the root block device");
+        for (info, part) in partitions {
+            let part_name = alloc::format!("{}{}", name, info.index);
+            if let Err(e) = self::devfs::register_named_block_device(&part_name, part) {
+                warn!("  failed to register /dev/{}: {:?}", part_name, e);
+            }
+        }
+        self::loopdev::init();
+    }
+}
+
+/// 在没有块设备的配置下初始化文件系统, 根文件系统是解包了initramfs的ramfs
+#[cfg(feature = "initramfs")]
+pub fn init_initramfs() {
+    info!("Initialize filesystems...");
+    info!("  use initramfs as the root filesystem");
+    self::root::init_rootfs_from_initramfs();
+
+    #[cfg(feature = "devfs")]
+    self::loopdev::init();
+}
+
+/// 根据`root=`参数选择根文件系统所在的块设备
+#[cfg(not(feature = "initramfs"))]
+fn select_root_device(
+    root: Option<&str>,
+    disk: &BlockDeviceRef,
+    partitions: &[(PartitionInfo, BlockDeviceRef)],
+) -> BlockDeviceRef {
+    let default = || partitions.first().map_or(disk, |(_, part)| part).clone();
+    let Some(root) = root else {
+        return default();
+    };
+    let suffix = root
+        .strip_prefix("/dev/")
+        .and_then(|name| name.strip_prefix(ROOT_DISK_NAME));
+    let selected = match suffix {
+        Some("") => Some(disk.clone()),
+        Some(index) => index.parse::<usize>().ok().and_then(|index| {
+            partitions
+                .iter()
+                .find(|(info, _)| info.index == index)
+                .map(|(_, part)| part.clone())
+        }),
+        None => None,
+    };
+    info!("  root device: {}", root);
+    selected.unwrap_or_else(|| {
+        warn!("  root device {} not found, use the default one", root);
+        default()
+    })
+}
+
+/// 写回块缓存中所有的脏块, 关机前调用
+///
+/// 可能在panic时调用, 块缓存正被占用(例如panic时持有它)时放弃写回, 而不是等待。
+fn flush_block_cache() {
+    let Some(mut cache) = BLOCK_CACHE.try_lock() else {
+        warn!("block cache is busy, dirty blocks are not written back");
+        return;
+    };
+    if let Err(e) = cache.flush() {
+        warn!("failed to write back the block cache: {:?}", e);
+    }
+}
+
+/// 定时写回块缓存中的脏块, 使系统崩溃时丢失的数据有上限
+fn writeback_task() {
+    loop {
+        axtask::sleep(WRITEBACK_INTERVAL);
+        let mut cache = BLOCK_CACHE.lock();
+        if cache.dirty_blocks() == 0 {
+            continue;
+        }
+        debug!("write back {} dirty blocks", cache.dirty_blocks());
+        if let Err(e) = cache.flush() {
+            warn!("failed to write back the block cache: {:?}", e);
+        }
+    }
+}
+
+/// 获取块缓存的命中和缺失统计
+///
+/// 没有块设备(如只使用initramfs启动)时没有块缓存, 返回`None`。
+pub fn block_cache_stats() -> Option<CacheStats> {
+    BLOCK_CACHE.is_init().then(|| BLOCK_CACHE.lock().stats())
 }
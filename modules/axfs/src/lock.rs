@@ -0,0 +1,321 @@
+//! 文件的建议锁
+//!
+//! 支持两类互不影响的锁:
+//! - `flock`锁属于打开的文件, 通过`dup`或`fork`共享同一个打开文件的描述符持有同一把锁,
+//!   显式解锁或最后一个描述符关闭时释放;
+//! - `fcntl`记录锁属于进程, 锁住文件中的一段字节, 进程关闭该文件的任意描述符或退出时释放。
+//!
+//! 与页缓存一样, 锁表以规范化的绝对路径为键。每个被加锁的文件有一个等待队列, 阻塞的加锁者
+//! 在上面睡眠, 锁被释放时唤醒它们重新尝试。等待条件在调度队列的锁内检查, 所以锁表使用
+//! 不会睡眠的自旋锁保护。
+//!
+//! 等待记录锁的请求也记录在锁表中, 进程在等待前沿着"等待者→阻止它加锁的锁的持有者"查找,
+//! 如果会回到自己, 说明等待会形成死锁, 返回`Deadlock`(EDEADLK)。
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use axerrno::{ax_err, AxResult};
+use axsync::spin::SpinNoIrq;
+use axtask::WaitQueue;
+
+use crate::root::absolute_path;
+
+/// 所有被加锁的文件的锁, 以绝对路径为键
+static LOCKS: SpinNoIrq<BTreeMap<String, FileLocks>> = SpinNoIrq::new(BTreeMap::new());
+
+/// 锁的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockKind {
+    /// 共享锁(读锁), 可以被多个持有者同时持有
+    Shared,
+    /// 排他锁(写锁)
+    Exclusive,
+}
+
+impl LockKind {
+    /// 两把锁是否冲突: 只有两把都是共享锁时才不冲突
+    fn conflicts(self, other: Self) -> bool {
+        self == Self::Exclusive || other == Self::Exclusive
+    }
+}
+
+/// 进程持有的一段记录锁, 锁住`[start, end)`中的字节
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordLock {
+    /// 锁的类型
+    pub kind: LockKind,
+    /// 起始偏移
+    pub start: u64,
+    /// 结束偏移(不含), `u64::MAX`表示一直锁到文件末尾, 包括之后扩展出的部分
+    pub end: u64,
+    /// 持有锁的进程
+    pub pid: u64,
+}
+
+impl RecordLock {
+    fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.start < end && start < self.end
+    }
+}
+
+/// 一个文件上的所有锁
+struct FileLocks {
+    /// `flock`锁, 持有者是打开的文件的标识
+    flocks: Vec<(usize, LockKind)>,
+    /// `fcntl`记录锁
+    records: Vec<RecordLock>,
+    /// 正在等待的记录锁请求, 用于检查死锁
+    waiting: Vec<RecordLock>,
+    /// 等待这个文件上的锁的任务
+    wq: Arc<WaitQueue>,
+}
+
+impl FileLocks {
+    fn new() -> Self {
+        Self {
+            flocks: Vec::new(),
+            records: Vec::new(),
+            waiting: Vec::new(),
+            wq: Arc::new(WaitQueue::new()),
+        }
+    }
+
+    /// 其他持有者的`flock`锁是否与`kind`冲突
+    fn flock_conflicts(&self, owner: usize, kind: LockKind) -> bool {
+        self.flocks
+            .iter()
+            .any(|&(other, other_kind)| other != owner && other_kind.conflicts(kind))
+    }
+
+    /// 其他进程持有的与`lock`冲突的记录锁
+    fn record_conflicts<'a>(
+        &'a self,
+        lock: &'a RecordLock,
+    ) -> impl Iterator<Item = &'a RecordLock> {
+        self.records.iter().filter(|l| {
+            l.pid != lock.pid && l.overlaps(lock.start, lock.end) && l.kind.conflicts(lock.kind)
+        })
+    }
+
+    /// 返回其他进程持有的与`lock`冲突的第一把记录锁
+    fn record_conflict(&self, lock: &RecordLock) -> Option<RecordLock> {
+        self.record_conflicts(lock).next().copied()
+    }
+
+    /// 去掉进程`pid`在`[start, end)`上的记录锁, 部分重叠的锁会被截短或拆成两段
+    fn unlock_records(&mut self, pid: u64, start: u64, end: u64) {
+        let mut kept = Vec::with_capacity(self.records.len());
+        for lock in self.records.drain(..) {
+            if lock.pid != pid || !lock.overlaps(start, end) {
+                kept.push(lock);
+                continue;
+            }
+            if lock.start < start {
+                kept.push(RecordLock { end: start, ..lock });
+            }
+            if lock.end > end {
+                kept.push(RecordLock { start: end, ..lock });
+            }
+        }
+        self.records = kept;
+    }
+
+    /// 没有锁, 也没有任务在等待时, 这一项可以从锁表中删除
+    fn is_unused(&self) -> bool {
+        self.flocks.is_empty() && self.records.is_empty() && Arc::strong_count(&self.wq) == 1
+    }
+}
+
+/// 进程`request.pid`在`path`上等待加锁`request`是否会形成死锁
+///
+/// 从阻止加锁的锁的持有者出发, 找到它们正在等待的请求, 再找阻止这些请求的锁的持有者,
+/// 如此反复, 遇到`request.pid`说明形成了等待环。
+fn would_deadlock(table: &BTreeMap<String, FileLocks>, path: &str, request: &RecordLock) -> bool {
+    let mut pending = alloc::vec![(path, *request)];
+    let mut visited = BTreeSet::new();
+    while let Some((path, wanted)) = pending.pop() {
+        let Some(locks) = table.get(path) else {
+            continue;
+        };
+        for holder in locks.record_conflicts(&wanted) {
+            if holder.pid == request.pid {
+                return true;
+            }
+            if visited.insert(holder.pid) {
+                for (path, locks) in table {
+                    let waits = locks.waiting.iter().filter(|w| w.pid == holder.pid);
+                    pending.extend(waits.map(|w| (path.as_str(), *w)));
+                }
+            }
+        }
+    }
+    false
+}
+
+/// 不断尝试在`path`上加锁: `conflicts`为假时调用`lock`修改锁表并返回。
+/// 存在冲突时, `wait`为假则返回`Again`, 否则在该文件的等待队列上睡眠, 被唤醒后重试。
+///
+/// `request`是要加的记录锁, 等待它会形成死锁时返回`Deadlock`。
+fn acquire<C, L>(
+    path: String,
+    wait: bool,
+    request: Option<RecordLock>,
+    conflicts: C,
+    lock: L,
+) -> AxResult
+where
+    C: Fn(&FileLocks) -> bool,
+    L: FnOnce(&mut FileLocks),
+{
+    loop {
+        let mut table = LOCKS.lock();
+        let locks = table.entry(path.clone()).or_insert_with(FileLocks::new);
+        if !conflicts(locks) {
+            lock(locks);
+            // 锁的降级或拆分可能让其他等待者可以继续
+            let wq = locks.wq.clone();
+            drop(table);
+            wq.notify_all(false);
+            return Ok(());
+        }
+        let deadlock = wait && request.map_or(false, |r| would_deadlock(&table, &path, &r));
+        let locks = table.get_mut(&path).unwrap();
+        if !wait || deadlock {
+            if locks.is_unused() {
+                table.remove(&path);
+            }
+            return if deadlock {
+                ax_err!(Deadlock)
+            } else {
+                ax_err!(Again)
+            };
+        }
+        locks.waiting.extend(request);
+        // 持有等待队列的引用期间这一项不会被删除, 释放锁的一方一定能唤醒我们
+        let wq = locks.wq.clone();
+        drop(table);
+        wq.wait_until(|| {
+            LOCKS
+                .lock()
+                .get(&path)
+                .map_or(true, |locks| !conflicts(locks))
+        });
+        if let Some(request) = request {
+            let mut table = LOCKS.lock();
+            let waiting = &mut table.get_mut(&path).unwrap().waiting;
+            let idx = waiting.iter().position(|w| *w == request).unwrap();
+            waiting.swap_remove(idx);
+        }
+    }
+}
+
+/// 用`f`修改`path`上的锁, 然后唤醒等待该文件的任务
+fn release<F: FnOnce(&mut FileLocks)>(path: &str, f: F) {
+    let mut table = LOCKS.lock();
+    let locks = match table.get_mut(path) {
+        Some(locks) => locks,
+        None => return,
+    };
+    f(locks);
+    let wq = locks.wq.clone();
+    if locks.flocks.is_empty() && locks.records.is_empty() && Arc::strong_count(&wq) == 2 {
+        table.remove(path);
+    }
+    drop(table);
+    wq.notify_all(false);
+}
+
+/// 以打开的文件`owner`的身份对`path`加`flock`锁
+///
+/// 已经持有锁时转换锁的类型。与Linux一样, 转换不是原子的: 先释放原来的锁再重新加锁,
+/// 这样两个持有共享锁的进程同时升级时不会死锁。
+pub fn flock(path: &str, owner: usize, kind: LockKind, wait: bool) -> AxResult {
+    let path = absolute_path(path)?;
+    let held = LOCKS.lock().get(&path).and_then(|locks| {
+        locks
+            .flocks
+            .iter()
+            .find(|&&(other, _)| other == owner)
+            .map(|&(_, kind)| kind)
+    });
+    match held {
+        Some(held) if held == kind => return Ok(()),
+        Some(_) => release(&path, |locks| {
+            locks.flocks.retain(|&(other, _)| other != owner)
+        }),
+        None => {}
+    }
+    acquire(
+        path,
+        wait,
+        None,
+        |locks| locks.flock_conflicts(owner, kind),
+        |locks| locks.flocks.push((owner, kind)),
+    )
+}
+
+/// 释放打开的文件`owner`在`path`上的`flock`锁, 没有持有锁时什么也不做
+pub fn funlock(path: &str, owner: usize) -> AxResult {
+    let path = absolute_path(path)?;
+    release(&path, |locks| {
+        locks.flocks.retain(|&(other, _)| other != owner)
+    });
+    Ok(())
+}
+
+/// 为进程`lock.pid`加记录锁, 替换该进程在同一范围内已有的锁
+///
+/// `wait`为真时等待冲突的锁被释放, 等待会形成死锁时返回`Deadlock`。
+pub fn lock_records(path: &str, lock: RecordLock, wait: bool) -> AxResult {
+    if lock.start >= lock.end {
+        return ax_err!(InvalidInput);
+    }
+    acquire(
+        absolute_path(path)?,
+        wait,
+        Some(lock),
+        |locks| locks.record_conflict(&lock).is_some(),
+        |locks| {
+            locks.unlock_records(lock.pid, lock.start, lock.end);
+            locks.records.push(lock);
+        },
+    )
+}
+
+/// 释放进程`pid`在`path`的`[start, end)`范围内的记录锁
+pub fn unlock_records(path: &str, pid: u64, start: u64, end: u64) -> AxResult {
+    let path = absolute_path(path)?;
+    release(&path, |locks| locks.unlock_records(pid, start, end));
+    Ok(())
+}
+
+/// 检查能否加记录锁`lock`, 返回阻止加锁的第一把锁, 可以加锁时返回`None`
+pub fn test_records(path: &str, lock: &RecordLock) -> AxResult<Option<RecordLock>> {
+    let path = absolute_path(path)?;
+    Ok(LOCKS
+        .lock()
+        .get(&path)
+        .and_then(|locks| locks.record_conflict(lock)))
+}
+
+/// 进程`pid`关闭`path`上的文件时释放它在该文件上的所有记录锁
+pub fn release_records(path: &str, pid: u64) {
+    if let Ok(path) = absolute_path(path) {
+        release(&path, |locks| locks.records.retain(|lock| lock.pid != pid));
+    }
+}
+
+/// 进程`pid`退出时释放它持有的所有记录锁
+pub fn release_process(pid: u64) {
+    let paths: Vec<String> = LOCKS
+        .lock()
+        .iter()
+        .filter(|(_, locks)| locks.records.iter().any(|lock| lock.pid == pid))
+        .map(|(path, _)| path.clone())
+        .collect();
+    for path in paths {
+        release(&path, |locks| locks.records.retain(|lock| lock.pid != pid));
+    }
+}
@@ -0,0 +1,233 @@
+//! 回环块设备`/dev/loopN`
+//!
+//! 回环设备把一个普通文件当作块设备使用, 文件中第`i`个512字节就是设备的第`i`个块,
+//! 于是文件系统镜像文件可以像磁盘分区一样被挂载。
+//!
+//! 启动时创建[`NUM_LOOP_DEVICES`]个未绑定文件的回环设备, 通过[`attach`]绑定文件、
+//! [`detach`]解除绑定, 分别对应`LOOP_SET_FD`和`LOOP_CLR_FD`。直接挂载镜像文件时会自动
+//! 占用一个空闲的回环设备, 卸载时自动释放。
+//!
+//! 回环设备通过[`File`]访问文件, 读写经过文件的页缓存, 与其他打开这个文件的进程看到的内容一致。
+
+use alloc::{format, string::String, sync::Arc, vec::Vec};
+
+use axerrno::{ax_err, ax_err_type, AxResult};
+use axio::SeekFrom;
+use axsync::Mutex;
+use driver_block::BlockDriverOps;
+use driver_common::{BaseDriverOps, DevError, DevResult, DeviceType};
+use lazy_init::LazyInit;
+
+use crate::fops::{File, OpenOptions};
+use crate::BlockDeviceRef;
+
+/// 启动时创建的回环设备个数
+pub const NUM_LOOP_DEVICES: usize = 8;
+
+/// 回环设备的块大小
+const BLOCK_SIZE: usize = 512;
+
+static LOOP_DEVICES: LazyInit<Vec<Arc<Mutex<LoopDevice>>>> = LazyInit::new();
+
+/// 回环设备绑定的文件
+struct Backing {
+    file: File,
+    path: String,
+    writable: bool,
+    num_blocks: u64,
+    /// 是否在卸载其上的文件系统时自动解除绑定
+    autoclear: bool,
+}
+
+/// 一个回环设备, 未绑定文件时大小为0, 读写返回`BadState`
+pub struct LoopDevice {
+    backing: Option<Backing>,
+}
+
+impl LoopDevice {
+    const fn new() -> Self {
+        Self { backing: None }
+    }
+
+    /// 检查访问是否在设备范围内, 返回绑定的文件和在文件中的偏移
+    fn locate(&mut self, block_id: u64, len: usize) -> DevResult<(&mut Backing, u64)> {
+        let backing = self.backing.as_mut().ok_or(DevError::BadState)?;
+        let count = (len / BLOCK_SIZE) as u64;
+        if len % BLOCK_SIZE != 0
+            || block_id > backing.num_blocks
+            || count > backing.num_blocks - block_id
+        {
+            return Err(DevError::InvalidParam);
+        }
+        Ok((backing, block_id * BLOCK_SIZE as u64))
+    }
+}
+
+impl BaseDriverOps for LoopDevice {
+    fn device_type(&self) -> DeviceType {
+        DeviceType::Block
+    }
+
+    fn device_name(&self) -> &str {
+        "loop"
+    }
+}
+
+impl BlockDriverOps for LoopDevice {
+    fn num_blocks(&self) -> u64 {
+        self.backing.as_ref().map_or(0, |backing| backing.num_blocks)
+    }
+
+    fn block_size(&self) -> usize {
+        BLOCK_SIZE
+    }
+
+    fn read_block(&mut self, block_id: u64, buf: &mut [u8]) -> DevResult {
+        let (backing, offset) = self.locate(block_id, buf.len())?;
+        let file = &mut backing.file;
+        file.seek(SeekFrom::Start(offset)).map_err(|_| DevError::Io)?;
+        let mut read = 0;
+        while read < buf.len() {
+            match file.read(&mut buf[read..]).map_err(|_| DevError::Io)? {
+                0 => break,
+                n => read += n,
+            }
+        }
+        // 文件在读的过程中可能被截断
+        buf[read..].fill(0);
+        Ok(())
+    }
+
+    fn write_block(&mut self, block_id: u64, buf: &[u8]) -> DevResult {
+        let (backing, offset) = self.locate(block_id, buf.len())?;
+        if !backing.writable {
+            return Err(DevError::Unsupported);
+        }
+        let file = &mut backing.file;
+        file.seek(SeekFrom::Start(offset)).map_err(|_| DevError::Io)?;
+        let mut written = 0;
+        while written < buf.len() {
+            match file.write(&buf[written..]).map_err(|_| DevError::Io)? {
+                0 => return Err(DevError::Io),
+                n => written += n,
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> DevResult {
+        match &self.backing {
+            Some(backing) if backing.writable => backing.file.flush().map_err(|_| DevError::Io),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 创建回环设备并注册为`/dev/loop0`到`/dev/loop7`
+pub(crate) fn init() {
+    let devices: Vec<_> = (0..NUM_LOOP_DEVICES)
+        .map(|_| Arc::new(Mutex::new(LoopDevice::new())))
+        .collect();
+    for (i, dev) in devices.iter().enumerate() {
+        let dev: BlockDeviceRef = dev.clone();
+        if let Err(e) = crate::devfs::register_named_block_device(&format!("loop{}", i), dev) {
+            warn!("  failed to register /dev/loop{}: {:?}", i, e);
+        }
+    }
+    LOOP_DEVICES.init_by(devices);
+}
+
+fn device(index: usize) -> AxResult<&'static Arc<Mutex<LoopDevice>>> {
+    LOOP_DEVICES
+        .try_get()
+        .and_then(|devices| devices.get(index))
+        .ok_or_else(|| ax_err_type!(NotFound, "no such loop device"))
+}
+
+fn is_same(dev: &Arc<Mutex<LoopDevice>>, other: &BlockDeviceRef) -> bool {
+    Arc::as_ptr(dev) as *const () == Arc::as_ptr(other) as *const ()
+}
+
+fn bind(dev: &Mutex<LoopDevice>, path: &str, writable: bool, autoclear: bool) -> AxResult {
+    let mut dev = dev.lock();
+    if dev.backing.is_some() {
+        return ax_err!(ResourceBusy);
+    }
+    let mut opts = OpenOptions::new();
+    opts.read(true);
+    opts.write(writable);
+    let file = File::open(path, &opts)?;
+    let attr = file.get_attr()?;
+    if !attr.is_file() {
+        return ax_err!(InvalidInput, "loop devices can only be backed by regular files");
+    }
+    dev.backing = Some(Backing {
+        file,
+        path: crate::root::absolute_path(path)?,
+        writable,
+        num_blocks: attr.size() / BLOCK_SIZE as u64,
+        autoclear,
+    });
+    Ok(())
+}
+
+/// 将文件`path`绑定到回环设备`loop{index}`, 对应`LOOP_SET_FD`
+///
+/// 设备已经绑定了文件时返回`ResourceBusy`。设备的大小在绑定时确定, 不足一个块的文件末尾被忽略。
+pub fn attach(index: usize, path: &str, writable: bool) -> AxResult {
+    bind(device(index)?, path, writable, false)?;
+    info!("attached {} to /dev/loop{}", path, index);
+    Ok(())
+}
+
+/// 解除回环设备`loop{index}`与文件的绑定, 对应`LOOP_CLR_FD`
+///
+/// 设备上还挂载着文件系统时返回`ResourceBusy`。
+pub fn detach(index: usize) -> AxResult {
+    let dev = device(index)?;
+    let as_ref: BlockDeviceRef = dev.clone();
+    if crate::root::device_busy(&as_ref) {
+        return ax_err!(ResourceBusy);
+    }
+    let backing = dev
+        .lock()
+        .backing
+        .take()
+        .ok_or_else(|| ax_err_type!(InvalidInput, "loop device is not attached"))?;
+    if backing.writable {
+        backing.file.flush()?;
+    }
+    info!("detached {} from /dev/loop{}", backing.path, index);
+    Ok(())
+}
+
+/// 将文件`path`绑定到一个空闲的回环设备, 卸载时自动解除绑定, 返回该设备
+pub(crate) fn attach_free(path: &str, writable: bool) -> AxResult<BlockDeviceRef> {
+    let devices = LOOP_DEVICES
+        .try_get()
+        .ok_or_else(|| ax_err_type!(BadState, "loop devices are not initialized"))?;
+    for (i, dev) in devices.iter().enumerate() {
+        match bind(dev, path, writable, true) {
+            Ok(()) => {
+                info!("attached {} to /dev/loop{}", path, i);
+                return Ok(dev.clone());
+            }
+            Err(axerrno::AxError::ResourceBusy) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    ax_err!(ResourceBusy, "no free loop device")
+}
+
+/// 块设备`dev`上的文件系统被卸载后调用, 自动绑定的回环设备在此解除绑定
+pub(crate) fn release(dev: &BlockDeviceRef) {
+    let Some(devices) = LOOP_DEVICES.try_get() else {
+        return;
+    };
+    if let Some(index) = devices.iter().position(|loop_dev| is_same(loop_dev, dev)) {
+        let autoclear = matches!(&devices[index].lock().backing, Some(b) if b.autoclear);
+        if autoclear {
+            detach(index).ok();
+        }
+    }
+}
@@ -0,0 +1,352 @@
+//! 按文件组织的页缓存
+//!
+//! 每个打开的普通文件对应一个[`PageCache`], 以4K页为单位缓存文件内容。通过路径打开同一个文件的
+//! 所有[`File`](crate::fops::File)共享同一个页缓存, `MAP_SHARED`的文件映射也直接映射其中的页帧,
+//! 因此读写和共享映射看到的是同一份数据。
+//!
+//! FAT等文件系统每次查找都会创建新的节点, 没有稳定的inode, 所以页缓存以规范化的绝对路径为键,
+//! 在重命名和删除文件时同步更新。FAT的节点记录着目录项的位置, 重命名后会失效, 所以页缓存在重命名前
+//! 写回原来的节点, 之后在新路径上重新打开节点。相对于目录打开的文件也先转换为绝对路径, 与通过路径打开的共享页缓存。
+//!
+//! 写入是直写的: `write_at`先写入文件再更新已缓存的页。共享映射中的修改无法感知, 被可写地共享映射过的页
+//! 标记为脏页, 在`msync`、解除映射和关闭文件时写回; 写回时不再被映射的页随后变为干净的。
+//!
+//! 每个文件最多缓存[`PAGE_CACHE_CAPACITY`]页, 超出时换出最久未使用的干净且没有被映射的页。
+
+use alloc::alloc::{alloc_zeroed, Layout};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axerrno::{ax_err_type, AxResult};
+use axfs_vfs::VfsNodeRef;
+use axhal::mem::{virt_to_phys, PhysAddr, PAGE_SIZE_4K};
+use axsync::Mutex;
+
+/// 页大小
+const PAGE_SIZE: u64 = PAGE_SIZE_4K as u64;
+/// 每个文件最多缓存的页数, 共4MiB。被映射的页和脏页不会被换出, 它们较多时会暂时超出这个数
+pub const PAGE_CACHE_CAPACITY: usize = 1024;
+
+/// 所有存活的页缓存, 以绝对路径为键。没有文件和映射引用时页缓存被释放
+static PAGE_CACHES: Mutex<BTreeMap<String, Weak<PageCache>>> = Mutex::new(BTreeMap::new());
+
+/// 按页对齐的一页内存
+///
+/// 内核堆位于线性映射区域, 其中的页可以通过物理地址直接映射给用户。
+#[repr(C, align(4096))]
+struct PageFrame([u8; PAGE_SIZE_4K]);
+
+impl PageFrame {
+    fn alloc_zero() -> AxResult<Box<Self>> {
+        let layout = Layout::new::<Self>();
+        let ptr = unsafe { alloc_zeroed(layout) } as *mut Self;
+        if ptr.is_null() {
+            return Err(ax_err_type!(NoMemory));
+        }
+        Ok(unsafe { Box::from_raw(ptr) })
+    }
+}
+
+/// 页缓存中的一页
+pub struct CachedPage {
+    frame: Mutex<Box<PageFrame>>,
+    /// 页帧的物理地址, 映射时在自旋锁内读取, 所以单独保存
+    paddr: PhysAddr,
+    /// 被可写地共享映射过, 映射者可能已经修改了它, 需要写回
+    dirty: AtomicBool,
+}
+
+impl CachedPage {
+    /// 页帧的物理地址
+    pub const fn paddr(&self) -> PhysAddr {
+        self.paddr
+    }
+}
+
+/// 一个文件已缓存的页
+struct Pages {
+    /// 页号到缓存页及其最近一次使用时刻的映射
+    slots: BTreeMap<u64, (Arc<CachedPage>, u64)>,
+    /// 最近一次使用的时刻到页号的映射, 最久未使用的在前
+    lru: BTreeMap<u64, u64>,
+    clock: u64,
+}
+
+impl Pages {
+    const fn new() -> Self {
+        Self {
+            slots: BTreeMap::new(),
+            lru: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// 获取第`index`页, 并记录这次使用
+    fn get(&mut self, index: u64) -> Option<Arc<CachedPage>> {
+        let now = self.tick();
+        let (page, last_use) = self.slots.get_mut(&index)?;
+        self.lru.remove(last_use);
+        *last_use = now;
+        self.lru.insert(now, index);
+        Some(page.clone())
+    }
+
+    /// 获取第`index`页, 不影响换出的顺序
+    fn peek(&self, index: u64) -> Option<&Arc<CachedPage>> {
+        self.slots.get(&index).map(|(page, _)| page)
+    }
+
+    /// 加入第`index`页, 已满时先换出一页
+    fn insert(&mut self, index: u64, page: Arc<CachedPage>) {
+        if self.slots.len() >= PAGE_CACHE_CAPACITY {
+            self.evict();
+        }
+        let now = self.tick();
+        self.slots.insert(index, (page, now));
+        self.lru.insert(now, index);
+    }
+
+    /// 换出最久未使用的、干净且没有被映射的一页
+    fn evict(&mut self) {
+        let victim = self.lru.iter().find_map(|(&last_use, &index)| {
+            let (page, _) = &self.slots[&index];
+            let evictable = Arc::strong_count(page) == 1 && !page.dirty.load(Ordering::Acquire);
+            evictable.then_some((last_use, index))
+        });
+        if let Some((last_use, index)) = victim {
+            self.lru.remove(&last_use);
+            self.slots.remove(&index);
+        }
+    }
+
+    /// 丢弃第`first`页及之后的页
+    fn truncate(&mut self, first: u64) {
+        for (_, (_, last_use)) in self.slots.split_off(&first) {
+            self.lru.remove(&last_use);
+        }
+    }
+}
+
+/// 一个文件的页缓存
+pub struct PageCache {
+    /// 文件的节点, 重命名后替换为在新路径上打开的节点
+    node: Mutex<VfsNodeRef>,
+    pages: Mutex<Pages>,
+}
+
+impl PageCache {
+    fn new(node: VfsNodeRef) -> Self {
+        Self {
+            node: Mutex::new(node),
+            pages: Mutex::new(Pages::new()),
+        }
+    }
+
+    /// 文件当前的节点
+    pub fn node(&self) -> VfsNodeRef {
+        self.node.lock().clone()
+    }
+
+    /// 当前缓存的页数
+    pub fn cached_pages(&self) -> usize {
+        self.pages.lock().slots.len()
+    }
+
+    /// 文件当前的大小
+    pub fn size(&self) -> AxResult<u64> {
+        Ok(self.node().get_attr()?.size())
+    }
+
+    /// 获取第`index`页, 不在缓存中时从文件读入, 超出文件末尾的部分填0
+    pub fn get_page(&self, index: u64) -> AxResult<Arc<CachedPage>> {
+        let mut pages = self.pages.lock();
+        if let Some(page) = pages.get(index) {
+            return Ok(page);
+        }
+        let mut frame = PageFrame::alloc_zero()?;
+        let buf = &mut frame.0;
+        let node = self.node();
+        let mut read = 0;
+        while read < buf.len() {
+            match node.read_at(index * PAGE_SIZE + read as u64, &mut buf[read..])? {
+                0 => break,
+                n => read += n,
+            }
+        }
+        let page = Arc::new(CachedPage {
+            paddr: virt_to_phys((frame.0.as_ptr() as usize).into()),
+            frame: Mutex::new(frame),
+            dirty: AtomicBool::new(false),
+        });
+        pages.insert(index, page.clone());
+        Ok(page)
+    }
+
+    /// 获取第`index`页用于共享映射, `writable`为真时映射者可能修改它, 之后同步时会写回这一页
+    pub fn get_shared_page(&self, index: u64, writable: bool) -> AxResult<Arc<CachedPage>> {
+        let page = self.get_page(index)?;
+        if writable {
+            page.dirty.store(true, Ordering::Release);
+        }
+        Ok(page)
+    }
+
+    /// 从`offset`处读取文件内容, 返回读取的字节数
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
+        let end = (offset + buf.len() as u64).min(self.size()?);
+        let mut pos = offset;
+        while pos < end {
+            let start = (pos % PAGE_SIZE) as usize;
+            let count = (PAGE_SIZE_4K - start).min((end - pos) as usize);
+            let page = self.get_page(pos / PAGE_SIZE)?;
+            let frame = page.frame.lock();
+            buf[(pos - offset) as usize..][..count].copy_from_slice(&frame.0[start..start + count]);
+            pos += count as u64;
+        }
+        Ok(end.saturating_sub(offset) as usize)
+    }
+
+    /// 向`offset`处写入数据, 返回写入的字节数
+    ///
+    /// 数据直接写入文件, 已缓存的页同时更新。
+    pub fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize> {
+        let written = self.node().write_at(offset, buf)?;
+        let pages = self.pages.lock();
+        let end = offset + written as u64;
+        let mut pos = offset;
+        while pos < end {
+            let start = (pos % PAGE_SIZE) as usize;
+            let count = (PAGE_SIZE_4K - start).min((end - pos) as usize);
+            if let Some(page) = pages.peek(pos / PAGE_SIZE) {
+                page.frame.lock().0[start..start + count]
+                    .copy_from_slice(&buf[(pos - offset) as usize..][..count]);
+            }
+            pos += count as u64;
+        }
+        Ok(written)
+    }
+
+    /// 将文件截断或扩展到`size`, 丢弃文件末尾之后的缓存
+    pub fn truncate(&self, size: u64) -> AxResult {
+        self.node().truncate(size)?;
+        let mut pages = self.pages.lock();
+        pages.truncate((size + PAGE_SIZE - 1) / PAGE_SIZE);
+        if size % PAGE_SIZE != 0 {
+            if let Some(page) = pages.peek(size / PAGE_SIZE) {
+                page.frame.lock().0[(size % PAGE_SIZE) as usize..].fill(0);
+            }
+        }
+        Ok(())
+    }
+
+    /// 将`[start, start + count)`页中的脏页写回文件
+    ///
+    /// 只写回文件末尾之前的部分, 映射不会改变文件的大小。写回后已经不再被映射的页变为干净的,
+    /// 仍被映射的页之后可能还会被修改, 保持为脏页。
+    pub fn sync_pages(&self, start: u64, count: u64) -> AxResult {
+        let size = self.size()?;
+        let pages: Vec<_> = self
+            .pages
+            .lock()
+            .slots
+            .range(start..start.saturating_add(count))
+            .filter(|(_, (page, _))| page.dirty.load(Ordering::Acquire))
+            .map(|(&index, (page, _))| (index, page.clone()))
+            .collect();
+        for (index, page) in pages {
+            let offset = index * PAGE_SIZE;
+            if offset < size {
+                let len = (size - offset).min(PAGE_SIZE) as usize;
+                let frame = page.frame.lock();
+                self.node().write_at(offset, &frame.0[..len])?;
+            }
+            // 在锁内检查, 这样新建立的映射在检查之后才会重新标记脏页; 引用者为页缓存和这里
+            let _pages = self.pages.lock();
+            if Arc::strong_count(&page) == 2 {
+                page.dirty.store(false, Ordering::Release);
+            }
+        }
+        Ok(())
+    }
+
+    /// 将所有脏页写回文件, 并同步文件本身
+    pub fn sync(&self) -> AxResult {
+        self.sync_pages(0, u64::MAX)?;
+        self.node().fsync()
+    }
+}
+
+/// 获取绝对路径`path`上的文件的页缓存, 不存在时以`node`创建
+pub(crate) fn get_or_create(path: String, node: VfsNodeRef) -> Arc<PageCache> {
+    let mut caches = PAGE_CACHES.lock();
+    if let Some(cache) = caches.get(&path).and_then(Weak::upgrade) {
+        return cache;
+    }
+    caches.retain(|_, cache| cache.strong_count() > 0);
+    let cache = Arc::new(PageCache::new(node));
+    caches.insert(path, Arc::downgrade(&cache));
+    cache
+}
+
+/// 文件被删除后, 同一路径上的新文件不能再使用原来的页缓存
+pub(crate) fn remove(path: &str) {
+    PAGE_CACHES.lock().remove(path);
+}
+
+/// `path`及其下所有文件的路径
+fn paths_under<'a>(
+    caches: &'a BTreeMap<String, Weak<PageCache>>,
+    path: &'a str,
+) -> impl Iterator<Item = &'a String> {
+    let prefix = String::from(path) + "/";
+    caches
+        .keys()
+        .filter(move |p| p.as_str() == path || p.starts_with(&prefix))
+}
+
+/// 重命名前写回`path`及其下所有文件的节点, 之后这些节点不再使用
+pub(crate) fn prepare_rename(path: &str) -> AxResult {
+    let path = path.trim_end_matches('/');
+    let caches: Vec<_> = {
+        let caches = PAGE_CACHES.lock();
+        paths_under(&caches, path)
+            .filter_map(|p| caches[p].upgrade())
+            .collect()
+    };
+    caches.iter().try_for_each(|cache| cache.node().fsync())
+}
+
+/// 重命名后更新`old`及其下所有文件的页缓存的路径, 并用`reopen`在新路径上重新打开它们的节点。
+/// `new`上原有的页缓存作废
+pub(crate) fn rename(old: &str, new: &str, reopen: impl Fn(&str) -> AxResult<VfsNodeRef>) {
+    let (old, new) = (old.trim_end_matches('/'), new.trim_end_matches('/'));
+    let mut moved = Vec::new();
+    {
+        let mut caches = PAGE_CACHES.lock();
+        caches.remove(new);
+        let paths: Vec<String> = paths_under(&caches, old).cloned().collect();
+        for path in paths {
+            let cache = caches.remove(&path).unwrap();
+            let new_path = String::from(new) + &path[old.len()..];
+            if let Some(cache) = cache.upgrade() {
+                moved.push((new_path.clone(), cache));
+            }
+            caches.insert(new_path, cache);
+        }
+    }
+    for (path, cache) in moved {
+        match reopen(&path) {
+            Ok(node) => *cache.node.lock() = node,
+            Err(e) => warn!("failed to reopen {} after rename: {:?}", path, e),
+        }
+    }
+}
@@ -0,0 +1,172 @@
+//! 基于Unix权限位的访问控制
+//!
+//! 每个节点的属性中记录了所有者的用户ID、组ID和权限位。调用者按照自己的身份属于所有者、
+//! 同组用户和其他用户中的一类, 只能使用这一类对应的三个权限位。root用户(用户ID为0)
+//! 不受读写权限的限制, 但执行普通文件仍然要求至少有一个执行权限位。
+//!
+//! 这里的检查只依赖调用者传入的[`Identity`], 由系统调用层根据当前进程的身份构造。
+//! 不记录权限的文件系统(如FAT)中所有节点都属于root, 权限为`0o755`。
+
+use alloc::{string::String, vec::Vec};
+use axerrno::{ax_err, AxResult};
+pub use capability::Cap;
+
+use crate::fops::{FileAttr, FilePerm};
+
+/// 进行权限检查时调用者的身份
+#[derive(Debug, Clone)]
+pub struct Identity {
+    /// 用户ID
+    pub uid: u32,
+    /// 组ID
+    pub gid: u32,
+    /// 附加组ID
+    pub groups: Vec<u32>,
+}
+
+impl Identity {
+    /// root用户的身份, 不受读写权限的限制
+    pub const fn root() -> Self {
+        Self {
+            uid: 0,
+            gid: 0,
+            groups: Vec::new(),
+        }
+    }
+
+    /// 是否是root用户
+    pub const fn is_root(&self) -> bool {
+        self.uid == 0
+    }
+
+    /// 是否属于组`gid`
+    pub fn in_group(&self, gid: u32) -> bool {
+        self.gid == gid || self.groups.contains(&gid)
+    }
+}
+
+/// 计算`id`对属性为`attr`的节点拥有的权限
+pub fn permitted(attr: &FileAttr, id: &Identity) -> Cap {
+    let bits = attr.perm().bits() as u32;
+    if id.is_root() {
+        let mut cap = Cap::READ | Cap::WRITE;
+        if attr.is_dir() || bits & 0o111 != 0 {
+            cap |= Cap::EXECUTE;
+        }
+        return cap;
+    }
+    let shift = if attr.uid() == id.uid {
+        6
+    } else if id.in_group(attr.gid()) {
+        3
+    } else {
+        0
+    };
+    let class = (bits >> shift) & 0o7;
+    let mut cap = Cap::empty();
+    if class & 0o4 != 0 {
+        cap |= Cap::READ;
+    }
+    if class & 0o2 != 0 {
+        cap |= Cap::WRITE;
+    }
+    if class & 0o1 != 0 {
+        cap |= Cap::EXECUTE;
+    }
+    cap
+}
+
+/// 检查`id`对属性为`attr`的节点是否拥有`access`中的全部权限, 否则返回`PermissionDenied`
+pub fn check(attr: &FileAttr, id: &Identity, access: Cap) -> AxResult {
+    if permitted(attr, id).contains(access) {
+        Ok(())
+    } else {
+        ax_err!(PermissionDenied)
+    }
+}
+
+/// 检查`id`对路径`path`上每一级目录的搜索(执行)权限, 不检查`path`本身
+fn check_search(path: &str, id: &Identity) -> AxResult {
+    if id.is_root() {
+        return Ok(());
+    }
+    for (n, _) in path.match_indices('/') {
+        let dir = if n == 0 { "/" } else { &path[..n] };
+        let node = crate::root::lookup(None, dir)?;
+        check(&node.get_attr()?, id, Cap::EXECUTE)?;
+    }
+    Ok(())
+}
+
+/// 将`path`转换为绝对路径, 返回它的父目录和它本身
+fn split_parent(path: &str) -> AxResult<(String, String)> {
+    let path = crate::root::absolute_path(path)?;
+    let path = path.trim_end_matches('/');
+    let parent = match path.rfind('/') {
+        Some(0) | None => "/",
+        Some(n) => &path[..n],
+    };
+    Ok((parent.into(), if path.is_empty() { "/" } else { path }.into()))
+}
+
+/// 检查`id`对节点`path`是否拥有`access`中的全部权限, 同时检查路径上每一级目录的搜索权限
+pub fn check_access(path: &str, id: &Identity, access: Cap) -> AxResult {
+    let (_, path) = split_parent(path)?;
+    check_search(&path, id)?;
+    check(&crate::root::lookup(None, &path)?.get_attr()?, id, access)
+}
+
+/// 检查`id`能否创建节点`path`, 要求对父目录有写和执行权限
+pub fn check_create(path: &str, id: &Identity) -> AxResult {
+    let (parent, _) = split_parent(path)?;
+    check_access(&parent, id, Cap::WRITE | Cap::EXECUTE)
+}
+
+/// 检查`id`能否删除节点`path`
+///
+/// 要求对父目录有写和执行权限。父目录设置了粘滞位时, 还要求`id`是节点或父目录的所有者。
+pub fn check_remove(path: &str, id: &Identity) -> AxResult {
+    let (parent, path) = split_parent(path)?;
+    check_access(&parent, id, Cap::WRITE | Cap::EXECUTE)?;
+    let dir_attr = crate::root::lookup(None, &parent)?.get_attr()?;
+    if id.is_root() || !dir_attr.perm().contains(FilePerm::STICKY) {
+        return Ok(());
+    }
+    let attr = crate::root::lookup(None, &path)?.get_attr()?;
+    if attr.uid() == id.uid || dir_attr.uid() == id.uid {
+        Ok(())
+    } else {
+        ax_err!(PermissionDenied)
+    }
+}
+
+/// 检查`id`能否修改节点`path`的权限位, 要求`id`是root或节点的所有者
+pub fn check_chmod(path: &str, id: &Identity) -> AxResult {
+    let (_, path) = split_parent(path)?;
+    check_search(&path, id)?;
+    let attr = crate::root::lookup(None, &path)?.get_attr()?;
+    if id.is_root() || attr.uid() == id.uid {
+        Ok(())
+    } else {
+        ax_err!(PermissionDenied)
+    }
+}
+
+/// 检查`id`能否将节点`path`的所有者改为`uid`、所属组改为`gid`, `None`表示不修改
+///
+/// 只有root能修改所有者。节点的所有者可以把所属组改为自己所在的组。
+pub fn check_chown(path: &str, id: &Identity, uid: Option<u32>, gid: Option<u32>) -> AxResult {
+    let (_, path) = split_parent(path)?;
+    check_search(&path, id)?;
+    let attr = crate::root::lookup(None, &path)?.get_attr()?;
+    if id.is_root() {
+        return Ok(());
+    }
+    let uid_ok = uid.map_or(true, |uid| uid == attr.uid());
+    let gid_ok = gid.map_or(true, |gid| gid == attr.gid() || id.in_group(gid));
+    if attr.uid() == id.uid && uid_ok && gid_ok {
+        Ok(())
+    } else {
+        ax_err!(PermissionDenied)
+    }
+}
@@ -11,7 +11,7 @@
 //! 根目录包含:
 //!
 //! - `main_fs`: 主文件系统
-//! - `mounts`: 挂载的数据结构,包含挂载点路径和文件系统
+//! - `mounts`: 挂载的数据结构,包含挂载点路径和文件系统, 运行时可以挂载和卸载块设备上的文件系统
 //!
 //! 大多数操作都委托给 `main_fs` 或 `mounts` 中对应的文件系统。但创建/删除挂载点目录
 //! 以及挂载/取消挂载文件系统的操作由根目录自己处理。
@@ -21,12 +21,13 @@
 //! 可以通过 `init_rootfs()` 初始化根目录。 然后使用各种方法在根目录下查找、创建和删除
 //! 文件和目录。可以通过 `set_current_dir()` 改变当前工作目录。
 use alloc::{string::String, sync::Arc, vec::Vec};
-use axerrno::{ax_err, AxError, AxResult};
+use core::time::Duration;
+use axerrno::{ax_err, ax_err_type, AxError, AxResult};
 use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
 use axsync::Mutex;
 use lazy_init::LazyInit;
 
-use crate::{api::FileType, fs};
+use crate::{api::FileType, fops::FilePerm, fs, BlockDeviceRef};
 
 static CURRENT_DIR_PATH: Mutex<String> = Mutex::new(String::new());
 static CURRENT_DIR: LazyInit<Mutex<VfsNodeRef>> = LazyInit::new();
@@ -36,22 +37,38 @@ type MainFileSystem = fs::fatfs::FatFileSystem;
 
 /// 表示一个挂载点的数据结构
 struct MountPoint {
-    path: &'static str,
+    path: String,
     fs: Arc<dyn VfsOps>,
+    /// 文件系统所在的块设备, 只有运行时挂载的文件系统才有
+    dev: Option<BlockDeviceRef>,
 }
 
 /// 文件系统的根目录
 struct RootDirectory {
     main_fs: Arc<dyn VfsOps>,
-    mounts: Vec<MountPoint>,
+    /// 主文件系统所在的块设备, 主文件系统是initramfs时没有
+    main_dev: Option<BlockDeviceRef>,
+    mounts: Mutex<Vec<MountPoint>>,
 }
 
 static MAIN_FS: LazyInit<Arc<MainFileSystem>> = LazyInit::new();
 static ROOT_DIR: LazyInit<Arc<RootDirectory>> = LazyInit::new();
 
+/// 运行时挂载在块设备上的文件系统, 用于检查其中是否还有被引用的节点
+///
+/// 卸载时移除, 文件系统在最后一个引用释放时写回设备, 这发生在回环设备解除绑定之前。
+#[cfg(feature = "devfs")]
+static DEVICE_FS: Mutex<Vec<DeviceFs>> = Mutex::new(Vec::new());
+
+#[cfg(feature = "devfs")]
+struct DeviceFs {
+    dev: BlockDeviceRef,
+    fs: Arc<MainFileSystem>,
+}
+
 impl MountPoint {
-    pub fn new(path: &'static str, fs: Arc<dyn VfsOps>) -> Self {
-        Self { path, fs }
+    pub fn new(path: String, fs: Arc<dyn VfsOps>, dev: Option<BlockDeviceRef>) -> Self {
+        Self { path, fs, dev }
     }
 }
 
@@ -63,36 +80,69 @@ impl Drop for MountPoint {
 
 impl RootDirectory {
     /// 创建一个新的根目录
-    pub const fn new(main_fs: Arc<dyn VfsOps>) -> Self {
+    pub const fn new(main_fs: Arc<dyn VfsOps>, main_dev: Option<BlockDeviceRef>) -> Self {
         Self {
             main_fs,
-            mounts: Vec::new(),
+            main_dev,
+            mounts: Mutex::new(Vec::new()),
         }
     }
-    /// 在路径`path`下挂载文件系统`fs`
-    pub fn mount(&mut self, path: &'static str, fs: Arc<dyn VfsOps>) -> AxResult {
-        if path == "/" {
+    /// 在路径`path`下挂载文件系统`fs`, `dev`为文件系统所在的块设备
+    pub fn mount(&self, path: &str, fs: Arc<dyn VfsOps>, dev: Option<BlockDeviceRef>) -> AxResult {
+        let path = path.trim_end_matches('/');
+        if path.is_empty() {
             return ax_err!(InvalidInput, "cannot mount root filesystem");
         }
         if !path.starts_with('/') {
             return ax_err!(InvalidInput, "mount path must start with '/'");
         }
-        if self.mounts.iter().any(|mp| mp.path == path) {       // 检查是否已经挂载
+        let mut mounts = self.mounts.lock();
+        if mounts.iter().any(|mp| mp.path == path) {       // 检查是否已经挂载
             return ax_err!(InvalidInput, "mount point already exists");
         }
         // create the mount point in the main filesystem if it does not exist
-        MAIN_FS.root_dir().create(path, FileType::Dir)?;
-        fs.mount(path, MAIN_FS.root_dir().lookup(path)?)?;
-        self.mounts.push(MountPoint::new(path, fs));
+        match self.main_fs.root_dir().create(path, FileType::Dir) {
+            Ok(()) | Err(AxError::AlreadyExists) => {}
+            Err(e) => return Err(e),
+        }
+        fs.mount(path, self.main_fs.root_dir().lookup(path)?)?;
+        mounts.push(MountPoint::new(path.into(), fs, dev));
         Ok(())
     }
-    /// 取消挂载路径`path`下的文件系统
-    pub fn _umount(&mut self, path: &str) {
-        self.mounts.retain(|mp| mp.path != path);
+    /// 卸载路径`path`下运行时挂载的文件系统, 卸载时写回其中缓存的数据, 返回文件系统所在的块设备
+    pub fn umount(&self, path: &str) -> AxResult<BlockDeviceRef> {
+        let path = path.trim_end_matches('/');
+        let mut mounts = self.mounts.lock();
+        let idx = mounts
+            .iter()
+            .position(|mp| mp.path == path)
+            .ok_or_else(|| ax_err_type!(InvalidInput, "not a mount point"))?;
+        let Some(dev) = mounts[idx].dev.clone() else {
+            return ax_err!(PermissionDenied, "cannot umount a builtin filesystem");
+        };
+        let fs = mounts[idx].fs.clone();
+        drop(mounts);
+        // 先写回, 失败时保持挂载
+        fs.sync()?;
+        let mut mounts = self.mounts.lock();
+        // 还有打开的文件、目录或当前目录在其中时不能卸载
+        if device_fs_busy(&fs) {
+            return ax_err!(ResourceBusy);
+        }
+        let mp = mounts.iter().position(|mp| mp.path == path).map(|idx| mounts.remove(idx));
+        drop(mounts);
+        drop(mp);
+        Ok(dev)
     }
     /// 检查路径`path`是否已经挂载
     pub fn contains(&self, path: &str) -> bool {
-        self.mounts.iter().any(|mp| mp.path == path)
+        self.mounts.lock().iter().any(|mp| mp.path == path)
+    }
+    /// 检查块设备`dev`上是否有已经挂载的文件系统, 与已挂载的设备重叠(如磁盘和它的分区)也算
+    fn device_busy(&self, dev: &BlockDeviceRef) -> bool {
+        let overlaps = |other: &BlockDeviceRef| crate::dev::overlaps(other, dev);
+        matches!(&self.main_dev, Some(d) if overlaps(d))
+            || self.mounts.lock().iter().any(|mp| matches!(&mp.dev, Some(d) if overlaps(d)))
     }
     /// 在路径`path`下查找已经挂载文件系统
     fn lookup_mounted_fs<F, T>(&self, path: &str, f: F) -> AxResult<T>
@@ -109,23 +159,26 @@ impl RootDirectory {
         猜测：因为`lookup_mounted_fs()`是根目录的方法，是在根目录上调用的，所以`path`一定是以'/'开头的，并且相对路径和绝对路径只差一个'/'。
          */
 
-        let mut idx = 0;
+        let mut matched = None;
         let mut max_len = 0;
 
         // Find the filesystem that has the longest mounted path match
         // TODO: more efficient, e.g. trie
-        for (i, mp) in self.mounts.iter().enumerate() {
+        // 挂载表可能在运行时改变, 取出文件系统后先释放锁再调用`f`, 因为`f`中可能再次查找
+        for mp in self.mounts.lock().iter() {
             // skip the first '/'
-            if path.starts_with(&mp.path[1..]) && mp.path.len() - 1 > max_len { // 如果`path`以已有的挂载点路径开头
-                max_len = mp.path.len() - 1;
-                idx = i;
+            let mp_path = &mp.path[1..];
+            let at_boundary = |rest: &str| rest.is_empty() || rest.starts_with('/');
+            let matches = path.strip_prefix(mp_path).map_or(false, at_boundary);
+            if matches && mp_path.len() > max_len { // 如果`path`以已有的挂载点路径开头
+                max_len = mp_path.len();
+                matched = Some(mp.fs.clone());
             }
         }
 
-        if max_len == 0 {
-            f(self.main_fs.clone(), path) // not matched any mount point // 没有匹配到挂载点，直接在主文件系统上继续
-        } else {
-            f(self.mounts[idx].fs.clone(), &path[max_len..]) // matched at `idx`  // 在路径匹配的挂载点上继续
+        match matched {
+            None => f(self.main_fs.clone(), path), // not matched any mount point // 没有匹配到挂载点，直接在主文件系统上继续
+            Some(fs) => f(fs, &path[max_len..]), // 在路径匹配的挂载点上继续
         }
     }
 }
@@ -161,6 +214,25 @@ impl VfsNodeOps for RootDirectory {
             }
         })
     }
+    /// 重命名时,源路径和目标路径必须位于同一个文件系统中,否则返回`CrossesDevices`错误。
+    /// 挂载点本身不能被重命名,也不能被替换。
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        self.lookup_mounted_fs(src_path, |src_fs, src_rest| {
+            if src_rest.is_empty() {
+                return ax_err!(PermissionDenied); // cannot rename mount points
+            }
+            self.lookup_mounted_fs(dst_path, |dst_fs, dst_rest| {
+                let same_fs = Arc::as_ptr(&src_fs) as *const () == Arc::as_ptr(&dst_fs) as *const ();
+                if dst_rest.is_empty() {
+                    ax_err!(PermissionDenied)
+                } else if !same_fs {
+                    ax_err!(CrossesDevices)
+                } else {
+                    src_fs.root_dir().rename(src_rest, dst_rest)
+                }
+            })
+        })
+    }
 }
 /// 初始化根文件系统。
 ///
@@ -177,30 +249,42 @@ impl VfsNodeOps for RootDirectory {
 ///
 /// 所以,这个函数会初始化文件系统的根目录,并在上面挂载必要的其它文件系统,为整个文件系统的使用做好准备。
 /// 之后,用户可以通过`ROOT_DIR`来访问根目录,通过`CURRENT_DIR`来访问当前目录。
+#[cfg_attr(feature = "initramfs", allow(dead_code))]
 pub(crate) fn init_rootfs(disk: crate::dev::Disk) {
+    let main_dev = disk.device();
     #[cfg(feature = "fatfs")]
         let main_fs = fs::fatfs::FatFileSystem::new(disk);
 
     MAIN_FS.init_by(Arc::new(main_fs));
     MAIN_FS.init();
 
-    let mut root_dir = RootDirectory::new(MAIN_FS.clone());
+    init_root_dir(MAIN_FS.clone(), Some(main_dev));
+}
 
-    #[cfg(feature = "devfs")]
-    {
-        let null = fs::devfs::NullDev;
-        let zero = fs::devfs::ZeroDev;
-        let bar = fs::devfs::ZeroDev;
-        let devfs = fs::devfs::DeviceFileSystem::new();
-        let foo_dir = devfs.mkdir("foo");
-        devfs.add("null", Arc::new(null));
-        devfs.add("zero", Arc::new(zero));
-        foo_dir.add("bar", Arc::new(bar));
+/// 以链接进内核镜像的initramfs作为根文件系统
+///
+/// initramfs是newc格式的cpio归档, 编译时由环境变量`AX_INITRAMFS`给出它的路径,
+/// 启动时解包到ramfs中。根文件系统不依赖任何块设备。
+#[cfg(feature = "initramfs")]
+pub(crate) fn init_rootfs_from_initramfs() {
+    static INITRAMFS: &[u8] = include_bytes!(env!("AX_INITRAMFS"));
 
-        root_dir
-            .mount("/dev", Arc::new(devfs))
-            .expect("failed to mount devfs at /dev");
+    let ramfs = fs::ramfs::RamFileSystem::new();
+    match ramfs.unpack_cpio(INITRAMFS) {
+        Ok(count) => info!("  unpacked {} entries from initramfs", count),
+        Err(e) => panic!("failed to unpack initramfs: {:?}", e),
     }
+    init_root_dir(Arc::new(ramfs), None);
+}
+
+/// 以`main_fs`为主文件系统创建根目录, 并挂载devfs
+fn init_root_dir(main_fs: Arc<dyn VfsOps>, main_dev: Option<BlockDeviceRef>) {
+    let root_dir = RootDirectory::new(main_fs, main_dev);
+
+    #[cfg(feature = "devfs")]
+    root_dir
+        .mount("/dev", crate::devfs::init_devfs(), None)
+        .expect("failed to mount devfs at /dev");
 
     ROOT_DIR.init_by(Arc::new(root_dir));
     CURRENT_DIR.init_by(Mutex::new(ROOT_DIR.clone()));
@@ -244,6 +328,17 @@ pub(crate) fn absolute_path(path: &str) -> AxResult<String> {
         Ok(axfs_vfs::path::canonicalize(&path))
     }
 }
+/// 将相对于绝对路径为`dir_path`的目录的路径`path`转换为绝对路径。
+/// 如果`dir_path`为`None`, 则相对于当前目录。
+pub(crate) fn absolute_path_at(dir_path: Option<&str>, path: &str) -> AxResult<String> {
+    match dir_path {
+        Some(dir_path) if !path.starts_with('/') => {
+            let path = String::from(dir_path) + "/" + path;
+            Ok(axfs_vfs::path::canonicalize(&path))
+        }
+        _ => absolute_path(path),
+    }
+}
 /// 在目录`dir`下查找路径`path`对应的节点。
 ///
 /// 这个函数会递归地解析路径`path`,获取路径上的每个目录节点并进入,直到找到目标节点。
@@ -312,7 +407,9 @@ pub(crate) fn create_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
 /// 否则获取父目录节点并删除文件节点。
 ///
 /// 用于在文件系统中删除文件节点。它会先判断节点类型和权限,确保我们删除的是可以删除的文件节点。
-pub(crate) fn remove_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
+///
+/// `abs_path`是被删除文件的绝对路径, 用于作废它的页缓存。
+pub(crate) fn remove_file(dir: Option<&VfsNodeRef>, path: &str, abs_path: &str) -> AxResult {
     let node = lookup(dir, path)?;
     let attr = node.get_attr()?;
     if attr.is_dir() {
@@ -322,7 +419,9 @@ pub(crate) fn remove_file(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
     else if !attr.perm().owner_writable() {
         ax_err!(PermissionDenied)
     } else {
-        parent_node_of(dir, path).remove(path)
+        parent_node_of(dir, path).remove(path)?;
+        crate::page_cache::remove(abs_path);
+        Ok(())
     }
 }
 /// 删除目录节点`path`。
@@ -364,6 +463,161 @@ pub(crate) fn remove_dir(dir: Option<&VfsNodeRef>, path: &str) -> AxResult {
         parent_node_of(dir, path).remove(path)
     }
 }
+/// 将`old_path`重命名为`new_path`。
+///
+/// 两个路径都会先转换为绝对路径,再交给根目录处理,因此可以跨越目录移动节点。
+/// 如果`new_path`已经存在,它会被替换(见[`VfsNodeOps::rename`])。
+pub(crate) fn rename(old_path: &str, new_path: &str) -> AxResult {
+    if old_path.is_empty() || new_path.is_empty() {
+        return ax_err!(NotFound);
+    }
+    let old_path = absolute_path(old_path)?;
+    let new_path = absolute_path(new_path)?;
+    if old_path == "/" || new_path == "/" {
+        return ax_err!(PermissionDenied);
+    }
+    crate::page_cache::prepare_rename(&old_path)?;
+    ROOT_DIR.rename(&old_path, &new_path)?;
+    crate::page_cache::rename(&old_path, &new_path, |path| lookup(None, path));
+    Ok(())
+}
+/// 两个路径是否位于同一个文件系统中,路径不需要存在。
+pub(crate) fn same_fs(path1: &str, path2: &str) -> AxResult<bool> {
+    let path1 = absolute_path(path1)?;
+    let path2 = absolute_path(path2)?;
+    ROOT_DIR.lookup_mounted_fs(&path1, |fs1, _| {
+        ROOT_DIR.lookup_mounted_fs(&path2, |fs2, _| {
+            Ok(Arc::as_ptr(&fs1) as *const () == Arc::as_ptr(&fs2) as *const ())
+        })
+    })
+}
+/// 设置路径`path`对应节点的访问时间和修改时间,为`None`的时间保持不变。
+pub(crate) fn set_times(
+    path: &str,
+    atime: Option<Duration>,
+    mtime: Option<Duration>,
+) -> AxResult {
+    lookup(None, path)?.set_times(atime, mtime)
+}
+/// 设置路径`path`对应节点的权限位
+pub(crate) fn set_perm(path: &str, perm: FilePerm) -> AxResult {
+    lookup(None, path)?.set_perm(perm)
+}
+/// 设置路径`path`对应节点的所有者和所属组,为`None`的保持不变
+pub(crate) fn set_owner(path: &str, uid: Option<u32>, gid: Option<u32>) -> AxResult {
+    lookup(None, path)?.set_owner(uid, gid)
+}
+/// 将主文件系统和所有挂载的文件系统中缓存的数据写回存储设备
+pub(crate) fn sync() -> AxResult {
+    ROOT_DIR.main_fs.sync()?;
+    let mounted: Vec<_> = ROOT_DIR.mounts.lock().iter().map(|mp| mp.fs.clone()).collect();
+    for fs in mounted {
+        fs.sync()?;
+    }
+    Ok(())
+}
+/// 将`source`上的FAT文件系统挂载到目录`target`。
+///
+/// `source`可以是在`/dev`下注册过的块设备(如`/dev/vda2`), 也可以是文件系统镜像文件,
+/// 镜像文件会自动绑定到一个空闲的回环设备上, 卸载时解除绑定。同一个设备不能同时挂载两次,
+/// 与已挂载的设备重叠的设备(如已挂载了`/dev/vda1`时的`/dev/vda`)也不能挂载。
+///
+/// 每次挂载都在设备上创建新的文件系统对象, 卸载时释放。
+pub(crate) fn mount(source: &str, target: &str) -> AxResult {
+    #[cfg(feature = "devfs")]
+    {
+        let source = absolute_path(source)?;
+        let target = absolute_path(target)?;
+        if !lookup(None, &target)?.get_attr()?.is_dir() {
+            return ax_err!(NotADirectory);
+        }
+        let dev = match source.strip_prefix("/dev/").and_then(crate::devfs::block_device) {
+            Some(dev) if ROOT_DIR.device_busy(&dev) => return ax_err!(ResourceBusy),
+            Some(dev) => dev,
+            None if lookup(None, &source)?.get_attr()?.is_file() => {
+                crate::loopdev::attach_free(&source, true)?
+            }
+            None => return ax_err!(InvalidInput, "not a block device or an image file"),
+        };
+        let result = device_fs(&dev).and_then(|fs| ROOT_DIR.mount(&target, fs, Some(dev.clone())));
+        if let Err(e) = result {
+            remove_device_fs(&dev);
+            crate::loopdev::release(&dev);
+            return Err(e);
+        }
+        info!("mounted {} at {}", source, target);
+        Ok(())
+    }
+    #[cfg(not(feature = "devfs"))]
+    {
+        let _ = (source, target);
+        ax_err!(Unsupported, "block devices are only available with devfs")
+    }
+}
+/// 在块设备`dev`上创建文件系统, 并记录到[`DEVICE_FS`]中
+#[cfg(feature = "devfs")]
+fn device_fs(dev: &BlockDeviceRef) -> AxResult<Arc<MainFileSystem>> {
+    let fs = Arc::new(MainFileSystem::try_new(crate::dev::Disk::new(dev.clone()))?);
+    fs.init();
+    DEVICE_FS.lock().push(DeviceFs {
+        dev: dev.clone(),
+        fs: fs.clone(),
+    });
+    Ok(fs)
+}
+/// 从[`DEVICE_FS`]中移除块设备`dev`上的文件系统
+///
+/// 在锁外释放移除的文件系统, 没有其它引用时会写回设备。
+#[cfg(feature = "devfs")]
+fn remove_device_fs(dev: &BlockDeviceRef) {
+    let removed: Vec<DeviceFs> = {
+        let mut cached = DEVICE_FS.lock();
+        let (removed, kept) = core::mem::take(&mut *cached)
+            .into_iter()
+            .partition(|d| crate::dev::same_device(&d.dev, dev));
+        *cached = kept;
+        removed
+    };
+    drop(removed);
+}
+/// 检查运行时挂载的文件系统`fs`中是否还有被引用的节点
+fn device_fs_busy(fs: &Arc<dyn VfsOps>) -> bool {
+    #[cfg(feature = "devfs")]
+    {
+        let ptr = Arc::as_ptr(fs) as *const ();
+        DEVICE_FS
+            .lock()
+            .iter()
+            .any(|d| Arc::as_ptr(&d.fs) as *const () == ptr && d.fs.busy())
+    }
+    #[cfg(not(feature = "devfs"))]
+    {
+        let _ = fs;
+        false
+    }
+}
+/// 卸载目录`target`上运行时挂载的文件系统
+///
+/// 其中还有打开的文件、目录或者当前目录在其中时返回`ResourceBusy`。
+pub(crate) fn umount(target: &str) -> AxResult {
+    let target = absolute_path(target)?;
+    let dev = ROOT_DIR.umount(&target)?;
+    #[cfg(feature = "devfs")]
+    {
+        // 先释放文件系统, 写回之后再解除回环设备的绑定
+        remove_device_fs(&dev);
+        crate::loopdev::release(&dev);
+    }
+    #[cfg(not(feature = "devfs"))]
+    let _ = dev;
+    info!("umounted {}", target);
+    Ok(())
+}
+/// 检查块设备`dev`上是否有已经挂载的文件系统
+#[cfg(feature = "devfs")]
+pub(crate) fn device_busy(dev: &BlockDeviceRef) -> bool {
+    ROOT_DIR.device_busy(dev)
+}
 /// 返回当前目录。
 pub(crate) fn current_dir() -> AxResult<String> {
     Ok(CURRENT_DIR_PATH.lock().clone())
@@ -6,6 +6,7 @@ use axio as io;
 use driver_block::ramdisk::RamDisk;
 use fs::{File, FileType, OpenOptions};
 use io::{prelude::*, Error, Result};
+use std::sync::Arc;
 
 const IMG_PATH: &str = "resources/fat16.img";
 
@@ -210,6 +211,20 @@ fn test_devfs() -> Result<()> {
         .collect::<Vec<_>>();
     assert!(dirents.contains(&"null".into()));
     assert!(dirents.contains(&"zero".into()));
+    assert!(dirents.contains(&"urandom".into()));
+    assert!(dirents.contains(&"vda".into()));
+
+    // read /dev/urandom
+    let mut file = File::open("/dev/urandom")?;
+    assert_eq!(file.read(&mut buf)?, N);
+    assert_ne!(buf, [0; N]);
+
+    // read the boot sector of the root disk from /dev/vda
+    let mut sector = [0; 512];
+    let mut file = File::open("/dev/vda")?;
+    assert_eq!(file.metadata()?.file_type(), FileType::BlockDevice);
+    assert_eq!(file.read(&mut sector)?, 512);
+    assert_eq!(sector[510..], [0x55, 0xaa]);
 
     // stat /dev
     let dname = "/dev";
@@ -252,6 +267,242 @@ fn test_devfs() -> Result<()> {
     Ok(())
 }
 
+fn test_page_cache() -> Result<()> {
+    let fname = "/page_cache.txt";
+    println!("test page cache {:?}:", fname);
+
+    let mut writer = File::create(fname)?;
+    let mut reader = File::open(fname)?;
+    let cache = writer.page_cache().unwrap();
+    assert!(Arc::ptr_eq(&cache, &reader.page_cache().unwrap()));
+
+    // writes go through the cache and are seen by other handles
+    assert_eq!(writer.write(b"cached data")?, 11);
+    let mut buf = [0u8; 32];
+    assert_eq!(reader.read(&mut buf)?, 11);
+    assert_eq!(&buf[..11], b"cached data");
+
+    // truncation drops the cached tail
+    writer.set_len(6)?;
+    assert_eq!(fs::read_to_string(fname)?, "cached");
+
+    // opening relative to a directory shares the same cache
+    let mut opts = axfs::fops::OpenOptions::new();
+    opts.read(true);
+    let dir = axfs::fops::Directory::open_dir("/", &opts)?;
+    let file = dir.open_file_at("./page_cache.txt", &opts)?;
+    assert!(Arc::ptr_eq(&cache, &file.page_cache().unwrap()));
+    drop(file);
+    drop(writer);
+    drop(reader);
+
+    // the cache follows renames and goes away on removal
+    let mut file = File::options().read(true).append(true).open(fname)?;
+    fs::rename(fname, "/page_cache2.txt")?;
+    assert!(Arc::ptr_eq(
+        &file.page_cache().unwrap(),
+        &File::open("/page_cache2.txt")?.page_cache().unwrap()
+    ));
+    // writes after the rename land in the renamed file
+    assert_eq!(file.write(b" renamed")?, 8);
+    drop(file);
+    assert_eq!(fs::read_to_string("/page_cache2.txt")?, "cached renamed");
+    fs::remove_file("/page_cache2.txt")?;
+
+    println!("test_page_cache() OK!");
+    Ok(())
+}
+
+fn test_block_cache() -> Result<()> {
+    println!("test block cache...");
+    let stats = axfs::block_cache_stats().unwrap();
+    assert!(stats.hits > 0);
+    assert!(stats.misses > 0);
+    assert_eq!(fs::sync(), Ok(()));
+    println!("test_block_cache() OK!");
+    Ok(())
+}
+
+fn test_loop_device() -> Result<()> {
+    let fname = "/loop.img";
+    println!("test loop device over {:?}:", fname);
+
+    let data: Vec<u8> = (0..4096u32).map(|i| (i % 251) as u8).collect();
+    fs::write(fname, &data)?;
+    axfs::loopdev::attach(0, fname, true)?;
+    assert_err!(axfs::loopdev::attach(0, fname, true), ResourceBusy);
+
+    // blocks of the device are the contents of the file
+    let mut dev = File::options().read(true).write(true).open("/dev/loop0")?;
+    assert_eq!(dev.metadata()?.len(), 4096);
+    let mut buf = vec![0; 4096];
+    dev.read_exact(&mut buf)?;
+    assert_eq!(buf, data);
+
+    // writes to the device show up in the file
+    dev.seek(io::SeekFrom::Start(512))?;
+    dev.write_all(b"loop")?;
+    assert_eq!(&fs::read(fname)?[512..516], b"loop");
+    drop(dev);
+    axfs::loopdev::detach(0)?;
+    assert_err!(axfs::loopdev::detach(0), InvalidInput);
+
+    // the root disk is in use, and the image does not hold a filesystem
+    fs::create_dir("/mnt")?;
+    assert_err!(fs::mount("/dev/vda", "/mnt"), ResourceBusy);
+    assert_err!(fs::mount(fname, "/mnt"));
+    assert_err!(fs::umount("/mnt"), InvalidInput);
+    assert_err!(fs::umount("/dev"), PermissionDenied);
+    // the loop device taken by the failed mount has been released
+    axfs::loopdev::attach(0, fname, false)?;
+    axfs::loopdev::detach(0)?;
+
+    fs::remove_dir("/mnt")?;
+    fs::remove_file(fname)?;
+    println!("test_loop_device() OK!");
+    Ok(())
+}
+
+fn test_unix_permission() -> Result<()> {
+    use axfs::perm::{self, Cap, Identity};
+    use fs::Permissions;
+    println!("test unix permission:");
+
+    let root = Identity::root();
+    let user = Identity {
+        uid: 1000,
+        gid: 1000,
+        groups: vec![100],
+    };
+    // the permission class is chosen by the owner and group of the node
+    let perm = Permissions::from_bits_truncate(0o640);
+    let attr = axfs::fops::FileAttr::new(perm, FileType::File, 0, 0).with_owner(0, 100);
+    assert_eq!(perm::permitted(&attr, &root), Cap::READ | Cap::WRITE);
+    assert_eq!(perm::permitted(&attr, &user), Cap::READ);
+    let other = Identity {
+        groups: vec![],
+        ..user.clone()
+    };
+    assert_eq!(perm::permitted(&attr, &other), Cap::empty());
+    let attr = attr.with_owner(1000, 100);
+    assert_eq!(perm::permitted(&attr, &user), Cap::READ | Cap::WRITE);
+
+    // FAT nodes belong to root and have the permission 0o755
+    let fname = "/short.txt";
+    assert_eq!(fs::metadata(fname)?.permissions().bits(), 0o755);
+    assert_eq!(fs::metadata(fname)?.uid(), 0);
+    assert!(perm::check_access(fname, &user, Cap::READ | Cap::EXECUTE).is_ok());
+    assert_err!(
+        perm::check_access(fname, &user, Cap::WRITE),
+        PermissionDenied
+    );
+    assert!(perm::check_access(fname, &root, Cap::READ | Cap::WRITE).is_ok());
+    assert_err!(perm::check_create("/new.txt", &user), PermissionDenied);
+    assert!(perm::check_create("/new.txt", &root).is_ok());
+    assert_err!(perm::check_remove(fname, &user), PermissionDenied);
+    assert_err!(perm::check_chmod(fname, &user), PermissionDenied);
+    assert_err!(
+        perm::check_chown(fname, &user, None, Some(100)),
+        PermissionDenied
+    );
+    assert!(perm::check_chown(fname, &root, Some(1000), None).is_ok());
+    // FAT cannot record permissions
+    assert_err!(
+        fs::set_permissions(fname, Permissions::from_bits_truncate(0o600)),
+        Unsupported
+    );
+
+    println!("test_unix_permission() OK!");
+    Ok(())
+}
+
+fn test_file_lock() -> Result<()> {
+    use axfs::lock::{self, LockKind, RecordLock};
+    println!("test file lock:");
+
+    let fname = "/short.txt";
+    // flock: shared locks coexist, an exclusive lock excludes every other owner
+    lock::flock(fname, 1, LockKind::Shared, false)?;
+    lock::flock(fname, 2, LockKind::Shared, false)?;
+    assert_err!(lock::flock(fname, 3, LockKind::Exclusive, false), Again);
+    lock::funlock(fname, 2)?;
+    // upgrading an owner's own lock only conflicts with other owners
+    lock::flock(fname, 1, LockKind::Exclusive, false)?;
+    assert_err!(lock::flock(fname, 2, LockKind::Shared, false), Again);
+    lock::funlock(fname, 1)?;
+    lock::flock(fname, 3, LockKind::Exclusive, false)?;
+    lock::funlock(fname, 3)?;
+
+    // record locks only conflict when the ranges overlap
+    let lock = |kind, start, end, pid| RecordLock {
+        kind,
+        start,
+        end,
+        pid,
+    };
+    lock::lock_records(fname, lock(LockKind::Exclusive, 0, 100, 1), false)?;
+    lock::lock_records(fname, lock(LockKind::Exclusive, 100, u64::MAX, 2), false)?;
+    assert_err!(
+        lock::lock_records(fname, lock(LockKind::Shared, 50, 150, 3), false),
+        Again
+    );
+    assert_eq!(
+        lock::test_records(fname, &lock(LockKind::Shared, 200, 300, 3))?,
+        Some(lock(LockKind::Exclusive, 100, u64::MAX, 2))
+    );
+    // unlocking the middle of a lock splits it in two
+    lock::unlock_records(fname, 1, 40, 60)?;
+    lock::lock_records(fname, lock(LockKind::Shared, 45, 55, 3), false)?;
+    assert_eq!(
+        lock::test_records(fname, &lock(LockKind::Exclusive, 30, 50, 3))?,
+        Some(lock(LockKind::Exclusive, 0, 40, 1))
+    );
+    // a process's own locks never conflict, and are replaced instead
+    lock::lock_records(fname, lock(LockKind::Shared, 0, 100, 1), false)?;
+    lock::lock_records(fname, lock(LockKind::Shared, 0, 40, 3), false)?;
+    lock::release_records(fname, 2);
+    lock::release_process(1);
+    lock::release_process(3);
+    assert_eq!(
+        lock::test_records(fname, &lock(LockKind::Exclusive, 0, u64::MAX, 4))?,
+        None
+    );
+
+    // two processes waiting for each other's locks: whichever waits second gets
+    // Deadlock, and the other one gets the lock once it is given up
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+    static DONE: AtomicBool = AtomicBool::new(false);
+    static OTHER_RESULT: Mutex<Option<Result<()>>> = Mutex::new(None);
+    lock::lock_records(fname, lock(LockKind::Exclusive, 0, 10, 1), false)?;
+    lock::lock_records(fname, lock(LockKind::Exclusive, 10, 20, 2), false)?;
+    axtask::spawn(move || {
+        let result = lock::lock_records(fname, lock(LockKind::Exclusive, 10, 20, 1), true);
+        if result.is_err() {
+            lock::release_process(1);
+        }
+        *OTHER_RESULT.lock().unwrap() = Some(result);
+        DONE.store(true, Ordering::Release);
+    });
+    let result = lock::lock_records(fname, lock(LockKind::Exclusive, 0, 10, 2), true);
+    if result.is_err() {
+        lock::release_process(2);
+    }
+    while !DONE.load(Ordering::Acquire) {
+        axtask::yield_now();
+    }
+    let other = OTHER_RESULT.lock().unwrap().take().unwrap();
+    assert!(matches!(
+        (&result, &other),
+        (Ok(()), Err(Error::Deadlock)) | (Err(Error::Deadlock), Ok(()))
+    ));
+    lock::release_process(1);
+    lock::release_process(2);
+
+    println!("test_file_lock() OK!");
+    Ok(())
+}
+
 #[test]
 fn test_axfs() {
     axtask::init_scheduler(); // call this to use `axsync::Mutex`.
@@ -265,4 +516,9 @@ fn test_axfs() {
     test_create_file_dir().expect("test_create_file_dir() failed");
     test_remove_file_dir().expect("test_remove_file_dir() failed");
     test_devfs().expect("test_devfs() failed");
+    test_page_cache().expect("test_page_cache() failed");
+    test_block_cache().expect("test_block_cache() failed");
+    test_loop_device().expect("test_loop_device() failed");
+    test_unix_permission().expect("test_unix_permission() failed");
+    test_file_lock().expect("test_file_lock() failed");
 }
@@ -4,6 +4,7 @@ use axerrno::{AxError, AxResult};
 use axfs::api;
 use crate::file_io::FileIO;
 use crate::flags::OpenFlags;
+use crate::poll::PollEvents;
 
 /// 目录描述符
 pub struct DirDesc {
@@ -46,6 +47,11 @@ impl FileIO for DirDesc {
     fn get_type(&self) -> String {
         "DirDesc".to_string()
     }
+
+    /// 与Linux一致, 目录总是报告可读写
+    fn poll(&self) -> PollEvents {
+        PollEvents::IN | PollEvents::OUT
+    }
 }
 
 pub fn new_dir(dir_path: String, _flags: OpenFlags) -> AxResult<DirDesc> {
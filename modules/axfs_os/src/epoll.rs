@@ -0,0 +1,400 @@
+//! epoll实例
+//!
+//! epoll实例本身也是一个文件, 保存在进程的文件描述符表中。兴趣列表中的每一项在对应文件上注册一个
+//! 就绪回调, 文件状态变化时回调将该项标记为已触发, 并转发给注册在这个epoll实例上的回调
+//! (例如包含它的另一个epoll实例), 等待的任务被唤醒后只需要检查兴趣列表。
+//! 不支持回调的文件(没有输入中断的控制台)在每次检查时重新获取就绪状态, 等待时需要定时重新检查。
+//!
+//! 每次从上次报告的最后一项之后开始检查兴趣列表, 这样就绪的项多于`maxevents`时,
+//! 编号小的文件描述符不会一直占满结果, 每一项都能轮流被报告。
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::time::Duration;
+
+use axerrno::{ax_err, AxResult};
+use axhal::time::current_time;
+use axsync::Mutex;
+use bitflags::bitflags;
+use log::debug;
+
+use crate::file_io::FileIO;
+use crate::poll::{needs_periodic_check, poll_until, PollEvents, PollWaker, PollWakers};
+
+/// epoll实例嵌套的最大深度, 与Linux的`EP_MAX_NESTS`一致
+const EPOLL_MAX_NESTS: usize = 4;
+
+/// 把epoll实例加入兴趣列表时持有, 使环的检查和加入不会与另一个这样的加入交错
+static EPOLL_NEST_LOCK: Mutex<()> = Mutex::new(());
+
+bitflags! {
+    /// epoll关心的事件和工作模式, 与Linux的`EPOLLxxx`一致
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct EpollEvents: u32 {
+        /// 有数据可读
+        const IN = 0x0001;
+        /// 有紧急数据可读
+        const PRI = 0x0002;
+        /// 可以写入数据
+        const OUT = 0x0004;
+        /// 发生错误, 总是会被报告
+        const ERR = 0x0008;
+        /// 对端已关闭, 总是会被报告
+        const HUP = 0x0010;
+        /// 对端关闭了写方向
+        const RDHUP = 0x2000;
+        /// 唤醒时只唤醒一个等待者, 目前忽略
+        const EXCLUSIVE = 1 << 28;
+        /// 防止系统休眠, 目前忽略
+        const WAKEUP = 1 << 29;
+        /// 报告一次后禁用该项, 直到用`EPOLL_CTL_MOD`重新启用
+        const ONESHOT = 1 << 30;
+        /// 边沿触发, 只在状态变化时报告
+        const ET = 1 << 31;
+    }
+}
+
+/// 用户态的epoll_event结构体
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EpollEvent {
+    /// 事件
+    pub events: u32,
+    /// 用户数据, 原样返回
+    pub data: u64,
+}
+
+/// epoll_ctl的操作
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EpollCtl {
+    /// 添加一项
+    Add,
+    /// 删除一项
+    Del,
+    /// 修改一项
+    Mod,
+}
+
+impl TryFrom<usize> for EpollCtl {
+    type Error = ();
+
+    fn try_from(op: usize) -> Result<Self, Self::Error> {
+        match op {
+            1 => Ok(Self::Add),
+            2 => Ok(Self::Del),
+            3 => Ok(Self::Mod),
+            _ => Err(()),
+        }
+    }
+}
+
+/// 兴趣列表中的一项在文件上注册的回调
+struct EntryWaker {
+    /// 自上次检查以来文件状态是否发生过变化
+    triggered: AtomicBool,
+    /// 所属epoll实例上注册的回调
+    outer: Arc<PollWakers>,
+}
+
+impl PollWaker for EntryWaker {
+    fn wake(&self) {
+        self.triggered.store(true, Ordering::Release);
+        self.outer.wake();
+    }
+
+    fn outer(&self) -> Option<Arc<PollWakers>> {
+        Some(self.outer.clone())
+    }
+}
+
+/// 兴趣列表中的一项
+struct EpollEntry {
+    /// 关心的文件, 文件被关闭后自动从兴趣列表中移除
+    file: Weak<dyn FileIO>,
+    /// 关心的事件和用户数据
+    event: EpollEvent,
+    /// 注册在文件上的回调
+    waker: Arc<EntryWaker>,
+    /// 文件是否支持回调, 不支持时每次检查都与上次的状态比较
+    has_waker: bool,
+    /// 上次检查时的就绪状态, 用于边沿触发
+    last: PollEvents,
+    /// ONESHOT模式下已经报告过, 暂时禁用
+    disabled: bool,
+}
+
+impl EpollEntry {
+    fn waker(&self) -> Arc<dyn PollWaker> {
+        self.waker.clone()
+    }
+}
+
+/// epoll实例
+pub struct EpollFile {
+    /// 兴趣列表, 以文件描述符为键
+    interest: Mutex<BTreeMap<usize, EpollEntry>>,
+    /// 下一次从哪个文件描述符开始检查
+    cursor: AtomicUsize,
+    /// 注册在这个epoll实例上的回调, 兴趣列表中的文件状态变化时被调用
+    wakers: Arc<PollWakers>,
+}
+
+impl EpollFile {
+    /// 创建一个空的epoll实例
+    pub fn new() -> Self {
+        Self {
+            interest: Mutex::new(BTreeMap::new()),
+            cursor: AtomicUsize::new(0),
+            wakers: Arc::new(PollWakers::new()),
+        }
+    }
+
+    /// 修改兴趣列表, 对应`epoll_ctl`
+    ///
+    /// - op: 操作类型
+    /// - fd: 目标文件描述符
+    /// - file: 目标文件
+    /// - event: 关心的事件和用户数据, 删除时忽略
+    ///
+    /// 添加已存在的项返回`AlreadyExists`, 修改或删除不存在的项返回`NotFound`;
+    /// 与Linux一致, 普通文件和目录不支持epoll, 返回`PermissionDenied`,
+    /// 添加epoll实例后会形成环(包括添加自身)或者嵌套超过[`EPOLL_MAX_NESTS`]层时返回`Loop`。
+    pub fn ctl(
+        &self,
+        op: EpollCtl,
+        fd: usize,
+        file: Arc<dyn FileIO>,
+        event: EpollEvent,
+    ) -> AxResult {
+        if matches!(file.get_type().as_str(), "FileDesc" | "DirDesc") {
+            return ax_err!(PermissionDenied, "epoll does not support regular files");
+        }
+        let nested = file.as_any().downcast_ref::<EpollFile>();
+        let _nest_guard = match nested {
+            Some(nested) if op == EpollCtl::Add => {
+                let guard = EPOLL_NEST_LOCK.lock();
+                self.check_nesting(nested, self.wakers.nest_depth() + 1)?;
+                Some(guard)
+            }
+            _ => None,
+        };
+        let mut interest = self.interest.lock();
+        match op {
+            EpollCtl::Add => {
+                if interest.contains_key(&fd) {
+                    return ax_err!(AlreadyExists);
+                }
+                let waker = Arc::new(EntryWaker {
+                    // 新加入的项需要检查一次当前状态
+                    triggered: AtomicBool::new(true),
+                    outer: self.wakers.clone(),
+                });
+                let mut entry = EpollEntry {
+                    file: Arc::downgrade(&file),
+                    event,
+                    waker,
+                    has_waker: false,
+                    last: PollEvents::empty(),
+                    disabled: false,
+                };
+                entry.has_waker = file.register_poll_waker(&entry.waker());
+                interest.insert(fd, entry);
+            }
+            EpollCtl::Mod => {
+                let entry = match interest.get_mut(&fd) {
+                    Some(entry) => entry,
+                    None => return ax_err!(NotFound),
+                };
+                entry.event = event;
+                entry.last = PollEvents::empty();
+                entry.disabled = false;
+                entry.waker.triggered.store(true, Ordering::Release);
+            }
+            EpollCtl::Del => {
+                let entry = match interest.remove(&fd) {
+                    Some(entry) => entry,
+                    None => return ax_err!(NotFound),
+                };
+                file.unregister_poll_waker(&entry.waker());
+            }
+        }
+        drop(interest);
+        // 正在等待的任务需要重新检查兴趣列表
+        self.wakers.wake();
+        Ok(())
+    }
+
+    /// 检查把`nested`加入兴趣列表后是否会形成环, 或者嵌套超过[`EPOLL_MAX_NESTS`]层
+    ///
+    /// `depth`为`nested`所在的层数, 从最外层的epoll实例算起。已有的嵌套不会形成环, 所以只要
+    /// `nested`及其兴趣列表中嵌套的epoll实例都不是自身, 加入后就不会形成环。
+    fn check_nesting(&self, nested: &EpollFile, depth: usize) -> AxResult {
+        if core::ptr::eq(nested, self) {
+            return ax_err!(Loop, "epoll instance would contain itself");
+        }
+        if depth > EPOLL_MAX_NESTS {
+            return ax_err!(Loop, "epoll instances nested too deep");
+        }
+        // 不持有兴趣列表的锁, 检查下一层时需要获取它们的锁
+        let files: Vec<Arc<dyn FileIO>> = nested
+            .interest
+            .lock()
+            .values()
+            .filter_map(|entry| entry.file.upgrade())
+            .collect();
+        for file in files {
+            if let Some(inner) = file.as_any().downcast_ref::<EpollFile>() {
+                self.check_nesting(inner, depth + 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 检查一遍兴趣列表, 将就绪的事件填入`events`, 返回就绪的个数
+    ///
+    /// 从[`Self::cursor`]开始按文件描述符的顺序检查, 到末尾后回到开头, 结果填满后停止。
+    fn collect(&self, events: &mut [EpollEvent]) -> usize {
+        let mut interest = self.interest.lock();
+        // 文件已经被关闭的项
+        interest.retain(|_, entry| entry.file.strong_count() > 0);
+        let cursor = self.cursor.load(Ordering::Acquire);
+        let fds: Vec<usize> = interest
+            .range(cursor..)
+            .chain(interest.range(..cursor))
+            .map(|(&fd, _)| fd)
+            .collect();
+        let mut count = 0;
+        for fd in fds {
+            if count == events.len() {
+                break;
+            }
+            let entry = interest.get_mut(&fd).unwrap();
+            if entry.disabled {
+                continue;
+            }
+            let file = match entry.file.upgrade() {
+                Some(file) => file,
+                None => continue,
+            };
+            let triggered = entry.waker.triggered.swap(false, Ordering::AcqRel);
+            let flags = EpollEvents::from_bits_truncate(entry.event.events);
+            // 错误和挂断总是会被报告
+            let mask = PollEvents::from_bits_truncate(flags.bits() as u16)
+                | PollEvents::ERR
+                | PollEvents::HUP;
+            let revents = file.poll() & mask;
+            let changed = revents != entry.last;
+            entry.last = revents;
+            if revents.is_empty() {
+                continue;
+            }
+            if flags.contains(EpollEvents::ET) {
+                // 支持回调的文件在回调被触发后报告, 否则在就绪状态变化时报告
+                let edge = if entry.has_waker { triggered } else { changed };
+                if !edge {
+                    continue;
+                }
+            }
+            if flags.contains(EpollEvents::ONESHOT) {
+                entry.disabled = true;
+            }
+            events[count] = EpollEvent {
+                events: revents.bits() as u32,
+                data: entry.event.data,
+            };
+            count += 1;
+            self.cursor.store(fd + 1, Ordering::Release);
+        }
+        count
+    }
+
+    /// 兴趣列表中是否有就绪状态变化时没有通知的文件, 等待时需要定时重新检查
+    ///
+    /// 嵌套的epoll实例取决于它自己的兴趣列表。
+    pub fn periodic(&self) -> bool {
+        self.interest.lock().values().any(|entry| {
+            entry
+                .file
+                .upgrade()
+                .map_or(false, |file| needs_periodic_check(file.as_ref()))
+        })
+    }
+
+    /// 等待兴趣列表中的事件, 对应`epoll_pwait`
+    ///
+    /// - events: 用于保存就绪事件的缓冲区, 最多返回`events.len()`个事件
+    /// - timeout: 超时时间, None表示一直等待
+    ///
+    /// 返回就绪事件的个数, 超时返回0
+    pub fn wait(&self, events: &mut [EpollEvent], timeout: Option<Duration>) -> usize {
+        let deadline = timeout.map(|timeout| current_time() + timeout);
+        let periodic = self.periodic();
+        let count = poll_until(deadline, periodic, || self.collect(events));
+        debug!("epoll wait: {} events", count);
+        count
+    }
+}
+
+impl Drop for EpollFile {
+    fn drop(&mut self) {
+        for entry in self.interest.lock().values() {
+            if let Some(file) = entry.file.upgrade() {
+                file.unregister_poll_waker(&entry.waker());
+            }
+        }
+    }
+}
+
+impl FileIO for EpollFile {
+    fn readable(&self) -> bool {
+        false
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    fn read(&self, _buf: &mut [u8]) -> AxResult<usize> {
+        ax_err!(InvalidInput, "cannot read an epoll instance")
+    }
+
+    fn write(&self, _buf: &[u8]) -> AxResult<usize> {
+        ax_err!(InvalidInput, "cannot write an epoll instance")
+    }
+
+    fn get_type(&self) -> String {
+        String::from("Epoll")
+    }
+
+    /// 兴趣列表中有就绪的项时可读, 用于嵌套的epoll和poll
+    fn poll(&self) -> PollEvents {
+        let ready = self.interest.lock().values().any(|entry| {
+            let mask = PollEvents::from_bits_truncate(entry.event.events as u16)
+                | PollEvents::ERR
+                | PollEvents::HUP;
+            !entry.disabled
+                && entry
+                    .file
+                    .upgrade()
+                    .map_or(false, |file| file.poll().intersects(mask))
+        });
+        if ready {
+            PollEvents::IN
+        } else {
+            PollEvents::empty()
+        }
+    }
+
+    /// 兴趣列表中的文件状态变化时调用回调
+    fn register_poll_waker(&self, waker: &Arc<dyn PollWaker>) -> bool {
+        self.wakers.register(waker);
+        true
+    }
+
+    fn unregister_poll_waker(&self, waker: &Arc<dyn PollWaker>) {
+        self.wakers.unregister(waker);
+    }
+}
@@ -1,7 +1,8 @@
 use super::file_io::FileIO;
 use crate::flags::OpenFlags;
 use crate::link::get_link_count;
-use crate::types::{normal_file_mode, Kstat, StMode};
+use crate::poll::PollEvents;
+use crate::types::{Kstat, StMode};
 use crate::FilePath;
 use alloc::string::{String, ToString};
 use alloc::sync::Arc;
@@ -19,22 +20,6 @@ pub struct FileDesc {
     pub file: Arc<Mutex<File>>,
     /// 文件打开的标志位
     pub flags: OpenFlags,
-    /// 文件信息
-    pub stat: Mutex<FileMetaData>,
-}
-
-/// 文件在os中运行时的可变信息
-/// TODO: 暂时全部记为usize
-pub struct FileMetaData {
-    /// 最后一次访问时间
-    pub atime: usize,
-    /// 最后一次改变(modify)内容的时间
-    pub mtime: usize,
-    /// 最后一次改变(change)属性的时间
-    pub ctime: usize,
-    // /// 打开时的选项。
-    // /// 主要用于判断 CLOEXEC，即 exec 时是否关闭。默认为 false。
-    // pub flags: OpenFlags,
 }
 
 /// 为FileDesc实现FileIO trait
@@ -68,30 +53,46 @@ impl FileIO for FileDesc {
         "FileDesc".to_string()
     }
 
+    /// 普通文件的读写不会阻塞, 总是就绪的
+    fn poll(&self) -> PollEvents {
+        let mut events = PollEvents::empty();
+        if self.readable() {
+            events |= PollEvents::IN;
+        }
+        if self.writable() {
+            events |= PollEvents::OUT;
+        }
+        events
+    }
+
     fn get_stat(&self) -> AxResult<Kstat> {
         let file = self.file.lock();
         let metadata = file.metadata()?;
         let raw_metadata = metadata.raw_metadata();
-        let stat = self.stat.lock();
+        let (atime, mtime, ctime) = (
+            raw_metadata.atime(),
+            raw_metadata.mtime(),
+            raw_metadata.ctime(),
+        );
         let kstat = Kstat {
             st_dev: 1,
             st_ino: 1,
-            st_mode: normal_file_mode(StMode::S_IFREG).bits(),
+            st_mode: StMode::S_IFREG.bits() | raw_metadata.perm().bits() as u32,
             st_nlink: get_link_count(&FilePath::new(self.path.as_str())) as u32,
-            st_uid: 0,
-            st_gid: 0,
+            st_uid: raw_metadata.uid(),
+            st_gid: raw_metadata.gid(),
             st_rdev: 0,
             _pad0: 0,
             st_size: raw_metadata.size() as u64,
             st_blksize: 0,
             _pad1: 0,
             st_blocks: raw_metadata.blocks() as u64,
-            st_atime_sec: stat.atime as isize,
-            st_atime_nsec: 0,
-            st_mtime_sec: stat.mtime as isize,
-            st_mtime_nsec: 0,
-            st_ctime_sec: stat.ctime as isize,
-            st_ctime_nsec: 0,
+            st_atime_sec: atime.as_secs() as isize,
+            st_atime_nsec: atime.subsec_nanos() as isize,
+            st_mtime_sec: mtime.as_secs() as isize,
+            st_mtime_nsec: mtime.subsec_nanos() as isize,
+            st_ctime_sec: ctime.as_secs() as isize,
+            st_ctime_nsec: ctime.subsec_nanos() as isize,
         };
         Ok(kstat)
     }
@@ -113,13 +114,20 @@ impl FileDesc {
             path: path.to_string(),
             file,
             flags,
-            stat: Mutex::new(FileMetaData {
-                atime: 0,
-                mtime: 0,
-                ctime: 0,
-            }),
         }
     }
+
+    /// 打开的文件的标识, 作为`flock`锁的持有者。`dup`和`fork`得到的描述符共享同一个值
+    pub fn lock_owner(&self) -> usize {
+        Arc::as_ptr(&self.file) as usize
+    }
+}
+
+/// 最后一个引用这个打开的文件的描述符关闭时, 释放其上的`flock`锁
+impl Drop for FileDesc {
+    fn drop(&mut self) {
+        axfs::lock::funlock(&self.path, self.lock_owner()).ok();
+    }
 }
 
 /// 新建一个文件描述符
@@ -1,7 +1,9 @@
 use alloc::string::String;
+use alloc::sync::Arc;
 use core::any::Any;
 use log::debug;
 use axerrno::{AxError, AxResult};
+use crate::poll::{PollEvents, PollWaker};
 use crate::types::Kstat;
 
 /// File I/O trait. 文件I/O操作
@@ -33,6 +35,20 @@ pub trait FileIO: Send + Sync + AsAny {
     fn get_stat(&self) -> AxResult<Kstat> {
         Err(AxError::Unsupported) // 如果没有实现get_stat, 则返回Unsupported
     }
+    /// 获取文件当前的就绪状态, 用于poll/select
+    fn poll(&self) -> PollEvents;
+    /// 是否是非阻塞的(O_NONBLOCK)
+    fn is_nonblocking(&self) -> bool {
+        false
+    }
+    /// 设置是否非阻塞, 读写总是不阻塞的文件忽略
+    fn set_nonblocking(&self, _nonblock: bool) {}
+    /// 注册就绪状态变化时的回调, 返回是否支持。不支持时调用者需要自己重新检查就绪状态
+    fn register_poll_waker(&self, _waker: &Arc<dyn PollWaker>) -> bool {
+        false
+    }
+    /// 注销就绪状态变化时的回调
+    fn unregister_poll_waker(&self, _waker: &Arc<dyn PollWaker>) {}
 
     /// debug
     fn print_content(&self) {
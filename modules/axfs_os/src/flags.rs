@@ -67,6 +67,11 @@ impl OpenFlags {
     pub fn is_dir(&self) -> bool {
         self.contains(Self::DIR)
     }
+
+    /// 获取是否是非阻塞模式
+    pub fn is_nonblock(&self) -> bool {
+        self.contains(Self::NON_BLOCK)
+    }
 }
 
 impl From<usize> for OpenFlags {
@@ -9,18 +9,24 @@ extern crate alloc;
 use alloc::vec::Vec;
 
 pub mod dir;
+pub mod epoll;
 pub mod flags;
 pub mod link;
+pub mod mknod;
 pub mod mount;
 pub mod pipe;
+pub mod poll;
+pub mod rename;
 pub mod types;
+pub mod unix;
 
 use axerrno::AxResult;
 pub use axfs::api;
+pub use axfs::lock;
 use axfs::api::OpenOptions;
 use axio::{Read, Seek, SeekFrom};
 pub use dir::{new_dir, DirDesc};
-pub use file::{new_fd, FileDesc, FileMetaData};
+pub use file::{new_fd, FileDesc};
 use log::info;
 pub use stdio::{Stderr, Stdin, Stdout};
 pub use types::{DirEnt, DirEntType, FilePath};
@@ -118,6 +118,40 @@ pub fn create_link(src_path: &FilePath, dest_path: &FilePath) -> bool {
     true
 }
 
+/// 文件或目录被重命名后, 更新链接表
+///
+/// 位于`src_path`(或其下)的用户路径和实际路径都改为`dest_path`下对应的路径。
+/// `dest_path`上原有的链接被移除, 若它指向的是别的文件且链接数降为0, 则删除该文件;
+/// 若指向的就是`dest_path`, 磁盘上的文件已经在重命名时被替换, 不需要再删除。
+pub fn rename_link(src_path: &FilePath, dest_path: &FilePath) {
+    trace!("rename_link: {} -> {}", src_path.path(), dest_path.path());
+    let mut map = LINK_PATH_MAP.lock();
+    let mut count_map = LINK_COUNT_MAP.lock();
+    if let Some(old_dest) = map.remove(dest_path) {
+        let count = count_map.entry(old_dest.clone()).or_insert(0);
+        *count = count.saturating_sub(1);
+        if *count == 0 {
+            count_map.remove(&old_dest);
+            if !old_dest.equal_to(dest_path) {
+                info!("link num down to zero, remove file: {}", old_dest.path());
+                let _ = remove_file(old_dest.path());
+            }
+        }
+    }
+    let rename = |path: FilePath| match path.renamed(src_path, dest_path) {
+        Some(new_path) => new_path,
+        None => path,
+    };
+    *map = core::mem::take(&mut *map)
+        .into_iter()
+        .map(|(user_path, real_path)| (rename(user_path), rename(real_path)))
+        .collect();
+    *count_map = core::mem::take(&mut *count_map)
+        .into_iter()
+        .map(|(real_path, count)| (rename(real_path), count))
+        .collect();
+}
+
 /// 获取文件的链接数
 ///
 /// 如果文件不存在，那么返回 0
@@ -0,0 +1,188 @@
+//! 模拟的特殊文件模块
+//! fat32无法保存FIFO、设备文件、socket等特殊文件, 这里在磁盘上创建一个空文件占位(使其出现在目录项中, 并能被unlink),
+//! 同时在内存中记录占位文件对应的特殊文件。打开占位文件时, 实际打开的是对应的FIFO或devfs中的设备。
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use axerrno::{ax_err, AxResult};
+use axfs::api::File;
+use axsync::Mutex;
+use log::{debug, info};
+
+use crate::file_io::FileIO;
+use crate::flags::OpenFlags;
+use crate::link::create_link;
+use crate::pipe::Fifo;
+use crate::types::{DirEntType, StMode};
+use crate::unix::UnixNode;
+use crate::{new_fd, FilePath};
+
+/// 特殊文件
+#[derive(Clone)]
+pub enum SpecialNode {
+    /// 命名管道
+    Fifo(Arc<Fifo>),
+    /// 字符设备, 记录设备在devfs中的路径
+    CharDevice(String),
+    /// 块设备, 记录设备在devfs中的路径
+    BlockDevice(String),
+    /// 绑定了Unix域socket的路径, socket关闭后弱引用失效, 连接被拒绝
+    Socket(Weak<UnixNode>),
+}
+
+/// 占位文件到特殊文件的映射
+static SPECIAL_NODES: Mutex<BTreeMap<FilePath, SpecialNode>> = Mutex::new(BTreeMap::new());
+
+/// devfs中字符设备的设备号, 与Linux保持一致
+const CHAR_DEVICES: &[(u32, u32, &str)] = &[
+    (1, 3, "null"),
+    (1, 5, "zero"),
+    (1, 8, "random"),
+    (1, 9, "urandom"),
+    (5, 0, "tty"),
+    (5, 1, "console"),
+    (29, 0, "fb0"),
+];
+
+/// virtio块设备的主设备号, 每个设备占16个次设备号(vda为0, vdb为16, 以此类推)
+const VIRTIO_BLK_MAJOR: u32 = 254;
+
+/// 从设备号中取出主设备号, 与musl的`major()`一致
+pub fn dev_major(dev: u64) -> u32 {
+    (((dev >> 32) & 0xffff_f000) | ((dev >> 8) & 0xfff)) as u32
+}
+
+/// 从设备号中取出次设备号, 与musl的`minor()`一致
+pub fn dev_minor(dev: u64) -> u32 {
+    (((dev >> 12) & 0xffff_ff00) | (dev & 0xff)) as u32
+}
+
+/// 根据设备号找到devfs中对应的设备路径
+fn device_path(file_type: StMode, dev: u64) -> Option<String> {
+    let (major, minor) = (dev_major(dev), dev_minor(dev));
+    if file_type == StMode::S_IFCHR {
+        CHAR_DEVICES
+            .iter()
+            .find(|&&(ma, mi, _)| ma == major && mi == minor)
+            .map(|&(_, _, name)| format!("/dev/{}", name))
+    } else if major == VIRTIO_BLK_MAJOR && minor % 16 == 0 && minor / 16 < 26 {
+        Some(format!("/dev/vd{}", (b'a' + (minor / 16) as u8) as char))
+    } else {
+        None
+    }
+}
+
+/// 创建特殊文件
+///
+/// - path: 要创建的文件路径
+/// - mode: 文件类型和权限, 支持S_IFREG、S_IFIFO、S_IFCHR、S_IFBLK、S_IFSOCK
+/// - dev: 设备号, 仅对S_IFCHR和S_IFBLK有效, 必须对应devfs中已有的设备
+///
+/// 若文件已存在, 返回`AlreadyExists`。
+pub fn mknod(path: &FilePath, mode: u32, dev: u64) -> AxResult {
+    let file_type = StMode::from_bits_truncate(mode) & StMode::S_IFMT;
+    let node = if file_type.is_empty() || file_type == StMode::S_IFREG {
+        None
+    } else if file_type == StMode::S_IFIFO {
+        Some(SpecialNode::Fifo(Arc::new(Fifo::new())))
+    } else if file_type == StMode::S_IFCHR || file_type == StMode::S_IFBLK {
+        let dev_path = match device_path(file_type, dev) {
+            Some(dev_path) => dev_path,
+            None => return ax_err!(NotFound, "no such device"),
+        };
+        if file_type == StMode::S_IFCHR {
+            Some(SpecialNode::CharDevice(dev_path))
+        } else {
+            Some(SpecialNode::BlockDevice(dev_path))
+        }
+    } else if file_type == StMode::S_IFSOCK {
+        // 没有socket绑定在上面, 连接总是被拒绝
+        Some(SpecialNode::Socket(Weak::new()))
+    } else {
+        return ax_err!(Unsupported, "unsupported file type for mknod");
+    };
+
+    // 在磁盘上创建占位文件
+    File::create_new(path.path())?;
+    create_link(path, path);
+
+    if let Some(node) = node {
+        info!("mknod: {}", path.path());
+        SPECIAL_NODES.lock().insert(path.clone(), node);
+    }
+    Ok(())
+}
+
+/// 创建命名管道
+pub fn mkfifo(path: &FilePath, mode: u32) -> AxResult {
+    mknod(path, StMode::S_IFIFO.bits() | (mode & 0o777), 0)
+}
+
+/// 创建绑定了Unix域socket的占位文件, 由bind调用
+///
+/// 若文件已存在, 返回`AlreadyExists`。
+pub fn mksock(path: &FilePath, node: Weak<UnixNode>) -> AxResult {
+    File::create_new(path.path())?;
+    create_link(path, path);
+    info!("mksock: {}", path.path());
+    SPECIAL_NODES
+        .lock()
+        .insert(path.clone(), SpecialNode::Socket(node));
+    Ok(())
+}
+
+/// 获取路径对应的特殊文件, 若不是特殊文件则返回None
+pub fn get_special_node(path: &FilePath) -> Option<SpecialNode> {
+    SPECIAL_NODES.lock().get(path).cloned()
+}
+
+/// 删除路径对应的特殊文件, 返回是否删除成功
+///
+/// 已经打开的FIFO不受影响, 直到所有端口都关闭
+pub fn remove_special_node(path: &FilePath) -> bool {
+    SPECIAL_NODES.lock().remove(path).is_some()
+}
+
+/// 占位文件被重命名后, 更新特殊文件表
+///
+/// 位于`src_path`(或其下)的特殊文件改为`dest_path`下对应的路径, `dest_path`上原有的特殊文件被移除
+pub fn rename_special_node(src_path: &FilePath, dest_path: &FilePath) {
+    let mut nodes = SPECIAL_NODES.lock();
+    nodes.remove(dest_path);
+    *nodes = core::mem::take(&mut *nodes)
+        .into_iter()
+        .map(|(path, node)| match path.renamed(src_path, dest_path) {
+            Some(new_path) => (new_path, node),
+            None => (path, node),
+        })
+        .collect();
+}
+
+/// 获取特殊文件在目录项中的类型, 若不是特殊文件则返回None
+pub fn special_dirent_type(path: &FilePath) -> Option<DirEntType> {
+    SPECIAL_NODES.lock().get(path).map(|node| match node {
+        SpecialNode::Fifo(_) => DirEntType::FIFO,
+        SpecialNode::CharDevice(_) => DirEntType::CHR,
+        SpecialNode::BlockDevice(_) => DirEntType::BLK,
+        SpecialNode::Socket(_) => DirEntType::SOCK,
+    })
+}
+
+/// 打开特殊文件
+///
+/// 若路径不是特殊文件, 返回None; 否则返回打开的结果。打开FIFO可能会阻塞,
+/// 调用者不能持有进程的锁。
+pub fn open_special_node(path: &FilePath, flags: OpenFlags) -> Option<AxResult<Arc<dyn FileIO>>> {
+    let node = get_special_node(path)?;
+    debug!("open special node: {}", path.path());
+    Some(match node {
+        SpecialNode::Fifo(fifo) => fifo.open(flags).map(|pipe| pipe as Arc<dyn FileIO>),
+        SpecialNode::CharDevice(dev_path) | SpecialNode::BlockDevice(dev_path) => {
+            new_fd(dev_path, flags).map(|file| Arc::new(file) as Arc<dyn FileIO>)
+        }
+        // 与Linux一样, socket只能通过connect使用, 不能打开
+        SpecialNode::Socket(_) => ax_err!(Unsupported, "cannot open a socket"),
+    })
+}
@@ -6,7 +6,8 @@ use crate::FilePath;
 // use crate::link::{real_path};
 
 /// 挂载的文件系统。
-/// 目前"挂载"的语义是，把一个文件当作文件系统读写
+/// 块设备和镜像文件上的文件系统由`axfs::api::mount`真正挂载, 镜像文件经由回环设备访问;
+/// 这里只记录挂载的设备和挂载点, 设备不存在时也只记录而不挂载
 pub struct MountedFs {
     //pub inner: Arc<Mutex<FATFileSystem>>,
     pub device: FilePath,
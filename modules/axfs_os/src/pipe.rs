@@ -1,115 +1,321 @@
 use alloc::string::String;
 use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use log::trace;
-use axerrno::AxResult;
+use axerrno::{ax_err, AxResult};
+use axsync::spin::SpinNoIrq;
 use axsync::Mutex;
-use axtask::yield_now;
+use axtask::WaitQueue;
 use crate::file_io::FileIO;
+use crate::flags::OpenFlags;
+use crate::poll::{PollEvents, PollWaker, PollWakers};
+use crate::types::{normal_file_mode, Kstat, StMode};
+
+/// 页大小, 管道缓冲区的大小以页为单位
+const PAGE_SIZE: usize = 4096;
+/// 管道缓冲区的默认大小, 与Linux一致
+pub const PIPE_DEFAULT_SIZE: usize = 16 * PAGE_SIZE;
+/// 管道缓冲区的最大大小, 与Linux的`/proc/sys/fs/pipe-max-size`一致
+pub const PIPE_MAX_SIZE: usize = 1024 * 1024;
+/// 不超过`PIPE_BUF`字节的写入是原子的, 不会与其他写者的数据交错
+pub const PIPE_BUF: usize = PAGE_SIZE;
 
 /// IPC pipe
 pub struct Pipe {
     readable: bool,
     writable: bool,
-    buffer: Arc<Mutex<PipeRingBuffer>>,
+    /// 是否是非阻塞的(O_NONBLOCK), 读写时不阻塞而是返回`Again`。可以由fcntl修改
+    nonblock: AtomicBool,
+    buffer: Arc<PipeBuffer>,
+    /// 若由命名管道打开, 记录所属的FIFO, 关闭时更新其读者/写者计数
+    fifo: Option<Arc<Fifo>>,
 }
 
 impl Pipe {
-    /// create readable pipe
-    pub fn read_end_with_buffer(buffer: Arc<Mutex<PipeRingBuffer>>) -> Self {
+    /// 创建管道的一端, 并在缓冲区中登记读者/写者
+    fn new(buffer: Arc<PipeBuffer>, readable: bool, writable: bool, nonblock: bool) -> Self {
+        {
+            let mut ring = buffer.ring.lock();
+            if readable {
+                ring.readers += 1;
+            }
+            if writable {
+                ring.writers += 1;
+            }
+        }
         Self {
-            readable: true,
-            writable: false,
+            readable,
+            writable,
+            nonblock: AtomicBool::new(nonblock),
             buffer,
+            fifo: None,
         }
     }
+    /// create readable pipe
+    pub fn read_end_with_buffer(buffer: Arc<PipeBuffer>) -> Self {
+        Self::new(buffer, true, false, false)
+    }
     /// create writable pipe
-    pub fn write_end_with_buffer(buffer: Arc<Mutex<PipeRingBuffer>>) -> Self {
-        Self {
-            readable: false,
-            writable: true,
-            buffer,
+    pub fn write_end_with_buffer(buffer: Arc<PipeBuffer>) -> Self {
+        Self::new(buffer, false, true, false)
+    }
+
+    /// 获取管道缓冲区的大小, 对应`F_GETPIPE_SZ`
+    pub fn capacity(&self) -> usize {
+        self.buffer.ring.lock().capacity()
+    }
+
+    /// 设置管道缓冲区的大小, 对应`F_SETPIPE_SZ`, 返回实际设置的大小
+    ///
+    /// 大小会向上取整为2的幂个页。若超过`PIPE_MAX_SIZE`, 返回`PermissionDenied`;
+    /// 若缓冲区中已有的数据放不下, 返回`ResourceBusy`。
+    pub fn set_capacity(&self, size: usize) -> AxResult<usize> {
+        if size > PIPE_MAX_SIZE {
+            return ax_err!(PermissionDenied, "pipe size exceeds the limit");
+        }
+        let size = size.max(PAGE_SIZE).next_power_of_two();
+        self.buffer.ring.lock().resize(size)?;
+        self.buffer.wake_writers();
+        Ok(size)
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let (readers, writers) = {
+            let mut ring = self.buffer.ring.lock();
+            if self.readable {
+                ring.readers -= 1;
+            }
+            if self.writable {
+                ring.writers -= 1;
+            }
+            (ring.readers, ring.writers)
+        };
+        // 读端全部关闭后, 阻塞的写者应当得到EPIPE; 写端全部关闭后, 阻塞的读者应当读到EOF
+        if self.readable && readers == 0 {
+            self.buffer.wake_writers();
+        }
+        if self.writable && writers == 0 {
+            self.buffer.wake_readers();
+        }
+        if let Some(fifo) = &self.fifo {
+            if self.readable {
+                fifo.readers.fetch_sub(1, Ordering::AcqRel);
+            }
+            if self.writable {
+                fifo.writers.fetch_sub(1, Ordering::AcqRel);
+            }
         }
     }
 }
 
-const RING_BUFFER_SIZE: usize = 32;
+/// 管道两端共享的环形缓冲区
+///
+/// 使用自旋锁保护, 因为等待队列检查条件时已经持有了调度队列的锁, 不能再使用会睡眠的锁。
+pub struct PipeBuffer {
+    ring: SpinNoIrq<PipeRingBuffer>,
+    /// 等待数据到达(或写端全部关闭)的读者
+    read_wq: WaitQueue,
+    /// 等待缓冲区腾出空间(或读端全部关闭)的写者
+    write_wq: WaitQueue,
+    /// 读端上注册的就绪回调
+    read_wakers: PollWakers,
+    /// 写端上注册的就绪回调
+    write_wakers: PollWakers,
+}
+
+impl PipeBuffer {
+    /// 创建一个默认大小的管道缓冲区
+    pub fn new() -> Self {
+        Self {
+            ring: SpinNoIrq::new(PipeRingBuffer::new(PIPE_DEFAULT_SIZE)),
+            read_wq: WaitQueue::new(),
+            write_wq: WaitQueue::new(),
+            read_wakers: PollWakers::new(),
+            write_wakers: PollWakers::new(),
+        }
+    }
 
-#[derive(Copy, Clone, PartialEq)]
-enum RingBufferStatus {
-    Full,
-    Empty,
-    Normal,
+    /// 有数据写入或写端全部关闭, 唤醒读者和读端上的就绪回调
+    fn wake_readers(&self) {
+        self.read_wq.notify_all(false);
+        self.read_wakers.wake();
+    }
+
+    /// 有空间腾出或读端全部关闭, 唤醒写者和写端上的就绪回调
+    fn wake_writers(&self) {
+        self.write_wq.notify_all(false);
+        self.write_wakers.wake();
+    }
 }
 
 pub struct PipeRingBuffer {
-    arr: [u8; RING_BUFFER_SIZE],
+    arr: Vec<u8>,
     head: usize,
-    tail: usize,
-    status: RingBufferStatus,
-    write_end: Option<Weak<Pipe>>,
+    len: usize,
+    readers: usize,
+    writers: usize,
 }
 
 impl PipeRingBuffer {
-    pub fn new() -> Self {
+    pub fn new(capacity: usize) -> Self {
         Self {
-            arr: [0; RING_BUFFER_SIZE],
+            arr: vec![0; capacity],
             head: 0,
-            tail: 0,
-            status: RingBufferStatus::Empty,
-            write_end: None,
+            len: 0,
+            readers: 0,
+            writers: 0,
         }
     }
-    pub fn set_write_end(&mut self, write_end: &Arc<Pipe>) {
-        self.write_end = Some(Arc::downgrade(write_end));
-    }
-    pub fn write_byte(&mut self, byte: u8) {
-        self.status = RingBufferStatus::Normal;
-        self.arr[self.tail] = byte;
-        self.tail = (self.tail + 1) % RING_BUFFER_SIZE;
-        if self.tail == self.head {
-            self.status = RingBufferStatus::Full;
-        }
-    }
-    pub fn read_byte(&mut self) -> u8 {
-        self.status = RingBufferStatus::Normal;
-        let c = self.arr[self.head];
-        self.head = (self.head + 1) % RING_BUFFER_SIZE;
-        if self.head == self.tail {
-            self.status = RingBufferStatus::Empty;
-        }
-        c
+    pub fn capacity(&self) -> usize {
+        self.arr.len()
     }
     pub fn available_read(&self) -> usize {
-        if self.status == RingBufferStatus::Empty {
-            0
-        } else if self.tail > self.head {
-            self.tail - self.head
-        } else {
-            self.tail + RING_BUFFER_SIZE - self.head
-        }
+        self.len
     }
     pub fn available_write(&self) -> usize {
-        if self.status == RingBufferStatus::Full {
-            0
-        } else {
-            RING_BUFFER_SIZE - self.available_read()
-        }
+        self.capacity() - self.len
     }
     pub fn all_write_ends_closed(&self) -> bool {
-        self.write_end.as_ref().unwrap().upgrade().is_none()
+        self.writers == 0
+    }
+    pub fn all_read_ends_closed(&self) -> bool {
+        self.readers == 0
+    }
+    /// 读出尽可能多的数据, 返回读出的字节数
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        let cap = self.capacity();
+        let n = buf.len().min(self.len);
+        let first = n.min(cap - self.head);
+        buf[..first].copy_from_slice(&self.arr[self.head..self.head + first]);
+        buf[first..n].copy_from_slice(&self.arr[..n - first]);
+        self.head = (self.head + n) % cap;
+        self.len -= n;
+        n
+    }
+    /// 写入尽可能多的数据, 返回写入的字节数
+    pub fn write(&mut self, buf: &[u8]) -> usize {
+        let cap = self.capacity();
+        let n = buf.len().min(self.available_write());
+        let tail = (self.head + self.len) % cap;
+        let first = n.min(cap - tail);
+        self.arr[tail..tail + first].copy_from_slice(&buf[..first]);
+        self.arr[..n - first].copy_from_slice(&buf[first..n]);
+        self.len += n;
+        n
+    }
+    /// 改变缓冲区的大小, 保留其中的数据
+    pub fn resize(&mut self, capacity: usize) -> AxResult {
+        if capacity < self.len {
+            return ax_err!(ResourceBusy, "pipe contains more data than the new size");
+        }
+        let mut arr = vec![0; capacity];
+        let len = self.len;
+        self.read(&mut arr[..len]);
+        self.arr = arr;
+        self.head = 0;
+        self.len = len;
+        Ok(())
     }
 }
 
 /// Return (read_end, write_end)
-pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
+pub fn make_pipe(nonblock: bool) -> (Arc<Pipe>, Arc<Pipe>) {
     trace!("kernel: make_pipe");
-    let buffer = Arc::new(Mutex::new(PipeRingBuffer::new()));
-    let read_end = Arc::new(Pipe::read_end_with_buffer(buffer.clone()));
-    let write_end = Arc::new(Pipe::write_end_with_buffer(buffer.clone()));
-    buffer.lock().set_write_end(&write_end);
+    let buffer = Arc::new(PipeBuffer::new());
+    let read_end = Arc::new(Pipe::new(buffer.clone(), true, false, nonblock));
+    let write_end = Arc::new(Pipe::new(buffer, false, true, nonblock));
     (read_end, write_end)
 }
 
+/// 命名管道(FIFO)
+///
+/// 同一个FIFO的读者和写者通过共享的`PipeBuffer`交换数据。所有端口都关闭后缓冲区被释放,
+/// 其中未读的数据也随之丢弃, 与Linux的行为一致。
+pub struct Fifo {
+    /// 当前打开的端口共享的缓冲区
+    buffer: Mutex<Weak<PipeBuffer>>,
+    /// 当前打开的读者数
+    readers: AtomicUsize,
+    /// 当前打开的写者数
+    writers: AtomicUsize,
+    /// 读者打开的总次数, 阻塞打开的写者据此判断是否有读者来过
+    reader_opens: AtomicUsize,
+    /// 写者打开的总次数, 阻塞打开的读者据此判断是否有写者来过
+    writer_opens: AtomicUsize,
+    /// 阻塞打开时等待另一端的任务
+    open_queue: WaitQueue,
+}
+
+impl Fifo {
+    /// 创建一个新的FIFO
+    pub fn new() -> Self {
+        Self {
+            buffer: Mutex::new(Weak::new()),
+            readers: AtomicUsize::new(0),
+            writers: AtomicUsize::new(0),
+            reader_opens: AtomicUsize::new(0),
+            writer_opens: AtomicUsize::new(0),
+            open_queue: WaitQueue::new(),
+        }
+    }
+
+    /// 按照POSIX语义打开FIFO, 返回对应的管道端口
+    ///
+    /// - O_RDONLY: 阻塞直到有写者打开; 指定O_NONBLOCK时立即返回
+    /// - O_WRONLY: 阻塞直到有读者打开; 指定O_NONBLOCK时若没有读者则失败(对应ENXIO)
+    /// - O_RDWR: 立即返回
+    pub fn open(self: &Arc<Self>, flags: OpenFlags) -> AxResult<Arc<Pipe>> {
+        let (readable, writable) = (flags.readable(), flags.writable());
+        let nonblock = flags.is_nonblock();
+        if writable && !readable && nonblock && self.readers.load(Ordering::Acquire) == 0 {
+            return ax_err!(NotConnected, "open fifo for writing without readers");
+        }
+        let reader_opens = self.reader_opens.load(Ordering::Acquire);
+        let writer_opens = self.writer_opens.load(Ordering::Acquire);
+
+        let buffer = {
+            let mut buffer = self.buffer.lock();
+            buffer.upgrade().unwrap_or_else(|| {
+                let new_buffer = Arc::new(PipeBuffer::new());
+                *buffer = Arc::downgrade(&new_buffer);
+                new_buffer
+            })
+        };
+        let mut pipe = Pipe::new(buffer, readable, writable, nonblock);
+        pipe.fifo = Some(self.clone());
+        if readable {
+            self.readers.fetch_add(1, Ordering::AcqRel);
+            self.reader_opens.fetch_add(1, Ordering::AcqRel);
+        }
+        if writable {
+            self.writers.fetch_add(1, Ordering::AcqRel);
+            self.writer_opens.fetch_add(1, Ordering::AcqRel);
+        }
+        self.open_queue.notify_all(false);
+
+        if !nonblock {
+            if readable && !writable {
+                trace!("kernel: Fifo::open wait for writers");
+                self.open_queue.wait_until(|| {
+                    self.writers.load(Ordering::Acquire) > 0
+                        || self.writer_opens.load(Ordering::Acquire) != writer_opens
+                });
+            } else if writable && !readable {
+                trace!("kernel: Fifo::open wait for readers");
+                self.open_queue.wait_until(|| {
+                    self.readers.load(Ordering::Acquire) > 0
+                        || self.reader_opens.load(Ordering::Acquire) != reader_opens
+                });
+            }
+        }
+        Ok(Arc::new(pipe))
+    }
+}
+
 impl FileIO for Pipe {
     fn readable(&self) -> bool {
         self.readable
@@ -119,65 +325,150 @@ impl FileIO for Pipe {
         self.writable
     }
 
+    /// 读取管道中已有的数据, 至少读到一个字节才返回
+    ///
+    /// 管道为空时, 若写端全部关闭则返回0(EOF), 否则阻塞等待, 非阻塞模式下返回`Again`。
     fn read(&self, buf: &mut [u8]) -> AxResult<usize> {
         trace!("kernel: Pipe::read");
         assert!(self.readable());
-        let want_to_read = buf.len();
-        let mut buf_iter = buf.iter_mut();
-        let mut already_read = 0usize;
+        if buf.is_empty() {
+            return Ok(0);
+        }
         loop {
-            let mut ring_buffer = self.buffer.lock();
-            let loop_read = ring_buffer.available_read();
-            if loop_read == 0 {
-                if ring_buffer.all_write_ends_closed() {
-                    return Ok(already_read);
-                }
-                drop(ring_buffer);
-                yield_now();
-                continue;
+            let mut ring = self.buffer.ring.lock();
+            if ring.available_read() > 0 {
+                let read_len = ring.read(buf);
+                drop(ring);
+                self.buffer.wake_writers();
+                return Ok(read_len);
             }
-            for _ in 0..loop_read {
-                if let Some(byte_ref) = buf_iter.next() {
-                    *byte_ref = ring_buffer.read_byte();
-                    already_read += 1;
-                    if already_read == want_to_read {
-                        return Ok(want_to_read);
-                    }
-                } else {
-                    return Ok(already_read);
-                }
+            if ring.all_write_ends_closed() {
+                return Ok(0);
             }
+            drop(ring);
+            if self.is_nonblocking() {
+                return ax_err!(Again);
+            }
+            self.buffer.read_wq.wait_until(|| {
+                let ring = self.buffer.ring.lock();
+                ring.available_read() > 0 || ring.all_write_ends_closed()
+            });
         }
     }
+
+    /// 向管道写入数据
+    ///
+    /// 不超过`PIPE_BUF`字节的写入是原子的, 要么一次全部写入, 要么等待。读端全部关闭时返回
+    /// `BrokenPipe`(EPIPE); 非阻塞模式下缓冲区已满时返回`Again`, 或者返回已经写入的字节数。
     fn write(&self, buf: &[u8]) -> AxResult<usize> {
         trace!("kernel: Pipe::write");
         assert!(self.writable());
-        let want_to_write = buf.len();
-        let mut buf_iter = buf.iter();
-        let mut already_write = 0usize;
-        loop {
-            let mut ring_buffer = self.buffer.lock();
-            let loop_write = ring_buffer.available_write();
-            if loop_write == 0 {
-                drop(ring_buffer);
-                yield_now();
+        let atomic = buf.len() <= PIPE_BUF;
+        let mut written = 0;
+        while written < buf.len() {
+            // 原子写入要求缓冲区能一次放下全部数据, 否则有空间就写
+            let need = if atomic { buf.len() } else { 1 };
+            let mut ring = self.buffer.ring.lock();
+            if ring.all_read_ends_closed() {
+                drop(ring);
+                return if written > 0 {
+                    Ok(written)
+                } else {
+                    ax_err!(BrokenPipe)
+                };
+            }
+            if ring.available_write() >= need {
+                written += ring.write(&buf[written..]);
+                drop(ring);
+                self.buffer.wake_readers();
                 continue;
             }
-            // write at most loop_write bytes
-            for _ in 0..loop_write {
-                if let Some(byte_ref) = buf_iter.next() {
-                    ring_buffer.write_byte(*byte_ref);
-                    already_write += 1;
-                    if already_write == want_to_write {
-                        return Ok(want_to_write);
-                    }
-                } else {
-                    return Ok(already_write);
-                }
+            drop(ring);
+            if self.is_nonblocking() {
+                return if written > 0 { Ok(written) } else { ax_err!(Again) };
             }
+            self.buffer.write_wq.wait_until(|| {
+                let ring = self.buffer.ring.lock();
+                ring.available_write() >= need || ring.all_read_ends_closed()
+            });
         }
+        Ok(written)
     }
+
     fn get_type(&self) -> String {
         String::from("Pipe")
     }
+
+    fn is_nonblocking(&self) -> bool {
+        self.nonblock.load(Ordering::Acquire)
+    }
+
+    fn set_nonblocking(&self, nonblock: bool) {
+        self.nonblock.store(nonblock, Ordering::Release);
+    }
+
+    /// 读端: 有数据时可读, 写端全部关闭时挂断;
+    /// 写端: 能原子地写入`PIPE_BUF`字节时可写, 读端全部关闭时出错
+    fn poll(&self) -> PollEvents {
+        let ring = self.buffer.ring.lock();
+        let mut events = PollEvents::empty();
+        if self.readable {
+            if ring.available_read() > 0 {
+                events |= PollEvents::IN;
+            }
+            if ring.all_write_ends_closed() {
+                events |= PollEvents::HUP;
+            }
+        }
+        if self.writable {
+            if ring.all_read_ends_closed() {
+                events |= PollEvents::ERR;
+            } else if ring.available_write() >= PIPE_BUF {
+                events |= PollEvents::OUT;
+            }
+        }
+        events
+    }
+
+    fn register_poll_waker(&self, waker: &Arc<dyn PollWaker>) -> bool {
+        if self.readable {
+            self.buffer.read_wakers.register(waker);
+        }
+        if self.writable {
+            self.buffer.write_wakers.register(waker);
+        }
+        true
+    }
+
+    fn unregister_poll_waker(&self, waker: &Arc<dyn PollWaker>) {
+        if self.readable {
+            self.buffer.read_wakers.unregister(waker);
+        }
+        if self.writable {
+            self.buffer.write_wakers.unregister(waker);
+        }
+    }
+
+    fn get_stat(&self) -> AxResult<Kstat> {
+        Ok(Kstat {
+            st_dev: 1,
+            st_ino: 1,
+            st_mode: normal_file_mode(StMode::S_IFIFO).bits(),
+            st_nlink: 1,
+            st_uid: 0,
+            st_gid: 0,
+            st_rdev: 0,
+            _pad0: 0,
+            st_size: self.buffer.ring.lock().available_read() as u64,
+            st_blksize: 0,
+            _pad1: 0,
+            st_blocks: 0,
+            st_atime_sec: 0,
+            st_atime_nsec: 0,
+            st_mtime_sec: 0,
+            st_mtime_nsec: 0,
+            st_ctime_sec: 0,
+            st_ctime_nsec: 0,
+        })
+    }
 }
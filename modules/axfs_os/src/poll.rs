@@ -0,0 +1,198 @@
+//! 文件的就绪状态
+//!
+//! 每个文件通过`FileIO::poll`报告当前是否可读、可写、是否已挂断。等待就绪的任务
+//! (poll/select/epoll)睡眠在全局的等待队列上, 管道等事件源在状态变化时调用
+//! [`poll_notify`]唤醒它们, 被唤醒的任务重新检查所有关心的文件。
+//!
+//! 事件源还可以通过[`PollWakers`]通知注册在它上面的回调(例如epoll实例),
+//! 使回调方知道具体是哪个文件的状态发生了变化。控制台有输入中断时在收到输入后通知;
+//! 平台没有控制台输入中断时(目前只有dummy平台), 等待标准输入的任务只能每隔[`POLL_INTERVAL`]
+//! 重新检查, 兴趣列表中有标准输入的epoll实例也是如此。
+
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+
+use axhal::time::current_time;
+use axsync::spin::SpinNoIrq;
+use axtask::WaitQueue;
+use bitflags::bitflags;
+
+use crate::epoll::EpollFile;
+use crate::file_io::FileIO;
+use crate::stdio::stdin_has_irq;
+
+/// 等待没有就绪通知的文件时, 每隔这么久重新检查一次
+pub const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+bitflags! {
+    /// 文件的就绪事件, 与Linux的`POLLxxx`一致
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct PollEvents: u16 {
+        /// 有数据可读
+        const IN = 0x0001;
+        /// 有紧急数据可读
+        const PRI = 0x0002;
+        /// 可以写入数据
+        const OUT = 0x0004;
+        /// 发生错误, 总是会被报告
+        const ERR = 0x0008;
+        /// 对端已关闭, 总是会被报告
+        const HUP = 0x0010;
+        /// 文件描述符无效, 总是会被报告
+        const NVAL = 0x0020;
+    }
+}
+
+/// 等待文件就绪的任务
+static POLL_QUEUE: WaitQueue = WaitQueue::new();
+/// 就绪状态变化的次数, 用于判断睡眠期间是否有事件发生
+static POLL_SEQ: AtomicUsize = AtomicUsize::new(0);
+
+/// 获取当前的事件序号, 应当在检查文件状态之前获取, 再传给[`poll_wait`]
+pub fn poll_seq() -> usize {
+    POLL_SEQ.load(Ordering::Acquire)
+}
+
+/// 通知等待者有文件的就绪状态发生了变化
+///
+/// 不能在持有等待条件会用到的自旋锁时调用。
+pub fn poll_notify() {
+    POLL_SEQ.fetch_add(1, Ordering::AcqRel);
+    POLL_QUEUE.notify_all(false);
+}
+
+/// 睡眠直到事件序号不再是`seq`, 或者超时
+///
+/// - seq: 检查文件状态之前通过[`poll_seq`]获取的事件序号, 检查期间发生的事件不会被错过
+/// - timeout: 超时时间, None表示一直等待
+///
+/// 返回是否超时
+pub fn poll_wait(seq: usize, timeout: Option<Duration>) -> bool {
+    let changed = || POLL_SEQ.load(Ordering::Acquire) != seq;
+    match timeout {
+        Some(dur) => POLL_QUEUE.wait_timeout_until(dur, changed),
+        None => {
+            POLL_QUEUE.wait_until(changed);
+            false
+        }
+    }
+}
+
+/// 文件就绪状态变化时的回调
+pub trait PollWaker: Send + Sync {
+    /// 文件的就绪状态可能发生了变化。在事件源的上下文中调用, 不能阻塞
+    fn wake(&self);
+
+    /// 回调所属的epoll实例上注册的回调, 不属于epoll实例时为None。用于计算epoll实例的嵌套层数
+    fn outer(&self) -> Option<Arc<PollWakers>> {
+        None
+    }
+}
+
+/// 事件源上注册的回调列表
+///
+/// 只保存回调的弱引用, 回调方释放后自动失效。
+pub struct PollWakers {
+    wakers: SpinNoIrq<Vec<Weak<dyn PollWaker>>>,
+}
+
+impl PollWakers {
+    /// 创建一个空的回调列表
+    pub const fn new() -> Self {
+        Self {
+            wakers: SpinNoIrq::new(Vec::new()),
+        }
+    }
+
+    /// 注册回调
+    pub fn register(&self, waker: &Arc<dyn PollWaker>) {
+        let mut wakers = self.wakers.lock();
+        wakers.retain(|w| w.strong_count() > 0);
+        wakers.push(Arc::downgrade(waker));
+    }
+
+    /// 注销回调
+    pub fn unregister(&self, waker: &Arc<dyn PollWaker>) {
+        let ptr = Arc::as_ptr(waker) as *const ();
+        self.wakers
+            .lock()
+            .retain(|w| w.strong_count() > 0 && w.as_ptr() as *const () != ptr);
+    }
+
+    /// 事件源外面嵌套的epoll实例的层数, 即沿着回调所属的epoll实例向上的最长路径, 没有时为0
+    pub fn nest_depth(&self) -> usize {
+        let outers: Vec<Arc<PollWakers>> = self
+            .wakers
+            .lock()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter_map(|waker| waker.outer())
+            .collect();
+        outers
+            .iter()
+            .map(|outer| outer.nest_depth() + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// 调用所有回调, 并唤醒poll的等待者
+    ///
+    /// 不能在持有事件源的自旋锁时调用。
+    pub fn wake(&self) {
+        let wakers: Vec<Arc<dyn PollWaker>> =
+            self.wakers.lock().iter().filter_map(Weak::upgrade).collect();
+        for waker in wakers {
+            waker.wake();
+        }
+        poll_notify();
+    }
+}
+
+/// 文件的就绪状态变化时是否没有通知, 需要定时重新检查
+pub fn needs_periodic_check(file: &dyn FileIO) -> bool {
+    match file.get_type().as_str() {
+        "Stdin" => !stdin_has_irq(),
+        "Epoll" => file
+            .as_any()
+            .downcast_ref::<EpollFile>()
+            .map_or(false, EpollFile::periodic),
+        _ => false,
+    }
+}
+
+/// 反复检查文件的就绪状态, 直到有文件就绪或者超时
+///
+/// - deadline: 截止时间, None表示一直等待
+/// - periodic: 是否需要定时重新检查, 见[`needs_periodic_check`]
+/// - check: 检查一遍所有文件, 返回就绪的个数
+///
+/// 返回就绪的个数, 超时返回0
+pub fn poll_until<F>(deadline: Option<Duration>, periodic: bool, mut check: F) -> usize
+where
+    F: FnMut() -> usize,
+{
+    loop {
+        // 先取得事件序号再检查, 检查过程中发生的事件会使接下来的等待立即返回
+        let seq = poll_seq();
+        let ready = check();
+        if ready > 0 {
+            return ready;
+        }
+        let mut timeout = match deadline {
+            Some(deadline) => {
+                let now = current_time();
+                if now >= deadline {
+                    return 0;
+                }
+                Some(deadline - now)
+            }
+            None => None,
+        };
+        if periodic {
+            timeout = Some(timeout.map_or(POLL_INTERVAL, |t| t.min(POLL_INTERVAL)));
+        }
+        poll_wait(seq, timeout);
+    }
+}
@@ -0,0 +1,131 @@
+//! 文件和目录的重命名
+//!
+//! 除了文件系统中的目录项, 模拟的链接表和特殊文件表也以路径为键, 重命名时需要一并更新。
+
+use alloc::format;
+use axerrno::{ax_err, AxResult};
+use axfs::api::{metadata, path_exists, rename as fs_rename, same_filesystem};
+use axsync::Mutex;
+use bitflags::bitflags;
+use log::{debug, error};
+
+use crate::link::{real_path, rename_link};
+use crate::mknod::rename_special_node;
+use crate::FilePath;
+
+bitflags! {
+    /// renameat2的标志位
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct RenameFlags: u32 {
+        /// 目标已存在时失败, 而不是替换
+        const NOREPLACE = 1 << 0;
+        /// 交换两个路径, 两者都必须存在。不是原子的, 见[`rename`]
+        const EXCHANGE = 1 << 1;
+        /// 在源路径留下whiteout, 只用于overlay文件系统, 不支持
+        const WHITEOUT = 1 << 2;
+    }
+}
+
+/// 交换两个路径时持有, 使同时进行的交换不会用到同一个临时名字
+static EXCHANGE_LOCK: Mutex<()> = Mutex::new(());
+
+/// 路径是否存在, 包括只存在于链接表中的链接
+fn exists(path: &FilePath) -> bool {
+    path_exists(path.path()) || real_path(path).is_some()
+}
+
+/// 将`old`移动到`new`, `new`已存在时被替换
+fn move_path(old: &FilePath, new: &FilePath) -> AxResult {
+    // 只存在于链接表中的链接没有对应的目录项
+    if path_exists(old.path()) {
+        fs_rename(old.path(), new.path())?;
+    }
+    rename_link(old, new);
+    rename_special_node(old, new);
+    Ok(())
+}
+
+/// 重命名文件或目录, 对应`renameat2`
+///
+/// - old: 原路径
+/// - new: 新路径
+/// - flags: 见[`RenameFlags`], 不能同时指定NOREPLACE和EXCHANGE
+///
+/// 交换两个路径(EXCHANGE)不是原子的: 文件系统不支持交换目录项, 这里借助`old`所在目录下的临时名字
+/// `<old>.exchange`分三步完成, 其他进程可能看到中间状态。中途失败时恢复原状, 恢复也失败时文件留在临时名字下,
+/// 并记录错误日志。为了不在中途失败时移动整棵目录树, 只支持交换同一文件系统中的非目录文件,
+/// 其他情况返回`Unsupported`。
+pub fn rename(old: &FilePath, new: &FilePath, flags: RenameFlags) -> AxResult {
+    debug!("rename: {} -> {}, flags: {:?}", old.path(), new.path(), flags);
+    if flags.contains(RenameFlags::WHITEOUT) {
+        return ax_err!(Unsupported, "RENAME_WHITEOUT is not supported");
+    }
+    if flags.contains(RenameFlags::NOREPLACE | RenameFlags::EXCHANGE) {
+        return ax_err!(InvalidInput);
+    }
+    if !exists(old) {
+        return ax_err!(NotFound);
+    }
+    if !flags.contains(RenameFlags::EXCHANGE) {
+        if flags.contains(RenameFlags::NOREPLACE) && exists(new) {
+            return ax_err!(AlreadyExists);
+        }
+        return move_path(old, new);
+    }
+
+    if !exists(new) {
+        return ax_err!(NotFound);
+    }
+    if old.path().trim_end_matches('/') == new.path().trim_end_matches('/') {
+        return Ok(());
+    }
+    if old.renamed(new, new).is_some() || new.renamed(old, old).is_some() {
+        // 一个是另一个的祖先, 交换后会形成环
+        return ax_err!(InvalidInput);
+    }
+    let is_dir = |path: &FilePath| metadata(path.path()).map_or(false, |m| m.is_dir());
+    if is_dir(old) || is_dir(new) {
+        return ax_err!(
+            Unsupported,
+            "RENAME_EXCHANGE of directories is not supported"
+        );
+    }
+    if !same_filesystem(old.path(), new.path())? {
+        return ax_err!(
+            Unsupported,
+            "RENAME_EXCHANGE across filesystems is not supported"
+        );
+    }
+    let _guard = EXCHANGE_LOCK.lock();
+    let tmp = FilePath::new(&format!(
+        "{}.{}.exchange",
+        old.dir(),
+        old.file().trim_end_matches('/')
+    ));
+    if exists(&tmp) {
+        return ax_err!(AlreadyExists);
+    }
+    move_path(old, &tmp)?;
+    if let Err(e) = move_path(new, old) {
+        restore(&tmp, old);
+        return Err(e);
+    }
+    if let Err(e) = move_path(&tmp, new) {
+        restore(old, new);
+        restore(&tmp, old);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// 交换失败时将`from`移回`to`, 失败时记录文件留在了哪里
+fn restore(from: &FilePath, to: &FilePath) {
+    if let Err(e) = move_path(from, to) {
+        error!(
+            "rename exchange: cannot move {} back to {}: {:?}",
+            from.path(),
+            to.path(),
+            e
+        );
+    }
+}
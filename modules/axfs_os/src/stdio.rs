@@ -1,9 +1,33 @@
 use alloc::string::String;
+use alloc::sync::Arc;
 use super::file_io::FileIO;
+use crate::poll::{PollEvents, PollWaker, PollWakers};
 use axerrno::{AxError, AxResult};
 use axhal::console::{getchar, write_bytes};
+use axsync::spin::SpinNoIrq;
 use axtask::yield_now;
 
+/// poll标准输入时预读的字符, 下一次读取时返回
+static STDIN_PENDING: SpinNoIrq<Option<u8>> = SpinNoIrq::new(None);
+/// 标准输入上注册的回调
+static STDIN_WAKERS: PollWakers = PollWakers::new();
+/// 控制台是否有输入中断, 第一次使用时设置
+static STDIN_IRQ: SpinNoIrq<Option<bool>> = SpinNoIrq::new(None);
+
+/// 控制台是否有输入中断
+///
+/// 有输入中断时, 控制台收到输入后在中断上下文中调用标准输入上注册的回调, 等待标准输入的任务不需要定时重新检查。
+pub fn stdin_has_irq() -> bool {
+    *STDIN_IRQ
+        .lock()
+        .get_or_insert_with(|| axhal::console::set_input_handler(wake_stdin_wakers))
+}
+
+/// 控制台收到输入时在中断上下文中调用
+fn wake_stdin_wakers() {
+    STDIN_WAKERS.wake();
+}
+
 /// stdin file for getting chars from console
 pub struct Stdin;
 
@@ -23,7 +47,9 @@ impl FileIO for Stdin {
     fn read(&self, _buf: &mut [u8]) -> AxResult<usize> {
         let ch: u8;
         loop {
-            match getchar() {
+            // 先取出poll时预读的字符
+            let c = STDIN_PENDING.lock().take().or_else(getchar);
+            match c {
                 Some(c) => {
                     ch = c;
                     break;
@@ -48,6 +74,29 @@ impl FileIO for Stdin {
     fn get_type(&self) -> String {
         String::from("Stdin")
     }
+    /// 控制台无法查询是否有输入, 只能预读一个字符来判断
+    fn poll(&self) -> PollEvents {
+        let mut pending = STDIN_PENDING.lock();
+        if pending.is_none() {
+            *pending = getchar();
+        }
+        if pending.is_some() {
+            PollEvents::IN
+        } else {
+            PollEvents::empty()
+        }
+    }
+    /// 控制台没有输入中断时不支持回调
+    fn register_poll_waker(&self, waker: &Arc<dyn PollWaker>) -> bool {
+        if !stdin_has_irq() {
+            return false;
+        }
+        STDIN_WAKERS.register(waker);
+        true
+    }
+    fn unregister_poll_waker(&self, waker: &Arc<dyn PollWaker>) {
+        STDIN_WAKERS.unregister(waker);
+    }
 }
 
 impl FileIO for Stdout {
@@ -73,6 +122,9 @@ impl FileIO for Stdout {
     fn get_type(&self) -> String {
         String::from("Stdout")
     }
+    fn poll(&self) -> PollEvents {
+        PollEvents::OUT
+    }
 }
 
 impl FileIO for Stderr {
@@ -95,4 +147,7 @@ impl FileIO for Stderr {
     fn get_type(&self) -> String {
         String::from("Stderr")
     }
+    fn poll(&self) -> PollEvents {
+        PollEvents::OUT
+    }
 }
@@ -72,6 +72,16 @@ impl FilePath {
     pub fn end_with(&self, other: &Self) -> bool {
         self.0.ends_with(other.0.as_str())
     }
+    /// 将`src`重命名为`dest`之后, 当前路径对应的新路径
+    ///
+    /// 当前路径就是`src`或位于`src`目录下时返回新路径, 否则返回None
+    pub fn renamed(&self, src: &Self, dest: &Self) -> Option<Self> {
+        let rest = self.0.strip_prefix(src.0.trim_end_matches('/'))?;
+        if !rest.is_empty() && !rest.starts_with('/') {
+            return None;
+        }
+        Some(Self(String::from(dest.0.trim_end_matches('/')) + rest))
+    }
 }
 
 // impl Clone for FilePath {
@@ -94,6 +104,7 @@ pub struct DirEnt {
     pub d_name: [u8; 0],
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum DirEntType {
     /// 未知类型文件
     UNKNOWN = 0,
@@ -169,6 +180,7 @@ pub struct Kstat {
 }
 bitflags! {
     /// 指定 st_mode 的选项
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
     pub struct StMode: u32 {
         /// 是普通文件
         const S_IFREG = 1 << 15;
@@ -176,6 +188,14 @@ bitflags! {
         const S_IFDIR = 1 << 14;
         /// 是字符设备
         const S_IFCHR = 1 << 13;
+        /// 是块设备
+        const S_IFBLK = (1 << 14) | (1 << 13);
+        /// 是FIFO
+        const S_IFIFO = 1 << 12;
+        /// 是socket
+        const S_IFSOCK = (1 << 15) | (1 << 14);
+        /// 文件类型的掩码
+        const S_IFMT = 0o170000;
         /// 是否设置 uid/gid/sticky
         //const S_ISUID = 1 << 14;
         //const S_ISGID = 1 << 13;
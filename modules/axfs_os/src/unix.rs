@@ -0,0 +1,901 @@
+//! Unix域socket(AF_UNIX)
+//!
+//! 完全在内核中实现, 不经过网络协议栈。支持流式(SOCK_STREAM)和数据报(SOCK_DGRAM)两种类型,
+//! 可以绑定到文件系统中的路径(创建一个socket类型的占位文件, 见[`crate::mknod`])或者抽象名字,
+//! 也可以由socketpair直接创建一对相连的socket。
+//!
+//! 消息可以携带文件(`SCM_RIGHTS`), 文件随消息放入对端的接收队列, 由系统调用在接收时放入接收进程
+//! 的文件描述符表。与Linux不同, 这里没有回收循环引用的垃圾收集: 若一个socket通过自身发送且从不被
+//! 接收, 它不会被释放。
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use axerrno::{ax_err, ax_err_type, AxResult};
+use axsync::spin::SpinNoIrq;
+use axsync::Mutex;
+use axtask::WaitQueue;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use log::debug;
+
+use crate::file_io::FileIO;
+use crate::mknod::{get_special_node, mksock, SpecialNode};
+use crate::pipe::PIPE_DEFAULT_SIZE;
+use crate::poll::{PollEvents, PollWaker, PollWakers};
+use crate::types::{normal_file_mode, Kstat, StMode};
+use crate::FilePath;
+
+/// 每个接收队列最多缓存的字节数, 也是数据报的最大长度
+pub const UNIX_BUF_SIZE: usize = PIPE_DEFAULT_SIZE;
+/// listen的backlog的上限, 与Linux的`somaxconn`默认值一致
+const UNIX_MAX_BACKLOG: usize = 4096;
+
+/// 绑定到抽象名字的socket
+static ABSTRACT_NAMES: Mutex<BTreeMap<Vec<u8>, Weak<UnixNode>>> = Mutex::new(BTreeMap::new());
+
+/// 消息携带的文件
+pub type ScmRights = Vec<Arc<dyn FileIO>>;
+
+/// Unix域socket的地址
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum UnixAddr {
+    /// 未绑定
+    Unnamed,
+    /// 文件系统中的路径
+    Path(FilePath),
+    /// 抽象名字, 不含开头的0字节
+    Abstract(Vec<u8>),
+}
+
+/// Unix域socket的类型
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UnixSocketType {
+    /// 面向连接的字节流
+    Stream,
+    /// 保留消息边界的数据报
+    Dgram,
+}
+
+/// 接收到的消息
+pub struct RecvMsg {
+    /// 写入缓冲区的字节数
+    pub len: usize,
+    /// 数据报是否因为缓冲区太小而被截断
+    pub truncated: bool,
+    /// 消息携带的文件
+    pub rights: ScmRights,
+    /// 发送方的地址
+    pub from: UnixAddr,
+}
+
+/// 接收队列中的一条消息
+struct Message {
+    data: Vec<u8>,
+    /// 流socket中已经读出的字节数
+    offset: usize,
+    rights: ScmRights,
+    from: UnixAddr,
+}
+
+struct MsgQueue {
+    msgs: VecDeque<Message>,
+    /// 队列中未读的字节数
+    len: usize,
+    /// 接收方已关闭, 之后的发送失败
+    reader_closed: bool,
+    /// 发送方已关闭, 读完已有的数据后读到EOF, 只用于流socket
+    writer_closed: bool,
+}
+
+/// 接收队列
+///
+/// 与管道一样使用自旋锁保护, 因为等待队列检查条件时已经持有了调度队列的锁, 不能再使用会睡眠的锁。
+struct Channel {
+    queue: SpinNoIrq<MsgQueue>,
+    /// 等待消息到达的接收者
+    read_wq: WaitQueue,
+    /// 等待队列腾出空间的发送者
+    write_wq: WaitQueue,
+    read_wakers: PollWakers,
+    write_wakers: PollWakers,
+}
+
+impl Channel {
+    fn new() -> Self {
+        Self {
+            queue: SpinNoIrq::new(MsgQueue {
+                msgs: VecDeque::new(),
+                len: 0,
+                reader_closed: false,
+                writer_closed: false,
+            }),
+            read_wq: WaitQueue::new(),
+            write_wq: WaitQueue::new(),
+            read_wakers: PollWakers::new(),
+            write_wakers: PollWakers::new(),
+        }
+    }
+
+    fn wake_readers(&self) {
+        self.read_wq.notify_all(false);
+        self.read_wakers.wake();
+    }
+
+    fn wake_writers(&self) {
+        self.write_wq.notify_all(false);
+        self.write_wakers.wake();
+    }
+
+    /// 关闭接收方, 丢弃未读的消息
+    fn close_reader(&self) {
+        let msgs = {
+            let mut queue = self.queue.lock();
+            queue.reader_closed = true;
+            queue.len = 0;
+            core::mem::take(&mut queue.msgs)
+        };
+        // 在锁外释放消息携带的文件
+        drop(msgs);
+        self.wake_readers();
+        self.wake_writers();
+    }
+
+    /// 关闭发送方, 接收方读完已有的数据后读到EOF
+    fn close_writer(&self) {
+        self.queue.lock().writer_closed = true;
+        self.wake_readers();
+        self.wake_writers();
+    }
+
+    /// 放入一个数据报, 队列已满时等待
+    fn send_dgram(&self, msg: Message, nonblock: bool) -> AxResult<usize> {
+        let len = msg.data.len();
+        if len > UNIX_BUF_SIZE {
+            return ax_err!(InvalidInput, "message too long");
+        }
+        let mut msg = Some(msg);
+        loop {
+            {
+                let mut queue = self.queue.lock();
+                if queue.reader_closed {
+                    return ax_err!(ConnectionRefused, "peer closed");
+                }
+                if queue.len + len <= UNIX_BUF_SIZE {
+                    queue.msgs.push_back(msg.take().unwrap());
+                    queue.len += len;
+                    break;
+                }
+            }
+            if nonblock {
+                return ax_err!(Again);
+            }
+            self.write_wq.wait_until(|| {
+                let queue = self.queue.lock();
+                queue.len + len <= UNIX_BUF_SIZE || queue.reader_closed
+            });
+        }
+        self.wake_readers();
+        Ok(len)
+    }
+
+    /// 取出一个数据报, 队列为空时等待
+    fn recv_dgram(&self, buf: &mut [u8], nonblock: bool) -> AxResult<RecvMsg> {
+        loop {
+            let msg = {
+                let mut queue = self.queue.lock();
+                let msg = queue.msgs.pop_front();
+                if let Some(msg) = &msg {
+                    queue.len -= msg.data.len();
+                }
+                msg
+            };
+            if let Some(msg) = msg {
+                self.wake_writers();
+                let len = msg.data.len().min(buf.len());
+                buf[..len].copy_from_slice(&msg.data[..len]);
+                return Ok(RecvMsg {
+                    len,
+                    truncated: len < msg.data.len(),
+                    rights: msg.rights,
+                    from: msg.from,
+                });
+            }
+            if nonblock {
+                return ax_err!(Again);
+            }
+            self.read_wq
+                .wait_until(|| !self.queue.lock().msgs.is_empty());
+        }
+    }
+
+    /// 写入字节流, 文件随第一段数据发送; 不阻塞时返回已经写入的字节数
+    fn send_stream(&self, buf: &[u8], rights: ScmRights, nonblock: bool) -> AxResult<usize> {
+        let mut rights = Some(rights);
+        let mut written = 0;
+        while written < buf.len() {
+            let mut queue = self.queue.lock();
+            if queue.reader_closed || queue.writer_closed {
+                drop(queue);
+                return if written > 0 {
+                    Ok(written)
+                } else {
+                    ax_err!(BrokenPipe)
+                };
+            }
+            let space = UNIX_BUF_SIZE - queue.len;
+            if space > 0 {
+                let len = space.min(buf.len() - written);
+                queue.msgs.push_back(Message {
+                    data: buf[written..written + len].to_vec(),
+                    offset: 0,
+                    rights: rights.take().unwrap_or_default(),
+                    from: UnixAddr::Unnamed,
+                });
+                queue.len += len;
+                written += len;
+                drop(queue);
+                self.wake_readers();
+                continue;
+            }
+            drop(queue);
+            if nonblock {
+                return if written > 0 {
+                    Ok(written)
+                } else {
+                    ax_err!(Again)
+                };
+            }
+            self.write_wq.wait_until(|| {
+                let queue = self.queue.lock();
+                queue.len < UNIX_BUF_SIZE || queue.reader_closed || queue.writer_closed
+            });
+        }
+        Ok(written)
+    }
+
+    /// 读出字节流, 至少读到一个字节才返回, 发送方关闭后返回0
+    ///
+    /// 携带文件的数据不与之前的数据在一次读取中合并, 使文件总是随它所附带的数据一起收到。
+    fn recv_stream(&self, buf: &mut [u8], nonblock: bool) -> AxResult<RecvMsg> {
+        let mut msg = RecvMsg {
+            len: 0,
+            truncated: false,
+            rights: Vec::new(),
+            from: UnixAddr::Unnamed,
+        };
+        if buf.is_empty() {
+            return Ok(msg);
+        }
+        loop {
+            let mut queue = self.queue.lock();
+            if !queue.msgs.is_empty() {
+                while msg.len < buf.len() {
+                    let front = match queue.msgs.front_mut() {
+                        Some(front) => front,
+                        None => break,
+                    };
+                    if msg.len > 0 && !front.rights.is_empty() {
+                        break;
+                    }
+                    let len = (front.data.len() - front.offset).min(buf.len() - msg.len);
+                    buf[msg.len..msg.len + len]
+                        .copy_from_slice(&front.data[front.offset..front.offset + len]);
+                    front.offset += len;
+                    msg.len += len;
+                    msg.rights.append(&mut front.rights);
+                    if front.offset == front.data.len() {
+                        queue.msgs.pop_front();
+                    }
+                }
+                queue.len -= msg.len;
+                drop(queue);
+                self.wake_writers();
+                return Ok(msg);
+            }
+            if queue.writer_closed || queue.reader_closed {
+                return Ok(msg);
+            }
+            drop(queue);
+            if nonblock {
+                return ax_err!(Again);
+            }
+            self.read_wq.wait_until(|| {
+                let queue = self.queue.lock();
+                !queue.msgs.is_empty() || queue.writer_closed || queue.reader_closed
+            });
+        }
+    }
+}
+
+/// 等待accept的连接
+struct Backlog {
+    /// 是否在监听
+    listening: bool,
+    /// 最多等待的连接数
+    max: usize,
+    queue: VecDeque<UnixSocket>,
+}
+
+/// socket中可以通过地址找到的部分
+///
+/// 地址表(文件系统中的占位文件和抽象名字表)只保存它的弱引用, socket关闭后地址随之失效。
+pub struct UnixNode {
+    socket_type: UnixSocketType,
+    /// 绑定的地址
+    addr: Mutex<UnixAddr>,
+    /// 数据报socket的接收队列
+    rx: Channel,
+    /// 监听中的流socket上等待accept的连接
+    backlog: SpinNoIrq<Backlog>,
+    /// 等待连接到达的accept
+    accept_wq: WaitQueue,
+    /// 等待backlog腾出空间的connect
+    connect_wq: WaitQueue,
+    accept_wakers: PollWakers,
+}
+
+impl UnixNode {
+    fn new(socket_type: UnixSocketType) -> Self {
+        Self {
+            socket_type,
+            addr: Mutex::new(UnixAddr::Unnamed),
+            rx: Channel::new(),
+            backlog: SpinNoIrq::new(Backlog {
+                listening: false,
+                max: 0,
+                queue: VecDeque::new(),
+            }),
+            accept_wq: WaitQueue::new(),
+            connect_wq: WaitQueue::new(),
+            accept_wakers: PollWakers::new(),
+        }
+    }
+}
+
+/// 根据地址找到绑定的socket
+///
+/// 路径不存在时返回`NotFound`, 路径不是socket或者没有socket绑定在地址上时返回`ConnectionRefused`。
+fn lookup(addr: &UnixAddr) -> AxResult<Arc<UnixNode>> {
+    let node = match addr {
+        UnixAddr::Unnamed => return ax_err!(InvalidInput, "unix socket address is empty"),
+        UnixAddr::Path(path) => match get_special_node(path) {
+            Some(SpecialNode::Socket(node)) => node,
+            Some(_) => return ax_err!(ConnectionRefused, "not a socket"),
+            None => return ax_err!(NotFound, "no such socket"),
+        },
+        UnixAddr::Abstract(name) => ABSTRACT_NAMES
+            .lock()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| ax_err_type!(ConnectionRefused, "no such socket"))?,
+    };
+    node.upgrade()
+        .ok_or_else(|| ax_err_type!(ConnectionRefused, "socket closed"))
+}
+
+/// 为绑定空地址的socket生成一个未被使用的抽象名字, 与Linux一样是5个十六进制数字
+fn autobind_name(names: &BTreeMap<Vec<u8>, Weak<UnixNode>>) -> AxResult<Vec<u8>> {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    for _ in 0..0x100000 {
+        let name = format!("{:05x}", NEXT.fetch_add(1, Ordering::Relaxed) & 0xfffff).into_bytes();
+        if names
+            .get(&name)
+            .map_or(true, |node| node.strong_count() == 0)
+        {
+            return Ok(name);
+        }
+    }
+    ax_err!(AlreadyExists, "no free abstract name")
+}
+
+enum State {
+    /// 未连接
+    Unconnected,
+    /// 监听中的流socket
+    Listening,
+    /// 已连接的流socket
+    Stream {
+        /// 本端的接收队列
+        rx: Arc<Channel>,
+        /// 对端的接收队列
+        tx: Arc<Channel>,
+        peer_addr: UnixAddr,
+    },
+    /// 设置了默认对端的数据报socket
+    Dgram {
+        peer: Weak<UnixNode>,
+        peer_addr: UnixAddr,
+    },
+}
+
+/// Unix域socket
+pub struct UnixSocket {
+    node: Arc<UnixNode>,
+    state: Mutex<State>,
+    /// 是否是非阻塞的
+    nonblock: AtomicBool,
+}
+
+impl UnixSocket {
+    /// 创建一个未绑定、未连接的socket
+    pub fn new(socket_type: UnixSocketType, nonblock: bool) -> Self {
+        Self {
+            node: Arc::new(UnixNode::new(socket_type)),
+            state: Mutex::new(State::Unconnected),
+            nonblock: AtomicBool::new(nonblock),
+        }
+    }
+
+    /// 创建一对互相连接的socket, 对应socketpair
+    pub fn pair(socket_type: UnixSocketType, nonblock: bool) -> (Self, Self) {
+        let a = Self::new(socket_type, nonblock);
+        let b = Self::new(socket_type, nonblock);
+        match socket_type {
+            UnixSocketType::Stream => {
+                let (a_to_b, b_to_a) = (Arc::new(Channel::new()), Arc::new(Channel::new()));
+                *a.state.lock() = State::Stream {
+                    rx: b_to_a.clone(),
+                    tx: a_to_b.clone(),
+                    peer_addr: UnixAddr::Unnamed,
+                };
+                *b.state.lock() = State::Stream {
+                    rx: a_to_b,
+                    tx: b_to_a,
+                    peer_addr: UnixAddr::Unnamed,
+                };
+            }
+            UnixSocketType::Dgram => {
+                *a.state.lock() = State::Dgram {
+                    peer: Arc::downgrade(&b.node),
+                    peer_addr: UnixAddr::Unnamed,
+                };
+                *b.state.lock() = State::Dgram {
+                    peer: Arc::downgrade(&a.node),
+                    peer_addr: UnixAddr::Unnamed,
+                };
+            }
+        }
+        (a, b)
+    }
+
+    /// socket类型
+    pub fn socket_type(&self) -> UnixSocketType {
+        self.node.socket_type
+    }
+
+    /// 本地地址, 未绑定时为`Unnamed`
+    pub fn local_addr(&self) -> UnixAddr {
+        self.node.addr.lock().clone()
+    }
+
+    /// 对端地址
+    pub fn peer_addr(&self) -> AxResult<UnixAddr> {
+        match &*self.state.lock() {
+            State::Stream { peer_addr, .. } | State::Dgram { peer_addr, .. } => {
+                Ok(peer_addr.clone())
+            }
+            _ => ax_err!(NotConnected),
+        }
+    }
+
+    /// 绑定地址
+    ///
+    /// 绑定路径时创建socket类型的占位文件, 路径已存在时返回`AlreadyExists`; 绑定`Unnamed`时
+    /// 自动分配一个抽象名字。
+    pub fn bind(&self, addr: UnixAddr) -> AxResult {
+        let mut local_addr = self.node.addr.lock();
+        if *local_addr != UnixAddr::Unnamed {
+            return ax_err!(InvalidInput, "unix socket already bound");
+        }
+        let addr = match addr {
+            UnixAddr::Path(path) => {
+                mksock(&path, Arc::downgrade(&self.node))?;
+                UnixAddr::Path(path)
+            }
+            UnixAddr::Abstract(name) => {
+                let mut names = ABSTRACT_NAMES.lock();
+                if names
+                    .get(&name)
+                    .map_or(false, |node| node.strong_count() > 0)
+                {
+                    return ax_err!(AlreadyExists, "abstract name in use");
+                }
+                names.insert(name.clone(), Arc::downgrade(&self.node));
+                UnixAddr::Abstract(name)
+            }
+            UnixAddr::Unnamed => {
+                let mut names = ABSTRACT_NAMES.lock();
+                let name = autobind_name(&names)?;
+                names.insert(name.clone(), Arc::downgrade(&self.node));
+                UnixAddr::Abstract(name)
+            }
+        };
+        debug!("unix socket bound to {:?}", addr);
+        *local_addr = addr;
+        Ok(())
+    }
+
+    /// 开始监听连接, 只有绑定了地址的流socket支持
+    pub fn listen(&self, backlog: usize) -> AxResult {
+        if self.node.socket_type != UnixSocketType::Stream {
+            return ax_err!(Unsupported);
+        }
+        let mut state = self.state.lock();
+        if !matches!(*state, State::Unconnected | State::Listening) {
+            return ax_err!(InvalidInput, "unix socket already connected");
+        }
+        if *self.node.addr.lock() == UnixAddr::Unnamed {
+            return ax_err!(InvalidInput, "unix socket not bound");
+        }
+        {
+            let mut pending = self.node.backlog.lock();
+            pending.listening = true;
+            pending.max = backlog.clamp(1, UNIX_MAX_BACKLOG);
+        }
+        *state = State::Listening;
+        self.node.connect_wq.notify_all(false);
+        Ok(())
+    }
+
+    /// 接受一个连接, 新的socket是阻塞的
+    pub fn accept(&self) -> AxResult<UnixSocket> {
+        if !matches!(*self.state.lock(), State::Listening) {
+            return ax_err!(InvalidInput, "unix socket not listening");
+        }
+        loop {
+            let socket = {
+                let mut pending = self.node.backlog.lock();
+                if !pending.listening {
+                    return ax_err!(InvalidInput, "unix socket not listening");
+                }
+                pending.queue.pop_front()
+            };
+            if let Some(socket) = socket {
+                self.node.connect_wq.notify_one(false);
+                return Ok(socket);
+            }
+            if self.is_nonblocking() {
+                return ax_err!(Again);
+            }
+            self.node.accept_wq.wait_until(|| {
+                let pending = self.node.backlog.lock();
+                !pending.queue.is_empty() || !pending.listening
+            });
+        }
+    }
+
+    /// 连接到`addr`
+    ///
+    /// 流socket的连接放入对端的backlog后立即返回, 不等待accept, backlog已满时等待; 数据报socket
+    /// 只记录默认的对端。
+    pub fn connect(&self, addr: &UnixAddr) -> AxResult {
+        let node = lookup(addr)?;
+        if node.socket_type != self.node.socket_type {
+            return ax_err!(InvalidInput, "unix socket type mismatch");
+        }
+        let peer_addr = node.addr.lock().clone();
+        match self.node.socket_type {
+            UnixSocketType::Stream => self.connect_stream(&node, peer_addr),
+            UnixSocketType::Dgram => {
+                *self.state.lock() = State::Dgram {
+                    peer: Arc::downgrade(&node),
+                    peer_addr,
+                };
+                Ok(())
+            }
+        }
+    }
+
+    fn connect_stream(&self, listener: &UnixNode, peer_addr: UnixAddr) -> AxResult {
+        let mut state = self.state.lock();
+        match *state {
+            State::Unconnected => {}
+            State::Stream { .. } => return ax_err!(AlreadyExists, "unix socket already connected"),
+            _ => return ax_err!(InvalidInput, "unix socket is listening"),
+        }
+
+        let (to_server, to_client) = (Arc::new(Channel::new()), Arc::new(Channel::new()));
+        let server = UnixSocket::new(UnixSocketType::Stream, false);
+        *server.node.addr.lock() = peer_addr.clone();
+        *server.state.lock() = State::Stream {
+            rx: to_server.clone(),
+            tx: to_client.clone(),
+            peer_addr: self.local_addr(),
+        };
+        loop {
+            {
+                let mut pending = listener.backlog.lock();
+                if !pending.listening {
+                    return ax_err!(ConnectionRefused, "unix socket not listening");
+                }
+                if pending.queue.len() < pending.max {
+                    pending.queue.push_back(server);
+                    break;
+                }
+            }
+            if self.is_nonblocking() {
+                return ax_err!(Again);
+            }
+            listener.connect_wq.wait_until(|| {
+                let pending = listener.backlog.lock();
+                pending.queue.len() < pending.max || !pending.listening
+            });
+        }
+        listener.accept_wq.notify_one(false);
+        listener.accept_wakers.wake();
+
+        *state = State::Stream {
+            rx: to_client,
+            tx: to_server,
+            peer_addr,
+        };
+        Ok(())
+    }
+
+    /// 发送数据和携带的文件, `nonblock`为真时即使socket是阻塞的也不等待
+    ///
+    /// `to`为数据报的目标地址, 为`None`时发送给连接的对端。流socket忽略`to`。
+    pub fn send_msg(
+        &self,
+        buf: &[u8],
+        rights: ScmRights,
+        to: Option<&UnixAddr>,
+        nonblock: bool,
+    ) -> AxResult<usize> {
+        let nonblock = nonblock || self.is_nonblocking();
+        match self.node.socket_type {
+            UnixSocketType::Stream => {
+                let tx = match &*self.state.lock() {
+                    State::Stream { tx, .. } => tx.clone(),
+                    _ => return ax_err!(NotConnected),
+                };
+                if buf.is_empty() {
+                    return Ok(0);
+                }
+                tx.send_stream(buf, rights, nonblock)
+            }
+            UnixSocketType::Dgram => {
+                let peer = match to {
+                    Some(addr) => lookup(addr)?,
+                    None => match &*self.state.lock() {
+                        State::Dgram { peer, .. } => peer
+                            .upgrade()
+                            .ok_or_else(|| ax_err_type!(ConnectionRefused, "peer closed"))?,
+                        _ => return ax_err!(NotConnected),
+                    },
+                };
+                if peer.socket_type != UnixSocketType::Dgram {
+                    return ax_err!(InvalidInput, "unix socket type mismatch");
+                }
+                let msg = Message {
+                    data: buf.to_vec(),
+                    offset: 0,
+                    rights,
+                    from: self.local_addr(),
+                };
+                peer.rx.send_dgram(msg, nonblock)
+            }
+        }
+    }
+
+    /// 接收数据和携带的文件, 同时返回发送方的地址; `nonblock`为真时即使socket是阻塞的也不等待
+    ///
+    /// 流socket在对端关闭后返回长度0。数据报比`buf`长时多余的部分被丢弃。
+    pub fn recv_msg(&self, buf: &mut [u8], nonblock: bool) -> AxResult<RecvMsg> {
+        let nonblock = nonblock || self.is_nonblocking();
+        match self.node.socket_type {
+            UnixSocketType::Stream => {
+                let (rx, peer_addr) = match &*self.state.lock() {
+                    State::Stream { rx, peer_addr, .. } => (rx.clone(), peer_addr.clone()),
+                    _ => return ax_err!(NotConnected),
+                };
+                let mut msg = rx.recv_stream(buf, nonblock)?;
+                msg.from = peer_addr;
+                Ok(msg)
+            }
+            UnixSocketType::Dgram => self.node.rx.recv_dgram(buf, nonblock),
+        }
+    }
+
+    /// 关闭连接的接收方向(`read`)和/或发送方向(`write`), 监听的socket停止监听
+    pub fn shutdown(&self, read: bool, write: bool) -> AxResult {
+        match &*self.state.lock() {
+            State::Stream { rx, tx, .. } => {
+                if read {
+                    rx.close_reader();
+                }
+                if write {
+                    tx.close_writer();
+                }
+                Ok(())
+            }
+            State::Listening => {
+                self.stop_listening();
+                Ok(())
+            }
+            State::Dgram { .. } => Ok(()),
+            State::Unconnected => ax_err!(NotConnected),
+        }
+    }
+
+    /// 停止监听, 拒绝所有还没有被accept的连接
+    fn stop_listening(&self) {
+        let pending = {
+            let mut backlog = self.node.backlog.lock();
+            backlog.listening = false;
+            core::mem::take(&mut backlog.queue)
+        };
+        drop(pending);
+        self.node.accept_wq.notify_all(false);
+        self.node.connect_wq.notify_all(false);
+        self.node.accept_wakers.wake();
+    }
+}
+
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        match &*self.state.lock() {
+            State::Stream { rx, tx, .. } => {
+                rx.close_reader();
+                tx.close_writer();
+            }
+            State::Listening => self.stop_listening(),
+            _ => {}
+        }
+        self.node.rx.close_reader();
+        if let UnixAddr::Abstract(name) = &*self.node.addr.lock() {
+            // accept得到的socket与监听的socket地址相同, 只有名字真正属于自己时才移除
+            let mut names = ABSTRACT_NAMES.lock();
+            if names
+                .get(name)
+                .map_or(false, |node| node.as_ptr() == Arc::as_ptr(&self.node))
+            {
+                names.remove(name);
+            }
+        }
+    }
+}
+
+impl FileIO for UnixSocket {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    /// 读取数据, 携带的文件被丢弃
+    fn read(&self, buf: &mut [u8]) -> AxResult<usize> {
+        self.recv_msg(buf, false).map(|msg| msg.len)
+    }
+
+    fn write(&self, buf: &[u8]) -> AxResult<usize> {
+        self.send_msg(buf, Vec::new(), None, false)
+    }
+
+    fn get_type(&self) -> String {
+        String::from("UnixSocket")
+    }
+
+    fn is_nonblocking(&self) -> bool {
+        self.nonblock.load(Ordering::Acquire)
+    }
+
+    fn set_nonblocking(&self, nonblock: bool) {
+        self.nonblock.store(nonblock, Ordering::Release);
+    }
+
+    fn get_stat(&self) -> AxResult<Kstat> {
+        Ok(Kstat {
+            st_dev: 1,
+            st_ino: 1,
+            st_mode: normal_file_mode(StMode::S_IFSOCK).bits(),
+            st_nlink: 1,
+            st_uid: 0,
+            st_gid: 0,
+            st_rdev: 0,
+            _pad0: 0,
+            st_size: 0,
+            st_blksize: 0,
+            _pad1: 0,
+            st_blocks: 0,
+            st_atime_sec: 0,
+            st_atime_nsec: 0,
+            st_mtime_sec: 0,
+            st_mtime_nsec: 0,
+            st_ctime_sec: 0,
+            st_ctime_nsec: 0,
+        })
+    }
+
+    /// 流socket: 有数据或对端关闭时可读, 对端的接收队列有空间时可写, 两个方向都关闭时挂断;
+    /// 数据报socket: 有数据报时可读, 对端(如果有)的接收队列有空间时可写;
+    /// 监听的socket: 有连接等待accept时可读
+    fn poll(&self) -> PollEvents {
+        let mut events = PollEvents::empty();
+        match &*self.state.lock() {
+            State::Unconnected => {}
+            State::Listening => {
+                if !self.node.backlog.lock().queue.is_empty() {
+                    events |= PollEvents::IN;
+                }
+            }
+            State::Stream { rx, tx, .. } => {
+                let eof = {
+                    let queue = rx.queue.lock();
+                    if !queue.msgs.is_empty() || queue.writer_closed || queue.reader_closed {
+                        events |= PollEvents::IN;
+                    }
+                    queue.writer_closed
+                };
+                let queue = tx.queue.lock();
+                if queue.len < UNIX_BUF_SIZE || queue.reader_closed || queue.writer_closed {
+                    events |= PollEvents::OUT;
+                }
+                if eof && (queue.reader_closed || queue.writer_closed) {
+                    events |= PollEvents::HUP;
+                }
+            }
+            State::Dgram { peer, .. } => {
+                let writable = peer
+                    .upgrade()
+                    .map_or(true, |peer| peer.rx.queue.lock().len < UNIX_BUF_SIZE);
+                if writable {
+                    events |= PollEvents::OUT;
+                }
+            }
+        }
+        if self.node.socket_type == UnixSocketType::Dgram {
+            if !self.node.rx.queue.lock().msgs.is_empty() {
+                events |= PollEvents::IN;
+            }
+            if matches!(*self.state.lock(), State::Unconnected) {
+                events |= PollEvents::OUT;
+            }
+        }
+        events
+    }
+
+    /// 未连接的流socket状态变化时没有通知, 返回false让调用者定时重新检查
+    fn register_poll_waker(&self, waker: &Arc<dyn PollWaker>) -> bool {
+        match &*self.state.lock() {
+            State::Unconnected => {
+                if self.node.socket_type == UnixSocketType::Stream {
+                    return false;
+                }
+            }
+            State::Listening => self.node.accept_wakers.register(waker),
+            State::Stream { rx, tx, .. } => {
+                rx.read_wakers.register(waker);
+                tx.write_wakers.register(waker);
+            }
+            State::Dgram { peer, .. } => {
+                if let Some(peer) = peer.upgrade() {
+                    peer.rx.write_wakers.register(waker);
+                }
+            }
+        }
+        self.node.rx.read_wakers.register(waker);
+        true
+    }
+
+    fn unregister_poll_waker(&self, waker: &Arc<dyn PollWaker>) {
+        match &*self.state.lock() {
+            State::Stream { rx, tx, .. } => {
+                rx.read_wakers.unregister(waker);
+                tx.write_wakers.unregister(waker);
+            }
+            State::Dgram { peer, .. } => {
+                if let Some(peer) = peer.upgrade() {
+                    peer.rx.write_wakers.unregister(waker);
+                }
+            }
+            _ => {}
+        }
+        self.node.accept_wakers.unregister(waker);
+        self.node.rx.read_wakers.unregister(waker);
+    }
+}
@@ -0,0 +1,118 @@
+//! Kernel command line passed by the bootloader.
+//!
+//! The command line is the `bootargs` property of the `/chosen` node in the
+//! flattened device tree (FDT). It is copied into a static buffer during early
+//! boot, before the memory holding the device tree may be reused.
+//!
+//! Both the RISC-V and the AArch64 QEMU virt platforms read it. On platforms
+//! without a device tree, such as the dummy one, the command line is empty.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// The longest command line that is kept, longer ones are truncated.
+const MAX_CMDLINE_LEN: usize = 512;
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+
+static mut CMDLINE: [u8; MAX_CMDLINE_LEN] = [0; MAX_CMDLINE_LEN];
+static CMDLINE_LEN: AtomicUsize = AtomicUsize::new(0);
+
+/// Reads the command line from the device tree at `dtb`.
+///
+/// # Safety
+///
+/// `dtb` must be null or point to a readable device tree blob. Must be called
+/// only once, on the primary CPU, before any call to [`cmdline`].
+pub(crate) unsafe fn init(dtb: *const u8) {
+    if let Some(bootargs) = find_bootargs(dtb) {
+        let len = bootargs.len().min(MAX_CMDLINE_LEN);
+        CMDLINE[..len].copy_from_slice(&bootargs[..len]);
+        CMDLINE_LEN.store(len, Ordering::Release);
+    }
+}
+
+/// Returns the kernel command line, or an empty string if there is none.
+pub fn cmdline() -> &'static str {
+    let len = CMDLINE_LEN.load(Ordering::Acquire);
+    let bytes = unsafe { &CMDLINE[..len] };
+    core::str::from_utf8(bytes).unwrap_or("")
+}
+
+/// Returns the value of the `key=value` argument on the command line.
+///
+/// An argument given without a value (just `key`) yields an empty string.
+pub fn get(key: &str) -> Option<&'static str> {
+    cmdline().split_whitespace().find_map(|arg| match arg.split_once('=') {
+        Some((k, v)) if k == key => Some(v),
+        None if arg == key => Some(""),
+        _ => None,
+    })
+}
+
+unsafe fn read_be32(base: *const u8, offset: usize) -> u32 {
+    u32::from_be_bytes(core::ptr::read_unaligned(base.add(offset) as *const [u8; 4]))
+}
+
+/// Walks the structure block of the FDT and returns the value of
+/// `/chosen/bootargs` without the trailing NUL.
+unsafe fn find_bootargs(dtb: *const u8) -> Option<&'static [u8]> {
+    if dtb.is_null() || read_be32(dtb, 0) != FDT_MAGIC {
+        return None;
+    }
+    let total_size = read_be32(dtb, 4) as usize;
+    let struct_off = read_be32(dtb, 8) as usize;
+    let strings_off = read_be32(dtb, 12) as usize;
+    let struct_size = read_be32(dtb, 36) as usize;
+    let struct_end = (struct_off + struct_size).min(total_size);
+
+    let cstr = |start: usize| {
+        let mut len = 0;
+        while start + len < total_size && *dtb.add(start + len) != 0 {
+            len += 1;
+        }
+        core::slice::from_raw_parts(dtb.add(start), len)
+    };
+    let align4 = |x: usize| (x + 3) & !3;
+
+    let mut pos = struct_off;
+    let mut depth = 0;
+    let mut in_chosen = false;
+    while pos + 4 <= struct_end {
+        let token = read_be32(dtb, pos);
+        pos += 4;
+        match token {
+            FDT_BEGIN_NODE => {
+                let name = cstr(pos);
+                depth += 1;
+                // the root node has an empty name, `/chosen` is one level below it
+                in_chosen = depth == 2 && (name == b"chosen" || name.starts_with(b"chosen@"));
+                pos = align4(pos + name.len() + 1);
+            }
+            FDT_END_NODE => {
+                depth -= 1;
+                in_chosen = false;
+            }
+            FDT_PROP => {
+                let len = read_be32(dtb, pos) as usize;
+                let name_off = read_be32(dtb, pos + 4) as usize;
+                let value = pos + 8;
+                if in_chosen
+                    && value + len <= struct_end
+                    && cstr(strings_off + name_off) == b"bootargs"
+                {
+                    let value = core::slice::from_raw_parts(dtb.add(value), len);
+                    let end = value.iter().position(|&b| b == 0).unwrap_or(len);
+                    return Some(&value[..end]);
+                }
+                pos = align4(value + len);
+            }
+            FDT_NOP => {}
+            _ => break, // FDT_END or a malformed blob
+        }
+    }
+    None
+}
@@ -11,6 +11,7 @@ extern crate log;
 pub mod platform;
 
 pub mod arch;
+pub mod cmdline;
 pub mod cpu;
 pub mod irq;
 pub mod mem;
@@ -38,6 +39,27 @@ pub mod console {
 
 pub mod misc {
     pub use super::platform::misc::*;
+
+    use spinlock::SpinNoIrq;
+
+    static TERMINATE_HOOK: SpinNoIrq<Option<fn()>> = SpinNoIrq::new(None);
+
+    /// Sets a function to be called right before the system shuts down, e.g.
+    /// to write back cached data. It may be called from the panic handler, so
+    /// it must not block on locks.
+    pub fn set_terminate_hook(hook: fn()) {
+        *TERMINATE_HOOK.lock() = Some(hook);
+    }
+
+    /// Calls the hook set by [`set_terminate_hook`], then shuts down the system.
+    pub fn terminate() -> ! {
+        // taken out, so that a panic in the hook does not call it again
+        let hook = TERMINATE_HOOK.lock().take();
+        if let Some(hook) = hook {
+            hook();
+        }
+        super::platform::misc::terminate()
+    }
 }
 
 #[cfg(feature = "smp")]
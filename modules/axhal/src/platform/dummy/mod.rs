@@ -11,6 +11,10 @@ pub mod console {
     pub fn getchar() -> Option<u8> {
         unimplemented!()
     }
+
+    pub fn set_input_handler(handler: fn()) -> bool {
+        unimplemented!()
+    }
 }
 
 pub mod misc {
@@ -45,6 +49,10 @@ pub mod time {
     }
 
     pub fn set_oneshot_timer(deadline_ns: u64) {}
+
+    pub fn epoch_offset_nanos() -> u64 {
+        0
+    }
 }
 
 pub mod irq {
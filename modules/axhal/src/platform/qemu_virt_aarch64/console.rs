@@ -1,9 +1,96 @@
-use super::pl011::{console_getchar, console_putchar};
+//! Console through the PL011 UART.
+//!
+//! Once an input handler is set, the UART raises an IRQ when input arrives.
+//! The IRQ handler moves the input into a buffer, which [`getchar`] reads
+//! first.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use spinlock::SpinNoIrq;
+
+use super::pl011::{
+    console_ack_rx_irq, console_enable_rx_irq, console_getchar, console_putchar, UART_IRQ_NUM,
+};
+
+const INPUT_BUF_LEN: usize = 256;
+
+/// Input received by the IRQ handler but not read yet.
+static INPUT: SpinNoIrq<InputBuffer> = SpinNoIrq::new(InputBuffer::new());
+static INPUT_HANDLER: SpinNoIrq<Option<fn()>> = SpinNoIrq::new(None);
+/// Whether the IRQ handler of the UART is registered.
+static INPUT_IRQ: AtomicBool = AtomicBool::new(false);
+
+/// A ring buffer of input characters. Input that does not fit is dropped.
+struct InputBuffer {
+    buf: [u8; INPUT_BUF_LEN],
+    head: usize,
+    len: usize,
+}
+
+impl InputBuffer {
+    const fn new() -> Self {
+        Self {
+            buf: [0; INPUT_BUF_LEN],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, c: u8) {
+        if self.len < INPUT_BUF_LEN {
+            self.buf[(self.head + self.len) % INPUT_BUF_LEN] = c;
+            self.len += 1;
+        }
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        let c = self.buf[self.head];
+        self.head = (self.head + 1) % INPUT_BUF_LEN;
+        self.len -= 1;
+        Some(c)
+    }
+}
 
 pub fn putchar(c: u8) {
     console_putchar(c);
 }
 
 pub fn getchar() -> Option<u8> {
-    console_getchar()
+    INPUT.lock().pop().or_else(console_getchar)
+}
+
+/// Sets a function to be called in IRQ context each time console input
+/// arrives, and enables the input IRQ.
+///
+/// Returns whether the console has an input IRQ. If not, the handler is never
+/// called, and the input has to be polled.
+pub fn set_input_handler(handler: fn()) -> bool {
+    *INPUT_HANDLER.lock() = Some(handler);
+    if INPUT_IRQ.swap(true, Ordering::AcqRel) {
+        return true;
+    }
+    if !crate::irq::register_handler(UART_IRQ_NUM, uart_irq_handler) {
+        INPUT_IRQ.store(false, Ordering::Release);
+        return false;
+    }
+    console_enable_rx_irq();
+    true
+}
+
+fn uart_irq_handler() {
+    // clear the interrupt before draining the FIFO, so that input arriving
+    // meanwhile raises a new one
+    console_ack_rx_irq();
+    let mut input = INPUT.lock();
+    while let Some(c) = console_getchar() {
+        input.push(c);
+    }
+    drop(input);
+    let handler = *INPUT_HANDLER.lock();
+    if let Some(handler) = handler {
+        handler();
+    }
 }
@@ -1,10 +1,23 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
 use aarch64_cpu::registers::{CNTFRQ_EL0, CNTPCT_EL0, CNTP_CTL_EL0, CNTP_TVAL_EL0};
 use lazy_init::LazyInit;
 use ratio::Ratio;
 use tock_registers::interfaces::{Readable, Writeable};
 
+use crate::mem::phys_to_virt;
+
 pub const TIMER_IRQ_NUM: usize = 30; // physical timer, type=PPI, id=14
 
+/// Physical address of the PL031 RTC on the QEMU virt board.
+const RTC_PADDR: usize = 0x0901_0000;
+/// Data Register, the current time in seconds since the Unix epoch.
+const RTC_DR: usize = 0x00;
+
+/// Wall-clock time when the counter was zero, in nanoseconds since the Unix
+/// epoch. Zero means the RTC has not been read yet.
+static EPOCH_OFFSET_NANOS: AtomicU64 = AtomicU64::new(0);
+
 static CNTPCT_TO_NANOS_RATIO: LazyInit<Ratio> = LazyInit::new();
 static NANOS_TO_CNTPCT_RATIO: LazyInit<Ratio> = LazyInit::new();
 
@@ -23,6 +36,31 @@ pub fn nanos_to_ticks(nanos: u64) -> u64 {
     NANOS_TO_CNTPCT_RATIO.mul_trunc(nanos)
 }
 
+/// Reads the RTC, in nanoseconds since the Unix epoch. The PL031 only counts
+/// whole seconds.
+fn rtc_nanos() -> u64 {
+    let base = phys_to_virt(RTC_PADDR.into()).as_usize();
+    let secs = unsafe { ((base + RTC_DR) as *const u32).read_volatile() } as u64;
+    secs * crate::time::NANOS_PER_SEC
+}
+
+/// Returns the wall-clock time when the counter was zero, in nanoseconds since
+/// the Unix epoch.
+///
+/// The RTC is read on the first call, which must happen after the kernel page
+/// table (which maps the RTC) is enabled.
+pub fn epoch_offset_nanos() -> u64 {
+    let offset = EPOCH_OFFSET_NANOS.load(Ordering::Relaxed);
+    if offset != 0 {
+        return offset;
+    }
+    let offset = rtc_nanos()
+        .saturating_sub(ticks_to_nanos(current_ticks()))
+        .max(1);
+    EPOCH_OFFSET_NANOS.store(offset, Ordering::Relaxed);
+    offset
+}
+
 pub fn set_oneshot_timer(deadline_ns: u64) {
     let cnptct = CNTPCT_EL0.get();
     let cnptct_deadline = nanos_to_ticks(deadline_ns);
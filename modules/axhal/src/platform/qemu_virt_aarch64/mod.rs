@@ -22,8 +22,15 @@ extern "C" {
     fn exception_vector_base();
 }
 
-pub(crate) fn platform_init(cpu_id: usize, _dtb: *const u8) {
+pub(crate) fn platform_init(cpu_id: usize, dtb: *const u8) {
     crate::mem::clear_bss();
+    // QEMU passes the device tree in x0 to Linux images only; for ELF images
+    // it is placed at the start of RAM. Both lie in the boot mapping.
+    let dtb = match dtb as usize {
+        0 => axconfig::PHYS_MEMORY_BASE,
+        dtb => dtb,
+    };
+    unsafe { crate::cmdline::init(crate::mem::phys_to_virt(dtb.into()).as_ptr()) };
     crate::arch::set_exception_vector_base(exception_vector_base as usize);
     crate::cpu::init_percpu(cpu_id, true);
     self::irq::init();
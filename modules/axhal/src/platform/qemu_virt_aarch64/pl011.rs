@@ -9,8 +9,13 @@ use tock_registers::registers::{ReadOnly, ReadWrite, WriteOnly};
 use crate::mem::phys_to_virt;
 
 const UART_BASE: PhysAddr = PhysAddr::from(0x0900_0000);
-#[allow(unused)]
-const UART_IRQ_NUM: usize = 33; // type=SPI, id=1
+pub(super) const UART_IRQ_NUM: usize = 33; // type=SPI, id=1
+
+/// Receive interrupt, raised when the RX FIFO reaches the trigger level.
+const INT_RX: u32 = 1 << 4;
+/// Receive timeout interrupt, raised when the RX FIFO holds less than the
+/// trigger level and no more data arrives.
+const INT_RX_TIMEOUT: u32 = 1 << 6;
 
 static UART: SpinNoIrq<Pl011Uart> = SpinNoIrq::new(Pl011Uart::new(phys_to_virt(UART_BASE)));
 
@@ -77,6 +82,14 @@ impl Pl011Uart {
             None
         }
     }
+
+    fn enable_rx_irq(&mut self) {
+        self.regs().imsc.set(INT_RX | INT_RX_TIMEOUT);
+    }
+
+    fn ack_rx_irq(&mut self) {
+        self.regs().icr.set(INT_RX | INT_RX_TIMEOUT);
+    }
 }
 
 pub(super) fn console_putchar(c: u8) {
@@ -87,6 +100,14 @@ pub(super) fn console_getchar() -> Option<u8> {
     UART.lock().getchar()
 }
 
+pub(super) fn console_enable_rx_irq() {
+    UART.lock().enable_rx_irq();
+}
+
+pub(super) fn console_ack_rx_irq() {
+    UART.lock().ack_rx_irq();
+}
+
 pub(super) fn init() {
     UART.lock().init();
 }
@@ -1,12 +1,123 @@
-pub fn putchar(c: u8) {
-    #[allow(deprecated)]
-    sbi_rt::legacy::console_putchar(c as usize);
+//! Console output through the SBI, and input through the SBI or the IRQ of
+//! the 16550 UART behind it.
+//!
+//! Once an input handler is set, the UART raises an IRQ when input arrives.
+//! The IRQ handler moves the input into a buffer, which [`getchar`] reads
+//! first.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use memory_addr::PhysAddr;
+use spinlock::SpinNoIrq;
+
+use crate::mem::phys_to_virt;
+
+const UART_BASE: PhysAddr = PhysAddr::from(0x1000_0000);
+/// The PLIC source of the UART.
+const UART_IRQ_NUM: usize = 10;
+/// Interrupt Enable Register.
+const UART_IER: usize = 1;
+/// Modem Control Register.
+const UART_MCR: usize = 4;
+/// Enables the "received data available" interrupt.
+const IER_RX_AVAILABLE: u8 = 1 << 0;
+/// Routes the interrupt of the UART to its IRQ line.
+const MCR_OUT2: u8 = 1 << 3;
+
+const INPUT_BUF_LEN: usize = 256;
+
+/// Input received by the IRQ handler but not read yet.
+static INPUT: SpinNoIrq<InputBuffer> = SpinNoIrq::new(InputBuffer::new());
+static INPUT_HANDLER: SpinNoIrq<Option<fn()>> = SpinNoIrq::new(None);
+/// Whether the IRQ handler of the UART is registered.
+static INPUT_IRQ: AtomicBool = AtomicBool::new(false);
+
+/// A ring buffer of input characters. Input that does not fit is dropped.
+struct InputBuffer {
+    buf: [u8; INPUT_BUF_LEN],
+    head: usize,
+    len: usize,
 }
 
-pub fn getchar() -> Option<u8> {
+impl InputBuffer {
+    const fn new() -> Self {
+        Self {
+            buf: [0; INPUT_BUF_LEN],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, c: u8) {
+        if self.len < INPUT_BUF_LEN {
+            self.buf[(self.head + self.len) % INPUT_BUF_LEN] = c;
+            self.len += 1;
+        }
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+        let c = self.buf[self.head];
+        self.head = (self.head + 1) % INPUT_BUF_LEN;
+        self.len -= 1;
+        Some(c)
+    }
+}
+
+fn uart_reg(offset: usize) -> *mut u8 {
+    (phys_to_virt(UART_BASE).as_usize() + offset) as *mut u8
+}
+
+fn sbi_getchar() -> Option<u8> {
     #[allow(deprecated)]
     match sbi_rt::legacy::console_getchar() as isize {
         -1 => None,
         c => Some(c as u8),
     }
 }
+
+pub fn putchar(c: u8) {
+    #[allow(deprecated)]
+    sbi_rt::legacy::console_putchar(c as usize);
+}
+
+pub fn getchar() -> Option<u8> {
+    INPUT.lock().pop().or_else(sbi_getchar)
+}
+
+/// Sets a function to be called in IRQ context each time console input
+/// arrives, and enables the input IRQ.
+///
+/// Returns whether the console has an input IRQ. If not, the handler is never
+/// called, and the input has to be polled.
+pub fn set_input_handler(handler: fn()) -> bool {
+    *INPUT_HANDLER.lock() = Some(handler);
+    if INPUT_IRQ.swap(true, Ordering::AcqRel) {
+        return true;
+    }
+    if !crate::irq::register_handler(UART_IRQ_NUM, uart_irq_handler) {
+        INPUT_IRQ.store(false, Ordering::Release);
+        return false;
+    }
+    unsafe {
+        let mcr = uart_reg(UART_MCR);
+        mcr.write_volatile(mcr.read_volatile() | MCR_OUT2);
+        uart_reg(UART_IER).write_volatile(IER_RX_AVAILABLE);
+    }
+    true
+}
+
+fn uart_irq_handler() {
+    // reading the received data clears the interrupt
+    let mut input = INPUT.lock();
+    while let Some(c) = sbi_getchar() {
+        input.push(c);
+    }
+    drop(input);
+    let handler = *INPUT_HANDLER.lock();
+    if let Some(handler) = handler {
+        handler();
+    }
+}
@@ -1,7 +1,14 @@
-//! TODO: PLIC
+//! Timer interrupts from the SBI, and external interrupts through the PLIC.
+//!
+//! Device IRQs are numbered by their PLIC interrupt source, and are all routed
+//! to the supervisor context of the primary CPU.
 
 use crate::irq::IrqHandler;
+use crate::mem::phys_to_virt;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use lazy_init::LazyInit;
+use memory_addr::PhysAddr;
+use spinlock::SpinNoIrq;
 
 /// `Interrupt` bit in `scause`
 pub(super) const INTC_IRQ_BASE: usize = 1 << (usize::BITS - 1);
@@ -20,6 +27,20 @@ static TIMER_HANDLER: LazyInit<IrqHandler> = LazyInit::new();
 
 pub const MAX_IRQ_COUNT: usize = 1024;
 
+const PLIC_BASE: PhysAddr = PhysAddr::from(0x0c00_0000);
+const PLIC_PRIORITY: usize = 0x0;
+const PLIC_ENABLE: usize = 0x2000;
+const PLIC_ENABLE_STRIDE: usize = 0x80;
+const PLIC_THRESHOLD: usize = 0x20_0000;
+const PLIC_CLAIM: usize = 0x20_0004;
+const PLIC_CONTEXT_STRIDE: usize = 0x1000;
+
+/// The CPU that handles all device IRQs.
+static IRQ_CPU: AtomicUsize = AtomicUsize::new(0);
+
+/// Serializes the read-modify-write of the enable bits.
+static PLIC_ENABLE_LOCK: SpinNoIrq<()> = SpinNoIrq::new(());
+
 macro_rules! with_cause {
     ($cause: expr, @TIMER => $timer_op: expr, @EXT => $ext_op: expr $(,)?) => {
         match $cause {
@@ -30,23 +51,63 @@ macro_rules! with_cause {
     };
 }
 
-pub fn set_enable(scause: usize, _enabled: bool) {
-    if scause == S_EXT {
-        // TODO: set enable in PLIC
+fn plic_reg(offset: usize) -> *mut u32 {
+    (phys_to_virt(PLIC_BASE).as_usize() + offset) as *mut u32
+}
+
+/// The PLIC context of the supervisor mode of `cpu_id`.
+const fn s_context(cpu_id: usize) -> usize {
+    cpu_id * 2 + 1
+}
+
+/// Enables or disables the device IRQ `irq_num` (a PLIC interrupt source).
+pub fn set_enable(irq_num: usize, enabled: bool) {
+    // source 0 is reserved for "no interrupt"
+    if irq_num == 0 || irq_num >= MAX_IRQ_COUNT {
+        return;
+    }
+    let context = s_context(IRQ_CPU.load(Ordering::Relaxed));
+    let enable = plic_reg(PLIC_ENABLE + context * PLIC_ENABLE_STRIDE + irq_num / 32 * 4);
+    let bit = 1 << (irq_num % 32);
+    let _guard = PLIC_ENABLE_LOCK.lock();
+    unsafe {
+        // accept IRQs of every priority
+        plic_reg(PLIC_THRESHOLD + context * PLIC_CONTEXT_STRIDE).write_volatile(0);
+        plic_reg(PLIC_PRIORITY + irq_num * 4).write_volatile(1);
+        let bits = enable.read_volatile();
+        enable.write_volatile(if enabled { bits | bit } else { bits & !bit });
     }
 }
 
-pub fn register_handler(scause: usize, handler: IrqHandler) -> bool {
-    with_cause!(
-        scause,
-        @TIMER => if !TIMER_HANDLER.is_init() {
+/// Registers the handler of the timer interrupt (`S_TIMER`), or of a device
+/// IRQ given by its PLIC interrupt source, and enables the IRQ.
+pub fn register_handler(irq_num: usize, handler: IrqHandler) -> bool {
+    if irq_num == S_TIMER {
+        if !TIMER_HANDLER.is_init() {
             TIMER_HANDLER.init_by(handler);
-            true
-        } else {
-            false
-        },
-        @EXT => crate::irq::register_handler_common(scause & !INTC_IRQ_BASE, handler),
-    )
+            return true;
+        }
+        false
+    } else if irq_num & INTC_IRQ_BASE == 0 {
+        crate::irq::register_handler_common(irq_num, handler)
+    } else {
+        warn!("register handler for trap cause {:#x} failed", irq_num);
+        false
+    }
+}
+
+/// Claims and handles the pending device IRQs of the current CPU.
+fn dispatch_ext_irqs() {
+    let context = s_context(crate::cpu::this_cpu_id());
+    let claim = plic_reg(PLIC_CLAIM + context * PLIC_CONTEXT_STRIDE);
+    loop {
+        let irq_num = unsafe { claim.read_volatile() } as usize;
+        if irq_num == 0 {
+            break;
+        }
+        crate::irq::dispatch_irq_common(irq_num);
+        unsafe { claim.write_volatile(irq_num as u32) };
+    }
 }
 
 pub fn dispatch_irq(scause: usize) {
@@ -56,8 +117,11 @@ pub fn dispatch_irq(scause: usize) {
             trace!("IRQ: timer");
             TIMER_HANDLER();
         },
-        @EXT => crate::irq::dispatch_irq_common(0), // TODO: get IRQ number from PLIC
+        @EXT => dispatch_ext_irqs(),
     );
 }
 
-pub(super) fn init() {}
+pub(super) fn init() {
+    // the PLIC is not mapped yet, it is set up when the first IRQ is enabled
+    IRQ_CPU.store(crate::cpu::this_cpu_id(), Ordering::Relaxed);
+}
@@ -14,8 +14,13 @@ extern "C" {
 }
 
 /// 初始化trap函数跳转位置
-pub(crate) fn platform_init(cpu_id: usize, _dtb: usize) {
+pub(crate) fn platform_init(cpu_id: usize, dtb: usize) {
     crate::mem::clear_bss();
+    // 启动页表已经线性映射了设备树所在的物理内存
+    if dtb != 0 {
+        let dtb = crate::mem::phys_to_virt(dtb.into());
+        unsafe { crate::cmdline::init(dtb.as_ptr()) };
+    }
     crate::arch::set_tap_vector_base(trap_vector_base as usize);
     crate::cpu::init_percpu(cpu_id, true);
     self::irq::init();
@@ -1,9 +1,22 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
 use riscv::register::{sie, time};
 
+use crate::mem::phys_to_virt;
+
 const NANOS_PER_TICK: u64 = crate::time::NANOS_PER_SEC / axconfig::TIMER_FREQUENCY as u64;
 
 pub const TIMER_IRQ_NUM: usize = super::irq::S_TIMER;
 
+/// Physical address of the Goldfish RTC on the QEMU virt board.
+const RTC_PADDR: usize = 0x10_1000;
+const RTC_TIME_LOW: usize = 0x00;
+const RTC_TIME_HIGH: usize = 0x04;
+
+/// Wall-clock time when the timer counter was zero, in nanoseconds since the
+/// Unix epoch. Zero means the RTC has not been read yet.
+static EPOCH_OFFSET_NANOS: AtomicU64 = AtomicU64::new(0);
+
 #[inline]
 pub fn current_ticks() -> u64 {
     time::read() as u64
@@ -19,6 +32,34 @@ pub const fn nanos_to_ticks(nanos: u64) -> u64 {
     nanos / NANOS_PER_TICK
 }
 
+/// Reads the RTC, in nanoseconds since the Unix epoch.
+fn rtc_nanos() -> u64 {
+    let base = phys_to_virt(RTC_PADDR.into()).as_usize();
+    // reading the low word latches the high word
+    unsafe {
+        let low = ((base + RTC_TIME_LOW) as *const u32).read_volatile() as u64;
+        let high = ((base + RTC_TIME_HIGH) as *const u32).read_volatile() as u64;
+        (high << 32) | low
+    }
+}
+
+/// Returns the wall-clock time when the timer counter was zero, in
+/// nanoseconds since the Unix epoch.
+///
+/// The RTC is read on the first call, which must happen after the kernel page
+/// table (which maps the RTC) is enabled.
+pub fn epoch_offset_nanos() -> u64 {
+    let offset = EPOCH_OFFSET_NANOS.load(Ordering::Relaxed);
+    if offset != 0 {
+        return offset;
+    }
+    let offset = rtc_nanos()
+        .saturating_sub(ticks_to_nanos(current_ticks()))
+        .max(1);
+    EPOCH_OFFSET_NANOS.store(offset, Ordering::Relaxed);
+    offset
+}
+
 pub fn set_oneshot_timer(deadline_ns: u64) {
     sbi_rt::set_timer(nanos_to_ticks(deadline_ns));
 }
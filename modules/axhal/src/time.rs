@@ -1,7 +1,8 @@
 pub type TimeValue = core::time::Duration;
 
 pub use crate::platform::time::{
-    current_ticks, nanos_to_ticks, set_oneshot_timer, ticks_to_nanos, TIMER_IRQ_NUM,
+    current_ticks, epoch_offset_nanos, nanos_to_ticks, set_oneshot_timer, ticks_to_nanos,
+    TIMER_IRQ_NUM,
 };
 
 pub const MILLIS_PER_SEC: u64 = 1_000;
@@ -17,3 +18,14 @@ pub fn current_time_nanos() -> u64 {
 pub fn current_time() -> TimeValue {
     TimeValue::from_nanos(current_time_nanos())
 }
+
+/// Returns the current wall-clock time in nanoseconds since the Unix epoch.
+pub fn wall_time_nanos() -> u64 {
+    current_time_nanos() + epoch_offset_nanos()
+}
+
+/// Returns the current wall-clock time, i.e. the time elapsed since the Unix
+/// epoch.
+pub fn wall_time() -> TimeValue {
+    TimeValue::from_nanos(wall_time_nanos())
+}
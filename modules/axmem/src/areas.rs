@@ -1,6 +1,9 @@
+use alloc::{sync::Arc, vec::Vec};
 use axalloc::GlobalPage;
+use axerrno::AxResult;
+use axfs_os::api::{CachedPage, PageCache};
 use axhal::paging::MappingFlags;
-use memory_addr::VirtAddr;
+use memory_addr::{VirtAddr, PAGE_SIZE_4K};
 /// 地址段实现
 /// 仅会给进程使用，内核不会改动其原有代码。
 pub struct MapArea {
@@ -22,3 +25,50 @@ impl MapArea {
         return self.start_va <= end_va && self.start_va + self.pages.size() >= start_va;
     }
 }
+
+/// 共享的文件映射段
+///
+/// 直接映射文件页缓存中的页帧, 所有映射同一文件的进程和文件读写看到的是同一份数据。
+pub struct SharedArea {
+    pub start_va: VirtAddr,
+    pub flags: MappingFlags,
+    /// 被映射文件的页缓存
+    pub cache: Arc<PageCache>,
+    /// 第一页在文件中的页号
+    pub start_index: u64,
+    pub pages: Vec<Arc<CachedPage>>,
+}
+
+impl SharedArea {
+    pub fn new(
+        start_va: VirtAddr,
+        flags: MappingFlags,
+        cache: Arc<PageCache>,
+        start_index: u64,
+        pages: Vec<Arc<CachedPage>>,
+    ) -> Self {
+        Self {
+            start_va,
+            flags,
+            cache,
+            start_index,
+            pages,
+        }
+    }
+    pub fn size(&self) -> usize {
+        self.pages.len() * PAGE_SIZE_4K
+    }
+    pub fn end_va(&self) -> VirtAddr {
+        self.start_va + self.size()
+    }
+    /// 与[start_va, end_va)是否相交
+    pub fn overlap_with(&self, start_va: VirtAddr, end_va: VirtAddr) -> bool {
+        self.start_va < end_va && start_va < self.end_va()
+    }
+    /// 将映射中的修改写回文件
+    ///
+    /// 可能阻塞, 调用时不能持有自旋锁
+    pub fn sync(&self) -> AxResult {
+        self.cache.sync_pages(self.start_index, self.pages.len() as u64)
+    }
+}
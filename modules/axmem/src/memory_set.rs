@@ -1,6 +1,10 @@
-use crate::{areas::MapArea, paging::copy_from_kernel_memory};
-use alloc::{string::String, vec::Vec};
+use crate::{
+    areas::{MapArea, SharedArea},
+    paging::copy_from_kernel_memory,
+};
+use alloc::{string::String, sync::Arc, vec::Vec};
 use axalloc::GlobalPage;
+use axfs_os::api::{CachedPage, PageCache};
 use axhal::{
     mem::{virt_to_phys, VirtAddr, PAGE_SIZE_4K},
     paging::{MappingFlags, PageTable},
@@ -13,6 +17,11 @@ use riscv::asm;
 pub struct MemorySet {
     pub page_table: PageTable,
     pub areas: Vec<MapArea>,
+    /// 共享的文件映射段
+    pub shared_areas: Vec<SharedArea>,
+    /// 已解除映射、等待写回文件的共享段
+    /// 写回可能阻塞, 不能在持有地址空间的锁时进行, 由调用者释放锁后取出写回
+    unmapped_shared_areas: Vec<SharedArea>,
 }
 
 impl MemorySet {
@@ -20,12 +29,16 @@ impl MemorySet {
         Self {
             page_table: copy_from_kernel_memory(),
             areas: Vec::new(),
+            shared_areas: Vec::new(),
+            unmapped_shared_areas: Vec::new(),
         }
     }
     pub fn new_empty() -> Self {
         Self {
             page_table: PageTable::try_new().unwrap(),
             areas: Vec::new(),
+            shared_areas: Vec::new(),
+            unmapped_shared_areas: Vec::new(),
         }
     }
     /// 从已有任务复制完整的地址空间过来
@@ -38,6 +51,16 @@ impl MemorySet {
             // 为新的地址空间复制原先地址空间的内容
             new_memory_set.map_region_4k(area.start_va, area.pages.size(), area.flags, Some(data));
         }
+        // 共享映射段映射到相同的页帧
+        for area in others.shared_areas.iter() {
+            new_memory_set.map_shared_area(SharedArea::new(
+                area.start_va,
+                area.flags,
+                area.cache.clone(),
+                area.start_index,
+                area.pages.clone(),
+            ));
+        }
         new_memory_set
     }
     /// 获取页表token
@@ -111,6 +134,36 @@ impl MemorySet {
                 .unmap_region(area.start_va, area.pages.size())
                 .unwrap();
         }
+        for area in self.shared_areas.drain(..) {
+            self.page_table.unmap_region(area.start_va, area.size()).unwrap();
+            self.unmapped_shared_areas.push(area);
+        }
+    }
+    /// 取出已解除映射的共享段, 调用者应在释放锁后调用[`SharedArea::sync`]写回
+    pub fn take_unmapped_shared_areas(&mut self) -> Vec<SharedArea> {
+        core::mem::take(&mut self.unmapped_shared_areas)
+    }
+    /// 获取与[start_va, start_va + size)相交的共享段中位于该范围内的部分,
+    /// 以(页缓存, 起始页号, 页数)表示, 用于msync
+    pub fn shared_ranges(
+        &self,
+        start_va: VirtAddr,
+        size: usize,
+    ) -> Vec<(Arc<PageCache>, u64, u64)> {
+        let end_va = start_va + size;
+        self.shared_areas
+            .iter()
+            .filter(|area| area.overlap_with(start_va, end_va))
+            .map(|area| {
+                let first = (start_va.max(area.start_va) - area.start_va.as_usize()).as_usize();
+                let last = (end_va.min(area.end_va()) - area.start_va.as_usize()).as_usize();
+                (
+                    area.cache.clone(),
+                    area.start_index + (first / PAGE_SIZE_4K) as u64,
+                    ((last - first) / PAGE_SIZE_4K) as u64,
+                )
+            })
+            .collect()
     }
     /// 注意: start_va不一定是4K对齐的
     pub fn map_region_4k(
@@ -161,6 +214,41 @@ impl MemorySet {
     pub fn split_for_area(&mut self, start_va: VirtAddr, size: usize) {
         let end_va = start_va + size;
         info!("start: {}, end: {}", start_va.as_usize(), end_va.as_usize());
+        // 共享段不需要复制数据，只要解除范围内的页并保留两边
+        let shared_to_modified: Vec<SharedArea> = self
+            .shared_areas
+            .drain_filter(|area: &mut SharedArea| area.overlap_with(start_va, end_va))
+            .collect();
+        for mut area in shared_to_modified {
+            let cut_start = start_va.max(area.start_va);
+            let cut_end = end_va.min(area.end_va());
+            let _ = self
+                .page_table
+                .unmap_region(cut_start, (cut_end - cut_start.as_usize()).as_usize());
+            let first = (cut_start - area.start_va.as_usize()).as_usize() / PAGE_SIZE_4K;
+            let last = (cut_end - area.start_va.as_usize()).as_usize() / PAGE_SIZE_4K;
+            let right = area.pages.split_off(last);
+            let middle = area.pages.split_off(first);
+            if !right.is_empty() {
+                self.shared_areas.push(SharedArea::new(
+                    cut_end,
+                    area.flags,
+                    area.cache.clone(),
+                    area.start_index + last as u64,
+                    right,
+                ));
+            }
+            self.unmapped_shared_areas.push(SharedArea::new(
+                cut_start,
+                area.flags,
+                area.cache.clone(),
+                area.start_index + first as u64,
+                middle,
+            ));
+            if !area.pages.is_empty() {
+                self.shared_areas.push(area);
+            }
+        }
         let ares_to_modified: Vec<MapArea> = self
             .areas
             .drain_filter(|area: &mut MapArea| area.overlap_with(start_va, end_va))
@@ -244,21 +332,7 @@ impl MemorySet {
         }
         if random_pos {
             // 任意分配地点，则随意找个地方插进去就好，不用释放原有内存区间
-            // 先找一个空闲的区间
-            let mut last_end: VirtAddr = axconfig::USER_MEMORY_START.into();
-            let mut result_va = None;
-            for area in self.areas.iter() {
-                if (area.start_va - last_end.as_usize()).as_usize() >= size {
-                    // 找到了区间
-                    // 左闭右开，一点不慌
-                    result_va = Some(last_end);
-                    break;
-                } else {
-                    // 找不到区间，继续找
-                    last_end = area.start_va + area.pages.size();
-                }
-            }
-            if let Some(new_start_va) = result_va {
+            if let Some(new_start_va) = self.find_free_area(size) {
                 self.map_region_4k(new_start_va, size, flags, data);
                 unsafe {
                     asm::sfence_vma_all();
@@ -280,6 +354,73 @@ impl MemorySet {
             start_va.as_usize() as isize
         }
     }
+    /// 找一段大小为size的空闲区间，普通段和共享段都要避开
+    fn find_free_area(&self, size: usize) -> Option<VirtAddr> {
+        let mut used: Vec<(VirtAddr, VirtAddr)> = self
+            .areas
+            .iter()
+            .map(|area| (area.start_va, area.start_va + area.pages.size()))
+            .chain(self.shared_areas.iter().map(|area| (area.start_va, area.end_va())))
+            .collect();
+        used.sort_by_key(|&(start, _)| start);
+        let mut last_end: VirtAddr = axconfig::USER_MEMORY_START.into();
+        for (start, end) in used {
+            if start >= last_end && (start - last_end.as_usize()).as_usize() >= size {
+                // 找到了区间
+                // 左闭右开，一点不慌
+                return Some(last_end);
+            }
+            // 找不到区间，继续找
+            last_end = last_end.max(end);
+        }
+        None
+    }
+    /// 将共享段中的页帧逐页映射到页表中
+    fn map_shared_area(&mut self, area: SharedArea) {
+        for (i, page) in area.pages.iter().enumerate() {
+            self.page_table
+                .map_region(
+                    area.start_va + i * PAGE_SIZE_4K,
+                    page.paddr(),
+                    PAGE_SIZE_4K,
+                    area.flags,
+                    false,
+                )
+                .expect("Error when mapping!");
+        }
+        self.shared_areas.push(area);
+    }
+    /// 以共享方式映射文件，pages为文件页缓存中从start_index开始的页
+    /// start_va已按页对齐，成功返回映射的起始地址，失败返回-1
+    pub fn mmap_shared(
+        &mut self,
+        start_va: VirtAddr,
+        flags: MappingFlags,
+        random_pos: bool,
+        cache: Arc<PageCache>,
+        start_index: u64,
+        pages: Vec<Arc<CachedPage>>,
+    ) -> isize {
+        let size = pages.len() * PAGE_SIZE_4K;
+        let start_va = if random_pos {
+            match self.find_free_area(size) {
+                Some(va) => va,
+                None => return -1,
+            }
+        } else {
+            if (start_va + size).as_usize() >= axconfig::USER_MEMORY_LIMIT {
+                return -1;
+            }
+            self.split_for_area(start_va, size);
+            start_va
+        };
+        self.map_shared_area(SharedArea::new(start_va, flags, cache, start_index, pages));
+        // 由于修改了页表，需要清空TLB
+        unsafe {
+            asm::sfence_vma_all();
+        }
+        start_va.as_usize() as isize
+    }
     /// 解除一段内存的映射，其实某种意义上它被mmap包含了
     pub fn munmap(&mut self, start_va: VirtAddr, size: usize) -> isize {
         self.split_for_area(start_va, size);
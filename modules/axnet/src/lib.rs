@@ -12,8 +12,14 @@ cfg_if::cfg_if! {
     }
 }
 
-pub use self::net_impl::TcpSocket;
-pub use smoltcp::wire::{IpAddress as IpAddr, IpEndpoint as SocketAddr, Ipv4Address as Ipv4Addr};
+pub use self::net_impl::{
+    block_on, block_on_timeout, dns_query, dns_servers, set_event_handler, set_random_source,
+    set_servers_handler, IcmpSocket, SocketKey, TcpSocket, UdpSocket,
+};
+pub use smoltcp::wire::{
+    IpAddress as IpAddr, IpEndpoint as SocketAddr, Ipv4Address as Ipv4Addr,
+    Ipv6Address as Ipv6Addr,
+};
 
 use axdriver::NetDevices;
 use driver_common::{BaseDriverOps, DeviceType};
@@ -21,11 +27,15 @@ use driver_common::{BaseDriverOps, DeviceType};
 pub fn init_network(net_devs: NetDevices) {
     info!("Initialize network subsystem...");
 
-    info!("number of NICs: {}", net_devs.len());
+    let mut nic_count = 0;
     axdriver::net_devices_enumerate!((i, dev) in net_devs {
-        assert_eq!(dev.device_type(), DeviceType::Net);
-        info!("  NIC {}: {:?}", i, dev.device_name());
+        if let Some(dev) = dev {
+            assert_eq!(dev.device_type(), DeviceType::Net);
+            info!("  NIC {}: {:?}", i, dev.device_name());
+            nic_count += 1;
+        }
     });
+    info!("number of NICs: {}", nic_count);
 
     net_impl::init(net_devs);
 }
@@ -0,0 +1,110 @@
+//! DHCPv4 client.
+//!
+//! A smoltcp `dhcpv4` socket lives in the socket set while a lease is wanted.
+//! Every time the interfaces are polled, its events are applied to the
+//! interface, so lease renewals and rebinds update the address and the default
+//! route. The first lease is waited for by polling in [`start`], before the
+//! network task is running; later the network task wakes up for the renewal
+//! timers of the socket.
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axhal::time::{current_time_nanos, NANOS_PER_SEC};
+use axsync::Mutex;
+use smoltcp::iface::{Interface, SocketHandle, SocketSet};
+use smoltcp::socket::dhcpv4;
+use smoltcp::wire::{IpAddress, IpCidr, Ipv4Cidr};
+
+use super::SOCKET_SET;
+
+/// How long `start` waits for the first lease before giving up.
+const DHCP_TIMEOUT_NANOS: u64 = 5 * NANOS_PER_SEC;
+
+static DHCP_HANDLE: Mutex<Option<SocketHandle>> = Mutex::new(None);
+static CONFIGURED: AtomicBool = AtomicBool::new(false);
+
+/// Starts the DHCP client and waits for the first lease.
+///
+/// Returns `false` if `ip=` on the kernel command line asks for a static
+/// address, or if no lease was offered in time. In the latter case the client
+/// is stopped, and the caller should fall back to the static configuration.
+pub(super) fn start() -> bool {
+    if !matches!(axhal::cmdline::get("ip"), None | Some("dhcp")) {
+        return false;
+    }
+
+    let handle = SOCKET_SET.add(dhcpv4::Socket::new());
+    *DHCP_HANDLE.lock() = Some(handle);
+
+    let deadline = current_time_nanos() + DHCP_TIMEOUT_NANOS;
+    while current_time_nanos() < deadline {
+        SOCKET_SET.poll_interfaces();
+        if CONFIGURED.load(Ordering::Acquire) {
+            return true;
+        }
+        axtask::yield_now();
+    }
+
+    warn!("DHCP: no lease, falling back to static configuration");
+    // don't hold `DHCP_HANDLE` while locking the socket set, `poll` locks them
+    // in the other order
+    let handle = DHCP_HANDLE.lock().take();
+    if let Some(handle) = handle {
+        SOCKET_SET.remove(handle);
+    }
+    false
+}
+
+/// Applies the pending event of the DHCP client, if any, to `iface`.
+pub(super) fn poll(iface: &mut Interface, sockets: &mut SocketSet) {
+    let handle = match *DHCP_HANDLE.lock() {
+        Some(handle) => handle,
+        None => return,
+    };
+    match sockets.get_mut::<dhcpv4::Socket>(handle).poll() {
+        None => {}
+        Some(dhcpv4::Event::Configured(config)) => {
+            set_ipv4_addr(iface, Some(config.address));
+            match config.router {
+                Some(router) => {
+                    iface.routes_mut().add_default_ipv4_route(router).unwrap();
+                }
+                None => {
+                    iface.routes_mut().remove_default_ipv4_route();
+                }
+            }
+            info!("DHCP: leased {}", config.address);
+            if let Some(router) = config.router {
+                info!("  gateway:  {}", router);
+            }
+            if !config.dns_servers.is_empty() {
+                let servers: Vec<IpAddress> =
+                    config.dns_servers.iter().map(|&ip| ip.into()).collect();
+                super::dns::set_servers(&servers);
+            }
+            CONFIGURED.store(true, Ordering::Release);
+        }
+        Some(dhcpv4::Event::Deconfigured) => {
+            set_ipv4_addr(iface, None);
+            iface.routes_mut().remove_default_ipv4_route();
+            info!("DHCP: lease lost");
+            CONFIGURED.store(false, Ordering::Release);
+        }
+    }
+}
+
+/// Replaces the IPv4 address of `iface`, leaving its IPv6 addresses alone.
+fn set_ipv4_addr(iface: &mut Interface, cidr: Option<Ipv4Cidr>) {
+    iface.update_ip_addrs(|addrs| {
+        while let Some(i) = addrs
+            .iter()
+            .position(|addr| matches!(addr, IpCidr::Ipv4(_)))
+        {
+            addrs.swap_remove(i);
+        }
+        if let Some(cidr) = cidr {
+            addrs.push(IpCidr::Ipv4(cidr)).unwrap();
+        }
+    });
+}
@@ -0,0 +1,331 @@
+//! Stub DNS resolver.
+//!
+//! Queries are sent over a UDP socket to the configured nameservers, which
+//! come from `dns=` on the kernel command line, the `dns` value of axconfig,
+//! or the DHCP lease. Answers are kept in a small cache for as long as the
+//! smallest TTL of the records that make up the answer, at most
+//! [`DNS_MAX_TTL_SECS`]. Names are case-insensitive, so they are cached in
+//! lowercase.
+//!
+//! To make forged answers hard to get accepted, each query is sent from a
+//! random source port with a random ID, both drawn from the source set by
+//! [`set_random_source`].
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use axerrno::{ax_err, ax_err_type, AxError, AxResult};
+use axhal::time::{current_time_nanos, NANOS_PER_SEC};
+use axsync::Mutex;
+use smoltcp::wire::{IpAddress, Ipv4Address, Ipv6Address};
+
+use super::{block_on_timeout, UdpSocket};
+use crate::SocketAddr;
+
+/// How long a query waits for the answer of one nameserver.
+const DNS_TIMEOUT: Duration = Duration::from_secs(2);
+/// How many times each nameserver is asked before giving up.
+const DNS_ATTEMPTS: usize = 2;
+/// The longest time an answer is cached, whatever its TTL.
+const DNS_MAX_TTL_SECS: u32 = 24 * 60 * 60;
+/// The largest DNS message over UDP without EDNS.
+const DNS_MAX_MESSAGE_LEN: usize = 512;
+const DNS_PORT: u16 = 53;
+const DNS_HEADER_LEN: usize = 12;
+const DNS_TYPE_A: u16 = 1;
+const DNS_TYPE_AAAA: u16 = 28;
+const DNS_CLASS_IN: u16 = 1;
+/// The rcode of an answer saying the name doesn't exist.
+const DNS_RCODE_NXDOMAIN: u16 = 3;
+/// The maximum number of cached names.
+const DNS_CACHE_SIZE: usize = 64;
+/// At most this many nameservers are used. Each one that doesn't answer
+/// makes a failing query wait [`DNS_TIMEOUT`] longer per attempt.
+const DNS_MAX_SERVERS: usize = 4;
+/// The range of the random source ports of queries.
+const DNS_SOURCE_PORTS: core::ops::RangeInclusive<u16> = 0xc000..=0xffff;
+/// How many random source ports are tried before taking any free port.
+const DNS_BIND_ATTEMPTS: usize = 8;
+
+static DNS_SERVERS: Mutex<Vec<IpAddress>> = Mutex::new(Vec::new());
+static DNS_CACHE: Mutex<BTreeMap<String, CacheEntry>> = Mutex::new(BTreeMap::new());
+static SERVERS_HANDLER: Mutex<Option<fn(&[IpAddress])>> = Mutex::new(None);
+static RANDOM_SOURCE: Mutex<Option<fn(&mut [u8])>> = Mutex::new(None);
+
+struct CacheEntry {
+    addrs: Vec<IpAddress>,
+    expires: u64,
+}
+
+/// Sets the nameservers from `dns=<addr>[,<addr>...]` on the kernel command
+/// line, or from the `dns` value of axconfig.
+pub(super) fn init_servers() {
+    let servers: Vec<IpAddress> = match axhal::cmdline::get("dns") {
+        Some(arg) => arg
+            .split(',')
+            .filter_map(|s| match s.parse() {
+                Ok(addr) => Some(addr),
+                Err(_) => {
+                    warn!("invalid nameserver {:?} in dns=", s);
+                    None
+                }
+            })
+            .collect(),
+        None => vec![axconfig::DNS.parse().expect("invalid DNS in axconfig")],
+    };
+    set_servers(&servers);
+}
+
+/// Replaces the nameservers, e.g. with the ones offered by DHCP. The cache is
+/// dropped since the new servers may answer differently.
+///
+/// The handler set by [`set_servers_handler`] is called with the new servers.
+pub(super) fn set_servers(servers: &[IpAddress]) {
+    let servers = &servers[..servers.len().min(DNS_MAX_SERVERS)];
+    *DNS_SERVERS.lock() = servers.to_vec();
+    DNS_CACHE.lock().clear();
+    for server in servers {
+        info!("  dns:      {}", server);
+    }
+    let handler = *SERVERS_HANDLER.lock();
+    if let Some(handler) = handler {
+        handler(servers);
+    }
+}
+
+/// Sets a function called each time the nameservers change, e.g. to rewrite
+/// `/etc/resolv.conf` when a new DHCP lease brings other servers.
+///
+/// The handler may be called while the interfaces are polled, so it must not
+/// block or use the network itself.
+pub fn set_servers_handler(handler: fn(&[IpAddress])) {
+    *SERVERS_HANDLER.lock() = Some(handler);
+}
+
+/// Sets the function filling a buffer with random bytes, used for the IDs and
+/// source ports of queries. It should be a CSPRNG, e.g. the one behind
+/// `/dev/urandom`.
+///
+/// Until it is set, the IDs and ports are derived from the current time and
+/// are easy to guess.
+pub fn set_random_source(source: fn(&mut [u8])) {
+    *RANDOM_SOURCE.lock() = Some(source);
+}
+
+fn random_u16() -> u16 {
+    let source = *RANDOM_SOURCE.lock();
+    match source {
+        Some(source) => {
+            let mut buf = [0; 2];
+            source(&mut buf);
+            u16::from_ne_bytes(buf)
+        }
+        None => (current_time_nanos() >> 10) as u16,
+    }
+}
+
+/// Creates the socket of a query, bound to a random source port.
+fn query_socket() -> AxResult<UdpSocket> {
+    let socket = UdpSocket::new();
+    let (start, end) = (*DNS_SOURCE_PORTS.start(), *DNS_SOURCE_PORTS.end());
+    for _ in 0..DNS_BIND_ATTEMPTS {
+        let port = start + random_u16() % (end - start + 1);
+        let addr = SocketAddr::new(IpAddress::Ipv4(Ipv4Address::UNSPECIFIED), port);
+        match socket.bind(addr) {
+            Err(AxError::AlreadyExists) => continue,
+            result => return result.map(|_| socket),
+        }
+    }
+    // the random ports were taken, leave it to the ephemeral port allocator
+    Ok(socket)
+}
+
+/// Returns the nameservers in use.
+pub fn dns_servers() -> Vec<IpAddress> {
+    DNS_SERVERS.lock().clone()
+}
+
+/// Resolves `name` to its IPv4 addresses, or to its IPv6 addresses if it has
+/// none. A literal address is returned as is, without any query.
+pub fn dns_query(name: &str) -> AxResult<Vec<IpAddress>> {
+    if let Ok(addr) = name.parse::<IpAddress>() {
+        return Ok(vec![addr]);
+    }
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+    let name = name.as_str();
+    if let Some(addrs) = cache_lookup(name) {
+        return Ok(addrs);
+    }
+
+    let servers = dns_servers();
+    if servers.is_empty() {
+        return ax_err!(NotFound, "DNS query failed: no nameserver");
+    }
+    let result = match query(&servers, name, DNS_TYPE_A) {
+        Err(AxError::NotFound) => query(&servers, name, DNS_TYPE_AAAA),
+        result => result,
+    };
+
+    let (addrs, ttl) = result?;
+    debug!("DNS: {} resolved to {:?}, ttl {}s", name, addrs, ttl);
+    cache_insert(name, &addrs, ttl);
+    Ok(addrs)
+}
+
+/// Asks the nameservers in turn for the records of `qtype` of `name`, and
+/// returns the addresses with the TTL of the answer.
+fn query(servers: &[IpAddress], name: &str, qtype: u16) -> AxResult<(Vec<IpAddress>, u32)> {
+    let id = random_u16();
+    let request = build_query(id, name, qtype)?;
+    let socket = query_socket()?;
+    socket.set_nonblocking(true);
+    let mut buf = [0; DNS_MAX_MESSAGE_LEN];
+    for _ in 0..DNS_ATTEMPTS {
+        for &server in servers {
+            let server = SocketAddr::new(server, DNS_PORT);
+            if let Err(e) = socket.send_to(&request, server) {
+                debug!("DNS: failed to ask {}: {:?}", server, e);
+                continue;
+            }
+            let deadline = current_time_nanos() + DNS_TIMEOUT.as_nanos() as u64;
+            loop {
+                let left = deadline.saturating_sub(current_time_nanos());
+                let timeout = Some(Duration::from_nanos(left));
+                let received =
+                    block_on_timeout(socket.key(), false, timeout, || socket.recv_from(&mut buf));
+                // timed out, ask the next nameserver
+                let Ok((len, from)) = received else { break };
+                // skip stray datagrams, e.g. late answers to an earlier query
+                if from == server {
+                    if let Some(answer) = parse_answer(&buf[..len], id, qtype) {
+                        return answer;
+                    }
+                }
+            }
+        }
+    }
+    ax_err!(TimedOut, "DNS query failed: no answer")
+}
+
+/// Builds a recursive query for the records of `qtype` of `name`.
+fn build_query(id: u16, name: &str, qtype: u16) -> AxResult<Vec<u8>> {
+    let mut msg = Vec::with_capacity(DNS_HEADER_LEN + name.len() + 6);
+    msg.extend_from_slice(&id.to_be_bytes());
+    // flags: recursion desired
+    msg.extend_from_slice(&0x0100u16.to_be_bytes());
+    // one question, no other records
+    msg.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return ax_err!(InvalidInput, "DNS query failed: invalid name");
+        }
+        msg.push(label.len() as u8);
+        msg.extend_from_slice(label.as_bytes());
+    }
+    msg.push(0);
+    if msg.len() - DNS_HEADER_LEN > 255 {
+        return ax_err!(InvalidInput, "DNS query failed: invalid name");
+    }
+    msg.extend_from_slice(&qtype.to_be_bytes());
+    msg.extend_from_slice(&DNS_CLASS_IN.to_be_bytes());
+    Ok(msg)
+}
+
+/// Parses the answer to the query `id`. Returns `None` if `msg` is not such an
+/// answer, so that it is ignored.
+///
+/// The TTL of the result is the smallest TTL of the answer records, which
+/// include the CNAME records leading to the addresses.
+fn parse_answer(msg: &[u8], id: u16, qtype: u16) -> Option<AxResult<(Vec<IpAddress>, u32)>> {
+    let be16 = |pos: usize| Some(u16::from_be_bytes(msg.get(pos..pos + 2)?.try_into().ok()?));
+    let be32 = |pos: usize| Some(u32::from_be_bytes(msg.get(pos..pos + 4)?.try_into().ok()?));
+    let flags = be16(2)?;
+    // not an answer to our query
+    if be16(0)? != id || flags & 0x8000 == 0 {
+        return None;
+    }
+    match flags & 0xf {
+        0 => {}
+        DNS_RCODE_NXDOMAIN => return Some(ax_err!(NotFound, "DNS query failed: no such name")),
+        _ => return Some(ax_err!(NotFound, "DNS query failed")),
+    }
+    let (questions, answers) = (be16(4)?, be16(6)?);
+    let mut pos = DNS_HEADER_LEN;
+    for _ in 0..questions {
+        pos = skip_name(msg, pos)? + 4;
+    }
+    let mut addrs = Vec::new();
+    let mut ttl = DNS_MAX_TTL_SECS;
+    for _ in 0..answers {
+        pos = skip_name(msg, pos)?;
+        let (rtype, class) = (be16(pos)?, be16(pos + 2)?);
+        let (rttl, rdlen) = (be32(pos + 4)?, be16(pos + 8)? as usize);
+        let rdata = msg.get(pos + 10..pos + 10 + rdlen)?;
+        pos += 10 + rdlen;
+        ttl = ttl.min(rttl);
+        if class != DNS_CLASS_IN || rtype != qtype {
+            continue;
+        }
+        match rdata.len() {
+            4 => addrs.push(IpAddress::Ipv4(Ipv4Address::from_bytes(rdata))),
+            16 => addrs.push(IpAddress::Ipv6(Ipv6Address::from_bytes(rdata))),
+            _ => return None,
+        }
+    }
+    if addrs.is_empty() {
+        return Some(ax_err!(NotFound, "DNS query failed: no address"));
+    }
+    Some(Ok((addrs, ttl)))
+}
+
+/// Returns the position after the (possibly compressed) name at `pos`.
+fn skip_name(msg: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *msg.get(pos)?;
+        match len {
+            0 => return Some(pos + 1),
+            // a pointer ends the name
+            len if len & 0xc0 == 0xc0 => return Some(pos + 2),
+            len => pos += 1 + len as usize,
+        }
+    }
+}
+
+fn cache_lookup(name: &str) -> Option<Vec<IpAddress>> {
+    let mut cache = DNS_CACHE.lock();
+    let entry = cache.get(name)?;
+    if entry.expires > current_time_nanos() {
+        Some(entry.addrs.clone())
+    } else {
+        cache.remove(name);
+        None
+    }
+}
+
+fn cache_insert(name: &str, addrs: &[IpAddress], ttl: u32) {
+    let now = current_time_nanos();
+    let mut cache = DNS_CACHE.lock();
+    if cache.len() >= DNS_CACHE_SIZE {
+        cache.retain(|_, entry| entry.expires > now);
+    }
+    if cache.len() >= DNS_CACHE_SIZE {
+        // evict the entry that would expire first
+        let oldest = cache
+            .iter()
+            .min_by_key(|(_, entry)| entry.expires)
+            .map(|(name, _)| name.clone());
+        if let Some(oldest) = oldest {
+            cache.remove(&oldest);
+        }
+    }
+    cache.insert(
+        name.to_string(),
+        CacheEntry {
+            addrs: addrs.to_vec(),
+            expires: now + ttl as u64 * NANOS_PER_SEC,
+        },
+    );
+}
@@ -0,0 +1,173 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
+use axerrno::{ax_err, ax_err_type, AxError, AxResult};
+use axio::PollState;
+use axsync::Mutex;
+use smoltcp::iface::SocketHandle;
+use smoltcp::socket::icmp::{self, BindError, Endpoint, SendError};
+use smoltcp::wire::IpAddress;
+
+use super::{block_on_timeout, SocketKey, SocketSetWrapper, SOCKET_SET};
+
+/// The type of an ICMPv4 echo request.
+const ICMPV4_ECHO_REQUEST: u8 = 8;
+/// The type of an ICMPv6 echo request.
+const ICMPV6_ECHO_REQUEST: u8 = 128;
+
+/// A socket that sends and receives ICMP echo messages, as used by `ping`.
+///
+/// A socket bound to an identifier receives the echo requests and replies
+/// that carry it. Messages are sent and received whole, including the ICMP
+/// header, without the IP header. The interfaces answer echo requests by
+/// themselves, whether such a socket exists or not.
+pub struct IcmpSocket {
+    handle: SocketHandle,
+    ident: Mutex<Option<u16>>,
+    nonblock: AtomicBool,
+    recv_timeout: Mutex<Option<Duration>>,
+}
+
+impl IcmpSocket {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        let socket = SocketSetWrapper::new_icmp_socket();
+        let handle = SOCKET_SET.add(socket);
+        Self {
+            handle,
+            ident: Mutex::new(None),
+            nonblock: AtomicBool::new(false),
+            recv_timeout: Mutex::new(None),
+        }
+    }
+
+    /// The echo identifier the socket is bound to.
+    pub fn ident(&self) -> Option<u16> {
+        *self.ident.lock()
+    }
+
+    /// Binds the socket to the echo identifier `ident`.
+    pub fn bind(&self, ident: u16) -> AxResult {
+        let mut bound = self.ident.lock();
+        if bound.is_some() {
+            return ax_err!(InvalidInput, "socket bind() failed: already bound");
+        }
+        SOCKET_SET.with_socket_mut::<icmp::Socket, _, _>(self.handle, |socket| {
+            socket.bind(Endpoint::Ident(ident)).or_else(|e| match e {
+                BindError::InvalidState => ax_err!(AlreadyExists, "socket bind() failed"),
+                BindError::Unaddressable => ax_err!(InvalidInput, "socket bind() failed"),
+            })
+        })?;
+        *bound = Some(ident);
+        debug!("ICMP socket {}: bound to ident {}", self.handle, ident);
+        Ok(())
+    }
+
+    /// Identifies the socket in the socket events, see [`SocketKey`].
+    pub fn key(&self) -> SocketKey {
+        SocketKey::handle(self.handle)
+    }
+
+    pub fn is_nonblocking(&self) -> bool {
+        self.nonblock.load(Ordering::Acquire)
+    }
+
+    /// Moves the socket into or out of nonblocking mode.
+    ///
+    /// In nonblocking mode, `send_to` and `recv_from` return
+    /// [`AxError::WouldBlock`] instead of waiting.
+    pub fn set_nonblocking(&self, nonblocking: bool) {
+        self.nonblock.store(nonblocking, Ordering::Release);
+    }
+
+    pub fn recv_timeout(&self) -> Option<Duration> {
+        *self.recv_timeout.lock()
+    }
+
+    /// Sets how long `recv_from` waits before returning [`AxError::WouldBlock`],
+    /// or [`None`] to wait forever.
+    pub fn set_recv_timeout(&self, timeout: Option<Duration>) {
+        *self.recv_timeout.lock() = timeout;
+    }
+
+    /// Sends the ICMP message `buf` to `addr`. The checksum is filled in by
+    /// the stack.
+    ///
+    /// An unbound socket sending an echo request is bound to its identifier
+    /// first, so that it receives the replies.
+    pub fn send_to(&self, buf: &[u8], addr: IpAddress) -> AxResult<usize> {
+        if addr.is_unspecified() {
+            return ax_err!(InvalidInput, "socket send_to() failed: invalid address");
+        }
+        if self.ident().is_none() {
+            let ident = echo_request_ident(buf, &addr)
+                .ok_or_else(|| ax_err_type!(InvalidInput, "socket send_to() failed: not bound"))?;
+            self.bind(ident)?;
+        }
+        let len = block_on_timeout(self.key(), self.is_nonblocking(), None, || {
+            SOCKET_SET.with_socket_mut::<icmp::Socket, _, _>(self.handle, |socket| {
+                if !socket.can_send() {
+                    // tx buffer is full
+                    return Err(AxError::WouldBlock);
+                }
+                socket.send_slice(buf, addr).map_err(|e| match e {
+                    SendError::BufferFull => AxError::WouldBlock,
+                    SendError::Unaddressable => {
+                        ax_err_type!(InvalidInput, "socket send_to() failed")
+                    }
+                })?;
+                Ok(buf.len())
+            })
+        })?;
+        SOCKET_SET.poll_interfaces();
+        Ok(len)
+    }
+
+    /// Receives an ICMP message, returning its length and the source address.
+    ///
+    /// If the message is larger than `buf`, the excess bytes are discarded.
+    pub fn recv_from(&self, buf: &mut [u8]) -> AxResult<(usize, IpAddress)> {
+        if self.ident().is_none() {
+            return ax_err!(NotConnected, "socket recv() failed: not bound");
+        }
+        block_on_timeout(
+            self.key(),
+            self.is_nonblocking(),
+            self.recv_timeout(),
+            || {
+                SOCKET_SET.with_socket_mut::<icmp::Socket, _, _>(self.handle, |socket| {
+                    // no more data
+                    socket.recv_slice(buf).map_err(|_| AxError::WouldBlock)
+                })
+            },
+        )
+    }
+
+    /// Whether the socket is readable or writable.
+    pub fn poll(&self) -> AxResult<PollState> {
+        Ok(
+            SOCKET_SET.with_socket::<icmp::Socket, _, _>(self.handle, |socket| PollState {
+                readable: socket.can_recv(),
+                writable: socket.can_send(),
+            }),
+        )
+    }
+}
+
+impl Drop for IcmpSocket {
+    fn drop(&mut self) {
+        SOCKET_SET.remove(self.handle);
+    }
+}
+
+/// The identifier of `buf` if it is an echo request for `addr`.
+fn echo_request_ident(buf: &[u8], addr: &IpAddress) -> Option<u16> {
+    let echo_request = match addr {
+        IpAddress::Ipv4(_) => ICMPV4_ECHO_REQUEST,
+        IpAddress::Ipv6(_) => ICMPV6_ECHO_REQUEST,
+    };
+    match *buf {
+        [ty, 0, _, _, id0, id1, ..] if ty == echo_request => Some(u16::from_be_bytes([id0, id1])),
+        _ => None,
+    }
+}
@@ -0,0 +1,249 @@
+//! IPv6 address configuration of the NIC.
+//!
+//! The NIC always gets a link-local address derived from its MAC address
+//! (EUI-64). The global address is either static, given by `ip6=<addr>/<prefix>`
+//! and `gw6=<addr>` on the kernel command line, or configured by SLAAC: a
+//! Router Solicitation is sent at startup and retransmitted as in RFC 4861
+//! until a router answers, and every Router Advertisement that allows
+//! autonomous configuration of a /64 prefix gives an address, with the
+//! advertising router as the default route. Address lifetimes are not tracked.
+//! Neighbor discovery itself is done by smoltcp.
+
+use alloc::vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
+use axsync::Mutex;
+use driver_net::NetDriverOps;
+use smoltcp::iface::Interface;
+use smoltcp::socket::icmp;
+use smoltcp::wire::{
+    EthernetAddress, Icmpv6Packet, IpAddress, IpCidr, IpProtocol, Ipv6Address, Ipv6Packet,
+};
+
+use super::{InterfaceWrapper, SOCKET_SET};
+
+const ICMPV6_ROUTER_SOLICIT: u8 = 133;
+const ICMPV6_ROUTER_ADVERT: u8 = 134;
+const RA_HEADER_LEN: usize = 16;
+const NDISC_OPTION_PREFIX_INFO: u8 = 3;
+const PREFIX_INFO_AUTONOMOUS: u8 = 0x40;
+const SLAAC_PREFIX_LEN: u8 = 64;
+/// How many Router Solicitations are sent at most (`MAX_RTR_SOLICITATIONS`).
+const MAX_ROUTER_SOLICITS: usize = 3;
+/// The time between Router Solicitations (`RTR_SOLICITATION_INTERVAL`).
+const ROUTER_SOLICIT_INTERVAL: Duration = Duration::from_secs(4);
+
+/// A Router Solicitation without options, the checksum is filled by smoltcp.
+const ROUTER_SOLICIT: [u8; 8] = [ICMPV6_ROUTER_SOLICIT, 0, 0, 0, 0, 0, 0, 0];
+
+static SLAAC_ENABLED: AtomicBool = AtomicBool::new(false);
+/// Whether a Router Advertisement has been received, which stops the Router
+/// Solicitations.
+static ADVERT_RECEIVED: AtomicBool = AtomicBool::new(false);
+static PENDING_ADVERT: Mutex<Option<RouterAdvert>> = Mutex::new(None);
+
+/// What we use of a Router Advertisement.
+struct RouterAdvert {
+    router: Ipv6Address,
+    /// Router lifetime in seconds, 0 means it is not a default router.
+    lifetime: u16,
+    /// The /64 prefix to configure an address in.
+    prefix: Option<Ipv6Address>,
+}
+
+/// Returns the address in the /64 `prefix` with the interface identifier
+/// derived from `mac` (modified EUI-64).
+fn eui64_addr(prefix: &Ipv6Address, mac: EthernetAddress) -> Ipv6Address {
+    let mut bytes = prefix.0;
+    bytes[8..11].copy_from_slice(&mac.0[..3]);
+    bytes[8] ^= 0x02; // flip the universal/local bit
+    bytes[11] = 0xff;
+    bytes[12] = 0xfe;
+    bytes[13..].copy_from_slice(&mac.0[3..]);
+    Ipv6Address(bytes)
+}
+
+/// The static address and gateway from the kernel command line, if any.
+fn static_config() -> Option<(IpCidr, Option<IpAddress>)> {
+    let arg = axhal::cmdline::get("ip6")?;
+    let cidr = match arg.parse() {
+        Ok(cidr @ IpCidr::Ipv6(_)) => cidr,
+        _ => {
+            warn!("invalid ip6={}, using SLAAC", arg);
+            return None;
+        }
+    };
+    let gateway = axhal::cmdline::get("gw6").and_then(|arg| match arg.parse() {
+        Ok(gateway @ IpAddress::Ipv6(_)) => Some(gateway),
+        _ => {
+            warn!("invalid gw6={}, ignored", arg);
+            None
+        }
+    });
+    Some((cidr, gateway))
+}
+
+/// Configures the link-local address of the NIC, then the static address or
+/// SLAAC.
+pub(super) fn init<D: NetDriverOps>(eth0: &InterfaceWrapper<D>) {
+    let mac = match eth0.ethernet_address() {
+        Some(mac) => mac,
+        None => return,
+    };
+    let link_local = eui64_addr(&Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0), mac);
+    eth0.setup_ip_addr(link_local.into(), SLAAC_PREFIX_LEN);
+    info!("  ip6:      {}/{}", link_local, SLAAC_PREFIX_LEN);
+
+    if let Some((cidr, gateway)) = static_config() {
+        eth0.setup_ip_addr(cidr.address(), cidr.prefix_len());
+        info!("  ip6:      {}", cidr);
+        if let Some(gateway) = gateway {
+            eth0.setup_gateway(gateway);
+            info!("  gateway6: {}", gateway);
+        }
+    } else {
+        SLAAC_ENABLED.store(true, Ordering::Release);
+        send_router_solicit();
+        axtask::spawn(retransmit_router_solicits);
+    }
+}
+
+/// Sends the remaining Router Solicitations, each after
+/// [`ROUTER_SOLICIT_INTERVAL`] without any Router Advertisement.
+fn retransmit_router_solicits() {
+    for _ in 1..MAX_ROUTER_SOLICITS {
+        axtask::sleep(ROUTER_SOLICIT_INTERVAL);
+        if ADVERT_RECEIVED.load(Ordering::Acquire) {
+            return;
+        }
+        send_router_solicit();
+    }
+}
+
+/// Asks the routers on the link to advertise themselves now, instead of at
+/// their next periodic advertisement.
+fn send_router_solicit() {
+    let rx_buffer = icmp::PacketBuffer::new(vec![icmp::PacketMetadata::EMPTY], vec![]);
+    let tx_buffer = icmp::PacketBuffer::new(
+        vec![icmp::PacketMetadata::EMPTY],
+        vec![0; ROUTER_SOLICIT.len()],
+    );
+    let mut socket = icmp::Socket::new(rx_buffer, tx_buffer);
+    socket.set_hop_limit(Some(255)); // required by NDP
+    if socket
+        .send_slice(&ROUTER_SOLICIT, Ipv6Address::LINK_LOCAL_ALL_ROUTERS.into())
+        .is_err()
+    {
+        return;
+    }
+    let handle = SOCKET_SET.add(socket);
+    SOCKET_SET.poll_interfaces();
+    SOCKET_SET.remove(handle);
+}
+
+/// Records a Router Advertisement received by the NIC, to be applied by
+/// [`apply_router_advert`] once the interface is locked.
+pub(super) fn snoop_router_advert(packet: &Ipv6Packet<&[u8]>) {
+    if !SLAAC_ENABLED.load(Ordering::Acquire)
+        || packet.next_header() != IpProtocol::Icmpv6
+        || packet.hop_limit() != 255
+        || !packet.src_addr().is_link_local()
+    {
+        return;
+    }
+    let icmp = match Icmpv6Packet::new_checked(packet.payload()) {
+        Ok(icmp) => icmp,
+        Err(_) => return,
+    };
+    let src_addr = IpAddress::from(packet.src_addr());
+    let dst_addr = IpAddress::from(packet.dst_addr());
+    if !icmp.verify_checksum(&src_addr, &dst_addr) {
+        return;
+    }
+    let msg = packet.payload();
+    if msg.len() < RA_HEADER_LEN || msg[0] != ICMPV6_ROUTER_ADVERT {
+        return;
+    }
+
+    let mut prefix = None;
+    let mut options = &msg[RA_HEADER_LEN..];
+    while options.len() >= 2 {
+        let len = options[1] as usize * 8;
+        if len == 0 || len > options.len() {
+            break;
+        }
+        let option = &options[..len];
+        // an autonomous /64 prefix with a non-zero valid lifetime
+        if option[0] == NDISC_OPTION_PREFIX_INFO
+            && len == 32
+            && option[2] == SLAAC_PREFIX_LEN
+            && option[3] & PREFIX_INFO_AUTONOMOUS != 0
+            && option[4..8] != [0; 4]
+        {
+            prefix = Some(Ipv6Address::from_bytes(&option[16..32]));
+        }
+        options = &options[len..];
+    }
+
+    ADVERT_RECEIVED.store(true, Ordering::Release);
+    *PENDING_ADVERT.lock() = Some(RouterAdvert {
+        router: packet.src_addr(),
+        lifetime: u16::from_be_bytes([msg[6], msg[7]]),
+        prefix,
+    });
+}
+
+/// Applies the last Router Advertisement recorded by [`snoop_router_advert`].
+pub(super) fn apply_router_advert(iface: &mut Interface, mac: EthernetAddress) {
+    let advert = match PENDING_ADVERT.lock().take() {
+        Some(advert) => advert,
+        None => return,
+    };
+    if let Some(prefix) = advert.prefix {
+        let addr = eui64_addr(&prefix, mac);
+        if !iface.has_ip_addr(addr) {
+            let cidr = IpCidr::new(addr.into(), SLAAC_PREFIX_LEN);
+            iface.update_ip_addrs(|addrs| match addrs.push(cidr) {
+                Ok(()) => info!("SLAAC: configured {}", cidr),
+                Err(_) => warn!("SLAAC: too many addresses, {} ignored", cidr),
+            });
+        }
+    }
+    if advert.lifetime > 0 {
+        let old = iface
+            .routes_mut()
+            .add_default_ipv6_route(advert.router)
+            .unwrap();
+        if old.map_or(true, |route| route.via_router != advert.router.into()) {
+            info!("SLAAC: default router {}", advert.router);
+        }
+    } else if let Some(route) = iface.routes_mut().remove_default_ipv6_route() {
+        // only the advertising router stops being the default router
+        match route.via_router {
+            IpAddress::Ipv6(router) if router != advert.router => {
+                iface.routes_mut().add_default_ipv6_route(router).unwrap();
+            }
+            _ => info!("SLAAC: default router {} withdrawn", advert.router),
+        }
+    }
+}
+
+/// Whether `buf`, an IPv6 packet sent by a NIC, is addressed to `::1`, or is
+/// a Neighbor Solicitation for it.
+pub(super) fn is_loopback_packet(buf: &[u8]) -> bool {
+    const ICMPV6_NEIGHBOR_SOLICIT: u8 = 135;
+
+    let packet = match Ipv6Packet::new_checked(buf) {
+        Ok(packet) => packet,
+        Err(_) => return false,
+    };
+    if packet.dst_addr().is_loopback() {
+        return true;
+    }
+    let msg = packet.payload();
+    packet.next_header() == IpProtocol::Icmpv6
+        && msg.len() >= 24
+        && msg[0] == ICMPV6_NEIGHBOR_SOLICIT
+        && Ipv6Address::from_bytes(&msg[8..24]).is_loopback()
+}
@@ -1,24 +1,41 @@
 use alloc::{boxed::Box, collections::VecDeque};
-use core::ops::DerefMut;
+use core::ops::{Deref, DerefMut};
 
 use axerrno::{ax_err, AxError, AxResult};
 use axsync::Mutex;
 use smoltcp::iface::SocketHandle;
 use smoltcp::socket::tcp::{self, State};
+use smoltcp::wire::IpAddress;
 
-use super::{SocketSetWrapper, LISTEN_QUEUE_SIZE, SOCKET_SET};
+use super::tcp::TcpConfig;
+use super::{LISTEN_QUEUE_SIZE, SOCKET_SET};
 use crate::SocketAddr;
 
 const PORT_NUM: usize = 65536;
 
 struct ListenTableEntry {
+    listen_addr: IpAddress,
     syn_queue: VecDeque<SocketHandle>,
+    /// The options of the listener, inherited by the new connections.
+    config: TcpConfig,
 }
 
 impl ListenTableEntry {
-    pub fn new() -> Self {
+    pub fn new(listen_addr: IpAddress, config: TcpConfig) -> Self {
         Self {
+            listen_addr,
             syn_queue: VecDeque::with_capacity(LISTEN_QUEUE_SIZE),
+            config,
+        }
+    }
+
+    /// Whether a connection to `dst` is for this listener. `0.0.0.0` accepts
+    /// every IPv4 address, and `::` every address of both versions.
+    fn accepts(&self, dst: &IpAddress) -> bool {
+        match self.listen_addr {
+            IpAddress::Ipv4(addr) if addr.is_unspecified() => matches!(dst, IpAddress::Ipv4(_)),
+            IpAddress::Ipv6(addr) if addr.is_unspecified() => true,
+            addr => addr == *dst,
         }
     }
 }
@@ -51,36 +68,41 @@ impl ListenTable {
         self.tcp[port as usize].lock().is_none()
     }
 
-    pub fn listen(&self, port: u16) -> AxResult {
+    pub fn listen(&self, listen_addr: SocketAddr, config: TcpConfig) -> AxResult {
+        let port = listen_addr.port;
         if port == 0 {
             return ax_err!(InvalidInput, "socket listen() failed");
         }
         let mut entry = self.tcp[port as usize].lock();
         if entry.is_none() {
-            *entry = Some(Box::new(ListenTableEntry::new()));
+            *entry = Some(Box::new(ListenTableEntry::new(listen_addr.addr, config)));
             Ok(())
         } else {
             ax_err!(AlreadyExists, "socket listen() failed")
         }
     }
 
+    /// Changes the options of the connections accepted from now on.
+    pub fn set_config(&self, port: u16, config: TcpConfig) {
+        if let Some(entry) = self.tcp[port as usize].lock().deref_mut() {
+            entry.config = config;
+        }
+    }
+
     pub fn unlisten(&self, port: u16) {
         debug!("socket unlisten on {}", port);
         *self.tcp[port as usize].lock() = None;
     }
 
-    pub fn accept(&self, port: u16) -> AxResult<(SocketHandle, Option<SocketAddr>)> {
-        fn get_socket_info(handle: SocketHandle) -> (bool, Option<SocketAddr>) {
-            let (connected, peer_addr) =
-                SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| {
-                    (
-                        !matches!(socket.state(), State::Listen | State::SynReceived),
-                        socket.remote_endpoint(),
-                    )
-                });
-            (connected, peer_addr)
+    pub fn can_accept(&self, port: u16) -> AxResult<bool> {
+        if let Some(entry) = self.tcp[port as usize].lock().deref() {
+            Ok(entry.syn_queue.iter().any(|&handle| get_socket_info(handle).0))
+        } else {
+            ax_err!(InvalidInput, "socket accept() failed: not listen")
         }
+    }
 
+    pub fn accept(&self, port: u16) -> AxResult<(SocketHandle, Option<SocketAddr>)> {
         if let Some(entry) = self.tcp[port as usize].lock().deref_mut() {
             let syn_queue = &mut entry.syn_queue;
             if let Some(&handle) = syn_queue.front() {
@@ -94,7 +116,7 @@ impl ListenTable {
                     return Ok((handle, peer_addr));
                 }
             } else {
-                return Err(AxError::Again);
+                return Err(AxError::WouldBlock);
             }
             if let Some((idx, peer_addr)) =
                 syn_queue
@@ -120,7 +142,7 @@ impl ListenTable {
                 Ok((handle, peer_addr))
             } else {
                 // wait for connection
-                Err(AxError::Again)
+                Err(AxError::WouldBlock)
             }
         } else {
             ax_err!(InvalidInput, "socket accept() failed: not listen")
@@ -129,13 +151,17 @@ impl ListenTable {
 
     pub fn incoming_tcp_packet(&self, src: SocketAddr, dst: SocketAddr) {
         if let Some(entry) = self.tcp[dst.port as usize].lock().deref_mut() {
+            if !entry.accepts(&dst.addr) {
+                return;
+            }
             if entry.syn_queue.len() >= LISTEN_QUEUE_SIZE {
                 // SYN queue is full, drop the packet
                 warn!("SYN queue overflow!");
                 return;
             }
-            let mut socket = SocketSetWrapper::new_tcp_socket();
+            let mut socket = entry.config.new_socket();
             if socket.listen(dst).is_ok() {
+                entry.config.apply(&mut socket);
                 let handle = SOCKET_SET.add(socket);
                 debug!(
                     "socket {}: prepare for connection {} -> {}",
@@ -146,3 +172,13 @@ impl ListenTable {
         }
     }
 }
+
+fn get_socket_info(handle: SocketHandle) -> (bool, Option<SocketAddr>) {
+    let (connected, peer_addr) = SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| {
+        (
+            !matches!(socket.state(), State::Listen | State::SynReceived),
+            socket.remote_endpoint(),
+        )
+    });
+    (connected, peer_addr)
+}
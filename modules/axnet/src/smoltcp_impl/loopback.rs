@@ -0,0 +1,191 @@
+//! Software loopback interface for `127.0.0.0/8` and `::1`.
+//!
+//! The loopback interface shares the socket set with the NIC, so a socket can
+//! talk to both. The interface that sends a packet is the first one that can
+//! route it: the NIC is polled first, and the loopback addresses are routed on
+//! it to a neighbor that never resolves, so such packets always fall through to
+//! the loopback interface. The ARP requests and Neighbor Solicitations for that
+//! neighbor are dropped before they reach the wire, see [`is_loopback_frame`].
+
+use alloc::{collections::VecDeque, vec, vec::Vec};
+use core::time::Duration;
+
+use axsync::Mutex;
+use smoltcp::iface::{Config, Interface, Route, SocketSet};
+use smoltcp::phy::{Device, DeviceCapabilities, Medium, RxToken, TxToken};
+use smoltcp::time::Instant;
+use smoltcp::wire::{
+    ArpPacket, EthernetFrame, EthernetProtocol, IpAddress, IpCidr, Ipv4Address, Ipv4Packet,
+    Ipv6Address,
+};
+
+use super::{poll_sockets, snoop_tcp_ip_packet, timestamp, SocketKey, RANDOM_SEED};
+
+const LOOPBACK_IP: IpAddress = IpAddress::v4(127, 0, 0, 1);
+const LOOPBACK_PREFIX: u8 = 8;
+const LOOPBACK_IPV6: IpAddress = IpAddress::Ipv6(Ipv6Address::LOOPBACK);
+const LOOPBACK_MTU: usize = 65535;
+
+/// A device that receives every packet it sends.
+///
+/// Sent packets are only delivered at the next [`LoopbackInterface::poll`], so
+/// that new TCP connections can be snooped outside of the interface poll, as
+/// for the NIC.
+struct LoopbackDev {
+    tx_queue: VecDeque<Vec<u8>>,
+    rx_queue: VecDeque<Vec<u8>>,
+}
+
+pub(super) struct LoopbackInterface {
+    dev: Mutex<LoopbackDev>,
+    pub(super) iface: Mutex<Interface>,
+}
+
+impl LoopbackDev {
+    fn new() -> Self {
+        Self {
+            tx_queue: VecDeque::new(),
+            rx_queue: VecDeque::new(),
+        }
+    }
+}
+
+impl LoopbackInterface {
+    pub fn new() -> Self {
+        let mut config = Config::new();
+        config.random_seed = RANDOM_SEED;
+
+        let mut dev = LoopbackDev::new();
+        let mut iface = Interface::new(config, &mut dev);
+        iface.update_ip_addrs(|ip_addrs| {
+            ip_addrs
+                .push(IpCidr::new(LOOPBACK_IP, LOOPBACK_PREFIX))
+                .unwrap();
+            ip_addrs.push(IpCidr::new(LOOPBACK_IPV6, 128)).unwrap();
+        });
+        Self {
+            dev: Mutex::new(dev),
+            iface: Mutex::new(iface),
+        }
+    }
+
+    /// Returns whether the state of the sockets may have changed, and appends
+    /// the sockets whose state changed to `changed_sockets`.
+    pub fn poll(&self, sockets: &Mutex<SocketSet>, changed_sockets: &mut Vec<SocketKey>) -> bool {
+        let mut dev = self.dev.lock();
+        while let Some(buf) = dev.tx_queue.pop_front() {
+            snoop_tcp_ip_packet(&buf).ok(); // preprocess TCP packets
+            dev.rx_queue.push_back(buf);
+        }
+
+        let mut iface = self.iface.lock();
+        let mut sockets = sockets.lock();
+        poll_sockets(&mut sockets, changed_sockets, |sockets| {
+            iface.poll(timestamp(), &mut *dev, sockets)
+        })
+    }
+
+    pub fn poll_delay(&self, sockets: &Mutex<SocketSet>) -> Option<Duration> {
+        // packets sent during the last poll are only received at the next one
+        if !self.dev.lock().tx_queue.is_empty() {
+            return Some(Duration::ZERO);
+        }
+        let delay = self.iface.lock().poll_delay(timestamp(), &sockets.lock());
+        delay.map(|delay| Duration::from_micros(delay.total_micros()))
+    }
+}
+
+impl Device for LoopbackDev {
+    type RxToken<'a>
+        = LoopbackRxToken
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = LoopbackTxToken<'a>
+    where
+        Self: 'a;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let buf = self.rx_queue.pop_front()?;
+        Some((LoopbackRxToken(buf), LoopbackTxToken(&mut self.tx_queue)))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Some(LoopbackTxToken(&mut self.tx_queue))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.max_transmission_unit = LOOPBACK_MTU;
+        caps.medium = Medium::Ip;
+        caps
+    }
+}
+
+struct LoopbackRxToken(Vec<u8>);
+struct LoopbackTxToken<'a>(&'a mut VecDeque<Vec<u8>>);
+
+impl RxToken for LoopbackRxToken {
+    fn consume<R, F>(mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        trace!("LOOPBACK RECV {} bytes", self.0.len());
+        f(&mut self.0)
+    }
+}
+
+impl<'a> TxToken for LoopbackTxToken<'a> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut buf = vec![0; len];
+        let result = f(&mut buf);
+        trace!("LOOPBACK SEND {} bytes", len);
+        self.0.push_back(buf);
+        result
+    }
+}
+
+/// Routes `127.0.0.0/8` and `::1` on a NIC to a neighbor that never answers,
+/// so that the NIC never sends such packets and leaves them to the loopback
+/// interface.
+pub(super) fn add_loopback_blackhole(iface: &mut Interface) {
+    iface.routes_mut().update(|routes| {
+        routes
+            .push(Route {
+                cidr: IpCidr::new(Ipv4Address::new(127, 0, 0, 0).into(), LOOPBACK_PREFIX),
+                via_router: LOOPBACK_IP,
+                preferred_until: None,
+                expires_at: None,
+            })
+            .unwrap();
+        routes
+            .push(Route {
+                cidr: IpCidr::new(LOOPBACK_IPV6, 128),
+                via_router: LOOPBACK_IPV6,
+                preferred_until: None,
+                expires_at: None,
+            })
+            .unwrap();
+    });
+}
+
+/// Whether an Ethernet frame sent by a NIC is addressed to `127.0.0.0/8` or
+/// `::1`, which must never appear on the wire.
+pub(super) fn is_loopback_frame(buf: &[u8]) -> bool {
+    let frame = match EthernetFrame::new_checked(buf) {
+        Ok(frame) => frame,
+        Err(_) => return false,
+    };
+    match frame.ethertype() {
+        EthernetProtocol::Arp => ArpPacket::new_checked(frame.payload()).map_or(false, |arp| {
+            arp.target_protocol_addr().first() == Some(&127)
+        }),
+        EthernetProtocol::Ipv4 => Ipv4Packet::new_checked(frame.payload())
+            .map_or(false, |packet| packet.dst_addr().is_loopback()),
+        EthernetProtocol::Ipv6 => super::ipv6::is_loopback_packet(frame.payload()),
+        _ => false,
+    }
+}
@@ -1,11 +1,21 @@
+#[cfg(feature = "dhcp")]
+mod dhcp;
+mod dns;
+mod icmp;
+mod ipv6;
 mod listen_table;
+mod loopback;
+mod task;
 mod tcp;
+mod udp;
 
-use alloc::{collections::VecDeque, vec};
+use alloc::{collections::VecDeque, vec, vec::Vec};
 use core::cell::RefCell;
 use core::ops::DerefMut;
+use core::time::Duration;
 
 use axdriver::NetDevices;
+use axerrno::{ax_err, AxResult};
 use axhal::time::{current_time_nanos, NANOS_PER_MICROS};
 use axsync::Mutex;
 use driver_common::DevError;
@@ -18,27 +28,52 @@ use smoltcp::time::Instant;
 use smoltcp::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr};
 
 use self::listen_table::ListenTable;
+use self::loopback::LoopbackInterface;
 
+pub use self::dns::{dns_query, dns_servers, set_random_source, set_servers_handler};
+pub use self::icmp::IcmpSocket;
+pub use self::task::{block_on, block_on_timeout, set_event_handler, SocketKey};
 pub use self::tcp::TcpSocket;
-
-const IP: IpAddress = IpAddress::v4(10, 0, 2, 15); // QEMU user networking default IP
-const GATEWAY: IpAddress = IpAddress::v4(10, 0, 2, 2); // QEMU user networking gateway
-const IP_PREFIX: u8 = 24;
+pub use self::udp::UdpSocket;
 
 const RANDOM_SEED: u64 = 0xA2CE_05A2_CE05_A2CE;
 
 const TCP_RX_BUF_LEN: usize = 4096;
 const TCP_TX_BUF_LEN: usize = 4096;
+const UDP_RX_BUF_LEN: usize = 64 * 1024;
+const UDP_TX_BUF_LEN: usize = 64 * 1024;
+const UDP_METADATA_BUF_LEN: usize = 256;
+const ICMP_RX_BUF_LEN: usize = 16 * 1024;
+const ICMP_TX_BUF_LEN: usize = 16 * 1024;
+const ICMP_METADATA_BUF_LEN: usize = 64;
 
 const RX_BUF_QUEUE_SIZE: usize = 64;
 const LISTEN_QUEUE_SIZE: usize = 512;
 
 static LISTEN_TABLE: LazyInit<ListenTable> = LazyInit::new();
 static SOCKET_SET: LazyInit<SocketSetWrapper> = LazyInit::new();
-static ETH0: LazyInit<InterfaceWrapper<axdriver::VirtIoNetDev>> = LazyInit::new();
+/// The NIC, [`None`] if there is none and only the loopback interface works.
+static ETH0: LazyInit<Option<InterfaceWrapper<axdriver::VirtIoNetDev>>> = LazyInit::new();
+static LOOPBACK: LazyInit<LoopbackInterface> = LazyInit::new();
 
 struct SocketSetWrapper<'a>(Mutex<SocketSet<'a>>);
 
+/// What the readiness of a socket depends on, compared before and after an
+/// interface poll to find the sockets whose state the poll changed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SocketSnapshot {
+    Tcp {
+        state: socket::tcp::State,
+        recv_queue: usize,
+        send_queue: usize,
+    },
+    Datagram {
+        can_recv: bool,
+        can_send: bool,
+    },
+    Other,
+}
+
 struct DeviceWrapper<D: NetDriverOps> {
     inner: RefCell<D>, // use `RefCell` is enough since it's wrapped in `Mutex` in `InterfaceWrapper`.
     rx_buf_queue: VecDeque<D::RxBuffer>,
@@ -56,12 +91,36 @@ impl<'a> SocketSetWrapper<'a> {
         Self(Mutex::new(SocketSet::new(vec![])))
     }
 
-    pub fn new_tcp_socket() -> socket::tcp::Socket<'a> {
-        let tcp_rx_buffer = socket::tcp::SocketBuffer::new(vec![0; TCP_RX_BUF_LEN]);
-        let tcp_tx_buffer = socket::tcp::SocketBuffer::new(vec![0; TCP_TX_BUF_LEN]);
+    pub fn new_tcp_socket(rx_buf_len: usize, tx_buf_len: usize) -> socket::tcp::Socket<'a> {
+        let tcp_rx_buffer = socket::tcp::SocketBuffer::new(vec![0; rx_buf_len]);
+        let tcp_tx_buffer = socket::tcp::SocketBuffer::new(vec![0; tx_buf_len]);
         socket::tcp::Socket::new(tcp_rx_buffer, tcp_tx_buffer)
     }
 
+    pub fn new_udp_socket() -> socket::udp::Socket<'a> {
+        let udp_rx_buffer = socket::udp::PacketBuffer::new(
+            vec![socket::udp::PacketMetadata::EMPTY; UDP_METADATA_BUF_LEN],
+            vec![0; UDP_RX_BUF_LEN],
+        );
+        let udp_tx_buffer = socket::udp::PacketBuffer::new(
+            vec![socket::udp::PacketMetadata::EMPTY; UDP_METADATA_BUF_LEN],
+            vec![0; UDP_TX_BUF_LEN],
+        );
+        socket::udp::Socket::new(udp_rx_buffer, udp_tx_buffer)
+    }
+
+    pub fn new_icmp_socket() -> socket::icmp::Socket<'a> {
+        let icmp_rx_buffer = socket::icmp::PacketBuffer::new(
+            vec![socket::icmp::PacketMetadata::EMPTY; ICMP_METADATA_BUF_LEN],
+            vec![0; ICMP_RX_BUF_LEN],
+        );
+        let icmp_tx_buffer = socket::icmp::PacketBuffer::new(
+            vec![socket::icmp::PacketMetadata::EMPTY; ICMP_METADATA_BUF_LEN],
+            vec![0; ICMP_TX_BUF_LEN],
+        );
+        socket::icmp::Socket::new(icmp_rx_buffer, icmp_tx_buffer)
+    }
+
     pub fn add<T: AnySocket<'a>>(&self, socket: T) -> SocketHandle {
         let handle = self.0.lock().add(socket);
         debug!("socket {}: created", handle);
@@ -86,8 +145,46 @@ impl<'a> SocketSetWrapper<'a> {
         f(socket)
     }
 
+    /// Whether no UDP socket is bound to `port`.
+    pub fn is_udp_port_free(&self, port: u16) -> bool {
+        self.0.lock().iter().all(|(_, socket)| match socket {
+            socket::Socket::Udp(udp) => udp.endpoint().port != port,
+            _ => true,
+        })
+    }
+
+    /// Polls the interfaces right away, e.g. to send the data just queued
+    /// without waiting for the network task.
     pub fn poll_interfaces(&self) {
-        ETH0.poll(&self.0);
+        if self.poll_once() {
+            // the timers of the sockets may have changed
+            task::wake_net_task();
+        }
+    }
+
+    /// Polls the NIC before the loopback interface, see the [`loopback`]
+    /// module for why the order matters. Returns whether the state of the
+    /// sockets may have changed, and if so wakes up the blocked operations.
+    fn poll_once(&self) -> bool {
+        let mut changed_sockets = Vec::new();
+        let changed = ETH0
+            .as_ref()
+            .map_or(false, |eth0| eth0.poll(&self.0, &mut changed_sockets));
+        let changed = changed | LOOPBACK.poll(&self.0, &mut changed_sockets);
+        if changed {
+            task::notify_socket_events(&changed_sockets);
+        }
+        changed
+    }
+
+    /// How long until the interfaces need to be polled again, or [`None`] if
+    /// not before the next packet.
+    fn poll_delay(&self) -> Option<Duration> {
+        let eth0_delay = ETH0.as_ref().and_then(|eth0| eth0.poll_delay(&self.0));
+        match (eth0_delay, LOOPBACK.poll_delay(&self.0)) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
     }
 
     pub fn remove(&self, handle: SocketHandle) {
@@ -103,7 +200,9 @@ impl<D: NetDriverOps> InterfaceWrapper<D> {
         config.hardware_addr = ether_addr.map(HardwareAddress::Ethernet);
 
         let mut dev = DeviceWrapper::new(dev);
-        let iface = Mutex::new(Interface::new(config, &mut dev));
+        let mut iface = Interface::new(config, &mut dev);
+        loopback::add_loopback_blackhole(&mut iface);
+        let iface = Mutex::new(iface);
         Self {
             name,
             ether_addr,
@@ -131,23 +230,112 @@ impl<D: NetDriverOps> InterfaceWrapper<D> {
         let mut iface = self.iface.lock();
         match gateway {
             IpAddress::Ipv4(v4) => iface.routes_mut().add_default_ipv4_route(v4).unwrap(),
+            IpAddress::Ipv6(v6) => iface.routes_mut().add_default_ipv6_route(v6).unwrap(),
         };
     }
 
-    pub fn poll(&self, sockets: &Mutex<SocketSet>) {
+    pub fn irq_num(&self) -> Option<usize> {
+        self.dev.lock().inner.borrow().irq_num()
+    }
+
+    pub fn ack_interrupt(&self) -> bool {
+        self.dev.lock().inner.borrow_mut().ack_interrupt()
+    }
+
+    /// Returns whether the state of the sockets may have changed, and appends
+    /// the sockets whose state changed to `changed_sockets`.
+    pub fn poll(&self, sockets: &Mutex<SocketSet>, changed_sockets: &mut Vec<SocketKey>) -> bool {
         let mut dev = self.dev.lock();
         dev.poll(|buf| {
             snoop_tcp_packet(buf).ok(); // preprocess TCP packets
         });
 
-        let timestamp =
-            Instant::from_micros_const((current_time_nanos() / NANOS_PER_MICROS) as i64);
         let mut iface = self.iface.lock();
         let mut sockets = sockets.lock();
-        iface.poll(timestamp, dev.deref_mut(), &mut sockets);
+        let changed = poll_sockets(&mut sockets, changed_sockets, |sockets| {
+            iface.poll(timestamp(), dev.deref_mut(), sockets)
+        });
+        if let Some(ether_addr) = self.ether_addr {
+            ipv6::apply_router_advert(&mut iface, ether_addr);
+        }
+        #[cfg(feature = "dhcp")]
+        dhcp::poll(&mut iface, &mut sockets);
+        changed
+    }
+
+    pub fn poll_delay(&self, sockets: &Mutex<SocketSet>) -> Option<Duration> {
+        let dev = self.dev.lock();
+        // packets left in the device when the receive queue was full
+        if dev.inner.borrow().can_recv() {
+            return Some(Duration::ZERO);
+        }
+        let delay = self.iface.lock().poll_delay(timestamp(), &sockets.lock());
+        delay.map(|delay| Duration::from_micros(delay.total_micros()))
+    }
+}
+
+impl SocketSnapshot {
+    fn of(socket: &socket::Socket) -> Self {
+        match socket {
+            socket::Socket::Tcp(tcp) => Self::Tcp {
+                state: tcp.state(),
+                recv_queue: tcp.recv_queue(),
+                send_queue: tcp.send_queue(),
+            },
+            socket::Socket::Udp(udp) => Self::Datagram {
+                can_recv: udp.can_recv(),
+                can_send: udp.can_send(),
+            },
+            socket::Socket::Icmp(icmp) => Self::Datagram {
+                can_recv: icmp.can_recv(),
+                can_send: icmp.can_send(),
+            },
+            _ => Self::Other,
+        }
     }
 }
 
+/// Calls `poll` on the sockets, and appends the sockets whose state it changed
+/// to `changed`. Returns what `poll` returns, whether the state of the sockets
+/// may have changed.
+///
+/// The comparison is done under the same lock as the poll, so that a change
+/// cannot be undone by a socket operation before it is seen.
+fn poll_sockets<F>(sockets: &mut SocketSet, changed: &mut Vec<SocketKey>, poll: F) -> bool
+where
+    F: FnOnce(&mut SocketSet) -> bool,
+{
+    let before: Vec<SocketSnapshot> = sockets
+        .iter()
+        .map(|(_, socket)| SocketSnapshot::of(socket))
+        .collect();
+    if !poll(sockets) {
+        return false;
+    }
+    // the poll does not add or remove sockets
+    for ((handle, socket), before) in sockets.iter().zip(before) {
+        if SocketSnapshot::of(socket) == before {
+            continue;
+        }
+        let key = match (before, socket) {
+            // a connection in the SYN queue of a listener, which may have
+            // become acceptable
+            (
+                SocketSnapshot::Tcp {
+                    state: socket::tcp::State::Listen | socket::tcp::State::SynReceived,
+                    ..
+                },
+                socket::Socket::Tcp(tcp),
+            ) => SocketKey::listen(tcp.listen_endpoint().port),
+            _ => SocketKey::handle(handle),
+        };
+        if !changed.contains(&key) {
+            changed.push(key);
+        }
+    }
+    true
+}
+
 impl<D: NetDriverOps> DeviceWrapper<D> {
     fn new(inner: D) -> Self {
         Self {
@@ -233,6 +421,10 @@ impl<'a, D: NetDriverOps> TxToken for AxNetTxToken<'a, D> {
         let mut dev = self.0.borrow_mut();
         let mut tx_buf = dev.new_tx_buffer(len).unwrap();
         let result = f(tx_buf.packet_mut());
+        if loopback::is_loopback_frame(tx_buf.packet()) {
+            trace!("DROP {} bytes to the loopback network", len);
+            return result;
+        }
         trace!("SEND {} bytes: {:02X?}", len, tx_buf.packet());
         dev.send(tx_buf).unwrap();
         result
@@ -240,16 +432,36 @@ impl<'a, D: NetDriverOps> TxToken for AxNetTxToken<'a, D> {
 }
 
 fn snoop_tcp_packet(buf: &[u8]) -> Result<(), smoltcp::wire::Error> {
-    use crate::SocketAddr;
-    use smoltcp::wire::{EthernetFrame, IpProtocol, Ipv4Packet, TcpPacket};
+    use smoltcp::wire::EthernetFrame;
 
     let ether_frame = EthernetFrame::new_checked(buf)?;
-    let ipv4_packet = Ipv4Packet::new_checked(ether_frame.payload())?;
+    snoop_tcp_ip_packet(ether_frame.payload())
+}
+
+fn snoop_tcp_ip_packet(buf: &[u8]) -> Result<(), smoltcp::wire::Error> {
+    use crate::SocketAddr;
+    use smoltcp::wire::{IpProtocol, Ipv4Packet, Ipv6Packet, TcpPacket};
+
+    let (src_ip, dst_ip, protocol, payload): (IpAddress, IpAddress, _, _) =
+        match buf.first().map(|b| b >> 4) {
+            Some(4) => {
+                let packet = Ipv4Packet::new_checked(buf)?;
+                let (src, dst) = (packet.src_addr().into(), packet.dst_addr().into());
+                (src, dst, packet.next_header(), packet.payload())
+            }
+            Some(6) => {
+                let packet = Ipv6Packet::new_checked(buf)?;
+                ipv6::snoop_router_advert(&packet);
+                let (src, dst) = (packet.src_addr().into(), packet.dst_addr().into());
+                (src, dst, packet.next_header(), packet.payload())
+            }
+            _ => return Ok(()),
+        };
 
-    if ipv4_packet.next_header() == IpProtocol::Tcp {
-        let tcp_packet = TcpPacket::new_checked(ipv4_packet.payload())?;
-        let src_addr = SocketAddr::new(ipv4_packet.src_addr().into(), tcp_packet.src_port());
-        let dst_addr = SocketAddr::new(ipv4_packet.dst_addr().into(), tcp_packet.dst_port());
+    if protocol == IpProtocol::Tcp {
+        let tcp_packet = TcpPacket::new_checked(payload)?;
+        let src_addr = SocketAddr::new(src_ip, tcp_packet.src_port());
+        let dst_addr = SocketAddr::new(dst_ip, tcp_packet.dst_port());
         let is_first = tcp_packet.syn() && !tcp_packet.ack();
         if is_first {
             // create a socket for the first incoming TCP packet, as the later accept() returns.
@@ -259,21 +471,116 @@ fn snoop_tcp_packet(buf: &[u8]) -> Result<(), smoltcp::wire::Error> {
     Ok(())
 }
 
+/// The current time, as a smoltcp timestamp.
+fn timestamp() -> Instant {
+    Instant::from_micros_const((current_time_nanos() / NANOS_PER_MICROS) as i64)
+}
+
+/// Returns the interface that reaches `addr`: the loopback interface for
+/// `127.0.0.0/8` and `::1`, otherwise the NIC. Fails if there is no NIC.
+fn route_iface(addr: &IpAddress) -> AxResult<&'static Mutex<Interface>> {
+    match addr {
+        IpAddress::Ipv4(ip) if ip.is_loopback() => Ok(&LOOPBACK.iface),
+        IpAddress::Ipv6(ip) if ip.is_loopback() => Ok(&LOOPBACK.iface),
+        _ => match ETH0.as_ref() {
+            Some(eth0) => Ok(&eth0.iface),
+            None => ax_err!(InvalidInput, "no NIC to reach the address"),
+        },
+    }
+}
+
+/// Allocates a free local port in the ephemeral range. TCP and UDP share the
+/// same range, and a port is free if neither a TCP listener nor a UDP socket
+/// uses it.
+fn get_ephemeral_port() -> AxResult<u16> {
+    const PORT_START: u16 = 0xc000;
+    const PORT_END: u16 = 0xffff;
+    static CURR: Mutex<u16> = Mutex::new(PORT_START);
+
+    let mut curr = CURR.lock();
+    let mut tries = 0;
+    // TODO: more robust
+    while tries <= PORT_END - PORT_START {
+        let port = *curr;
+        if *curr == PORT_END {
+            *curr = PORT_START;
+        } else {
+            *curr += 1;
+        }
+        if LISTEN_TABLE.can_listen(port) && SOCKET_SET.is_udp_port_free(port) {
+            return Ok(port);
+        }
+        tries += 1;
+    }
+    ax_err!(NoMemory, "no avaliable ports!")
+}
+
+/// The static address and gateway, used when DHCP is not used or fails.
+///
+/// `ip=<addr>/<prefix>` and `gw=<addr>` on the kernel command line override
+/// the `ip`, `ip-prefix` and `gateway` values of axconfig.
+fn static_config() -> (IpCidr, IpAddress) {
+    let default_ip = IpCidr::new(
+        axconfig::IP.parse().expect("invalid IP in axconfig"),
+        axconfig::IP_PREFIX as u8,
+    );
+    let default_gateway: IpAddress = axconfig::GATEWAY
+        .parse()
+        .expect("invalid GATEWAY in axconfig");
+
+    let ip = match axhal::cmdline::get("ip") {
+        Some(arg) if arg != "dhcp" => arg.parse().unwrap_or_else(|_| {
+            warn!("invalid ip={}, using {}", arg, default_ip);
+            default_ip
+        }),
+        _ => default_ip,
+    };
+    let gateway = match axhal::cmdline::get("gw") {
+        Some(arg) => arg.parse().unwrap_or_else(|_| {
+            warn!("invalid gw={}, using {}", arg, default_gateway);
+            default_gateway
+        }),
+        None => default_gateway,
+    };
+    (ip, gateway)
+}
+
+/// Creates the loopback interface, and the NIC interface if there is a NIC.
 pub(crate) fn init(net_devs: NetDevices) {
-    let dev = net_devs.0;
-    let ether_addr = EthernetAddress(dev.mac_address().0);
-    let eth0 = InterfaceWrapper::new("eth0", dev, Some(ether_addr));
-    eth0.setup_ip_addr(IP, IP_PREFIX);
-    eth0.setup_gateway(GATEWAY);
+    let eth0 = net_devs.0.map(|dev| {
+        let ether_addr = EthernetAddress(dev.mac_address().0);
+        InterfaceWrapper::new("eth0", dev, Some(ether_addr))
+    });
 
     ETH0.init_by(eth0);
+    LOOPBACK.init_by(LoopbackInterface::new());
     SOCKET_SET.init_by(SocketSetWrapper::new());
     LISTEN_TABLE.init_by(ListenTable::new());
 
-    info!("created net interface {:?}:", ETH0.name());
-    if let Some(ether_addr) = ETH0.ethernet_address() {
-        info!("  ether:    {}", ether_addr);
+    info!("created loopback interface, ip: 127.0.0.1/8, ::1/128");
+    // before DHCP, which may replace them
+    dns::init_servers();
+    if let Some(eth0) = ETH0.as_ref() {
+        info!("created net interface {:?}:", eth0.name());
+        if let Some(ether_addr) = eth0.ethernet_address() {
+            info!("  ether:    {}", ether_addr);
+        }
+
+        ipv6::init(eth0);
+        #[cfg(feature = "dhcp")]
+        let leased = dhcp::start();
+        #[cfg(not(feature = "dhcp"))]
+        let leased = false;
+        if !leased {
+            let (ip, gateway) = static_config();
+            eth0.setup_ip_addr(ip.address(), ip.prefix_len());
+            eth0.setup_gateway(gateway);
+            info!("  ip:       {}", ip);
+            info!("  gateway:  {}", gateway);
+        }
+    } else {
+        warn!("no NIC found, only the loopback interface is available");
     }
-    info!("  ip:       {}/{}", IP, IP_PREFIX);
-    info!("  gateway:  {}", GATEWAY);
+
+    task::start();
 }
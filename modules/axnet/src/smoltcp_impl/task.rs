@@ -0,0 +1,247 @@
+//! The network task, and the wait queue that blocked sockets sleep on.
+//!
+//! The interfaces are polled by a kernel task instead of by the sockets. The
+//! IRQ handler of the NIC masks the IRQ (the device keeps it asserted until it
+//! is acknowledged) and wakes the task, which acknowledges the interrupt,
+//! unmasks it and polls. Between interrupts the task sleeps until the earliest
+//! smoltcp timer (retransmissions, delayed ACKs, DNS retries, DHCP renewals),
+//! so an idle system never polls. Without a NIC IRQ, the task polls every
+//! [`POLL_INTERVAL`] instead. Without a NIC, only the loopback interface is
+//! polled and nothing arrives from outside, so only its timers wake the task.
+//!
+//! Each poll compares the sockets before and after, and finds the
+//! [`SocketKey`]s of the sockets whose state changed. A blocked socket
+//! operation sleeps on the wait queue of its socket until a poll changes that
+//! socket, see [`block_on`], and the changed sockets are also reported to the
+//! handler set by [`set_event_handler`]. Socket operations still poll right
+//! after queueing data, so that it is sent without waiting for the task.
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::time::Duration;
+
+use axerrno::{AxError, AxResult};
+use axsync::spin::SpinNoIrq;
+use axtask::WaitQueue;
+use smoltcp::iface::SocketHandle;
+
+use super::{ETH0, SOCKET_SET};
+
+/// How often the interfaces are polled if the NIC has no IRQ.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The network task sleeps here.
+static NET_WQ: WaitQueue = WaitQueue::new();
+/// Whether the network task should poll now.
+static NET_PENDING: AtomicBool = AtomicBool::new(false);
+/// The IRQ of the NIC, valid once the IRQ handler is registered.
+static NET_IRQ: AtomicUsize = AtomicUsize::new(0);
+
+/// The wait queues of the sockets with blocked operations. An entry is
+/// removed when the last operation blocked on it returns.
+static SOCKET_WAITERS: SpinNoIrq<BTreeMap<SocketKey, Arc<SocketWaiter>>> =
+    SpinNoIrq::new(BTreeMap::new());
+/// Called with each socket whose state changed after its waiters are woken.
+static EVENT_HANDLER: SpinNoIrq<Option<fn(SocketKey)>> = SpinNoIrq::new(None);
+
+/// Where the blocked operations on one socket sleep.
+struct SocketWaiter {
+    wq: WaitQueue,
+    /// Incremented each time the state of the socket changed.
+    events: AtomicUsize,
+}
+
+/// Identifies a socket in the events reported to the handler set by
+/// [`set_event_handler`].
+///
+/// A listening [`TcpSocket`](super::TcpSocket) is identified by its port, and
+/// the other sockets by their smoltcp handle, so the key of a TCP socket
+/// changes when it starts listening.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SocketKey(Key);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Key {
+    Handle(SocketHandle),
+    Listen(u16),
+}
+
+impl SocketKey {
+    pub(super) const fn handle(handle: SocketHandle) -> Self {
+        Self(Key::Handle(handle))
+    }
+
+    pub(super) const fn listen(port: u16) -> Self {
+        Self(Key::Listen(port))
+    }
+}
+
+/// Registers the IRQ handler of the NIC and starts the network task.
+pub(super) fn start() {
+    let irq_num = ETH0
+        .as_ref()
+        .and_then(|eth0| eth0.irq_num())
+        .filter(|&irq_num| axhal::irq::register_handler(irq_num, nic_irq_handler));
+    let periodic = match (ETH0.as_ref(), irq_num) {
+        (Some(_), Some(irq_num)) => {
+            NET_IRQ.store(irq_num, Ordering::Release);
+            info!("  irq:      {}", irq_num);
+            false
+        }
+        (Some(eth0), None) => {
+            warn!(
+                "no IRQ for {}, polling every {:?}",
+                eth0.name(),
+                POLL_INTERVAL
+            );
+            true
+        }
+        (None, _) => false,
+    };
+    axtask::spawn(move || net_task(irq_num, periodic));
+}
+
+fn nic_irq_handler() {
+    axhal::irq::set_enable(NET_IRQ.load(Ordering::Acquire), false);
+    wake_net_task();
+}
+
+/// Asks the network task to poll the interfaces and recompute when its next
+/// timer expires.
+pub(super) fn wake_net_task() {
+    NET_PENDING.store(true, Ordering::Release);
+    NET_WQ.notify_one(false);
+}
+
+/// Polls the interfaces when woken up or when a timer expires, and at least
+/// every [`POLL_INTERVAL`] if `periodic`.
+fn net_task(irq_num: Option<usize>, periodic: bool) {
+    loop {
+        let delay = if periodic {
+            Some(
+                SOCKET_SET
+                    .poll_delay()
+                    .map_or(POLL_INTERVAL, |d| d.min(POLL_INTERVAL)),
+            )
+        } else {
+            SOCKET_SET.poll_delay()
+        };
+        let pending = || NET_PENDING.load(Ordering::Acquire);
+        match delay {
+            Some(delay) if delay.is_zero() => {}
+            Some(delay) => {
+                NET_WQ.wait_timeout_until(delay, pending);
+            }
+            None => NET_WQ.wait_until(pending),
+        }
+        NET_PENDING.store(false, Ordering::Release);
+        if let (Some(irq_num), Some(eth0)) = (irq_num, ETH0.as_ref()) {
+            // acknowledge before polling, so that packets received during the
+            // poll raise a new interrupt
+            eth0.ack_interrupt();
+            axhal::irq::set_enable(irq_num, true);
+        }
+        SOCKET_SET.poll_once();
+    }
+}
+
+/// Wakes up the operations blocked on `key`, e.g. because the key of the
+/// socket changed and they must wait on the new one.
+pub(super) fn wake_socket(key: SocketKey) {
+    let waiter = SOCKET_WAITERS.lock().get(&key).cloned();
+    if let Some(waiter) = waiter {
+        waiter.events.fetch_add(1, Ordering::AcqRel);
+        waiter.wq.notify_all(false);
+    }
+}
+
+/// Wakes up the operations blocked on the `changed` sockets, and calls the
+/// handler set by [`set_event_handler`] with each of them.
+///
+/// Must not be called with a spin lock held or in an IRQ handler.
+pub(super) fn notify_socket_events(changed: &[SocketKey]) {
+    for &key in changed {
+        wake_socket(key);
+    }
+    let handler = *EVENT_HANDLER.lock();
+    if let Some(handler) = handler {
+        for &key in changed {
+            handler(key);
+        }
+    }
+}
+
+/// Sets a function to be called with each socket whose state may have
+/// changed, e.g. to wake up the tasks polling it.
+///
+/// The function is called in task context, and must not block.
+pub fn set_event_handler(handler: fn(SocketKey)) {
+    *EVENT_HANDLER.lock() = Some(handler);
+}
+
+/// Calls `f` until it returns anything other than [`AxError::WouldBlock`] (or
+/// [`AxError::Again`]), sleeping until the state of the socket `key` changes
+/// in between.
+///
+/// If `nonblock` is true, returns [`AxError::WouldBlock`] instead of sleeping.
+pub fn block_on<T, F>(key: SocketKey, nonblock: bool, f: F) -> AxResult<T>
+where
+    F: FnMut() -> AxResult<T>,
+{
+    block_on_timeout(key, nonblock, None, f)
+}
+
+/// Like [`block_on`], but gives up with [`AxError::WouldBlock`] once `timeout` has
+/// passed, as a socket with a receive or send timeout does.
+pub fn block_on_timeout<T, F>(
+    key: SocketKey,
+    nonblock: bool,
+    timeout: Option<Duration>,
+    mut f: F,
+) -> AxResult<T>
+where
+    F: FnMut() -> AxResult<T>,
+{
+    if nonblock {
+        return f();
+    }
+    let waiter = SOCKET_WAITERS
+        .lock()
+        .entry(key)
+        .or_insert_with(|| {
+            Arc::new(SocketWaiter {
+                wq: WaitQueue::new(),
+                events: AtomicUsize::new(0),
+            })
+        })
+        .clone();
+    let deadline = timeout.map(|timeout| axhal::time::current_time() + timeout);
+    let result = loop {
+        // take the counter before checking, so that events during the check
+        // are not missed
+        let events = waiter.events.load(Ordering::Acquire);
+        match f() {
+            Err(AxError::WouldBlock | AxError::Again) => {
+                let changed = || waiter.events.load(Ordering::Acquire) != events;
+                match deadline {
+                    Some(deadline) => {
+                        let now = axhal::time::current_time();
+                        if now >= deadline {
+                            break Err(AxError::WouldBlock);
+                        }
+                        waiter.wq.wait_timeout_until(deadline - now, changed);
+                    }
+                    None => waiter.wq.wait_until(changed),
+                }
+            }
+            result => break result,
+        }
+    };
+    let mut waiters = SOCKET_WAITERS.lock();
+    // the map and this operation hold the last references
+    if Arc::strong_count(&waiter) == 2 {
+        waiters.remove(&key);
+    }
+    result
+}
@@ -1,27 +1,96 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
 use axerrno::{ax_err, ax_err_type, AxError, AxResult};
+use axio::PollState;
 use axsync::Mutex;
 use smoltcp::iface::SocketHandle;
 use smoltcp::socket::tcp::{self, ConnectError, RecvError, State};
 use smoltcp::wire::IpAddress;
 
-use super::{SocketSetWrapper, ETH0, LISTEN_TABLE, SOCKET_SET};
+use super::{
+    block_on_timeout, get_ephemeral_port, route_iface, SocketKey, SocketSetWrapper, LISTEN_TABLE,
+    SOCKET_SET, TCP_RX_BUF_LEN, TCP_TX_BUF_LEN,
+};
 use crate::SocketAddr;
 
+/// The smallest buffer size that can be set.
+const TCP_MIN_BUF_LEN: usize = 1024;
+/// The largest buffer size that can be set.
+const TCP_MAX_BUF_LEN: usize = 1024 * 1024;
+
+/// The options of a [`TcpSocket`] that are kept by smoltcp. The connections
+/// accepted by a listener inherit its options.
+#[derive(Clone, Copy)]
+pub(super) struct TcpConfig {
+    recv_buffer_size: usize,
+    send_buffer_size: usize,
+    nodelay: bool,
+    keep_alive: Option<Duration>,
+}
+
+impl TcpConfig {
+    pub const fn new() -> Self {
+        Self {
+            recv_buffer_size: TCP_RX_BUF_LEN,
+            send_buffer_size: TCP_TX_BUF_LEN,
+            nodelay: false,
+            keep_alive: None,
+        }
+    }
+
+    /// Creates a smoltcp socket with the buffer sizes of the config.
+    pub fn new_socket(&self) -> tcp::Socket<'static> {
+        SocketSetWrapper::new_tcp_socket(self.recv_buffer_size, self.send_buffer_size)
+    }
+
+    /// Applies the options other than the buffer sizes to `socket`.
+    ///
+    /// smoltcp resets them in `connect` and `listen`, so this must be called
+    /// again after those.
+    pub fn apply(&self, socket: &mut tcp::Socket) {
+        socket.set_nagle_enabled(!self.nodelay);
+        socket.set_keep_alive(
+            self.keep_alive
+                .map(|interval| smoltcp::time::Duration::from_micros(interval.as_micros() as u64)),
+        );
+    }
+}
+
 pub struct TcpSocket {
     handle: Option<SocketHandle>, // `None` if is listening
     local_addr: Option<SocketAddr>,
     peer_addr: Option<SocketAddr>,
+    nonblock: AtomicBool,
+    reuse_addr: AtomicBool,
+    recv_timeout: Mutex<Option<Duration>>,
+    send_timeout: Mutex<Option<Duration>>,
+    config: Mutex<TcpConfig>,
 }
 
 impl TcpSocket {
     #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
-        let socket = SocketSetWrapper::new_tcp_socket();
-        let handle = Some(SOCKET_SET.add(socket));
+        let config = TcpConfig::new();
+        let handle = Some(SOCKET_SET.add(config.new_socket()));
+        Self::with_handle(handle, None, None, config)
+    }
+
+    fn with_handle(
+        handle: Option<SocketHandle>,
+        local_addr: Option<SocketAddr>,
+        peer_addr: Option<SocketAddr>,
+        config: TcpConfig,
+    ) -> Self {
         Self {
             handle,
-            local_addr: None,
-            peer_addr: None,
+            local_addr,
+            peer_addr,
+            nonblock: AtomicBool::new(false),
+            reuse_addr: AtomicBool::new(false),
+            recv_timeout: Mutex::new(None),
+            send_timeout: Mutex::new(None),
+            config: Mutex::new(config),
         }
     }
 
@@ -33,49 +102,212 @@ impl TcpSocket {
         self.local_addr.ok_or(AxError::NotConnected)
     }
 
+    /// Identifies the socket in the socket events, see [`SocketKey`].
+    pub fn key(&self) -> SocketKey {
+        match self.handle {
+            Some(handle) => SocketKey::handle(handle),
+            None => SocketKey::listen(self.local_addr.unwrap().port),
+        }
+    }
+
     pub fn peer_addr(&self) -> AxResult<SocketAddr> {
         self.peer_addr.ok_or(AxError::NotConnected)
     }
 
+    pub fn is_nonblocking(&self) -> bool {
+        self.nonblock.load(Ordering::Acquire)
+    }
+
+    /// Moves the socket into or out of nonblocking mode.
+    ///
+    /// In nonblocking mode, `connect`, `accept`, `send` and `recv` return
+    /// [`AxError::WouldBlock`] instead of waiting. A nonblocking `connect` starts
+    /// the handshake, and the socket becomes writable once it is connected.
+    pub fn set_nonblocking(&self, nonblocking: bool) {
+        self.nonblock.store(nonblocking, Ordering::Release);
+    }
+
+    pub fn reuse_address(&self) -> bool {
+        self.reuse_addr.load(Ordering::Acquire)
+    }
+
+    /// Sets `SO_REUSEADDR`.
+    ///
+    /// Closed connections are removed at once instead of lingering in
+    /// TIME-WAIT, so a local address can always be reused; the option is
+    /// only recorded.
+    pub fn set_reuse_address(&self, reuse: bool) {
+        self.reuse_addr.store(reuse, Ordering::Release);
+    }
+
+    pub fn recv_timeout(&self) -> Option<Duration> {
+        *self.recv_timeout.lock()
+    }
+
+    /// Sets how long `recv` and `accept` wait before returning
+    /// [`AxError::WouldBlock`], or [`None`] to wait forever.
+    pub fn set_recv_timeout(&self, timeout: Option<Duration>) {
+        *self.recv_timeout.lock() = timeout;
+    }
+
+    pub fn send_timeout(&self) -> Option<Duration> {
+        *self.send_timeout.lock()
+    }
+
+    /// Sets how long `send` and `connect` wait before returning
+    /// [`AxError::WouldBlock`], or [`None`] to wait forever.
+    pub fn set_send_timeout(&self, timeout: Option<Duration>) {
+        *self.send_timeout.lock() = timeout;
+    }
+
+    pub fn nodelay(&self) -> bool {
+        self.config.lock().nodelay
+    }
+
+    /// Sets `TCP_NODELAY`, which disables the Nagle algorithm so that small
+    /// segments are sent at once.
+    pub fn set_nodelay(&self, nodelay: bool) -> AxResult {
+        self.update_config(|config| config.nodelay = nodelay)
+    }
+
+    pub fn keep_alive(&self) -> Option<Duration> {
+        self.config.lock().keep_alive
+    }
+
+    /// Sends a keep-alive segment after the connection has been idle for
+    /// `interval`, or never if [`None`].
+    pub fn set_keep_alive(&self, interval: Option<Duration>) -> AxResult {
+        self.update_config(|config| config.keep_alive = interval)
+    }
+
+    pub fn recv_buffer_size(&self) -> usize {
+        self.config.lock().recv_buffer_size
+    }
+
+    /// Sets the size of the receive buffer, which also bounds the advertised
+    /// window. The size is clamped to a supported range.
+    ///
+    /// The buffers can only be resized before connecting, or on a listener,
+    /// where the new size applies to the connections accepted afterwards.
+    pub fn set_recv_buffer_size(&self, size: usize) -> AxResult {
+        let size = size.clamp(TCP_MIN_BUF_LEN, TCP_MAX_BUF_LEN);
+        self.update_config(|config| config.recv_buffer_size = size)
+    }
+
+    pub fn send_buffer_size(&self) -> usize {
+        self.config.lock().send_buffer_size
+    }
+
+    /// Sets the size of the send buffer, see [`set_recv_buffer_size`] for
+    /// when it can be changed.
+    ///
+    /// [`set_recv_buffer_size`]: Self::set_recv_buffer_size
+    pub fn set_send_buffer_size(&self, size: usize) -> AxResult {
+        let size = size.clamp(TCP_MIN_BUF_LEN, TCP_MAX_BUF_LEN);
+        self.update_config(|config| config.send_buffer_size = size)
+    }
+
+    /// Applies a change of the options to the smoltcp socket, or to the
+    /// connections accepted later if listening.
+    fn update_config<F: FnOnce(&mut TcpConfig)>(&self, f: F) -> AxResult {
+        let mut config = self.config.lock();
+        let mut new_config = *config;
+        f(&mut new_config);
+        let resize = new_config.recv_buffer_size != config.recv_buffer_size
+            || new_config.send_buffer_size != config.send_buffer_size;
+        match self.handle {
+            Some(handle) => {
+                SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                    if resize {
+                        if socket.state() != State::Closed {
+                            return ax_err!(InvalidInput, "socket buffers can not be resized now");
+                        }
+                        *socket = new_config.new_socket();
+                    }
+                    new_config.apply(socket);
+                    Ok(())
+                })?;
+            }
+            None => LISTEN_TABLE.set_config(self.local_addr.unwrap().port, new_config),
+        }
+        *config = new_config;
+        Ok(())
+    }
+
+    /// Connects to `addr`, waiting for the handshake unless nonblocking.
     pub fn connect(&mut self, addr: SocketAddr) -> AxResult {
+        self.start_connect(addr)?;
+        // a nonblocking or timed out connect keeps connecting in the background
+        block_on_timeout(
+            self.key(),
+            self.is_nonblocking(),
+            self.send_timeout(),
+            || self.poll_connect(),
+        )
+    }
+
+    /// Starts connecting to `addr` without waiting for the handshake.
+    ///
+    /// Callers that share the socket behind a lock can wait with
+    /// [`poll_connect`](Self::poll_connect) without holding the lock.
+    pub fn start_connect(&mut self, addr: SocketAddr) -> AxResult {
         let handle = if self.is_listening() {
             return ax_err!(AlreadyExists, "socket connect() failed: already connected");
         } else {
             self.handle.unwrap()
         };
 
-        // TODO: check host unreachable
-        let local_port = get_ephemeral_port()?;
-        let iface = &ETH0.iface;
-        let (local_addr, peer_addr) =
-            SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
-                socket
-                    .connect(iface.lock().context(), addr, local_port)
-                    .or_else(|e| match e {
-                        ConnectError::InvalidState => {
-                            ax_err!(AlreadyExists, "socket connect() failed")
-                        }
-                        ConnectError::Unaddressable => {
-                            ax_err!(InvalidInput, "socket connect() failed")
-                        }
-                    })?;
-                Ok((socket.local_endpoint(), socket.remote_endpoint()))
-            })?;
+        let state = SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| socket.state());
+        if state == State::Closed {
+            // TODO: check host unreachable
+            let local_port = get_ephemeral_port()?;
+            let iface = route_iface(&addr.addr)?;
+            let config = *self.config.lock();
+            let (local_addr, peer_addr) =
+                SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                    socket
+                        .connect(iface.lock().context(), addr, local_port)
+                        .or_else(|e| match e {
+                            ConnectError::InvalidState => {
+                                ax_err!(AlreadyExists, "socket connect() failed")
+                            }
+                            ConnectError::Unaddressable => {
+                                ax_err!(InvalidInput, "socket connect() failed")
+                            }
+                        })?;
+                    config.apply(socket);
+                    Ok((socket.local_endpoint(), socket.remote_endpoint()))
+                })?;
+            self.local_addr = local_addr;
+            self.peer_addr = peer_addr;
 
-        loop {
+            // send the SYN
             SOCKET_SET.poll_interfaces();
-            let (state, may_recv) = SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| {
-                (socket.state(), socket.may_recv())
-            });
-            if may_recv || state == State::Established {
-                self.local_addr = local_addr;
-                self.peer_addr = peer_addr;
-                return Ok(());
-            } else if state == State::SynSent {
-                axtask::yield_now();
-            } else {
-                return ax_err!(ConnectionRefused, "socket connect() failed");
-            }
+        } else if state != State::SynSent {
+            return ax_err!(AlreadyExists, "socket connect() failed: already connected");
+        }
+        Ok(())
+    }
+
+    /// Checks a connect started by [`start_connect`](Self::start_connect).
+    ///
+    /// Returns [`AxError::WouldBlock`] while the handshake is in progress, and an
+    /// error if the connection is refused, after which the socket can connect
+    /// again.
+    pub fn poll_connect(&mut self) -> AxResult {
+        let handle = self
+            .handle
+            .ok_or_else(|| ax_err_type!(InvalidInput, "socket connect() failed"))?;
+        let (state, may_recv) = SOCKET_SET
+            .with_socket::<tcp::Socket, _, _>(handle, |socket| (socket.state(), socket.may_recv()));
+        if may_recv || state == State::Established {
+            Ok(())
+        } else if state == State::SynSent {
+            Err(AxError::WouldBlock)
+        } else {
+            self.local_addr = None;
+            self.peer_addr = None;
+            ax_err!(ConnectionRefused, "socket connect() failed")
         }
     }
 
@@ -98,19 +330,21 @@ impl TcpSocket {
             return Ok(()); // already listening
         }
 
-        let local_port = if let Some(local_addr) = self.local_addr {
-            local_addr.port
+        let local_addr = if let Some(local_addr) = self.local_addr {
+            local_addr
         } else {
             let addr = IpAddress::v4(0, 0, 0, 0);
             let port = get_ephemeral_port()?;
             self.local_addr = Some(SocketAddr::new(addr, port));
-            port
+            self.local_addr.unwrap()
         };
 
-        LISTEN_TABLE.listen(local_port)?;
+        LISTEN_TABLE.listen(local_addr, *self.config.lock())?;
         debug!("socket listening on {}", self.local_addr.unwrap());
         let handle = self.handle.take().unwrap(); // should not connect/send/recv any more
         SOCKET_SET.remove(handle);
+        // the operations waiting on the old key must wait on the new one
+        super::task::wake_socket(SocketKey::handle(handle));
         Ok(())
     }
 
@@ -124,20 +358,53 @@ impl TcpSocket {
             .ok_or_else(|| ax_err_type!(InvalidInput, "socket accept() failed: no address bound"))?
             .port;
 
-        loop {
-            SOCKET_SET.poll_interfaces();
-            match LISTEN_TABLE.accept(local_port) {
-                Ok((handle, peer_addr)) => {
-                    debug!("socket accepted a new connection {}", peer_addr.unwrap());
-                    return Ok(TcpSocket {
-                        handle: Some(handle),
-                        local_addr: self.local_addr,
-                        peer_addr,
-                    });
-                }
-                Err(AxError::Again) => axtask::yield_now(),
-                Err(e) => return Err(e),
-            }
+        let (handle, peer_addr) = block_on_timeout(
+            self.key(),
+            self.is_nonblocking(),
+            self.recv_timeout(),
+            || LISTEN_TABLE.accept(local_port),
+        )?;
+        debug!("socket accepted a new connection {}", peer_addr.unwrap());
+        // the listener may be bound to an unspecified address
+        let local_addr =
+            SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| socket.local_endpoint());
+        Ok(TcpSocket::with_handle(
+            Some(handle),
+            local_addr,
+            peer_addr,
+            *self.config.lock(),
+        ))
+    }
+
+    /// Whether the socket is readable or writable.
+    ///
+    /// A stream socket is readable if it has received data or the peer has
+    /// closed the connection (so that `recv` returns immediately), and is
+    /// writable if the transmit buffer has free space. A listening socket is
+    /// readable if there is an established connection waiting to be accepted.
+    pub fn poll(&self) -> AxResult<PollState> {
+        if let Some(handle) = self.handle {
+            Ok(
+                SOCKET_SET.with_socket::<tcp::Socket, _, _>(handle, |socket| {
+                    // a nonblocking connect is still in progress
+                    let connecting = socket.state() == State::SynSent;
+                    PollState {
+                        readable: !connecting && (!socket.may_recv() || socket.can_recv()),
+                        writable: !connecting && (!socket.may_send() || socket.can_send()),
+                    }
+                }),
+            )
+        } else {
+            let local_port = self
+                .local_addr
+                .ok_or_else(|| {
+                    ax_err_type!(InvalidInput, "socket poll() failed: no address bound")
+                })?
+                .port;
+            Ok(PollState {
+                readable: LISTEN_TABLE.can_accept(local_port)?,
+                writable: false,
+            })
         }
     }
 
@@ -162,68 +429,68 @@ impl TcpSocket {
         let handle = self
             .handle
             .ok_or_else(|| ax_err_type!(NotConnected, "socket recv() failed"))?;
-        loop {
-            SOCKET_SET.poll_interfaces();
-            match SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
-                if !socket.is_open() {
-                    // not connected
-                    ax_err!(NotConnected, "socket recv() failed")
-                } else if !socket.may_recv() {
-                    // connection closed
-                    Ok(0)
-                } else if socket.can_recv() {
-                    // data available
-                    // TODO: use socket.recv(|buf| {...})
-                    match socket.recv_slice(buf) {
-                        Ok(len) => Ok(len),
-                        Err(RecvError::Finished) => Ok(0),
-                        Err(_) => ax_err!(ConnectionRefused, "socket recv() failed"),
+        let len = block_on_timeout(
+            self.key(),
+            self.is_nonblocking(),
+            self.recv_timeout(),
+            || {
+                SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                    if !socket.is_open() {
+                        // not connected
+                        ax_err!(NotConnected, "socket recv() failed")
+                    } else if !socket.may_recv() {
+                        // connection closed
+                        Ok(0)
+                    } else if socket.can_recv() {
+                        // data available
+                        // TODO: use socket.recv(|buf| {...})
+                        match socket.recv_slice(buf) {
+                            Ok(len) => Ok(len),
+                            Err(RecvError::Finished) => Ok(0),
+                            Err(_) => ax_err!(ConnectionRefused, "socket recv() failed"),
+                        }
+                    } else {
+                        // no more data
+                        Err(AxError::WouldBlock)
                     }
-                } else {
-                    // no more data
-                    Err(AxError::Again)
-                }
-            }) {
-                Ok(n) => {
-                    SOCKET_SET.poll_interfaces();
-                    return Ok(n);
-                }
-                Err(AxError::Again) => axtask::yield_now(),
-                Err(e) => return Err(e),
-            }
-        }
+                })
+            },
+        )?;
+        // acknowledge the received data, or send the queued data
+        SOCKET_SET.poll_interfaces();
+        Ok(len)
     }
 
     pub fn send(&self, buf: &[u8]) -> AxResult<usize> {
         let handle = self
             .handle
             .ok_or_else(|| ax_err_type!(NotConnected, "socket send() failed"))?;
-        loop {
-            SOCKET_SET.poll_interfaces();
-            match SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
-                if !socket.is_open() || !socket.may_send() {
-                    // not connected
-                    ax_err!(NotConnected, "socket send() failed")
-                } else if socket.can_send() {
-                    // connected, and the tx buffer is not full
-                    // TODO: use socket.send(|buf| {...})
-                    let len = socket
-                        .send_slice(buf)
-                        .map_err(|_| ax_err_type!(ConnectionRefused, "socket send() failed"))?;
-                    Ok(len)
-                } else {
-                    // tx buffer is full
-                    Err(AxError::Again)
-                }
-            }) {
-                Ok(n) => {
-                    SOCKET_SET.poll_interfaces();
-                    return Ok(n);
-                }
-                Err(AxError::Again) => axtask::yield_now(),
-                Err(e) => return Err(e),
-            }
-        }
+        let len = block_on_timeout(
+            self.key(),
+            self.is_nonblocking(),
+            self.send_timeout(),
+            || {
+                SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                    if !socket.is_open() || !socket.may_send() {
+                        // not connected
+                        ax_err!(NotConnected, "socket send() failed")
+                    } else if socket.can_send() {
+                        // connected, and the tx buffer is not full
+                        // TODO: use socket.send(|buf| {...})
+                        let len = socket
+                            .send_slice(buf)
+                            .map_err(|_| ax_err_type!(ConnectionRefused, "socket send() failed"))?;
+                        Ok(len)
+                    } else {
+                        // tx buffer is full
+                        Err(AxError::WouldBlock)
+                    }
+                })
+            },
+        )?;
+        // acknowledge the received data, or send the queued data
+        SOCKET_SET.poll_interfaces();
+        Ok(len)
     }
 }
 
@@ -235,26 +502,3 @@ impl Drop for TcpSocket {
         }
     }
 }
-
-fn get_ephemeral_port() -> AxResult<u16> {
-    const PORT_START: u16 = 0xc000;
-    const PORT_END: u16 = 0xffff;
-    static CURR: Mutex<u16> = Mutex::new(PORT_START);
-
-    let mut curr = CURR.lock();
-    let mut tries = 0;
-    // TODO: more robust
-    while tries <= PORT_END - PORT_START {
-        let port = *curr;
-        if *curr == PORT_END {
-            *curr = PORT_START;
-        } else {
-            *curr += 1;
-        }
-        if LISTEN_TABLE.can_listen(port) {
-            return Ok(port);
-        }
-        tries += 1;
-    }
-    ax_err!(NoMemory, "no avaliable ports!")
-}
@@ -0,0 +1,211 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axerrno::{ax_err, ax_err_type, AxError, AxResult};
+use axio::PollState;
+use axsync::Mutex;
+use smoltcp::iface::SocketHandle;
+use smoltcp::socket::udp::{self, BindError, SendError};
+use smoltcp::wire::IpListenEndpoint;
+
+use super::{get_ephemeral_port, SocketKey, SocketSetWrapper, SOCKET_SET};
+use crate::SocketAddr;
+
+pub struct UdpSocket {
+    handle: SocketHandle,
+    local_addr: Mutex<Option<SocketAddr>>,
+    peer_addr: Mutex<Option<SocketAddr>>,
+    nonblock: AtomicBool,
+}
+
+impl UdpSocket {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        let socket = SocketSetWrapper::new_udp_socket();
+        let handle = SOCKET_SET.add(socket);
+        Self {
+            handle,
+            local_addr: Mutex::new(None),
+            peer_addr: Mutex::new(None),
+            nonblock: AtomicBool::new(false),
+        }
+    }
+
+    /// Identifies the socket in the socket events, see [`SocketKey`].
+    pub fn key(&self) -> SocketKey {
+        SocketKey::handle(self.handle)
+    }
+
+    pub fn local_addr(&self) -> AxResult<SocketAddr> {
+        self.local_addr.lock().ok_or(AxError::NotConnected)
+    }
+
+    pub fn peer_addr(&self) -> AxResult<SocketAddr> {
+        self.peer_addr.lock().ok_or(AxError::NotConnected)
+    }
+
+    pub fn is_nonblocking(&self) -> bool {
+        self.nonblock.load(Ordering::Acquire)
+    }
+
+    /// Moves the socket into or out of nonblocking mode.
+    ///
+    /// In nonblocking mode, `send_to` and `recv_from` return
+    /// [`AxError::WouldBlock`] instead of waiting.
+    pub fn set_nonblocking(&self, nonblocking: bool) {
+        self.nonblock.store(nonblocking, Ordering::Release);
+    }
+
+    /// Binds the socket to `addr`. If the port is 0, an ephemeral port is
+    /// allocated, and an unspecified IP address accepts datagrams sent to any
+    /// local address.
+    pub fn bind(&self, addr: SocketAddr) -> AxResult {
+        let mut local_addr = self.local_addr.lock();
+        if local_addr.is_some() {
+            return ax_err!(InvalidInput, "socket bind() failed: already bound");
+        }
+
+        let mut addr = addr;
+        if addr.port == 0 {
+            addr.port = get_ephemeral_port()?;
+        } else if !SOCKET_SET.is_udp_port_free(addr.port) {
+            return ax_err!(AlreadyExists, "socket bind() failed: address in use");
+        }
+        let endpoint = IpListenEndpoint {
+            addr: (!addr.addr.is_unspecified()).then_some(addr.addr),
+            port: addr.port,
+        };
+        SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
+            socket.bind(endpoint).or_else(|e| match e {
+                BindError::InvalidState => ax_err!(AlreadyExists, "socket bind() failed"),
+                BindError::Unaddressable => ax_err!(InvalidInput, "socket bind() failed"),
+            })
+        })?;
+        *local_addr = Some(addr);
+        debug!("UDP socket {}: bound on {}", self.handle, addr);
+        Ok(())
+    }
+
+    /// Sets the default destination of `send` and the only source accepted
+    /// by `recv`. The socket is bound to an ephemeral port if not bound yet.
+    pub fn connect(&self, addr: SocketAddr) -> AxResult {
+        self.bind_if_unbound()?;
+        *self.peer_addr.lock() = Some(addr);
+        debug!("UDP socket {}: connected to {}", self.handle, addr);
+        Ok(())
+    }
+
+    /// Sends a datagram to `addr`, binding the socket to an ephemeral port
+    /// first if needed.
+    pub fn send_to(&self, buf: &[u8], addr: SocketAddr) -> AxResult<usize> {
+        if addr.port == 0 || addr.addr.is_unspecified() {
+            return ax_err!(InvalidInput, "socket send_to() failed: invalid address");
+        }
+        self.bind_if_unbound()?;
+        self.block_on(|| {
+            SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
+                if socket.can_send() {
+                    socket.send_slice(buf, addr).map_err(|e| match e {
+                        SendError::BufferFull => AxError::WouldBlock,
+                        SendError::Unaddressable => {
+                            ax_err_type!(ConnectionRefused, "socket send_to() failed")
+                        }
+                    })?;
+                    Ok(buf.len())
+                } else {
+                    // tx buffer is full
+                    Err(AxError::WouldBlock)
+                }
+            })
+        })
+    }
+
+    /// Receives a datagram, returning its length and the source address.
+    ///
+    /// If the datagram is larger than `buf`, the excess bytes are discarded.
+    pub fn recv_from(&self, buf: &mut [u8]) -> AxResult<(usize, SocketAddr)> {
+        self.recv_impl(buf, None)
+    }
+
+    /// Sends a datagram to the connected peer.
+    pub fn send(&self, buf: &[u8]) -> AxResult<usize> {
+        let peer_addr = self.peer_addr()?;
+        self.send_to(buf, peer_addr)
+    }
+
+    /// Receives a datagram from the connected peer, dropping datagrams from
+    /// other sources.
+    pub fn recv(&self, buf: &mut [u8]) -> AxResult<usize> {
+        let peer_addr = self.peer_addr()?;
+        self.recv_impl(buf, Some(peer_addr)).map(|(len, _)| len)
+    }
+
+    /// Whether the socket is readable or writable.
+    ///
+    /// An unbound socket can not receive anything, but sending on it binds
+    /// it first, so it is considered writable.
+    pub fn poll(&self) -> AxResult<PollState> {
+        if self.local_addr.lock().is_none() {
+            return Ok(PollState {
+                readable: false,
+                writable: true,
+            });
+        }
+        Ok(
+            SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| PollState {
+                readable: socket.can_recv(),
+                writable: socket.can_send(),
+            }),
+        )
+    }
+
+    pub fn shutdown(&self) -> AxResult {
+        SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
+            debug!("UDP socket {}: shutting down", self.handle);
+            socket.close();
+        });
+        SOCKET_SET.poll_interfaces();
+        Ok(())
+    }
+
+    fn bind_if_unbound(&self) -> AxResult {
+        if self.local_addr.lock().is_none() {
+            self.bind(SocketAddr::new(crate::IpAddr::v4(0, 0, 0, 0), 0))?;
+        }
+        Ok(())
+    }
+
+    fn recv_impl(&self, buf: &mut [u8], from: Option<SocketAddr>) -> AxResult<(usize, SocketAddr)> {
+        if self.local_addr.lock().is_none() {
+            return ax_err!(NotConnected, "socket recv() failed: not bound");
+        }
+        self.block_on(|| {
+            SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| loop {
+                match socket.recv_slice(buf) {
+                    Ok((_, addr)) if from.map_or(false, |from| from != addr) => continue,
+                    Ok((len, addr)) => return Ok((len, addr)),
+                    // no more data
+                    Err(_) => return Err(AxError::WouldBlock),
+                }
+            })
+        })
+    }
+
+    /// Calls `f` until it returns anything other than [`AxError::WouldBlock`], or
+    /// returns immediately in nonblocking mode, then polls the interfaces to
+    /// send what `f` queued.
+    fn block_on<F, T>(&self, f: F) -> AxResult<T>
+    where
+        F: FnMut() -> AxResult<T>,
+    {
+        let result = super::block_on(self.key(), self.is_nonblocking(), f)?;
+        SOCKET_SET.poll_interfaces();
+        Ok(result)
+    }
+}
+
+impl Drop for UdpSocket {
+    fn drop(&mut self) {
+        self.shutdown().ok();
+        SOCKET_SET.remove(self.handle);
+    }
+}
@@ -0,0 +1,165 @@
+//! 进程的身份凭证
+//!
+//! 每个进程有实际、有效和保存的三组用户ID与组ID, 以及附加组列表。文件权限检查使用有效ID,
+//! 保存的ID让执行过setuid程序的进程可以暂时放弃特权, 之后再恢复。
+//!
+//! 有效用户ID为0的进程是特权进程, 可以任意修改自己的身份; 普通进程只能在自己已有的
+//! 实际、有效和保存ID之间切换。
+use alloc::vec::Vec;
+
+/// 附加组的最大个数, 与Linux的`NGROUPS_MAX`一致
+pub const NGROUPS_MAX: usize = 65536;
+
+/// 进程的身份凭证
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    /// 实际用户ID
+    pub uid: u32,
+    /// 有效用户ID
+    pub euid: u32,
+    /// 保存的用户ID
+    pub suid: u32,
+    /// 实际组ID
+    pub gid: u32,
+    /// 有效组ID
+    pub egid: u32,
+    /// 保存的组ID
+    pub sgid: u32,
+    /// 附加组ID
+    pub groups: Vec<u32>,
+}
+
+impl Credentials {
+    /// root用户的凭证, 内核和初始进程使用
+    pub const fn root() -> Self {
+        Self {
+            uid: 0,
+            euid: 0,
+            suid: 0,
+            gid: 0,
+            egid: 0,
+            sgid: 0,
+            groups: Vec::new(),
+        }
+    }
+
+    /// 是否是特权进程
+    pub const fn is_privileged(&self) -> bool {
+        self.euid == 0
+    }
+
+    /// 对应`setuid`: 特权进程同时设置三个用户ID, 普通进程只能把有效用户ID设为实际或保存的用户ID
+    ///
+    /// 返回是否有权限修改。
+    pub fn set_uid(&mut self, uid: u32) -> bool {
+        if self.is_privileged() {
+            (self.uid, self.euid, self.suid) = (uid, uid, uid);
+        } else if uid == self.uid || uid == self.suid {
+            self.euid = uid;
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// 对应`setgid`, 规则与[`Credentials::set_uid`]相同
+    pub fn set_gid(&mut self, gid: u32) -> bool {
+        if self.is_privileged() {
+            (self.gid, self.egid, self.sgid) = (gid, gid, gid);
+        } else if gid == self.gid || gid == self.sgid {
+            self.egid = gid;
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// 对应`setresuid`, `None`表示不修改。普通进程设置的每个ID都必须是自己已有的用户ID之一
+    pub fn set_resuid(&mut self, uid: Option<u32>, euid: Option<u32>, suid: Option<u32>) -> bool {
+        let old = [self.uid, self.euid, self.suid];
+        let allowed = |id: Option<u32>| id.map_or(true, |id| old.contains(&id));
+        if !self.is_privileged() && !(allowed(uid) && allowed(euid) && allowed(suid)) {
+            return false;
+        }
+        self.uid = uid.unwrap_or(self.uid);
+        self.euid = euid.unwrap_or(self.euid);
+        self.suid = suid.unwrap_or(self.suid);
+        true
+    }
+
+    /// 对应`setresgid`, 规则与[`Credentials::set_resuid`]相同
+    pub fn set_resgid(&mut self, gid: Option<u32>, egid: Option<u32>, sgid: Option<u32>) -> bool {
+        let old = [self.gid, self.egid, self.sgid];
+        let allowed = |id: Option<u32>| id.map_or(true, |id| old.contains(&id));
+        if !self.is_privileged() && !(allowed(gid) && allowed(egid) && allowed(sgid)) {
+            return false;
+        }
+        self.gid = gid.unwrap_or(self.gid);
+        self.egid = egid.unwrap_or(self.egid);
+        self.sgid = sgid.unwrap_or(self.sgid);
+        true
+    }
+
+    /// 对应`setreuid`: 修改了实际用户ID, 或有效用户ID被设为与原实际用户ID不同的值时,
+    /// 保存的用户ID也设为新的有效用户ID
+    pub fn set_reuid(&mut self, uid: Option<u32>, euid: Option<u32>) -> bool {
+        let old_uid = self.uid;
+        let ok = if self.is_privileged() {
+            true
+        } else {
+            uid.map_or(true, |id| id == self.uid || id == self.euid)
+                && euid.map_or(true, |id| [self.uid, self.euid, self.suid].contains(&id))
+        };
+        if !ok {
+            return false;
+        }
+        self.uid = uid.unwrap_or(self.uid);
+        self.euid = euid.unwrap_or(self.euid);
+        if uid.is_some() || euid.map_or(false, |id| id != old_uid) {
+            self.suid = self.euid;
+        }
+        true
+    }
+
+    /// 对应`setregid`, 规则与[`Credentials::set_reuid`]相同
+    pub fn set_regid(&mut self, gid: Option<u32>, egid: Option<u32>) -> bool {
+        let old_gid = self.gid;
+        let ok = if self.is_privileged() {
+            true
+        } else {
+            gid.map_or(true, |id| id == self.gid || id == self.egid)
+                && egid.map_or(true, |id| [self.gid, self.egid, self.sgid].contains(&id))
+        };
+        if !ok {
+            return false;
+        }
+        self.gid = gid.unwrap_or(self.gid);
+        self.egid = egid.unwrap_or(self.egid);
+        if gid.is_some() || egid.map_or(false, |id| id != old_gid) {
+            self.sgid = self.egid;
+        }
+        true
+    }
+
+    /// 对应`setgroups`, 只有特权进程可以修改附加组
+    pub fn set_groups(&mut self, groups: Vec<u32>) -> bool {
+        if !self.is_privileged() || groups.len() > NGROUPS_MAX {
+            return false;
+        }
+        self.groups = groups;
+        true
+    }
+
+    /// 执行新程序时调用。程序文件设置了setuid/setgid位时, `set_uid`/`set_gid`为文件的所有者/所属组,
+    /// 有效ID变为它们; 之后保存的ID总是等于有效ID
+    pub fn exec(&mut self, set_uid: Option<u32>, set_gid: Option<u32>) {
+        if let Some(uid) = set_uid {
+            self.euid = uid;
+        }
+        if let Some(gid) = set_gid {
+            self.egid = gid;
+        }
+        self.suid = self.euid;
+        self.sgid = self.egid;
+    }
+}
@@ -12,6 +12,7 @@ use process::KERNEL_PROCESS_ID;
 
 extern crate alloc;
 
+pub mod cred;
 pub mod flags;
 pub mod process;
 pub mod fd_manager;
@@ -1,6 +1,11 @@
 use alloc::string::ToString;
 use alloc::vec;
-use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    string::String,
+    sync::Arc,
+    vec::Vec,
+};
 use axfs_os::read_file;
 use axfs_os::{file_io::FileIO, Stderr, Stdin, Stdout};
 use axhal::arch::{write_page_table_root, TrapFrame};
@@ -11,6 +16,7 @@ use axmem::memory_set::USER_STACK_SIZE;
 
 const KERNEL_STACK_SIZE: usize = 4096;
 
+use crate::cred::Credentials;
 use crate::flags::{CloneFlags, WaitStatus};
 use crate::test::finish_one_test;
 use axmem::memory_set::MemorySet;
@@ -50,10 +56,18 @@ pub struct ProcessInner {
     pub is_zombie: bool,
     /// 退出状态码
     pub exit_code: i32,
+    /// 终止进程的信号, 进程正常退出时为None
+    pub exit_signal: Option<i32>,
     /// 文件描述符表
     pub fd_table: Vec<Option<Arc<dyn FileIO>>>,
+    /// 设置了FD_CLOEXEC的文件描述符, exec时关闭
+    pub cloexec: BTreeSet<usize>,
     /// 进程工作目录
     pub cwd: String,
+    /// 进程的身份凭证
+    pub cred: Credentials,
+    /// 创建文件和目录时屏蔽的权限位
+    pub umask: u32,
 }
 
 impl ProcessInner {
@@ -72,21 +86,46 @@ impl ProcessInner {
             heap_top: heap_bottom,
             is_zombie: false,
             exit_code: 0,
+            exit_signal: None,
             fd_table,
+            cloexec: BTreeSet::new(),
             cwd: "/".to_string(), // 这里的工作目录是根目录
+            cred: Credentials::root(),
+            umask: 0o022,
         }
     }
     pub fn get_page_table_token(&self) -> usize {
         self.memory_set.lock().page_table_token()
     }
     pub fn alloc_fd(&mut self) -> usize {
-        for (i, fd) in self.fd_table.iter().enumerate() {
-            if fd.is_none() {
-                return i;
-            }
+        self.alloc_fd_from(0)
+    }
+    /// 分配不小于`min`的最小的空闲文件描述符, 新的文件描述符没有设置FD_CLOEXEC
+    pub fn alloc_fd_from(&mut self, min: usize) -> usize {
+        let fd = (min..self.fd_table.len())
+            .find(|&fd| self.fd_table[fd].is_none())
+            .unwrap_or_else(|| self.fd_table.len().max(min));
+        if fd >= self.fd_table.len() {
+            self.fd_table.resize(fd + 1, None);
         }
-        self.fd_table.push(None);
-        self.fd_table.len() - 1
+        self.cloexec.remove(&fd);
+        fd
+    }
+    /// 设置或清除文件描述符的FD_CLOEXEC
+    pub fn set_cloexec(&mut self, fd: usize, cloexec: bool) {
+        if cloexec {
+            self.cloexec.insert(fd);
+        } else {
+            self.cloexec.remove(&fd);
+        }
+    }
+    /// 从文件描述符表中取出设置了FD_CLOEXEC的文件, exec时由调用者关闭
+    pub fn take_cloexec_files(&mut self) -> Vec<Arc<dyn FileIO>> {
+        let cloexec = core::mem::take(&mut self.cloexec);
+        cloexec
+            .into_iter()
+            .filter_map(|fd| self.fd_table.get_mut(fd).and_then(Option::take))
+            .collect()
     }
     pub fn get_cwd(&self) -> String {
         self.cwd.clone()
@@ -234,7 +273,12 @@ impl Process {
         // 重置用户堆
         inner.heap_bottom = heap_bottom;
         inner.heap_top = inner.heap_bottom;
+        let shared_areas = inner.memory_set.lock().take_unmapped_shared_areas();
         drop(inner);
+        // 原有的共享文件映射在释放锁之后写回
+        for area in shared_areas {
+            area.sync().ok();
+        }
         let mut user_stack_top = user_stack_bottom + USER_STACK_SIZE;
         // 将参数写入即将执行的应用程序的用户栈
         user_stack_top -= (args.len() + 1) * core::mem::size_of::<usize>();
@@ -345,6 +389,13 @@ impl Process {
                 )),
             });
 
+            // 子进程继承父进程的身份凭证、umask和文件描述符的FD_CLOEXEC
+            {
+                let mut new_inner = new_process.inner.lock();
+                new_inner.cred = inner.cred.clone();
+                new_inner.umask = inner.umask;
+                new_inner.cloexec = inner.cloexec.clone();
+            }
             // 记录该进程，防止被回收
             PID2PC.lock().insert(process_id, Arc::clone(&new_process));
             new_process.inner.lock().tasks.push(Arc::clone(&new_task));
@@ -382,12 +433,12 @@ impl Process {
         RUN_QUEUE.lock().add_task(new_task);
         return_id
     }
-    /// 若进程运行完成，则获取其返回码
+    /// 若进程运行完成，则获取其返回码，以及终止它的信号（如果有）
     /// 若正在运行（可能上锁或没有上锁），则返回None
-    fn get_code_if_exit(&self) -> Option<i32> {
+    fn get_code_if_exit(&self) -> Option<(i32, Option<i32>)> {
         let inner = self.inner.try_lock()?;
         if inner.is_zombie {
-            return Some(inner.exit_code);
+            return Some((inner.exit_code, inner.exit_signal));
         }
         None
     }
@@ -456,9 +507,21 @@ pub fn exit(exit_code: i32) -> isize {
 
             drop(pid2pc);
         }
-        inner.memory_set.lock().areas.clear();
+        let mut memory_set = inner.memory_set.lock();
+        memory_set.areas.clear();
+        // 共享文件映射的写回可能阻塞，放到释放锁之后进行
+        let mut shared_areas = core::mem::take(&mut memory_set.shared_areas);
+        shared_areas.append(&mut memory_set.take_unmapped_shared_areas());
+        drop(memory_set);
+        // 关闭所有文件, 文件的析构同样可能阻塞
+        let fd_table = core::mem::take(&mut inner.fd_table);
         // 页表不用特意解除，因为整个对象都将被析构
         drop(inner);
+        for area in shared_areas {
+            area.sync().ok();
+        }
+        drop(fd_table);
+        axfs_os::lock::release_process(process_id);
         drop(process);
         let mut pid2pc = PID2PC.lock();
         pid2pc.remove(&process_id);
@@ -472,6 +535,17 @@ pub fn exit(exit_code: i32) -> isize {
     exit_code as isize
 }
 
+/// 按照信号的默认处理方式终止当前进程
+///
+/// 目前没有信号机制，由内核在需要发送信号时直接调用。父进程等待时得到被信号`signal`终止的状态（WIFSIGNALED为真），
+/// 返回码记为`128 + signal`。
+pub fn exit_by_signal(signal: i32) -> isize {
+    if current().is_leader() {
+        current_process().inner.lock().exit_signal = Some(signal);
+    }
+    exit(128 + signal)
+}
+
 /// 在当前进程找对应的子进程，并等待子进程结束
 /// 若找到了则返回对应的pid
 /// 否则返回一个状态
@@ -486,13 +560,14 @@ pub fn wait_pid(pid: isize, exit_code_ptr: *mut i32) -> Result<u64, WaitStatus>
         if pid == -1 {
             // 任意一个进程结束都可以的
             answer_status = WaitStatus::Running;
-            if let Some(exit_code) = child.get_code_if_exit() {
+            if let Some((exit_code, exit_signal)) = child.get_code_if_exit() {
                 answer_status = WaitStatus::Exited;
                 exit_task_id = index;
                 if !exit_code_ptr.is_null() {
                     unsafe {
                         // 因为没有切换页表，所以可以直接填写
-                        *exit_code_ptr = exit_code;
+                        // 被信号终止时低7位为信号编号
+                        *exit_code_ptr = exit_signal.unwrap_or(exit_code);
                     }
                 }
                 answer_id = child.pid;
@@ -500,13 +575,17 @@ pub fn wait_pid(pid: isize, exit_code_ptr: *mut i32) -> Result<u64, WaitStatus>
             }
         } else if child.pid == pid as u64 {
             // 找到了对应的进程
-            if let Some(exit_code) = child.get_code_if_exit() {
+            if let Some((exit_code, exit_signal)) = child.get_code_if_exit() {
                 answer_status = WaitStatus::Exited;
                 exit_task_id = index;
                 if !exit_code_ptr.is_null() {
                     unsafe {
-                        *exit_code_ptr = exit_code << 8;
-                        // 用于WEXITSTATUS设置编码
+                        *exit_code_ptr = match exit_signal {
+                            // 用于WTERMSIG设置编码
+                            Some(signal) => signal,
+                            // 用于WEXITSTATUS设置编码
+                            None => exit_code << 8,
+                        };
                     }
                 }
                 answer_id = child.pid;
@@ -51,6 +51,9 @@ const JUNIOR_TESTCASES: &[&str] = &[
     "yield",
 ];
 
+/// 仓库中的用户态测例(`testcases/unix`), 由build_img.sh编译后放入磁盘镜像, 在初赛测例之后执行
+const UNIX_TESTCASES: &[&str] = &["unix_socket"];
+
 /// libc静态测例
 pub const LIBC_STATIC_TESTCASES: &[&str] = &[
     "argv",
@@ -231,8 +234,8 @@ impl TestResult {
     }
 }
 lazy_static::lazy_static! {
-    static ref TESTITER: SpinNoIrq<Box<dyn Iterator<Item = &'static &'static str> + Send>> = SpinNoIrq::new(Box::new(JUNIOR_TESTCASES.iter()));
-    static ref TESTRESULT: SpinNoIrq<TestResult> = SpinNoIrq::new(TestResult::new(JUNIOR_TESTCASES.len()));
+    static ref TESTITER: SpinNoIrq<Box<dyn Iterator<Item = &'static &'static str> + Send>> = SpinNoIrq::new(Box::new(JUNIOR_TESTCASES.iter().chain(UNIX_TESTCASES)));
+    static ref TESTRESULT: SpinNoIrq<TestResult> = SpinNoIrq::new(TestResult::new(JUNIOR_TESTCASES.len() + UNIX_TESTCASES.len()));
 }
 
 /// 某一个测试用例完成之后调用，记录测试结果
@@ -2,6 +2,8 @@
 
 #[macro_use]
 extern crate axlog;
+#[cfg(feature = "alloc")]
+extern crate alloc;
 
 // #[cfg(all(target_os = "none", not(test)))]
 mod lang_items;
@@ -132,11 +134,34 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
         #[cfg(feature = "net")]
         axnet::init_network(all_devices.net);
 
+        // DNS queries draw their IDs and source ports from the CSPRNG of /dev/urandom
+        #[cfg(all(feature = "net", feature = "fs"))]
+        axnet::set_random_source(axfs::devfs::fill_random);
+
         #[cfg(feature = "display")]
         axdisplay::init_display(all_devices.display);
     }
 
+    #[cfg(all(feature = "initramfs", not(feature = "fs")))]
+    {
+        axfs::init_initramfs();
+        info!("Filesystems initialized.");
+    }
+
+    #[cfg(all(feature = "net", any(feature = "fs", feature = "initramfs")))]
+    {
+        write_resolv_conf();
+        // rewrite it when a DHCP lease brings other nameservers; the handler
+        // runs while the interfaces are polled, so write from a task
+        axnet::set_servers_handler(|_| {
+            axtask::spawn(write_resolv_conf);
+        });
+    }
+
+    #[cfg(not(feature = "app"))]
     axprocess::init_tasks();
+    #[cfg(feature = "app")]
+    axtask::spawn(run_app);
 
     info!("Initialize interrupt handlers...");
     init_interrupt();
@@ -155,6 +180,17 @@ pub extern "C" fn rust_main(cpu_id: usize, dtb: usize) -> ! {
     unreachable!("can not reach!");
 }
 
+/// Runs the `main` of the application linked with the kernel, instead of the
+/// first user process, then shuts down.
+#[cfg(feature = "app")]
+fn run_app() {
+    extern "Rust" {
+        fn main();
+    }
+    unsafe { main() };
+    axhal::misc::terminate();
+}
+
 #[cfg(feature = "alloc")]
 fn init_allocator() {
     use axhal::mem::{memory_regions, phys_to_virt, MemRegionFlags};
@@ -181,6 +217,35 @@ fn init_allocator() {
     }
 }
 
+/// Writes the nameservers in use to `/etc/resolv.conf`, where the resolver of
+/// the C library (e.g. `getaddrinfo` of musl) looks for them.
+///
+/// A file written by hand is left untouched; only a file generated here, as
+/// told by its first line, is replaced.
+#[cfg(all(feature = "net", any(feature = "fs", feature = "initramfs")))]
+fn write_resolv_conf() {
+    use alloc::string::String;
+    use core::fmt::Write;
+
+    const RESOLV_CONF: &str = "/etc/resolv.conf";
+    const GENERATED: &str = "# generated by the kernel from the nameservers in use\n";
+    if axfs::api::path_exists(RESOLV_CONF) {
+        match axfs::api::read_to_string(RESOLV_CONF) {
+            Ok(conf) if conf.starts_with(GENERATED) => {}
+            _ => return,
+        }
+    }
+    let mut conf = String::from(GENERATED);
+    for server in axnet::dns_servers() {
+        writeln!(conf, "nameserver {}", server).unwrap();
+    }
+    let result =
+        axfs::api::create_dir_all("/etc").and_then(|_| axfs::api::write(RESOLV_CONF, conf));
+    if let Err(e) = result {
+        warn!("failed to write {}: {:?}", RESOLV_CONF, e);
+    }
+}
+
 #[cfg(feature = "paging")]
 fn remap_kernel_memory() -> Result<(), axhal::paging::PagingError> {
     axmem::paging::remap_kernel_memory()
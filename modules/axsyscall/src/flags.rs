@@ -39,6 +39,18 @@ pub struct TimeSecs {
     pub tv_nsec: usize,
 }
 
+// sys_ppoll指定的结构体类型
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PollFd {
+    /// 要等待的文件描述符
+    pub fd: i32,
+    /// 要等待的事件
+    pub events: i16,
+    /// 发生的事件
+    pub revents: i16,
+}
+
 bitflags! {
     /// 指定 mmap 的选项
     pub struct MMAPPROT: u32 {
@@ -84,6 +96,34 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// 指定 msync 的选项
+    #[derive(Debug)]
+    pub struct MSyncFlags: u32 {
+        /// 发起写回后立即返回
+        const MS_ASYNC = 1 << 0;
+        /// 使同一文件的其他映射失效
+        const MS_INVALIDATE = 1 << 1;
+        /// 写回完成后再返回
+        const MS_SYNC = 1 << 2;
+    }
+}
+
+/// fcntl 的 F_GETLK/F_SETLK/F_SETLKW 中指定的记录锁
+#[repr(C)]
+pub struct Flock {
+    /// 锁的类型: F_RDLCK、F_WRLCK或F_UNLCK
+    pub l_type: i16,
+    /// `l_start`的起点: SEEK_SET、SEEK_CUR或SEEK_END
+    pub l_whence: i16,
+    /// 锁的起始偏移
+    pub l_start: i64,
+    /// 锁住的字节数, 0表示一直到文件末尾, 负数表示`l_start`之前的字节
+    pub l_len: i64,
+    /// F_GETLK返回的持有冲突锁的进程
+    pub l_pid: i32,
+}
+
 /// sys_uname 中指定的结构体类型
 #[repr(C)]
 pub struct UtsName {
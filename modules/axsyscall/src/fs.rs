@@ -1,22 +1,60 @@
 use alloc::format;
 use alloc::string::ToString;
 use alloc::sync::Arc;
-use axfs::api;
+use alloc::vec::Vec;
+use axfs::api::{self, Permissions};
+use axfs::lock::{self, LockKind, RecordLock};
+use axfs::loopdev;
+use axfs::perm::{self, Cap, Identity};
+use axfs_os::file_io::FileIO;
 use axfs_os::flags::OpenFlags;
 use axfs_os::link::{create_link, remove_link};
+use axfs_os::mknod::{mknod, open_special_node, remove_special_node, special_dirent_type};
 use axfs_os::mount::{check_mounted, mount_fat_fs, umount_fat_fs};
-use axfs_os::pipe::make_pipe;
+use axfs_os::pipe::{make_pipe, Pipe};
+use axfs_os::rename::{rename, RenameFlags};
 use axfs_os::types::Kstat;
-use axfs_os::{new_dir, new_fd, DirEnt, DirEntType, FilePath};
-use axprocess::process::current_process;
+use axfs_os::{new_dir, new_fd, DirEnt, DirEntType, FileDesc, FilePath};
+use axerrno::{AxError, AxResult, LinuxError};
+use axhal::time::wall_time;
+use axio::{Seek, SeekFrom};
+use axprocess::process::{current_process, exit_by_signal, PID2PC};
 use core::mem::transmute;
 use core::ptr::copy_nonoverlapping;
+use core::time::Duration;
 use log::{debug, info};
 
+use crate::flags::{Flock, TimeSecs};
+
 #[allow(unused)]
 const AT_FDCWD: usize = -100isize as usize;
 // Special value used to indicate openat should use the current working directory.
 const AT_REMOVEDIR: usize = 0x200; // Remove directory instead of unlinking file.
+const F_DUPFD: usize = 0; // Duplicate to the lowest free fd not below the argument.
+const F_GETFD: usize = 1; // Get the fd flags.
+const F_SETFD: usize = 2; // Set the fd flags.
+const F_GETFL: usize = 3; // Get the file status flags.
+const F_SETFL: usize = 4; // Set the file status flags.
+const F_GETLK: usize = 5; // Get the first lock blocking a record lock.
+const F_SETLK: usize = 6; // Set or release a record lock.
+const F_SETLKW: usize = 7; // Set a record lock, waiting for conflicting locks.
+const F_SETPIPE_SZ: usize = 1031; // Set pipe buffer size.
+const F_GETPIPE_SZ: usize = 1032; // Get pipe buffer size.
+const F_DUPFD_CLOEXEC: usize = 1030; // Like F_DUPFD, and set FD_CLOEXEC on the new fd.
+const FD_CLOEXEC: usize = 1; // Close the fd on exec.
+const FD_LIMIT: usize = 1024; // F_DUPFD fails at or above this, like the default RLIMIT_NOFILE.
+const F_RDLCK: i16 = 0; // Shared record lock.
+const F_WRLCK: i16 = 1; // Exclusive record lock.
+const F_UNLCK: i16 = 2; // Release a record lock.
+const LOCK_SH: usize = 1; // Shared flock.
+const LOCK_EX: usize = 2; // Exclusive flock.
+const LOCK_NB: usize = 4; // Don't block when locking.
+const LOCK_UN: usize = 8; // Release a flock.
+const SIGPIPE: i32 = 13;
+const UTIME_NOW: usize = (1 << 30) - 1; // Set the time to the current time.
+const UTIME_OMIT: usize = (1 << 30) - 2; // Leave the time unchanged.
+const LOOP_SET_FD: usize = 0x4c00; // Attach a file to a loop device.
+const LOOP_CLR_FD: usize = 0x4c01; // Detach the file from a loop device.
 
 // const STDIN: usize = 0;
 // const STDOUT: usize = 1;
@@ -111,11 +149,16 @@ pub fn syscall_read(fd: usize, buf: *mut u8, count: usize) -> isize {
         // file.print_content();
 
         drop(process_inner); // release current inner manually to avoid multi-borrow
-        let read_size = file
-            .read(unsafe { core::slice::from_raw_parts_mut(buf, count) })
-            .unwrap() as isize;
-        debug!("read_size: {}", read_size);
-        read_size as isize
+        match file.read(unsafe { core::slice::from_raw_parts_mut(buf, count) }) {
+            Ok(read_size) => {
+                debug!("read_size: {}", read_size);
+                read_size as isize
+            }
+            Err(e) => {
+                debug!("read failed: {:?}", e);
+                -1
+            }
+        }
     } else {
         -1
     }
@@ -144,8 +187,20 @@ pub fn syscall_write(fd: usize, buf: *const u8, count: usize) -> isize {
         let file = file.clone();
         drop(process_inner); // release current inner manually to avoid multi-borrow
                              // file.write("Test SysWrite\n".as_bytes()).unwrap();
-        file.write(unsafe { core::slice::from_raw_parts(buf, count) })
-            .unwrap() as isize
+        let result = file.write(unsafe { core::slice::from_raw_parts(buf, count) });
+        drop(file);
+        match result {
+            Ok(write_size) => write_size as isize,
+            Err(AxError::BrokenPipe) => {
+                // 目前没有信号机制, 按照SIGPIPE的默认处理方式直接终止进程
+                debug!("write to a broken pipe, killed by SIGPIPE");
+                exit_by_signal(SIGPIPE)
+            }
+            Err(e) => {
+                debug!("write failed: {:?}", e);
+                -1
+            }
+        }
     } else {
         -1
     }
@@ -156,18 +211,60 @@ pub fn syscall_write(fd: usize, buf: *const u8, count: usize) -> isize {
 ///     - fd：文件所在目录的文件描述符。
 ///     - filename：要打开或创建的文件名。如为绝对路径，则忽略fd。如为相对路径，且fd是AT_FDCWD，则filename是相对于当前工作目录来说的。如为相对路径，且fd是一个文件描述符，则filename是相对于fd所指向的目录来说的。
 ///     - flags：必须包含如下访问模式的其中一种：O_RDONLY，O_WRONLY，O_RDWR。还可以包含文件创建标志和文件状态标志。
-///     - mode：文件的所有权描述。详见`man 7 inode `。
+///     - mode：文件的所有权描述。详见`man 7 inode `。仅在创建新文件时使用，会屏蔽进程umask中的权限位。
 /// 返回值：成功执行，返回新的文件描述符。失败，返回-1。
 ///
 /// 说明：如果打开的是一个目录，那么返回的文件描述符指向的是该目录的描述符。(后面会用到针对目录的文件描述符)
+/// 打开已有的文件需要对应的读/写权限，创建新文件需要父目录的写权限。
 /// flags: O_RDONLY: 0, O_WRONLY: 1, O_RDWR: 2, O_CREAT: 64, O_DIRECTORY: 65536
-pub fn syscall_openat(fd: usize, path: *const u8, flags: usize, _mode: u8) -> isize {
-    let force_dir = OpenFlags::from(flags).is_dir();
+pub fn syscall_openat(fd: usize, path: *const u8, flags: usize, mode: u32) -> isize {
+    let open_flags = OpenFlags::from(flags);
+    let force_dir = open_flags.is_dir();
     let path = deal_with_path(fd, Some(path), force_dir).unwrap();
+    let id = current_identity();
+    let exists = api::path_exists(path.path());
+    let checked = if exists {
+        let mut access = Cap::empty();
+        if open_flags.readable() {
+            access |= Cap::READ;
+        }
+        if open_flags.writable() {
+            access |= Cap::WRITE;
+        }
+        perm::check_access(path.path(), &id, access)
+    } else if open_flags.creatable() {
+        perm::check_create(path.path(), &id)
+    } else {
+        Ok(())
+    };
+    if let Err(e) = checked {
+        debug!("openat {} denied: {:?}", path.path(), e);
+        return -1;
+    }
+    let new_perm = creation_perm(mode);
+    // 命名管道和设备文件, 打开FIFO可能会阻塞, 所以不能持有进程的锁
+    if let Some(result) = open_special_node(&path, flags.into()) {
+        return match result {
+            Ok(file) => {
+                let process = current_process();
+                let mut process_inner = process.inner.lock();
+                let fd_num = process_inner.alloc_fd();
+                debug!("open special node, allocated fd_num: {}", fd_num);
+                process_inner.fd_table[fd_num] = Some(file);
+                process_inner.set_cloexec(fd_num, open_flags.contains(OpenFlags::CLOEXEC));
+                fd_num as isize
+            }
+            Err(e) => {
+                debug!("open special node failed: {:?}", e);
+                -1
+            }
+        };
+    }
     let process = current_process();
     let mut process_inner = process.inner.lock();
     let fd_num = process_inner.alloc_fd();
     debug!("allocated fd_num: {}", fd_num);
+    process_inner.set_cloexec(fd_num, open_flags.contains(OpenFlags::CLOEXEC));
     // 如果是DIR
     if path.is_dir() {
         debug!("open dir");
@@ -186,7 +283,11 @@ pub fn syscall_openat(fd: usize, path: *const u8, flags: usize, _mode: u8) -> is
         if let Ok(file) = new_fd(path.path().to_string(), flags.into()) {
             debug!("new file_desc successfully allocated");
             process_inner.fd_table[fd_num] = Some(Arc::new(file));
+            drop(process_inner);
             let _ = create_link(&path, &path); // 不需要检查是否成功，因为如果成功，说明是新建的文件，如果失败，说明已经存在了
+            if !exists {
+                init_new_node(path.path(), new_perm, &id);
+            }
             fd_num as isize
         } else {
             debug!("open file failed");
@@ -217,7 +318,10 @@ pub fn syscall_close(fd: usize) -> isize {
         debug!("fd {} is none", fd);
         return -1;
     }
-    process_inner.fd_table[fd].take();
+    let file = process_inner.fd_table[fd].take();
+    // 关闭文件时会写回共享映射, 可能阻塞, 要在释放进程的锁之后进行
+    drop(process_inner);
+    close_file(process.pid, file);
 
     // for i in 0..process_inner.fd_table.len() {
     //     if let Some(file) = process_inner.fd_table[i].as_ref() {
@@ -261,20 +365,25 @@ pub fn syscall_getcwd(buf: *mut u8, len: usize) -> isize {
 /// 功能：创建管道；
 /// 输入：
 ///     - fd[2]：用于保存2个文件描述符。其中，fd[0]为管道的读出端，fd[1]为管道的写入端。
+///     - flags：目前只支持O_NONBLOCK和O_CLOEXEC，其余标志被忽略。
 /// 返回值：成功执行，返回0。失败，返回-1。
 ///
 /// 注意：fd[2]是32位数组，所以这里的 fd 是 u32 类型的指针，而不是 usize 类型的指针。
-pub fn syscall_pipe2(fd: *mut u32) -> isize {
-    debug!("Into syscall_pipe2. fd: {}", fd as usize);
+pub fn syscall_pipe2(fd: *mut u32, flags: usize) -> isize {
+    debug!("Into syscall_pipe2. fd: {}, flags: {}", fd as usize, flags);
     let process = current_process();
     let mut process_inner = process.inner.lock();
 
-    let (read, write) = make_pipe();
+    let flags = OpenFlags::from(flags);
+    let (read, write) = make_pipe(flags.is_nonblock());
 
     let fd_num = process_inner.alloc_fd();
     process_inner.fd_table[fd_num] = Some(read);
     let fd_num2 = process_inner.alloc_fd();
     process_inner.fd_table[fd_num2] = Some(write);
+    let cloexec = flags.contains(OpenFlags::CLOEXEC);
+    process_inner.set_cloexec(fd_num, cloexec);
+    process_inner.set_cloexec(fd_num2, cloexec);
 
     debug!("fd_num: {}, fd_num2: {}", fd_num, fd_num2);
 
@@ -308,11 +417,27 @@ pub fn syscall_dup(fd: usize) -> isize {
     fd_num as isize
 }
 
+/// 关闭进程`pid`从文件描述符表中取出的文件, 调用时不能持有进程的锁
+///
+/// 进程关闭文件的任意一个描述符都会释放它在该文件上的所有记录锁。
+pub(crate) fn close_file(pid: u64, file: Option<Arc<dyn FileIO>>) {
+    if let Some(desc) = file
+        .as_ref()
+        .and_then(|file| file.as_any().downcast_ref::<FileDesc>())
+    {
+        lock::release_records(&desc.path, pid);
+    }
+    drop(file);
+}
+
 /// 功能：复制文件描述符，并指定了新的文件描述符；
 /// 输入：
 ///     - old：被复制的文件描述符。
 ///     - new：新的文件描述符。
 /// 返回值：成功执行，返回新的文件描述符。失败，返回-1。
+///
+/// 说明：新的文件描述符已经打开时先将其关闭，与`close`一样释放进程在原文件上的记录锁。
+/// 新的文件描述符没有设置FD_CLOEXEC。
 pub fn syscall_dup3(fd: usize, new_fd: usize) -> isize {
     let process = current_process();
     let mut process_inner = process.inner.lock();
@@ -330,20 +455,252 @@ pub fn syscall_dup3(fd: usize, new_fd: usize) -> isize {
             process_inner.fd_table.push(None);
         }
     }
-    if process_inner.fd_table[new_fd].is_some() {
-        debug!("new_fd {} is already opened", new_fd);
+    if new_fd == fd {
+        debug!("new_fd {} is the same as fd", new_fd);
         return -1;
     }
-    process_inner.fd_table[new_fd] = process_inner.fd_table[fd].clone();
+    let new_file = process_inner.fd_table[fd].clone();
+    let old_file = core::mem::replace(&mut process_inner.fd_table[new_fd], new_file);
+    process_inner.set_cloexec(new_fd, false);
+    drop(process_inner);
+    close_file(process.pid, old_file);
 
     new_fd as isize
 }
 
+/// 功能：操作文件描述符；
+/// 输入：
+///     - fd：要操作的文件描述符。
+///     - cmd：操作类型，目前支持F_DUPFD、F_DUPFD_CLOEXEC、F_GETFD、F_SETFD、F_GETFL、F_SETFL、
+///       F_GETPIPE_SZ、F_SETPIPE_SZ和记录锁操作F_GETLK、F_SETLK、F_SETLKW。
+///     - arg：操作的参数。对于F_DUPFD和F_DUPFD_CLOEXEC，为新文件描述符的最小值；对于F_SETFD和F_SETFL，
+///       为新的标志；对于F_SETPIPE_SZ，为管道缓冲区的新大小；对于记录锁操作，为`struct flock`的指针。
+/// 返回值：F_DUPFD和F_DUPFD_CLOEXEC返回新的文件描述符，F_GETFD和F_GETFL返回标志，
+/// F_GETPIPE_SZ和F_SETPIPE_SZ返回管道缓冲区的大小，其余操作成功返回0。失败，返回-1，
+/// 其中加锁和解锁失败时返回负的错误码，如锁被占用时的-EAGAIN和会死锁时的-EDEADLK。
+///
+/// 说明：F_SETFL只能修改O_NONBLOCK，管道和socket据此决定读写是否阻塞，其余标志被忽略。
+/// F_SETLK在锁被其他进程占用时直接失败，F_SETLKW则等待锁被释放，等待会与其他进程形成死锁时失败。
+/// F_GETLK把阻止加锁的第一把锁写回`struct flock`，没有冲突时将`l_type`设为F_UNLCK。
+pub fn syscall_fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    debug!("Into syscall_fcntl. fd: {}, cmd: {}, arg: {}", fd, cmd, arg);
+    let process = current_process();
+    let process_inner = process.inner.lock();
+    if fd >= process_inner.fd_table.len() {
+        debug!("fd {} is out of range", fd);
+        return -1;
+    }
+    let file = match process_inner.fd_table[fd].as_ref() {
+        Some(file) => file.clone(),
+        None => {
+            debug!("fd {} is not opened", fd);
+            return -1;
+        }
+    };
+    drop(process_inner);
+
+    match cmd {
+        F_DUPFD | F_DUPFD_CLOEXEC => {
+            if arg >= FD_LIMIT {
+                debug!("fd {} is out of range", arg);
+                return -1;
+            }
+            let mut process_inner = process.inner.lock();
+            let new_fd = process_inner.alloc_fd_from(arg);
+            process_inner.fd_table[new_fd] = Some(file);
+            process_inner.set_cloexec(new_fd, cmd == F_DUPFD_CLOEXEC);
+            new_fd as isize
+        }
+        F_GETFD => {
+            if process.inner.lock().cloexec.contains(&fd) {
+                FD_CLOEXEC as isize
+            } else {
+                0
+            }
+        }
+        F_SETFD => {
+            process.inner.lock().set_cloexec(fd, arg & FD_CLOEXEC != 0);
+            0
+        }
+        F_GETFL => {
+            let mut flags = match (file.readable(), file.writable()) {
+                (true, true) => OpenFlags::RDWR,
+                (false, true) => OpenFlags::WRONLY,
+                _ => OpenFlags::RDONLY,
+            };
+            if file.is_nonblocking() {
+                flags |= OpenFlags::NON_BLOCK;
+            }
+            flags.bits() as isize
+        }
+        F_SETFL => {
+            file.set_nonblocking(OpenFlags::from(arg).is_nonblock());
+            0
+        }
+        F_GETPIPE_SZ | F_SETPIPE_SZ => {
+            let pipe = match file.as_ref().as_any().downcast_ref::<Pipe>() {
+                Some(pipe) => pipe,
+                None => {
+                    debug!("fd {} is not a pipe", fd);
+                    return -1;
+                }
+            };
+            if cmd == F_GETPIPE_SZ {
+                return pipe.capacity() as isize;
+            }
+            match pipe.set_capacity(arg) {
+                Ok(size) => size as isize,
+                Err(e) => {
+                    debug!("set pipe size failed: {:?}", e);
+                    -1
+                }
+            }
+        }
+        F_GETLK | F_SETLK | F_SETLKW => {
+            let desc = match file.as_ref().as_any().downcast_ref::<FileDesc>() {
+                Some(desc) => desc,
+                None => {
+                    debug!("fd {} is not a regular file", fd);
+                    return -1;
+                }
+            };
+            let flock = unsafe { &mut *(arg as *mut Flock) };
+            let (start, end) = match record_range(desc, flock) {
+                Some(range) => range,
+                None => {
+                    debug!("invalid lock range");
+                    return -1;
+                }
+            };
+            let kind = match flock.l_type {
+                F_RDLCK if cmd == F_GETLK || desc.readable() => Some(LockKind::Shared),
+                F_WRLCK if cmd == F_GETLK || desc.writable() => Some(LockKind::Exclusive),
+                F_UNLCK if cmd != F_GETLK => None,
+                _ => {
+                    debug!("invalid lock type {} for fd {}", flock.l_type, fd);
+                    return -1;
+                }
+            };
+            let pid = process.pid;
+            let result = match kind {
+                Some(kind) if cmd == F_GETLK => {
+                    let lock = RecordLock {
+                        kind,
+                        start,
+                        end,
+                        pid,
+                    };
+                    lock::test_records(&desc.path, &lock).map(|conflict| match conflict {
+                        Some(conflict) => {
+                            flock.l_type = match conflict.kind {
+                                LockKind::Shared => F_RDLCK,
+                                LockKind::Exclusive => F_WRLCK,
+                            };
+                            flock.l_whence = 0;
+                            flock.l_start = conflict.start as i64;
+                            flock.l_len = if conflict.end == u64::MAX {
+                                0
+                            } else {
+                                (conflict.end - conflict.start) as i64
+                            };
+                            flock.l_pid = conflict.pid as i32;
+                        }
+                        None => flock.l_type = F_UNLCK,
+                    })
+                }
+                Some(kind) => {
+                    let lock = RecordLock {
+                        kind,
+                        start,
+                        end,
+                        pid,
+                    };
+                    lock::lock_records(&desc.path, lock, cmd == F_SETLKW)
+                }
+                None => lock::unlock_records(&desc.path, pid, start, end),
+            };
+            match result {
+                Ok(()) => 0,
+                Err(e) => {
+                    debug!("fcntl lock failed: {:?}", e);
+                    -(LinuxError::from(e) as isize)
+                }
+            }
+        }
+        _ => {
+            debug!("fcntl cmd {} is not supported", cmd);
+            -1
+        }
+    }
+}
+
+/// 辅助函数：将`struct flock`描述的范围转换为文件中的`[start, end)`，`end`为`u64::MAX`表示到文件末尾
+fn record_range(desc: &FileDesc, flock: &Flock) -> Option<(u64, u64)> {
+    let base = match flock.l_whence {
+        0 => 0,
+        1 => desc.file.lock().seek(SeekFrom::Current(0)).ok()? as i64,
+        2 => desc.file.lock().metadata().ok()?.len() as i64,
+        _ => return None,
+    };
+    let start = base.checked_add(flock.l_start)?;
+    let (start, end) = match flock.l_len {
+        0 => (start, None),
+        len if len > 0 => (start, Some(start.checked_add(len)?)),
+        len => (start.checked_add(len)?, Some(start)),
+    };
+    if start < 0 {
+        return None;
+    }
+    Some((start as u64, end.map_or(u64::MAX, |end| end as u64)))
+}
+
+/// 功能：对文件加或解除`flock`建议锁；
+/// 输入：
+///     - fd：文件描述符。
+///     - operation：LOCK_SH加共享锁，LOCK_EX加排他锁，LOCK_UN解锁，可以与LOCK_NB组合表示不等待。
+/// 返回值：成功执行，返回0。失败（包括LOCK_NB时锁被占用），返回-1。
+///
+/// 说明：锁属于打开的文件，通过dup或fork共享同一个打开文件的描述符持有同一把锁。
+pub fn syscall_flock(fd: usize, operation: usize) -> isize {
+    debug!("Into syscall_flock. fd: {}, operation: {}", fd, operation);
+    let file = match get_file(fd) {
+        Some(file) => file,
+        None => {
+            debug!("fd {} is not opened", fd);
+            return -1;
+        }
+    };
+    let desc = match file.as_any().downcast_ref::<FileDesc>() {
+        Some(desc) => desc,
+        None => {
+            debug!("fd {} is not a regular file", fd);
+            return -1;
+        }
+    };
+    let wait = operation & LOCK_NB == 0;
+    let result = match operation & !LOCK_NB {
+        LOCK_SH => lock::flock(&desc.path, desc.lock_owner(), LockKind::Shared, wait),
+        LOCK_EX => lock::flock(&desc.path, desc.lock_owner(), LockKind::Exclusive, wait),
+        LOCK_UN => lock::funlock(&desc.path, desc.lock_owner()),
+        _ => {
+            debug!("invalid flock operation {}", operation);
+            return -1;
+        }
+    };
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            debug!("flock failed: {:?}", e);
+            -1
+        }
+    }
+}
+
 /// 功能：创建目录；
 /// 输入：
 ///     - dirfd：要创建的目录所在的目录的文件描述符。
 ///     - path：要创建的目录的名称。如果path是相对路径，则它是相对于dirfd目录而言的。如果path是相对路径，且dirfd的值为AT_FDCWD，则它是相对于当前路径而言的。如果path是绝对路径，则dirfd被忽略。
-///     - mode：文件的所有权描述。详见`man 7 inode `。
+///     - mode：文件的所有权描述。详见`man 7 inode `。会屏蔽进程umask中的权限位。
 /// 返回值：成功执行，返回0。失败，返回-1。
 pub fn syscall_mkdirat(dir_fd: usize, path: *const u8, mode: u32) -> isize {
     let path = deal_with_path(dir_fd, Some(path), true).unwrap();
@@ -353,7 +710,16 @@ pub fn syscall_mkdirat(dir_fd: usize, path: *const u8, mode: u32) -> isize {
         path.path(),
         mode
     );
-    let _ = api::create_dir(path.path());
+    if !api::path_exists(path.path()) {
+        let id = current_identity();
+        if let Err(e) = perm::check_create(path.path(), &id) {
+            debug!("mkdirat denied: {:?}", e);
+            return -1;
+        }
+        if api::create_dir(path.path()).is_ok() {
+            init_new_node(path.path(), creation_perm(mode), &id);
+        }
+    }
 
     // 只要文件夹存在就返回0
     if api::path_exists(path.path()) {
@@ -363,6 +729,37 @@ pub fn syscall_mkdirat(dir_fd: usize, path: *const u8, mode: u32) -> isize {
     }
 }
 
+/// 功能：创建文件系统节点(普通文件、命名管道或设备文件)；
+/// 输入：
+///     - dirfd：要创建的节点所在的目录的文件描述符。
+///     - path：要创建的节点的名称。path的使用规则同mkdirat。
+///     - mode：节点的类型和权限。类型可以是S_IFREG、S_IFIFO、S_IFCHR或S_IFBLK。
+///     - dev：设备号，仅在类型为S_IFCHR或S_IFBLK时有效，必须对应/dev下已有的设备。
+/// 返回值：成功执行，返回0。失败，返回-1。
+///
+/// 说明：mkfifo(path, mode)由libc实现为mknodat(AT_FDCWD, path, mode | S_IFIFO, 0)。
+pub fn syscall_mknodat(dir_fd: usize, path: *const u8, mode: u32, dev: usize) -> isize {
+    let path = deal_with_path(dir_fd, Some(path), false).unwrap();
+    debug!(
+        "Into syscall_mknodat. dirfd: {}, path: {:?}, mode: {:#o}, dev: {:#x}",
+        dir_fd,
+        path.path(),
+        mode,
+        dev
+    );
+    if let Err(e) = perm::check_create(path.path(), &current_identity()) {
+        debug!("mknodat denied: {:?}", e);
+        return -1;
+    }
+    match mknod(&path, mode, dev as u64) {
+        Ok(_) => 0,
+        Err(e) => {
+            debug!("mknod error: {:?}", e);
+            -1
+        }
+    }
+}
+
 /// 功能：切换工作目录；
 /// 输入：
 ///     - path：需要切换到的目录。
@@ -377,6 +774,119 @@ pub fn syscall_chdir(path: *const u8) -> isize {
     }
 }
 
+/// 功能：修改文件的权限位；
+/// 输入：
+///     - fd：要修改的文件的文件描述符。
+///     - mode：新的权限位，包括setuid、setgid和粘滞位。
+/// 返回值：成功执行，返回0。失败，返回-1。
+pub fn syscall_fchmod(fd: usize, mode: u32) -> isize {
+    debug!("Into syscall_fchmod. fd: {}, mode: {:#o}", fd, mode);
+    match get_file(fd) {
+        Some(file) => chmod(&file.get_path(), mode),
+        None => -1,
+    }
+}
+
+/// 功能：修改文件的权限位，chmod由libc实现为fchmodat(AT_FDCWD, path, mode, 0)；
+/// 输入：
+///     - dir_fd：文件所在目录的文件描述符。
+///     - path：文件路径，使用规则同openat。
+///     - mode：新的权限位，包括setuid、setgid和粘滞位。
+///     - flags：可以为0或AT_SYMLINK_NOFOLLOW。目前没有符号链接，忽略。
+/// 返回值：成功执行，返回0。失败，返回-1。
+pub fn syscall_fchmodat(dir_fd: usize, path: *const u8, mode: u32, flags: usize) -> isize {
+    let Some(path) = deal_with_path(dir_fd, Some(path), false) else {
+        return -1;
+    };
+    debug!(
+        "Into syscall_fchmodat. path: {}, mode: {:#o}, flags: {}",
+        path.path(),
+        mode,
+        flags
+    );
+    chmod(path.path(), mode)
+}
+
+/// 功能：修改文件的所有者和所属组；
+/// 输入：
+///     - fd：要修改的文件的文件描述符。
+///     - uid：新的所有者，为-1时不修改。
+///     - gid：新的所属组，为-1时不修改。
+/// 返回值：成功执行，返回0。失败，返回-1。
+pub fn syscall_fchown(fd: usize, uid: u32, gid: u32) -> isize {
+    debug!(
+        "Into syscall_fchown. fd: {}, uid: {}, gid: {}",
+        fd, uid, gid
+    );
+    match get_file(fd) {
+        Some(file) => chown(&file.get_path(), uid, gid),
+        None => -1,
+    }
+}
+
+/// 功能：修改文件的所有者和所属组，chown由libc实现为fchownat(AT_FDCWD, path, uid, gid, 0)；
+/// 输入：
+///     - dir_fd：文件所在目录的文件描述符。
+///     - path：文件路径，使用规则同openat。
+///     - uid：新的所有者，为-1时不修改。
+///     - gid：新的所属组，为-1时不修改。
+///     - flags：可以为0或AT_SYMLINK_NOFOLLOW。目前没有符号链接，忽略。
+/// 返回值：成功执行，返回0。失败，返回-1。
+pub fn syscall_fchownat(dir_fd: usize, path: *const u8, uid: u32, gid: u32, flags: usize) -> isize {
+    let Some(path) = deal_with_path(dir_fd, Some(path), false) else {
+        return -1;
+    };
+    debug!(
+        "Into syscall_fchownat. path: {}, uid: {}, gid: {}, flags: {}",
+        path.path(),
+        uid,
+        gid,
+        flags
+    );
+    chown(path.path(), uid, gid)
+}
+
+/// 功能：设置进程创建文件时屏蔽的权限位；
+/// 输入：
+///     - mask：新的umask，只有低9位有效。
+/// 返回值：总是成功，返回原来的umask。
+pub fn syscall_umask(mask: u32) -> isize {
+    let process = current_process();
+    let mut process_inner = process.inner.lock();
+    let old = process_inner.umask;
+    process_inner.umask = mask & 0o777;
+    old as isize
+}
+
+/// 辅助函数：检查权限后修改`path`的权限位
+fn chmod(path: &str, mode: u32) -> isize {
+    let perm = Permissions::from_bits_truncate((mode & 0o7777) as u16);
+    let result =
+        perm::check_chmod(path, &current_identity()).and_then(|_| api::set_permissions(path, perm));
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            debug!("chmod {} error: {:?}", path, e);
+            -1
+        }
+    }
+}
+
+/// 辅助函数：检查权限后修改`path`的所有者和所属组，-1表示不修改
+fn chown(path: &str, uid: u32, gid: u32) -> isize {
+    let uid = (uid != u32::MAX).then_some(uid);
+    let gid = (gid != u32::MAX).then_some(gid);
+    let result = perm::check_chown(path, &current_identity(), uid, gid)
+        .and_then(|_| api::set_owner(path, uid, gid));
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            debug!("chown {} error: {:?}", path, e);
+            -1
+        }
+    }
+}
+
 /// 功能：获取目录的条目;
 /// 参数：
 ///     -fd：所要读取目录的文件描述符。
@@ -419,13 +929,20 @@ pub fn syscall_getdents64(fd: usize, buf: *mut u8, len: usize) -> isize {
         // 转换为DirEnt
         let dirent: &mut DirEnt = unsafe { transmute(buf.as_mut_ptr().offset(count as isize)) };
         // 设置定长部分
-        if file_type.is_dir() {
-            dirent.set_fixed_part(i as u64, entry_size as i64, entry_size, DirEntType::DIR);
-        } else if file_type.is_file() {
-            dirent.set_fixed_part(i as u64, entry_size as i64, entry_size, DirEntType::REG);
-        } else {
-            dirent.set_fixed_part(i as u64, entry_size as i64, entry_size, DirEntType::UNKNOWN);
-        }
+        let entry_path = FilePath::new(&format!("{}{}", path.path(), entry.file_name()));
+        let dirent_type = match special_dirent_type(&entry_path) {
+            Some(special_type) => special_type, // 命名管道或设备文件的占位文件
+            None => match file_type {
+                api::FileType::Dir => DirEntType::DIR,
+                api::FileType::File => DirEntType::REG,
+                api::FileType::CharDevice => DirEntType::CHR,
+                api::FileType::BlockDevice => DirEntType::BLK,
+                api::FileType::Fifo => DirEntType::FIFO,
+                api::FileType::SymLink => DirEntType::LNK,
+                api::FileType::Socket => DirEntType::SOCK,
+            },
+        };
+        dirent.set_fixed_part(i as u64, entry_size as i64, entry_size, dirent_type);
 
         // 写入文件名
         unsafe { copy_nonoverlapping(name.as_ptr(), dirent.d_name.as_mut_ptr(), name_len + 1) };
@@ -468,6 +985,10 @@ pub fn sys_linkat(
 /// 返回值：成功执行，返回0。失败，返回-1。
 pub fn syscall_unlinkat(dir_fd: usize, path: *const u8, flags: usize) -> isize {
     let path = deal_with_path(dir_fd, Some(path), false).unwrap();
+    if let Err(e) = perm::check_remove(path.path(), &current_identity()) {
+        debug!("unlinkat denied: {:?}", e);
+        return -1;
+    }
 
     // unlink file
     if flags == 0 {
@@ -475,6 +996,7 @@ pub fn syscall_unlinkat(dir_fd: usize, path: *const u8, flags: usize) -> isize {
             debug!("unlink file error");
             return -1;
         }
+        remove_special_node(&path);
     }
     // remove dir
     else if flags == AT_REMOVEDIR {
@@ -518,6 +1040,9 @@ pub fn syscall_mount(
         // data可以为NULL, 必须判断, 否则会panic, 发生LoadPageFault
         _data_str = memory_set.translate_str(_data);
     }
+    // 挂载时会读写磁盘, 不能持有进程的锁
+    drop(memory_set);
+    drop(process_inner);
     if device_path.is_dir() {
         debug!("device_path should not be a dir");
         return -1;
@@ -540,6 +1065,14 @@ pub fn syscall_mount(
         debug!("mount path includes mounted fs");
         return -1;
     }
+    // 设备是块设备时真正挂载其上的文件系统, 设备不存在时只记录挂载信息
+    match api::mount(device_path.path(), mount_path.path()) {
+        Ok(()) | Err(AxError::NotFound) => {}
+        Err(e) => {
+            debug!("mount {} error: {:?}", device_path.path(), e);
+            return -1;
+        }
+    }
     // 挂载
     if !mount_fat_fs(&device_path, &mount_path) {
         debug!("mount error");
@@ -565,6 +1098,14 @@ pub fn syscall_umount(dir: *const u8, flags: usize) -> isize {
         debug!("mount path not exist");
         return -1;
     }
+    // 只记录了挂载信息的挂载点不是真正的挂载点
+    match api::umount(mount_path.path()) {
+        Ok(()) | Err(AxError::InvalidInput) => {}
+        Err(e) => {
+            debug!("umount {} error: {:?}", mount_path.path(), e);
+            return -1;
+        }
+    }
     // 从挂载点中删除
     if !umount_fat_fs(&mount_path) {
         debug!("umount error");
@@ -574,6 +1115,61 @@ pub fn syscall_umount(dir: *const u8, flags: usize) -> isize {
     0
 }
 
+/// 功能：控制设备；
+/// 输入：
+///     - fd：设备的文件描述符；
+///     - request：请求码，目前支持回环设备的LOOP_SET_FD和LOOP_CLR_FD；
+///     - arg：请求的参数，对于LOOP_SET_FD为要绑定的文件的描述符；
+/// 返回值：成功返回0，失败返回-1。
+pub fn syscall_ioctl(fd: usize, request: usize, arg: usize) -> isize {
+    debug!("Into syscall_ioctl. fd: {}, request: {:#x}, arg: {:#x}", fd, request, arg);
+    let file = match get_file(fd) {
+        Some(file) => file,
+        None => return -1,
+    };
+    match request {
+        LOOP_SET_FD | LOOP_CLR_FD => {
+            let index = match file
+                .get_path()
+                .strip_prefix("/dev/loop")
+                .and_then(|index| index.parse::<usize>().ok())
+            {
+                Some(index) => index,
+                None => {
+                    debug!("fd {} is not a loop device", fd);
+                    return -1;
+                }
+            };
+            let result = if request == LOOP_SET_FD {
+                let backing = match get_file(arg) {
+                    Some(backing) => backing,
+                    None => return -1,
+                };
+                match backing.as_ref().as_any().downcast_ref::<FileDesc>() {
+                    Some(desc) => loopdev::attach(index, &desc.path, desc.flags.writable()),
+                    None => {
+                        debug!("fd {} is not a regular file", arg);
+                        return -1;
+                    }
+                }
+            } else {
+                loopdev::detach(index)
+            };
+            match result {
+                Ok(()) => 0,
+                Err(e) => {
+                    debug!("loop device ioctl error: {:?}", e);
+                    -1
+                }
+            }
+        }
+        _ => {
+            debug!("ioctl request {:#x} is not supported", request);
+            -1
+        }
+    }
+}
+
 /// 功能：获取文件状态；
 /// 输入：
 ///     - fd: 文件句柄；
@@ -604,20 +1200,13 @@ pub fn syscall_fstat(fd: usize, kst: *mut Kstat) -> isize {
     let process = current_process();
     let process_inner = process.inner.lock();
 
-    if fd >= process_inner.fd_table.len() || fd < 3 {
-        debug!("fd {} is out of range", fd);
-        return -1;
-    }
-    if process_inner.fd_table[fd].is_none() {
-        debug!("fd {} is none", fd);
-        return -1;
-    }
-    let file = process_inner.fd_table[fd].clone().unwrap();
-    if file.get_type() != "FileDesc" {
-        debug!("fd {} is not a file", fd);
+    let Some(Some(file)) = process_inner.fd_table.get(fd).cloned() else {
+        debug!("fd {} is not open", fd);
         return -1;
-    }
+    };
+    drop(process_inner);
 
+    // 文件类型由各自的get_stat给出, 不支持的类型返回Unsupported
     match file.get_stat() {
         Ok(stat) => {
             unsafe {
@@ -632,109 +1221,297 @@ pub fn syscall_fstat(fd: usize, kst: *mut Kstat) -> isize {
     }
 }
 
-// // ## sys_renameat2()
-// //
-// // sys_renameat2()是Linux内核中的一个系统调用,用于重命名文件或目录。与renameat()系统调用不同,它允许指定标志位来控制文件重命名的行为。它的原型如下:
-// //
-// // ```c
-// // int sys_renameat2(int olddirfd, const char *oldpath, int newdirfd, const char *newpath, unsigned int flags)
-// // ```
-// //
-// // \- olddirfd: 源文件路径名的目录文件描述符
-// // \- oldpath: 源文件路径名
-// // \- newdirfd: 目标文件路径名的目录文件描述符
-// // \- newpath: 目标文件路径名
-// // \- flags: 标志位,控制重命名行为flags可以设置以下值:- RENAME_NOREPLACE: 重命名失败,如果目标文件已经存在
-// // \- RENAME_EXCHANGE: 重命名交换两个文件
-// // \- RENAME_WHITEOUT: 创建目标文件并插入whiteout标记如果newpath已经存在,并且未指定RENAME_NOREPLACE标志,则新文件会替换旧文件。成功重命名文件后,sys_renameat2()返回0。失败时返回-1,并设置错误码。举个例子:
-// //
-// // ```c
-// // //重命名/tmp/file1为/tmp/file2,如果file2已存在则失败
-// // sys_renameat2(AT_FDCWD, "/tmp/file1", AT_FDCWD, "/tmp/file2", RENAME_NOREPLACE);
-// //
-// // //交换/tmp/file1和/tmp/file2
-// // sys_renameat2(AT_FDCWD, "/tmp/file1", AT_FDCWD, "/tmp/file2", RENAME_EXCHANGE);
-// // ```
-// //
-// // 这个示例演示了如何使用RENAME_NOREPLACE防止目标文件被替换,和使用RENAME_EXCHANGE交换两个文件的重命名。sys_renameat2()提供了更加灵活的文件重命名机制,通过标志位可以实现只有当目标文件不存在时才重命名、交换两个文件的重命名等功能。
-// pub fn syscall_renameat2(old_dirfd: usize, old_path: *const u8, new_dirfd: usize, new_path: *const u8, flags: usize) -> isize {
-//     let process = current_process();
-//     let mut process_inner = process.inner.lock();
-//
-//     let old_path = process_inner.memory_set.lock().translate_str(old_path);
-//     let new_path = process_inner.memory_set.lock().translate_str(new_path);
-//
-//     // 处理path
-//     let mut old_path_ = "".to_string();
-//     if !old_path.starts_with('/') {
-//         if old_dirfd == AT_FDCWD {
-//             old_path_ = api::canonicalize(old_path.as_str()).unwrap();
-//         }else{
-//             if old_dirfd >= process_inner.fd_table.len() || old_dirfd < 0 {
-//                 debug!("old_dirfd index out of range");
-//                 return -1;
-//             }
-//             if let Some(dir) = process_inner.fd_table[old_dirfd].as_ref() {
-//                 let dir = dir.clone();
-//                 old_path_ = format!("{}/{}", dir.get_path(), old_path);
-//             } else {
-//                 debug!("old_dirfd not exist");
-//                 return -1;
-//             }
-//         }
-//     }
-//     let mut new_path_ = "".to_string();
-//     if !new_path.starts_with('/') {
-//         if new_dirfd == AT_FDCWD {
-//             new_path_ = api::canonicalize(new_path.as_str()).unwrap();
-//         }else{
-//             if new_dirfd >= process_inner.fd_table.len() || new_dirfd < 0 {
-//                 debug!("old_dirfd index out of range");
-//                 return -1;
-//             }
-//             if let Some(dir) = process_inner.fd_table[new_dirfd].as_ref() {
-//                 let dir = dir.clone();
-//                 new_path_ = format!("{}/{}", dir.get_path(), new_path);
-//             } else {
-//                 debug!("old_dirfd not exist");
-//                 return -1;
-//             }
-//         }
-//     }
-//
-//     if flags == RENAME_NOREPLACE {
-//         if api::metadata(new_path_.as_str()).is_ok() {
-//             debug!("new_path_ already exist");
-//             return -1;
-//         }
-//     }
-//
-//     if flags == RENAME_EXCHANGE {
-//         let old_metadata = api::metadata(old_path_.as_str()).unwrap();
-//         let new_metadata = api::metadata(new_path_.as_str()).unwrap();
-//         if old_metadata.is_dir() != new_metadata.is_dir() {
-//             debug!("old_path_ and new_path_ is not the same type");
-//             return -1;
-//         }
-//     }
-//
-//     if flags == RENAME_WHITEOUT {
-//         let new_metadata = api::metadata(new_path_.as_str()).unwrap();
-//         if new_metadata.is_dir() {
-//             debug!("new_path_ is a directory");
-//             return -1;
-//         }
-//     }
-//
-//     if flags != RENAME_NOREPLACE && flags != RENAME_EXCHANGE && flags != RENAME_WHITEOUT {
-//         debug!("flags is not valid");
-//         return -1;
-//     }
-//
-//     if api::rename(old_path_.as_str(), new_path_.as_str()).is_err() {
-//         debug!("rename failed");
-//         return -1;
-//     }
-//
-//     0
-// }
+/// 辅助函数：当前进程进行文件权限检查时使用的身份，即有效用户ID、有效组ID和附加组
+pub(crate) fn current_identity() -> Identity {
+    let process = current_process();
+    let process_inner = process.inner.lock();
+    let cred = &process_inner.cred;
+    Identity {
+        uid: cred.euid,
+        gid: cred.egid,
+        groups: cred.groups.clone(),
+    }
+}
+
+/// 辅助函数：按照创建时指定的mode和当前进程的umask计算新节点的权限位
+fn creation_perm(mode: u32) -> Permissions {
+    let umask = current_process().inner.lock().umask;
+    Permissions::from_bits_truncate((mode & !umask & 0o7777) as u16)
+}
+
+/// 辅助函数：设置新建节点的权限位，并将所有者设为`id`。
+/// 不记录权限的文件系统(如FAT)不支持修改，忽略这种情况
+fn init_new_node(path: &str, perm: Permissions, id: &Identity) {
+    let results = [
+        api::set_permissions(path, perm),
+        api::set_owner(path, Some(id.uid), Some(id.gid)),
+    ];
+    for result in results {
+        match result {
+            Ok(()) | Err(AxError::Unsupported) => {}
+            Err(e) => debug!("init {} failed: {:?}", path, e),
+        }
+    }
+}
+
+/// 辅助函数：获取文件描述符对应的文件，文件描述符无效时返回None
+pub(crate) fn get_file(fd: usize) -> Option<Arc<dyn FileIO>> {
+    let process = current_process();
+    let process_inner = process.inner.lock();
+    process_inner.fd_table.get(fd).cloned().flatten()
+}
+
+/// 辅助函数：将普通文件的数据和目录项写回磁盘。只读打开的文件没有需要写回的内容，直接返回
+fn sync_file(file: &dyn FileIO) -> AxResult {
+    match file.as_any().downcast_ref::<FileDesc>() {
+        Some(file_desc) if file_desc.writable() => file_desc.file.lock().sync_all(),
+        _ => Ok(()),
+    }
+}
+
+/// 辅助函数：将所有进程打开的文件写回磁盘
+fn sync_all_files() {
+    // 先收集文件，写回磁盘时不能持有进程的锁
+    let mut files: Vec<Arc<dyn FileIO>> = Vec::new();
+    let processes: Vec<_> = PID2PC.lock().values().cloned().collect();
+    for process in processes {
+        let process_inner = process.inner.lock();
+        files.extend(process_inner.fd_table.iter().flatten().cloned());
+    }
+    for file in files {
+        if let Err(e) = sync_file(file.as_ref()) {
+            debug!("sync {} failed: {:?}", file.get_path(), e);
+        }
+    }
+    // 文件写回后数据可能还在块缓存中
+    if let Err(e) = axfs::api::sync() {
+        debug!("sync filesystems failed: {:?}", e);
+    }
+}
+
+/// 功能：重命名文件或目录；
+/// 输入：
+///     - old_dir_fd：原路径所在目录的文件描述符。
+///     - old_path：原路径。使用规则同linkat。
+///     - new_dir_fd：新路径所在目录的文件描述符。
+///     - new_path：新路径。使用规则同old_path。
+///     - flags：可以为0、RENAME_NOREPLACE(1)或RENAME_EXCHANGE(2)。
+///         - RENAME_NOREPLACE：新路径已存在时失败，而不是替换它。
+///         - RENAME_EXCHANGE：交换两个路径，两者都必须存在。不是原子的，不支持目录和跨文件系统的交换。
+/// 返回值：成功执行，返回0。失败，返回-1。
+///
+/// 说明：新旧路径必须位于同一个文件系统中。新路径已存在时，文件只能替换文件，目录只能替换空目录。
+pub fn syscall_renameat2(
+    old_dir_fd: usize,
+    old_path: *const u8,
+    new_dir_fd: usize,
+    new_path: *const u8,
+    flags: usize,
+) -> isize {
+    let (old_path, new_path) = match (
+        deal_with_path(old_dir_fd, Some(old_path), false),
+        deal_with_path(new_dir_fd, Some(new_path), false),
+    ) {
+        (Some(old_path), Some(new_path)) => (old_path, new_path),
+        _ => return -1,
+    };
+    debug!(
+        "Into syscall_renameat2. old_path: {}, new_path: {}, flags: {}",
+        old_path.path(),
+        new_path.path(),
+        flags
+    );
+    let flags = match RenameFlags::from_bits(flags as u32) {
+        Some(flags) => flags,
+        None => {
+            debug!("invalid rename flags: {:#x}", flags);
+            return -1;
+        }
+    };
+    match rename(&old_path, &new_path, flags) {
+        Ok(()) => 0,
+        Err(e) => {
+            debug!("rename error: {:?}", e);
+            -1
+        }
+    }
+}
+
+/// 功能：将文件截断或扩展到指定长度；
+/// 输入：
+///     - path：文件路径。
+///     - length：新的长度。扩展的部分填充0。
+/// 返回值：成功执行，返回0。失败，返回-1。
+pub fn syscall_truncate(path: *const u8, length: isize) -> isize {
+    let path = match deal_with_path(AT_FDCWD, Some(path), false) {
+        Some(path) => path,
+        None => return -1,
+    };
+    debug!("Into syscall_truncate. path: {}, length: {}", path.path(), length);
+    if length < 0 || path.is_dir() {
+        return -1;
+    }
+    let result = api::File::options()
+        .write(true)
+        .open(path.path())
+        .and_then(|file| file.set_len(length as u64));
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            debug!("truncate error: {:?}", e);
+            -1
+        }
+    }
+}
+
+/// 功能：将打开的文件截断或扩展到指定长度；
+/// 输入：
+///     - fd：文件描述符，必须以可写方式打开。
+///     - length：新的长度。扩展的部分填充0。
+/// 返回值：成功执行，返回0。失败，返回-1。
+pub fn syscall_ftruncate(fd: usize, length: isize) -> isize {
+    debug!("Into syscall_ftruncate. fd: {}, length: {}", fd, length);
+    if length < 0 {
+        return -1;
+    }
+    let file = match get_file(fd) {
+        Some(file) => file,
+        None => return -1,
+    };
+    let file_desc = match file.as_ref().as_any().downcast_ref::<FileDesc>() {
+        Some(file_desc) => file_desc,
+        None => {
+            debug!("fd {} is not a regular file", fd);
+            return -1;
+        }
+    };
+    if !file_desc.writable() {
+        return -1;
+    }
+    match file_desc.file.lock().set_len(length as u64) {
+        Ok(()) => 0,
+        Err(e) => {
+            debug!("ftruncate error: {:?}", e);
+            -1
+        }
+    }
+}
+
+/// 功能：将文件的数据和元数据写回磁盘；
+/// 输入：
+///     - fd：文件描述符。
+/// 返回值：成功执行，返回0。失败，返回-1。
+///
+/// 说明：管道等不支持同步的文件返回-1。
+pub fn syscall_fsync(fd: usize) -> isize {
+    debug!("Into syscall_fsync. fd: {}", fd);
+    let file = match get_file(fd) {
+        Some(file) => file,
+        None => return -1,
+    };
+    if !matches!(file.get_type().as_str(), "FileDesc" | "DirDesc") {
+        debug!("fd {} does not support synchronization", fd);
+        return -1;
+    }
+    match sync_file(file.as_ref()) {
+        Ok(()) => 0,
+        Err(e) => {
+            debug!("fsync error: {:?}", e);
+            -1
+        }
+    }
+}
+
+/// 功能：将文件的数据写回磁盘；
+/// 输入：
+///     - fd：文件描述符。
+/// 返回值：成功执行，返回0。失败，返回-1。
+///
+/// 说明：目前与fsync相同，总是同时写回元数据。
+pub fn syscall_fdatasync(fd: usize) -> isize {
+    syscall_fsync(fd)
+}
+
+/// 功能：将所有文件系统的缓存写回磁盘；
+/// 返回值：总是返回0。
+pub fn syscall_sync() -> isize {
+    debug!("Into syscall_sync");
+    sync_all_files();
+    0
+}
+
+/// 功能：将文件所在文件系统的缓存写回磁盘；
+/// 输入：
+///     - fd：文件系统中任一文件的文件描述符。
+/// 返回值：成功执行，返回0。失败，返回-1。
+///
+/// 说明：目前会写回所有文件系统。
+pub fn syscall_syncfs(fd: usize) -> isize {
+    debug!("Into syscall_syncfs. fd: {}", fd);
+    if get_file(fd).is_none() {
+        return -1;
+    }
+    sync_all_files();
+    0
+}
+
+/// 功能：修改文件的访问时间和修改时间；
+/// 输入：
+///     - dir_fd：文件所在目录的文件描述符。
+///     - path：文件路径，使用规则同openat。如为空指针，则修改dir_fd自身指向的文件(即futimens)。
+///     - times：两个timespec组成的数组，依次为访问时间和修改时间。如为空指针，则都设为当前时间。
+///         tv_nsec为UTIME_NOW时设为当前时间，为UTIME_OMIT时保持不变。
+///     - flags：可以为0或AT_SYMLINK_NOFOLLOW。目前没有符号链接，忽略。
+/// 返回值：成功执行，返回0。失败，返回-1。
+pub fn syscall_utimensat(
+    dir_fd: usize,
+    path: *const u8,
+    times: *const TimeSecs,
+    flags: usize,
+) -> isize {
+    let path = if path.is_null() {
+        match get_file(dir_fd) {
+            Some(file) => FilePath::new(file.get_path().as_str()),
+            None => return -1,
+        }
+    } else {
+        match deal_with_path(dir_fd, Some(path), false) {
+            Some(path) => path,
+            None => return -1,
+        }
+    };
+    debug!(
+        "Into syscall_utimensat. path: {}, times: {:?}, flags: {}",
+        path.path(),
+        times as usize,
+        flags
+    );
+    let now = wall_time();
+    let to_duration = |time: &TimeSecs| match time.tv_nsec {
+        UTIME_NOW => Some(now),
+        UTIME_OMIT => None,
+        nsec => Some(Duration::new(time.tv_sec as u64, nsec as u32)),
+    };
+    let (atime, mtime) = if times.is_null() {
+        (Some(now), Some(now))
+    } else {
+        let times = unsafe { core::slice::from_raw_parts(times, 2) };
+        let valid = |t: &TimeSecs| {
+            t.tv_nsec < 1_000_000_000 || t.tv_nsec == UTIME_NOW || t.tv_nsec == UTIME_OMIT
+        };
+        if !times.iter().all(valid) {
+            return -1;
+        }
+        (to_duration(&times[0]), to_duration(&times[1]))
+    };
+    if atime.is_none() && mtime.is_none() {
+        return 0;
+    }
+    match api::set_times(path.path(), atime, mtime) {
+        Ok(()) => 0,
+        Err(e) => {
+            debug!("utimensat error: {:?}", e);
+            -1
+        }
+    }
+}
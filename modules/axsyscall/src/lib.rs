@@ -1,10 +1,15 @@
 #![cfg_attr(not(test), no_std)]
 
+use axfs_os::epoll::EpollEvent;
 use axfs_os::types::Kstat;
-use flags::{MMAPFlags, TimeSecs, TimeVal, UtsName, WaitFlags, MMAPPROT, TMS};
+use flags::{MMAPFlags, MSyncFlags, PollFd, TimeSecs, TimeVal, UtsName, WaitFlags, MMAPPROT, TMS};
 use fs::*;
 use log::{debug, error, info};
-use mem::{syscall_brk, syscall_mmap, syscall_munmap};
+use mem::{syscall_brk, syscall_mmap, syscall_msync, syscall_munmap};
+use net::*;
+use poll::{
+    syscall_epoll_create1, syscall_epoll_ctl, syscall_epoll_pwait, syscall_ppoll, syscall_pselect6,
+};
 use task::*;
 
 extern crate axlog;
@@ -14,7 +19,12 @@ extern crate alloc;
 mod flags;
 mod fs;
 mod mem;
+mod net;
+mod poll;
+#[cfg(feature = "net")]
+mod socket;
 mod syscall_id;
+mod unix;
 #[allow(unused)]
 use syscall_id::*;
 
@@ -29,7 +39,7 @@ pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
             args[0],
             args[1] as *const u8,
             args[2] as usize,
-            args[3] as u8,
+            args[3] as u32,
         ), // args[0] is fd, args[1] is filename, args[2] is flags, args[3] is mode
         SYSCALL_CLOSE => syscall_close(args[0]), // args[0] is fd
         // SYSCALL_GETDENTS64 => syscall_getdents64(args[0], args[1] as *mut u8, args[2]),
@@ -45,6 +55,28 @@ pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
         SYSCALL_GETTIMEOFDAY => syscall_get_time_of_day(args[0] as *mut TimeVal),
         SYSCALL_GETPID => syscall_getpid(),
         SYSCALL_GETPPID => syscall_getppid(),
+        SYSCALL_GETUID => syscall_getuid(),
+        SYSCALL_GETEUID => syscall_geteuid(),
+        SYSCALL_GETGID => syscall_getgid(),
+        SYSCALL_GETEGID => syscall_getegid(),
+        SYSCALL_SETUID => syscall_setuid(args[0] as u32),
+        SYSCALL_SETGID => syscall_setgid(args[0] as u32),
+        SYSCALL_SETREUID => syscall_setreuid(args[0] as u32, args[1] as u32),
+        SYSCALL_SETREGID => syscall_setregid(args[0] as u32, args[1] as u32),
+        SYSCALL_SETRESUID => syscall_setresuid(args[0] as u32, args[1] as u32, args[2] as u32),
+        SYSCALL_GETRESUID => syscall_getresuid(
+            args[0] as *mut u32,
+            args[1] as *mut u32,
+            args[2] as *mut u32,
+        ),
+        SYSCALL_SETRESGID => syscall_setresgid(args[0] as u32, args[1] as u32, args[2] as u32),
+        SYSCALL_GETRESGID => syscall_getresgid(
+            args[0] as *mut u32,
+            args[1] as *mut u32,
+            args[2] as *mut u32,
+        ),
+        SYSCALL_GETGROUPS => syscall_getgroups(args[0], args[1] as *mut u32),
+        SYSCALL_SETGROUPS => syscall_setgroups(args[0], args[1] as *const u32),
         SYSCALL_WAIT4 => syscall_wait4(
             args[0] as isize,
             args[1] as *mut i32,
@@ -60,12 +92,34 @@ pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
             args[4] as i32,
             args[5],
         ),
+        SYSCALL_MSYNC => syscall_msync(
+            args[0],
+            args[1],
+            MSyncFlags::from_bits_truncate(args[2] as u32),
+        ),
         SYSCALL_GETCWD => syscall_getcwd(args[0] as *mut u8, args[1]),
-        SYSCALL_PIPE2 => syscall_pipe2(args[0] as *mut u32),
+        SYSCALL_PIPE2 => syscall_pipe2(args[0] as *mut u32, args[1]),
         SYSCALL_DUP => syscall_dup(args[0]),
         SYSCALL_DUP3 => syscall_dup3(args[0], args[1]),
+        SYSCALL_FCNTL => syscall_fcntl(args[0], args[1], args[2]),
+        SYSCALL_IOCTL => syscall_ioctl(args[0], args[1], args[2]),
+        SYSCALL_FLOCK => syscall_flock(args[0], args[1]),
+        SYSCALL_MKNODAT => syscall_mknodat(args[0], args[1] as *const u8, args[2] as u32, args[3]),
         SYSCALL_MKDIRAT => syscall_mkdirat(args[0], args[1] as *const u8, args[2] as u32),
         SYSCALL_CHDIR => syscall_chdir(args[0] as *const u8),
+        SYSCALL_FCHMOD => syscall_fchmod(args[0], args[1] as u32),
+        SYSCALL_FCHMODAT => {
+            syscall_fchmodat(args[0], args[1] as *const u8, args[2] as u32, args[3])
+        }
+        SYSCALL_FCHOWNAT => syscall_fchownat(
+            args[0],
+            args[1] as *const u8,
+            args[2] as u32,
+            args[3] as u32,
+            args[4],
+        ),
+        SYSCALL_FCHOWN => syscall_fchown(args[0], args[1] as u32, args[2] as u32),
+        SYSCALL_UMASK => syscall_umask(args[0] as u32),
         // SYSCALL_GETDENTS64 => syscall_getdents64(args[0], args[1] as *mut u8, args[2] as usize),
         SYSCALL_UNLINKAT => syscall_unlinkat(args[0], args[1] as *const u8, args[2] as usize),
         SYSCALL_MOUNT => syscall_mount(
@@ -77,6 +131,97 @@ pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
         ),
         SYSCALL_UNMOUNT => syscall_umount(args[0] as *const u8, args[1] as usize),
         SYSCALL_FSTAT => syscall_fstat(args[0], args[1] as *mut Kstat),
+        SYSCALL_TRUNCATE => syscall_truncate(args[0] as *const u8, args[1] as isize),
+        SYSCALL_FTRUNCATE => syscall_ftruncate(args[0], args[1] as isize),
+        SYSCALL_SYNC => syscall_sync(),
+        SYSCALL_FSYNC => syscall_fsync(args[0]),
+        SYSCALL_FDATASYNC => syscall_fdatasync(args[0]),
+        SYSCALL_SYNCFS => syscall_syncfs(args[0]),
+        SYSCALL_UTIMENSAT => syscall_utimensat(
+            args[0],
+            args[1] as *const u8,
+            args[2] as *const TimeSecs,
+            args[3],
+        ),
+        SYSCALL_RENAMEAT2 => syscall_renameat2(
+            args[0],
+            args[1] as *const u8,
+            args[2],
+            args[3] as *const u8,
+            args[4],
+        ),
+        SYSCALL_PPOLL => syscall_ppoll(
+            args[0] as *mut PollFd,
+            args[1],
+            args[2] as *const TimeSecs,
+            args[3],
+        ),
+        SYSCALL_PSELECT6 => syscall_pselect6(
+            args[0],
+            args[1] as *mut usize,
+            args[2] as *mut usize,
+            args[3] as *mut usize,
+            args[4] as *const TimeSecs,
+            args[5],
+        ),
+        SYSCALL_EPOLL_CREATE1 => syscall_epoll_create1(args[0]),
+        SYSCALL_EPOLL_CTL => syscall_epoll_ctl(
+            args[0],
+            args[1],
+            args[2],
+            args[3] as *const EpollEvent,
+        ),
+        SYSCALL_EPOLL_PWAIT => syscall_epoll_pwait(
+            args[0],
+            args[1] as *mut EpollEvent,
+            args[2],
+            args[3] as i32,
+            args[4],
+        ),
+        SYSCALL_SOCKET => syscall_socket(args[0], args[1], args[2]),
+        SYSCALL_SOCKETPAIR => syscall_socketpair(args[0], args[1], args[2], args[3] as *mut u32),
+        SYSCALL_BIND => syscall_bind(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_LISTEN => syscall_listen(args[0], args[1]),
+        SYSCALL_ACCEPT => syscall_accept4(args[0], args[1] as *mut u8, args[2] as *mut u32, 0),
+        SYSCALL_ACCEPT4 => {
+            syscall_accept4(args[0], args[1] as *mut u8, args[2] as *mut u32, args[3])
+        }
+        SYSCALL_CONNECT => syscall_connect(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_GETSOCKNAME => {
+            syscall_getsockname(args[0], args[1] as *mut u8, args[2] as *mut u32)
+        }
+        SYSCALL_GETPEERNAME => {
+            syscall_getpeername(args[0], args[1] as *mut u8, args[2] as *mut u32)
+        }
+        SYSCALL_SENDTO => syscall_sendto(
+            args[0],
+            args[1] as *const u8,
+            args[2],
+            args[3],
+            args[4] as *const u8,
+            args[5],
+        ),
+        SYSCALL_RECVFROM => syscall_recvfrom(
+            args[0],
+            args[1] as *mut u8,
+            args[2],
+            args[3],
+            args[4] as *mut u8,
+            args[5] as *mut u32,
+        ),
+        SYSCALL_SENDMSG => syscall_sendmsg(args[0], args[1] as *const u8, args[2]),
+        SYSCALL_RECVMSG => syscall_recvmsg(args[0], args[1] as *mut u8, args[2]),
+        SYSCALL_SETSOCKOPT => {
+            syscall_setsockopt(args[0], args[1], args[2], args[3] as *const u8, args[4])
+        }
+        SYSCALL_GETSOCKOPT => syscall_getsockopt(
+            args[0],
+            args[1],
+            args[2],
+            args[3] as *mut u8,
+            args[4] as *mut u32,
+        ),
+        SYSCALL_SHUTDOWN => syscall_shutdown(args[0], args[1]),
 
         _ => {
             error!("Invalid Syscall Id: {}!", syscall_id);
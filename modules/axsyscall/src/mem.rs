@@ -1,8 +1,9 @@
-use crate::flags::{MMAPFlags, MMAPPROT};
-use alloc::vec;
+use crate::flags::{MMAPFlags, MSyncFlags, MMAPPROT};
+use alloc::{vec, vec::Vec};
+use axfs_os::FileDesc;
 use axprocess::process::current_process;
-use log::info;
-use memory_addr::{align_down_4k, align_up_4k};
+use log::debug;
+use memory_addr::{align_down_4k, align_up_4k, PAGE_SIZE_4K};
 const MAX_HEAP_SIZE: usize = 4096;
 /// 修改用户堆大小，
 ///
@@ -29,6 +30,8 @@ pub fn syscall_brk(brk: usize) -> isize {
 /// len指定了映射文件的长度
 /// prot指定了页面的权限
 /// flags指定了映射的方法
+///
+/// MAP_SHARED的文件映射直接映射文件页缓存中的页帧，修改在msync、解除映射和关闭文件时写回
 pub fn syscall_mmap(
     start: usize,
     len: usize,
@@ -37,7 +40,7 @@ pub fn syscall_mmap(
     fd: i32,
     offest: usize,
 ) -> isize {
-    let len = align_up_4k(start + len);
+    let len = align_up_4k(start + len) - align_down_4k(start);
     let start = align_down_4k(start);
     // start为0代表自动分配起始地址
     // 不可以与MMAP_FIXED同时使用
@@ -46,49 +49,129 @@ pub fn syscall_mmap(
     }
     let random_pos = start == 0 || !flags.contains(MMAPFlags::MAP_FIXED);
     let curr_process = current_process();
-    let inner = curr_process.inner.lock();
     // 若是不要求实际映射到文件，则只需要为其分配一段物理空间即可
     if flags.contains(MMAPFlags::MAP_ANONYMOUS) {
         // 此时应当要求fd = -1, offset = 0。
-        if fd == -1 && offest == 0 {
-            inner
-                .memory_set
-                .lock()
-                .mmap(start.into(), len, prot.into(), random_pos, None)
-        } else {
-            drop(inner);
-            -1
-        }
-    } else {
-        if fd as usize >= inner.fd_table.len() || fd < 0 || inner.fd_table[fd as usize].is_none() {
-            drop(inner);
+        if fd != -1 || offest != 0 {
             return -1;
         }
-        // 为了进行映射，有以下几个步骤
-        // 一是读取文件内容，由于我们未实现懒分配，所以map时要把文件实际内容写入到物理页面中
-        // 二是为文件内容分配物理页面，若是任意寻找位置，则直接找一个大小适合的连续物理页面放进去即可
-        // 若是固定位置，则需要在固定位置处进行解映射，然后再进行映射。这个过程需要检查是否越界
-        if let Some(file) = &inner.fd_table[fd as usize] {
-            file.seek(offest).unwrap();
-            // 获取文件数据
-            let mut data = vec![0u8; len];
-            file.read(&mut data).unwrap();
-            inner
-                .memory_set
-                .lock()
-                .mmap(start.into(), len, prot.into(), random_pos, Some(&data))
-        } else {
+        let inner = curr_process.inner.lock();
+        let answer = inner
+            .memory_set
+            .lock()
+            .mmap(start.into(), len, prot.into(), random_pos, None);
+        drop(inner);
+        sync_unmapped_areas();
+        return answer;
+    }
+    let inner = curr_process.inner.lock();
+    let file = match inner.fd_table.get(fd as usize) {
+        Some(Some(file)) if fd >= 0 => file.clone(),
+        _ => return -1,
+    };
+    // 读取文件可能阻塞，不能持有进程的锁
+    drop(inner);
+    let cache = file
+        .as_ref()
+        .as_any()
+        .downcast_ref::<FileDesc>()
+        .and_then(|file_desc| file_desc.file.lock().page_cache());
+    if let (true, Some(cache)) = (flags.contains(MMAPFlags::MAP_SHARED), cache) {
+        // 共享的可写映射会写回文件, 文件必须以可写方式打开
+        if offest % PAGE_SIZE_4K != 0 || (prot.contains(MMAPPROT::PROT_WRITE) && !file.writable()) {
             return -1;
         }
+        let start_index = (offest / PAGE_SIZE_4K) as u64;
+        let mut pages = Vec::new();
+        for index in start_index..start_index + (len / PAGE_SIZE_4K) as u64 {
+            match cache.get_shared_page(index, prot.contains(MMAPPROT::PROT_WRITE)) {
+                Ok(page) => pages.push(page),
+                Err(_) => return -1,
+            }
+        }
+        let answer = curr_process.inner.lock().memory_set.lock().mmap_shared(
+            start.into(),
+            prot.into(),
+            random_pos,
+            cache,
+            start_index,
+            pages,
+        );
+        sync_unmapped_areas();
+        return answer;
+    }
+    // 为了进行映射，有以下几个步骤
+    // 一是读取文件内容，由于我们未实现懒分配，所以map时要把文件实际内容写入到物理页面中
+    // 二是为文件内容分配物理页面，若是任意寻找位置，则直接找一个大小适合的连续物理页面放进去即可
+    // 若是固定位置，则需要在固定位置处进行解映射，然后再进行映射。这个过程需要检查是否越界
+    if file.seek(offest).is_err() {
+        return -1;
+    }
+    // 获取文件数据
+    let mut data = vec![0u8; len];
+    if file.read(&mut data).is_err() {
+        return -1;
     }
+    let answer = curr_process
+        .inner
+        .lock()
+        .memory_set
+        .lock()
+        .mmap(start.into(), len, prot.into(), random_pos, Some(&data));
+    sync_unmapped_areas();
+    answer
 }
 
 pub fn syscall_munmap(start: usize, len: usize) -> isize {
-    let len = align_up_4k(start + len);
+    let len = align_up_4k(start + len) - align_down_4k(start);
     let start = align_down_4k(start);
     let curr_process = current_process();
     let inner = curr_process.inner.lock();
     let answer = inner.memory_set.lock().munmap(start.into(), len);
     drop(inner);
+    sync_unmapped_areas();
     answer
 }
+
+/// 功能：将共享文件映射中的修改写回文件；
+/// 输入：
+///     - start：映射区域的起始地址，必须按页对齐。
+///     - len：区域的长度。
+///     - flags：MS_ASYNC、MS_SYNC或MS_INVALIDATE。两种写回方式都会同步完成写回。
+/// 返回值：成功执行，返回0。失败，返回-1。
+pub fn syscall_msync(start: usize, len: usize, flags: MSyncFlags) -> isize {
+    debug!("Into syscall_msync. start: {:#x}, len: {}, flags: {:?}", start, len, flags);
+    if start % PAGE_SIZE_4K != 0 || flags.contains(MSyncFlags::MS_ASYNC | MSyncFlags::MS_SYNC) {
+        return -1;
+    }
+    let len = align_up_4k(len);
+    let ranges = current_process()
+        .inner
+        .lock()
+        .memory_set
+        .lock()
+        .shared_ranges(start.into(), len);
+    for (cache, start_index, count) in ranges {
+        if cache.sync_pages(start_index, count).is_err() {
+            return -1;
+        }
+    }
+    0
+}
+
+/// 辅助函数：写回当前进程中已解除映射的共享文件映射
+///
+/// 写回可能阻塞，必须在释放进程和地址空间的锁之后调用
+fn sync_unmapped_areas() {
+    let areas = current_process()
+        .inner
+        .lock()
+        .memory_set
+        .lock()
+        .take_unmapped_shared_areas();
+    for area in areas {
+        if let Err(e) = area.sync() {
+            debug!("sync unmapped area failed: {:?}", e);
+        }
+    }
+}
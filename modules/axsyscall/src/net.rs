@@ -0,0 +1,973 @@
+//! 处理与网络有关的系统调用
+//!
+//! 网络socket(AF_INET/AF_INET6)需要开启`net` feature, Unix域socket(AF_UNIX)总是可用。
+//! 这里的系统调用出错时返回负的Linux错误码(-errno), 用户程序可以据此区分EAGAIN、EINPROGRESS等情况。
+
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use axerrno::{AxError, AxResult, LinuxError};
+use axfs_os::file_io::FileIO;
+use axfs_os::unix::{ScmRights, UnixSocket, UnixSocketType};
+use axprocess::process::current_process;
+use core::mem::size_of;
+use log::debug;
+
+#[cfg(feature = "net")]
+use axnet::{IcmpSocket, TcpSocket};
+#[cfg(feature = "net")]
+use axsync::Mutex;
+#[cfg(feature = "net")]
+use core::time::Duration;
+
+#[cfg(feature = "net")]
+use crate::flags::TimeVal;
+use crate::fs::get_file;
+#[cfg(feature = "net")]
+use crate::socket::{read_sockaddr, write_sockaddr, Socket};
+use crate::unix::{read_sockaddr_un, write_sockaddr_un, AF_UNIX};
+
+/// 面向连接的字节流
+pub const SOCK_STREAM: usize = 1;
+/// 数据报
+pub const SOCK_DGRAM: usize = 2;
+/// 原始socket, 目前只支持ICMP
+pub const SOCK_RAW: usize = 3;
+/// socket类型中的标志位: 非阻塞
+pub const SOCK_NONBLOCK: usize = 0x800;
+/// socket类型中的标志位: 执行新程序时关闭
+pub const SOCK_CLOEXEC: usize = 0x80000;
+
+/// setsockopt/getsockopt的level: socket本身; 也是SCM_RIGHTS控制消息的level
+const SOL_SOCKET: usize = 1;
+/// 协议: ICMP
+pub const IPPROTO_ICMP: usize = 1;
+/// 协议: ICMPv6
+pub const IPPROTO_ICMPV6: usize = 58;
+/// setsockopt/getsockopt的level: TCP
+const IPPROTO_TCP: usize = 6;
+/// setsockopt/getsockopt的level: IPv6
+const IPPROTO_IPV6: usize = 41;
+/// 允许重用本地地址
+const SO_REUSEADDR: usize = 2;
+/// socket的类型, 只能读取
+const SO_TYPE: usize = 3;
+/// 待处理的错误, 只能读取
+const SO_ERROR: usize = 4;
+/// 发送缓冲区大小
+const SO_SNDBUF: usize = 7;
+/// 接收缓冲区大小
+const SO_RCVBUF: usize = 8;
+/// 保持连接
+const SO_KEEPALIVE: usize = 9;
+/// 接收超时
+#[cfg(feature = "net")]
+const SO_RCVTIMEO: usize = 20;
+/// 发送超时
+#[cfg(feature = "net")]
+const SO_SNDTIMEO: usize = 21;
+/// socket的协议族, 只能读取
+const SO_DOMAIN: usize = 39;
+/// 禁用Nagle算法
+const TCP_NODELAY: usize = 1;
+/// IPv6 socket只与IPv6地址通信
+const IPV6_V6ONLY: usize = 26;
+/// 控制消息的类型: 传递文件描述符
+const SCM_RIGHTS: i32 = 1;
+/// recvmsg返回的标志位: 控制消息被截断
+const MSG_CTRUNC: i32 = 0x8;
+/// recvmsg返回的标志位: 数据报被截断
+const MSG_TRUNC: i32 = 0x20;
+/// 本次操作不阻塞
+const MSG_DONTWAIT: usize = 0x40;
+/// 关闭接收方向
+const SHUT_RD: usize = 0;
+/// 关闭发送方向
+const SHUT_WR: usize = 1;
+/// 同时关闭接收和发送方向
+const SHUT_RDWR: usize = 2;
+/// 打开SO_KEEPALIVE时, 连接空闲多久后开始发送保活报文, 与Linux的默认值相同
+#[cfg(feature = "net")]
+const TCP_KEEPALIVE_IDLE: Duration = Duration::from_secs(7200);
+
+/// 用户态的`struct iovec`
+#[repr(C)]
+struct IoVec {
+    base: *mut u8,
+    len: usize,
+}
+
+/// 用户态的`struct msghdr`, 与musl的布局一致, 长度字段为32位
+#[repr(C)]
+struct MsgHdr {
+    name: *mut u8,
+    name_len: u32,
+    _pad0: u32,
+    iov: *mut IoVec,
+    iov_len: u32,
+    _pad1: u32,
+    control: *mut u8,
+    control_len: u32,
+    _pad2: u32,
+    flags: i32,
+}
+
+/// 用户态的`struct cmsghdr`, 数据紧跟其后
+#[repr(C)]
+struct CmsgHdr {
+    len: u32,
+    _pad: u32,
+    level: i32,
+    cmsg_type: i32,
+}
+
+/// 文件描述符对应的socket
+enum SocketFile<'a> {
+    /// 网络socket
+    #[cfg(feature = "net")]
+    Inet(&'a Socket),
+    /// Unix域socket
+    Unix(&'a UnixSocket),
+}
+
+impl SocketFile<'_> {
+    /// socket的协议族
+    fn domain(&self) -> usize {
+        match self {
+            #[cfg(feature = "net")]
+            SocketFile::Inet(socket) => socket.domain(),
+            SocketFile::Unix(_) => AF_UNIX,
+        }
+    }
+
+    /// socket的类型, 不含标志位
+    fn socket_type(&self) -> usize {
+        match self {
+            #[cfg(feature = "net")]
+            SocketFile::Inet(socket) => socket.socket_type(),
+            SocketFile::Unix(socket) => match socket.socket_type() {
+                UnixSocketType::Stream => SOCK_STREAM,
+                UnixSocketType::Dgram => SOCK_DGRAM,
+            },
+        }
+    }
+
+    /// TCP socket, 其他socket返回`None`
+    #[cfg(feature = "net")]
+    fn tcp(&self) -> Option<&Mutex<TcpSocket>> {
+        match self {
+            SocketFile::Inet(socket) => socket.tcp(),
+            SocketFile::Unix(_) => None,
+        }
+    }
+
+    /// ICMP socket, 其他socket返回`None`
+    #[cfg(feature = "net")]
+    fn icmp(&self) -> Option<&IcmpSocket> {
+        match self {
+            SocketFile::Inet(socket) => socket.icmp(),
+            SocketFile::Unix(_) => None,
+        }
+    }
+}
+
+/// 辅助函数：获取文件描述符对应的socket，交给`f`处理
+fn with_socket<R>(fd: usize, f: impl FnOnce(SocketFile) -> AxResult<R>) -> AxResult<R> {
+    let file = match get_file(fd) {
+        Some(file) => file,
+        None => {
+            debug!("fd {} is not opened", fd);
+            return Err(AxError::InvalidInput);
+        }
+    };
+    let file = file.as_ref().as_any();
+    #[cfg(feature = "net")]
+    if let Some(socket) = file.downcast_ref::<Socket>() {
+        return f(SocketFile::Inet(socket));
+    }
+    match file.downcast_ref::<UnixSocket>() {
+        Some(socket) => f(SocketFile::Unix(socket)),
+        None => {
+            debug!("fd {} is not a socket", fd);
+            Err(AxError::InvalidInput)
+        }
+    }
+}
+
+/// 辅助函数：将socket放入文件描述符表，返回文件描述符
+fn add_socket<T: FileIO + 'static>(socket: T) -> usize {
+    let process = current_process();
+    let mut process_inner = process.inner.lock();
+    let fd = process_inner.alloc_fd();
+    process_inner.fd_table[fd] = Some(Arc::new(socket) as Arc<dyn FileIO>);
+    fd
+}
+
+/// 辅助函数：将系统调用的结果转换为返回值，出错时返回负的错误码
+fn syscall_ret(name: &str, result: AxResult<usize>) -> isize {
+    match result {
+        Ok(ret) => ret as isize,
+        Err(e) => {
+            debug!("{} failed: {:?}", name, e);
+            -(LinuxError::from(e) as isize)
+        }
+    }
+}
+
+/// 辅助函数：处理不支持的socket选项，其中一些常用的选项被接受但不起作用
+fn ignore_option(level: usize, optname: usize) -> AxResult {
+    match (level, optname) {
+        (SOL_SOCKET, SO_REUSEADDR | SO_KEEPALIVE | SO_SNDBUF | SO_RCVBUF)
+        | (IPPROTO_TCP, TCP_NODELAY)
+        | (IPPROTO_IPV6, IPV6_V6ONLY) => {
+            debug!("socket option {} at level {} is ignored", optname, level);
+            Ok(())
+        }
+        _ => Err(AxError::Unsupported),
+    }
+}
+
+/// 辅助函数：从用户态读取选项值
+#[cfg(feature = "net")]
+unsafe fn read_option<T>(optval: *const u8, optlen: usize) -> AxResult<T> {
+    if optval.is_null() || optlen < size_of::<T>() {
+        return Err(AxError::InvalidInput);
+    }
+    Ok((optval as *const T).read_unaligned())
+}
+
+/// 辅助函数：从用户态读取`struct timeval`类型的超时时间，0表示永不超时
+#[cfg(feature = "net")]
+unsafe fn read_timeout_option(optval: *const u8, optlen: usize) -> AxResult<Option<Duration>> {
+    let tv = read_option::<TimeVal>(optval, optlen)?;
+    if tv.usec >= 1_000_000 {
+        return Err(AxError::InvalidInput);
+    }
+    let timeout = Duration::new(tv.sec as u64, tv.usec as u32 * 1000);
+    Ok((!timeout.is_zero()).then_some(timeout))
+}
+
+/// 辅助函数：将超时时间转换为`struct timeval`，永不超时为0
+#[cfg(feature = "net")]
+fn to_timeval(timeout: Option<Duration>) -> TimeVal {
+    let timeout = timeout.unwrap_or_default();
+    TimeVal {
+        sec: timeout.as_secs() as usize,
+        usec: timeout.subsec_micros() as usize,
+    }
+}
+
+/// 辅助函数：将选项值写入用户态缓冲区，超出缓冲区的部分被截断
+///
+/// `optlen`输入时为缓冲区的长度，返回时为选项值的实际长度。
+unsafe fn write_option<T>(optval: *mut u8, optlen: *mut u32, value: &T) -> AxResult {
+    if optval.is_null() || optlen.is_null() {
+        return Err(AxError::InvalidInput);
+    }
+    let len = (*optlen as usize).min(size_of::<T>());
+    core::ptr::copy_nonoverlapping(value as *const T as *const u8, optval, len);
+    *optlen = size_of::<T>() as u32;
+    Ok(())
+}
+
+/// 辅助函数：设置TCP socket的选项
+#[cfg(feature = "net")]
+unsafe fn set_tcp_option(
+    socket: &TcpSocket,
+    level: usize,
+    optname: usize,
+    optval: *const u8,
+    optlen: usize,
+) -> AxResult {
+    match (level, optname) {
+        (SOL_SOCKET, SO_REUSEADDR) => {
+            socket.set_reuse_address(read_option::<i32>(optval, optlen)? != 0);
+            Ok(())
+        }
+        (SOL_SOCKET, SO_KEEPALIVE) => {
+            let keep_alive = read_option::<i32>(optval, optlen)? != 0;
+            socket.set_keep_alive(keep_alive.then_some(TCP_KEEPALIVE_IDLE))
+        }
+        (SOL_SOCKET, SO_SNDBUF) => {
+            socket.set_send_buffer_size(read_option::<i32>(optval, optlen)?.max(0) as usize)
+        }
+        (SOL_SOCKET, SO_RCVBUF) => {
+            socket.set_recv_buffer_size(read_option::<i32>(optval, optlen)?.max(0) as usize)
+        }
+        (SOL_SOCKET, SO_SNDTIMEO) => {
+            socket.set_send_timeout(read_timeout_option(optval, optlen)?);
+            Ok(())
+        }
+        (SOL_SOCKET, SO_RCVTIMEO) => {
+            socket.set_recv_timeout(read_timeout_option(optval, optlen)?);
+            Ok(())
+        }
+        (IPPROTO_TCP, TCP_NODELAY) => socket.set_nodelay(read_option::<i32>(optval, optlen)? != 0),
+        _ => ignore_option(level, optname),
+    }
+}
+
+/// 辅助函数：读取TCP socket的选项
+#[cfg(feature = "net")]
+unsafe fn get_tcp_option(
+    socket: &TcpSocket,
+    level: usize,
+    optname: usize,
+    optval: *mut u8,
+    optlen: *mut u32,
+) -> AxResult {
+    let value = match (level, optname) {
+        (SOL_SOCKET, SO_REUSEADDR) => socket.reuse_address() as i32,
+        (SOL_SOCKET, SO_KEEPALIVE) => socket.keep_alive().is_some() as i32,
+        (SOL_SOCKET, SO_SNDBUF) => socket.send_buffer_size() as i32,
+        (SOL_SOCKET, SO_RCVBUF) => socket.recv_buffer_size() as i32,
+        (SOL_SOCKET, SO_SNDTIMEO) => {
+            return write_option(optval, optlen, &to_timeval(socket.send_timeout()))
+        }
+        (SOL_SOCKET, SO_RCVTIMEO) => {
+            return write_option(optval, optlen, &to_timeval(socket.recv_timeout()))
+        }
+        (IPPROTO_TCP, TCP_NODELAY) => socket.nodelay() as i32,
+        _ => return Err(AxError::Unsupported),
+    };
+    write_option(optval, optlen, &value)
+}
+
+/// 辅助函数：设置ICMP socket的选项，只支持SO_RCVTIMEO
+#[cfg(feature = "net")]
+unsafe fn set_icmp_option(
+    socket: &IcmpSocket,
+    level: usize,
+    optname: usize,
+    optval: *const u8,
+    optlen: usize,
+) -> AxResult {
+    match (level, optname) {
+        (SOL_SOCKET, SO_RCVTIMEO) => {
+            socket.set_recv_timeout(read_timeout_option(optval, optlen)?);
+            Ok(())
+        }
+        _ => ignore_option(level, optname),
+    }
+}
+
+/// 辅助函数：读取ICMP socket的选项，只支持SO_RCVTIMEO
+#[cfg(feature = "net")]
+unsafe fn get_icmp_option(
+    socket: &IcmpSocket,
+    level: usize,
+    optname: usize,
+    optval: *mut u8,
+    optlen: *mut u32,
+) -> AxResult {
+    match (level, optname) {
+        (SOL_SOCKET, SO_RCVTIMEO) => {
+            write_option(optval, optlen, &to_timeval(socket.recv_timeout()))
+        }
+        _ => Err(AxError::Unsupported),
+    }
+}
+
+/// 辅助函数：解析Unix域socket的类型，同时返回是否带有SOCK_NONBLOCK
+fn unix_socket_type(socket_type: usize) -> AxResult<(UnixSocketType, bool)> {
+    let nonblock = socket_type & SOCK_NONBLOCK != 0;
+    match socket_type & !(SOCK_NONBLOCK | SOCK_CLOEXEC) {
+        SOCK_STREAM => Ok((UnixSocketType::Stream, nonblock)),
+        SOCK_DGRAM => Ok((UnixSocketType::Dgram, nonblock)),
+        _ => Err(AxError::Unsupported),
+    }
+}
+
+/// 辅助函数：获取用户态的iovec数组
+unsafe fn iovecs<'a>(iov: *const IoVec, iov_len: usize) -> &'a [IoVec] {
+    if iov.is_null() || iov_len == 0 {
+        &[]
+    } else {
+        core::slice::from_raw_parts(iov, iov_len)
+    }
+}
+
+/// 辅助函数：控制消息按`usize`对齐后的长度
+fn cmsg_align(len: usize) -> usize {
+    (len + size_of::<usize>() - 1) & !(size_of::<usize>() - 1)
+}
+
+/// 辅助函数：从控制消息中取出SCM_RIGHTS传递的文件，其他类型的控制消息被忽略
+unsafe fn read_scm_rights(control: *const u8, control_len: usize) -> AxResult<ScmRights> {
+    let mut rights = Vec::new();
+    if control.is_null() {
+        return Ok(rights);
+    }
+    let header_len = size_of::<CmsgHdr>();
+    let mut offset = 0;
+    while offset + header_len <= control_len {
+        let header = (control.add(offset) as *const CmsgHdr).read_unaligned();
+        let len = header.len as usize;
+        if len < header_len || offset + len > control_len {
+            return Err(AxError::InvalidInput);
+        }
+        if header.level == SOL_SOCKET as i32 && header.cmsg_type == SCM_RIGHTS {
+            let fds = control.add(offset + header_len) as *const i32;
+            for i in 0..(len - header_len) / size_of::<i32>() {
+                let fd = fds.add(i).read_unaligned();
+                rights.push(get_file(fd as usize).ok_or(AxError::InvalidInput)?);
+            }
+        }
+        offset += cmsg_align(len);
+    }
+    Ok(rights)
+}
+
+/// 辅助函数：把收到的文件放入文件描述符表，并写成SCM_RIGHTS控制消息
+///
+/// 返回控制消息的长度和是否被截断。放不下的文件被关闭。
+unsafe fn write_scm_rights(
+    control: *mut u8,
+    control_len: usize,
+    mut rights: ScmRights,
+) -> (usize, bool) {
+    if rights.is_empty() {
+        return (0, false);
+    }
+    let header_len = size_of::<CmsgHdr>();
+    let max = if control.is_null() || control_len < header_len {
+        0
+    } else {
+        (control_len - header_len) / size_of::<i32>()
+    };
+    let truncated = rights.len() > max;
+    // 在取得进程的锁之前关闭放不下的文件
+    drop(rights.split_off(max.min(rights.len())));
+    if rights.is_empty() {
+        return (0, truncated);
+    }
+
+    let fds = control.add(header_len) as *mut i32;
+    let count = rights.len();
+    let process = current_process();
+    let mut process_inner = process.inner.lock();
+    for (i, file) in rights.into_iter().enumerate() {
+        let fd = process_inner.alloc_fd();
+        process_inner.fd_table[fd] = Some(file);
+        fds.add(i).write_unaligned(fd as i32);
+    }
+    let len = header_len + count * size_of::<i32>();
+    (control as *mut CmsgHdr).write_unaligned(CmsgHdr {
+        len: len as u32,
+        _pad: 0,
+        level: SOL_SOCKET as i32,
+        cmsg_type: SCM_RIGHTS,
+    });
+    (cmsg_align(len).min(control_len), truncated)
+}
+
+/// 功能：创建一个socket；
+/// 输入：
+///     - domain：协议族，支持AF_UNIX、AF_INET和AF_INET6，后两者需要开启`net` feature。
+///     - socket_type：socket类型，支持SOCK_STREAM和SOCK_DGRAM，网络socket还支持SOCK_RAW，
+///       可以与SOCK_NONBLOCK、SOCK_CLOEXEC组合。
+///     - protocol：协议，为0时根据类型自动选择；SOCK_RAW的socket必须为IPPROTO_ICMP(AF_INET)或IPPROTO_ICMPV6(AF_INET6)。
+/// 返回值：成功执行，返回新的文件描述符。失败，返回负的错误码。
+pub fn syscall_socket(domain: usize, socket_type: usize, protocol: usize) -> isize {
+    debug!(
+        "Into syscall_socket. domain: {}, type: {:#x}, protocol: {}",
+        domain, socket_type, protocol
+    );
+    let result = match domain {
+        AF_UNIX => unix_socket_type(socket_type)
+            .map(|(socket_type, nonblock)| add_socket(UnixSocket::new(socket_type, nonblock))),
+        #[cfg(feature = "net")]
+        _ => Socket::new(domain, socket_type, protocol).map(add_socket),
+        #[cfg(not(feature = "net"))]
+        _ => Err(AxError::Unsupported),
+    };
+    syscall_ret("socket", result)
+}
+
+/// 功能：创建一对互相连接的socket；
+/// 输入：
+///     - domain：协议族，只支持AF_UNIX。
+///     - socket_type：socket类型，支持SOCK_STREAM和SOCK_DGRAM，可以与SOCK_NONBLOCK、SOCK_CLOEXEC组合。
+///     - protocol：协议，必须为0。
+///     - fds：写入两个文件描述符的数组。
+/// 返回值：成功执行，返回0。失败，返回负的错误码。
+pub fn syscall_socketpair(
+    domain: usize,
+    socket_type: usize,
+    protocol: usize,
+    fds: *mut u32,
+) -> isize {
+    debug!(
+        "Into syscall_socketpair. domain: {}, type: {:#x}, protocol: {}",
+        domain, socket_type, protocol
+    );
+    let result = match domain {
+        AF_UNIX if protocol == 0 && !fds.is_null() => {
+            unix_socket_type(socket_type).map(|(socket_type, nonblock)| {
+                let (socket1, socket2) = UnixSocket::pair(socket_type, nonblock);
+                let (fd1, fd2) = (add_socket(socket1), add_socket(socket2));
+                unsafe {
+                    core::ptr::write(fds, fd1 as u32);
+                    core::ptr::write(fds.offset(1), fd2 as u32);
+                }
+                0
+            })
+        }
+        AF_UNIX => Err(AxError::InvalidInput),
+        _ => Err(AxError::Unsupported),
+    };
+    syscall_ret("socketpair", result)
+}
+
+/// 功能：为socket绑定本地地址；
+/// 输入：
+///     - fd：socket的文件描述符。
+///     - addr：要绑定的地址，为`struct sockaddr`的指针。
+///     - addrlen：地址的长度。
+/// 返回值：成功执行，返回0。失败，返回负的错误码。
+///
+/// 说明：Unix域socket绑定路径时在文件系统中创建socket文件，路径已存在时失败；地址只有协议族时自动分配抽象名字。
+pub fn syscall_bind(fd: usize, addr: *const u8, addrlen: usize) -> isize {
+    debug!("Into syscall_bind. fd: {}", fd);
+    let result = with_socket(fd, |socket| {
+        match socket {
+            #[cfg(feature = "net")]
+            SocketFile::Inet(socket) => socket.bind(unsafe { read_sockaddr(addr, addrlen)? })?,
+            SocketFile::Unix(socket) => socket.bind(unsafe { read_sockaddr_un(addr, addrlen)? })?,
+        }
+        Ok(0)
+    });
+    syscall_ret("bind", result)
+}
+
+/// 功能：开始在socket上监听连接；
+/// 输入：
+///     - fd：socket的文件描述符。
+///     - backlog：等待接受的连接数上限，网络socket目前忽略。
+/// 返回值：成功执行，返回0。失败，返回负的错误码。
+pub fn syscall_listen(fd: usize, backlog: usize) -> isize {
+    debug!("Into syscall_listen. fd: {}, backlog: {}", fd, backlog);
+    let result = with_socket(fd, |socket| {
+        match socket {
+            #[cfg(feature = "net")]
+            SocketFile::Inet(socket) => socket.listen()?,
+            SocketFile::Unix(socket) => socket.listen(backlog)?,
+        }
+        Ok(0)
+    });
+    syscall_ret("listen", result)
+}
+
+/// 功能：接受socket上的一个连接；
+/// 输入：
+///     - fd：监听的socket的文件描述符。
+///     - addr：如不为空指针，写入对端的地址。
+///     - addrlen：输入时为addr缓冲区的长度，返回时为地址的实际长度。
+///     - flags：新socket的标志位，可以是SOCK_NONBLOCK、SOCK_CLOEXEC的组合。
+/// 返回值：成功执行，返回新连接的文件描述符。失败，返回负的错误码。
+///
+/// 说明：accept等价于flags为0的accept4。
+pub fn syscall_accept4(fd: usize, addr: *mut u8, addrlen: *mut u32, flags: usize) -> isize {
+    debug!("Into syscall_accept4. fd: {}, flags: {:#x}", fd, flags);
+    let result = with_socket(fd, |socket| match socket {
+        #[cfg(feature = "net")]
+        SocketFile::Inet(socket) => {
+            let new_socket = socket.accept()?;
+            new_socket.set_nonblocking(flags & SOCK_NONBLOCK != 0);
+            if let Ok(peer_addr) = new_socket.peer_addr() {
+                unsafe { write_sockaddr(addr, addrlen, peer_addr, socket.domain())? };
+            }
+            Ok(add_socket(new_socket))
+        }
+        SocketFile::Unix(socket) => {
+            let new_socket = socket.accept()?;
+            new_socket.set_nonblocking(flags & SOCK_NONBLOCK != 0);
+            unsafe { write_sockaddr_un(addr, addrlen, &new_socket.peer_addr()?)? };
+            Ok(add_socket(new_socket))
+        }
+    });
+    syscall_ret("accept4", result)
+}
+
+/// 功能：将socket连接到指定地址；
+/// 输入：
+///     - fd：socket的文件描述符。
+///     - addr：要连接的地址，为`struct sockaddr`的指针。
+///     - addrlen：地址的长度。
+/// 返回值：成功执行，返回0。失败，返回负的错误码。
+///
+/// 说明：TCP socket在连接建立或失败后才返回；非阻塞的TCP socket发起连接后立即返回EINPROGRESS，连接在后台继续进行，
+/// 建立后socket变为可写。UDP socket只记录默认的对端地址。
+/// Unix域流socket进入监听socket的等待队列后即返回，队列已满时等待，非阻塞时返回EAGAIN。
+pub fn syscall_connect(fd: usize, addr: *const u8, addrlen: usize) -> isize {
+    debug!("Into syscall_connect. fd: {}", fd);
+    let result = with_socket(fd, |socket| {
+        match socket {
+            #[cfg(feature = "net")]
+            SocketFile::Inet(socket) => socket.connect(unsafe { read_sockaddr(addr, addrlen)? })?,
+            SocketFile::Unix(socket) => {
+                socket.connect(&unsafe { read_sockaddr_un(addr, addrlen)? })?
+            }
+        }
+        Ok(0)
+    });
+    syscall_ret("connect", result)
+}
+
+/// 功能：获取socket绑定的本地地址；
+/// 输入：
+///     - fd：socket的文件描述符。
+///     - addr：写入地址的缓冲区。
+///     - addrlen：输入时为addr缓冲区的长度，返回时为地址的实际长度。
+/// 返回值：成功执行，返回0。失败，返回负的错误码。
+pub fn syscall_getsockname(fd: usize, addr: *mut u8, addrlen: *mut u32) -> isize {
+    debug!("Into syscall_getsockname. fd: {}", fd);
+    let result = with_socket(fd, |socket| {
+        match socket {
+            #[cfg(feature = "net")]
+            SocketFile::Inet(socket) => unsafe {
+                write_sockaddr(addr, addrlen, socket.local_addr()?, socket.domain())?
+            },
+            SocketFile::Unix(socket) => unsafe {
+                write_sockaddr_un(addr, addrlen, &socket.local_addr())?
+            },
+        }
+        Ok(0)
+    });
+    syscall_ret("getsockname", result)
+}
+
+/// 功能：获取socket所连接的对端地址；
+/// 输入：
+///     - fd：socket的文件描述符。
+///     - addr：写入地址的缓冲区。
+///     - addrlen：输入时为addr缓冲区的长度，返回时为地址的实际长度。
+/// 返回值：成功执行，返回0。失败（包括socket未连接），返回负的错误码。
+pub fn syscall_getpeername(fd: usize, addr: *mut u8, addrlen: *mut u32) -> isize {
+    debug!("Into syscall_getpeername. fd: {}", fd);
+    let result = with_socket(fd, |socket| {
+        match socket {
+            #[cfg(feature = "net")]
+            SocketFile::Inet(socket) => unsafe {
+                write_sockaddr(addr, addrlen, socket.peer_addr()?, socket.domain())?
+            },
+            SocketFile::Unix(socket) => unsafe {
+                write_sockaddr_un(addr, addrlen, &socket.peer_addr()?)?
+            },
+        }
+        Ok(0)
+    });
+    syscall_ret("getpeername", result)
+}
+
+/// 功能：通过socket发送数据；
+/// 输入：
+///     - fd：socket的文件描述符。
+///     - buf：要发送的数据。
+///     - len：数据的长度。
+///     - flags：目前只支持MSG_DONTWAIT。
+///     - addr：数据报的目标地址，为空指针时发送给连接的对端。对于TCP socket忽略。
+///     - addrlen：目标地址的长度。
+/// 返回值：成功执行，返回发送的字节数。失败，返回负的错误码。
+pub fn syscall_sendto(
+    fd: usize,
+    buf: *const u8,
+    len: usize,
+    flags: usize,
+    addr: *const u8,
+    addrlen: usize,
+) -> isize {
+    debug!(
+        "Into syscall_sendto. fd: {}, len: {}, flags: {:#x}",
+        fd, len, flags
+    );
+    let result = with_socket(fd, |socket| {
+        let buf = unsafe { core::slice::from_raw_parts(buf, len) };
+        let nonblock = flags & MSG_DONTWAIT != 0;
+        match socket {
+            #[cfg(feature = "net")]
+            SocketFile::Inet(socket) => {
+                let to = if addr.is_null() {
+                    None
+                } else {
+                    Some(unsafe { read_sockaddr(addr, addrlen)? })
+                };
+                socket.send_to(buf, to, nonblock)
+            }
+            SocketFile::Unix(socket) => {
+                let to = if addr.is_null() {
+                    None
+                } else {
+                    Some(unsafe { read_sockaddr_un(addr, addrlen)? })
+                };
+                socket.send_msg(buf, Vec::new(), to.as_ref(), nonblock)
+            }
+        }
+    });
+    syscall_ret("sendto", result)
+}
+
+/// 功能：从socket接收数据；
+/// 输入：
+///     - fd：socket的文件描述符。
+///     - buf：接收数据的缓冲区。
+///     - len：缓冲区的长度。
+///     - flags：目前只支持MSG_DONTWAIT。
+///     - addr：如不为空指针，写入发送方的地址。
+///     - addrlen：输入时为addr缓冲区的长度，返回时为地址的实际长度。
+/// 返回值：成功执行，返回接收的字节数，对端关闭连接时返回0。失败，返回负的错误码。
+pub fn syscall_recvfrom(
+    fd: usize,
+    buf: *mut u8,
+    len: usize,
+    flags: usize,
+    addr: *mut u8,
+    addrlen: *mut u32,
+) -> isize {
+    debug!(
+        "Into syscall_recvfrom. fd: {}, len: {}, flags: {:#x}",
+        fd, len, flags
+    );
+    let result = with_socket(fd, |socket| {
+        let buf = unsafe { core::slice::from_raw_parts_mut(buf, len) };
+        let nonblock = flags & MSG_DONTWAIT != 0;
+        match socket {
+            #[cfg(feature = "net")]
+            SocketFile::Inet(socket) => {
+                let (len, from) = socket.recv_from(buf, nonblock)?;
+                if let Some(from) = from {
+                    unsafe { write_sockaddr(addr, addrlen, from, socket.domain())? };
+                }
+                Ok(len)
+            }
+            SocketFile::Unix(socket) => {
+                // 随数据到达的文件在这里被关闭
+                let msg = socket.recv_msg(buf, nonblock)?;
+                unsafe { write_sockaddr_un(addr, addrlen, &msg.from)? };
+                Ok(msg.len)
+            }
+        }
+    });
+    syscall_ret("recvfrom", result)
+}
+
+/// 功能：关闭socket的连接；
+/// 输入：
+///     - fd：socket的文件描述符。
+///     - how：SHUT_RD关闭接收方向，SHUT_WR关闭发送方向，SHUT_RDWR关闭两个方向。
+/// 返回值：成功执行，返回0。失败，返回负的错误码。
+///
+/// 说明：网络socket关闭接收方向时没有额外的操作，之后仍然可以接收已到达的数据；
+/// Unix域socket关闭接收方向后，对端的写入失败。
+pub fn syscall_shutdown(fd: usize, how: usize) -> isize {
+    debug!("Into syscall_shutdown. fd: {}, how: {}", fd, how);
+    let result = with_socket(fd, |socket| {
+        if how > SHUT_RDWR {
+            return Err(AxError::InvalidInput);
+        }
+        match socket {
+            #[cfg(feature = "net")]
+            SocketFile::Inet(socket) => {
+                if how != SHUT_RD {
+                    socket.shutdown()?;
+                }
+            }
+            SocketFile::Unix(socket) => socket.shutdown(how != SHUT_WR, how != SHUT_RD)?,
+        }
+        Ok(0)
+    });
+    syscall_ret("shutdown", result)
+}
+
+/// 功能：设置socket选项；
+/// 输入：
+///     - fd：socket的文件描述符。
+///     - level：选项所在的协议层，支持SOL_SOCKET、IPPROTO_TCP和IPPROTO_IPV6。
+///     - optname：选项名。
+///     - optval：选项值的指针。
+///     - optlen：选项值的长度。
+/// 返回值：成功执行，返回0。失败，返回负的错误码。
+///
+/// 说明：TCP socket支持SO_REUSEADDR、SO_KEEPALIVE、SO_SNDBUF、SO_RCVBUF、SO_SNDTIMEO、SO_RCVTIMEO和TCP_NODELAY，
+/// 缓冲区大小只能在连接之前或者在监听的socket上修改，对之后接受的连接生效。
+/// ICMP socket支持SO_RCVTIMEO。
+/// 其他socket上的SO_REUSEADDR、SO_KEEPALIVE、SO_SNDBUF、SO_RCVBUF和TCP_NODELAY被接受但不起作用。
+/// IPV6_V6ONLY也被接受但不起作用，IPv6 socket总是双栈的。
+pub fn syscall_setsockopt(
+    fd: usize,
+    level: usize,
+    optname: usize,
+    optval: *const u8,
+    optlen: usize,
+) -> isize {
+    debug!(
+        "Into syscall_setsockopt. fd: {}, level: {}, optname: {}",
+        fd, level, optname
+    );
+    let _ = (optval, optlen);
+    let result = with_socket(fd, |socket| {
+        #[cfg(feature = "net")]
+        if let Some(tcp) = socket.tcp() {
+            return unsafe { set_tcp_option(&tcp.lock(), level, optname, optval, optlen) };
+        }
+        #[cfg(feature = "net")]
+        if let Some(icmp) = socket.icmp() {
+            return unsafe { set_icmp_option(icmp, level, optname, optval, optlen) };
+        }
+        let _ = socket;
+        ignore_option(level, optname)
+    });
+    syscall_ret("setsockopt", result.map(|_| 0))
+}
+
+/// 功能：获取socket选项；
+/// 输入：
+///     - fd：socket的文件描述符。
+///     - level：选项所在的协议层，支持SOL_SOCKET和IPPROTO_TCP。
+///     - optname：选项名，支持SO_TYPE、SO_DOMAIN和SO_ERROR，TCP socket还支持setsockopt能设置的选项，
+///       ICMP socket还支持SO_RCVTIMEO。
+///     - optval：写入选项值的缓冲区。
+///     - optlen：输入时为optval缓冲区的长度，返回时为选项值的实际长度。
+/// 返回值：成功执行，返回0。失败，返回负的错误码。
+pub fn syscall_getsockopt(
+    fd: usize,
+    level: usize,
+    optname: usize,
+    optval: *mut u8,
+    optlen: *mut u32,
+) -> isize {
+    debug!(
+        "Into syscall_getsockopt. fd: {}, level: {}, optname: {}",
+        fd, level, optname
+    );
+    let result = with_socket(fd, |socket| {
+        let value = match (level, optname) {
+            (SOL_SOCKET, SO_TYPE) => socket.socket_type() as i32,
+            (SOL_SOCKET, SO_DOMAIN) => socket.domain() as i32,
+            // 错误在发生时直接由系统调用返回, 不会被挂起
+            (SOL_SOCKET, SO_ERROR) => 0,
+            _ => {
+                #[cfg(feature = "net")]
+                if let Some(tcp) = socket.tcp() {
+                    return unsafe { get_tcp_option(&tcp.lock(), level, optname, optval, optlen) };
+                }
+                #[cfg(feature = "net")]
+                if let Some(icmp) = socket.icmp() {
+                    return unsafe { get_icmp_option(icmp, level, optname, optval, optlen) };
+                }
+                return Err(AxError::Unsupported);
+            }
+        };
+        unsafe { write_option(optval, optlen, &value) }
+    });
+    syscall_ret("getsockopt", result.map(|_| 0))
+}
+
+/// 功能：通过socket发送消息；
+/// 输入：
+///     - fd：socket的文件描述符。
+///     - msg：`struct msghdr`的指针，msg_name为数据报的目标地址，msg_iov为要发送的数据，
+///       msg_control为控制消息。
+///     - flags：目前只支持MSG_DONTWAIT。
+/// 返回值：成功执行，返回发送的字节数。失败，返回负的错误码。
+///
+/// 说明：Unix域socket支持SCM_RIGHTS控制消息传递文件描述符，其他控制消息被忽略；网络socket忽略所有控制消息。
+pub fn syscall_sendmsg(fd: usize, msg: *const u8, flags: usize) -> isize {
+    debug!("Into syscall_sendmsg. fd: {}, flags: {:#x}", fd, flags);
+    let result = with_socket(fd, |socket| {
+        let msg = unsafe { (msg as *const MsgHdr).as_ref() }.ok_or(AxError::InvalidInput)?;
+        let buf: Vec<u8> = unsafe { iovecs(msg.iov, msg.iov_len as usize) }
+            .iter()
+            .filter(|iov| iov.len > 0)
+            .flat_map(|iov| unsafe { core::slice::from_raw_parts(iov.base, iov.len) })
+            .copied()
+            .collect();
+        let has_name = !msg.name.is_null() && msg.name_len > 0;
+        let nonblock = flags & MSG_DONTWAIT != 0;
+        match socket {
+            #[cfg(feature = "net")]
+            SocketFile::Inet(socket) => {
+                let to = if has_name {
+                    Some(unsafe { read_sockaddr(msg.name, msg.name_len as usize)? })
+                } else {
+                    None
+                };
+                socket.send_to(&buf, to, nonblock)
+            }
+            SocketFile::Unix(socket) => {
+                let to = if has_name {
+                    Some(unsafe { read_sockaddr_un(msg.name, msg.name_len as usize)? })
+                } else {
+                    None
+                };
+                let rights = unsafe { read_scm_rights(msg.control, msg.control_len as usize)? };
+                socket.send_msg(&buf, rights, to.as_ref(), nonblock)
+            }
+        }
+    });
+    syscall_ret("sendmsg", result)
+}
+
+/// 功能：从socket接收消息；
+/// 输入：
+///     - fd：socket的文件描述符。
+///     - msg：`struct msghdr`的指针，返回时msg_name为发送方的地址，数据写入msg_iov，
+///       控制消息写入msg_control，msg_flags为MSG_TRUNC、MSG_CTRUNC的组合。
+///     - flags：目前只支持MSG_DONTWAIT。
+/// 返回值：成功执行，返回接收的字节数，对端关闭连接时返回0。失败，返回负的错误码。
+///
+/// 说明：通过SCM_RIGHTS收到的文件被放入当前进程的文件描述符表，msg_control放不下的文件被关闭，并设置MSG_CTRUNC。
+pub fn syscall_recvmsg(fd: usize, msg: *mut u8, flags: usize) -> isize {
+    debug!("Into syscall_recvmsg. fd: {}, flags: {:#x}", fd, flags);
+    let result = with_socket(fd, |socket| {
+        let msg = unsafe { (msg as *mut MsgHdr).as_mut() }.ok_or(AxError::InvalidInput)?;
+        let iovs = unsafe { iovecs(msg.iov, msg.iov_len as usize) };
+        let mut buf = vec![0u8; iovs.iter().map(|iov| iov.len).sum()];
+        let nonblock = flags & MSG_DONTWAIT != 0;
+        let mut name_len = msg.name_len;
+        msg.flags = 0;
+        let len = match socket {
+            #[cfg(feature = "net")]
+            SocketFile::Inet(socket) => {
+                let (len, from) = socket.recv_from(&mut buf, nonblock)?;
+                match from {
+                    Some(from) => unsafe {
+                        write_sockaddr(msg.name, &mut name_len, from, socket.domain())?
+                    },
+                    None => name_len = 0,
+                }
+                msg.control_len = 0;
+                len
+            }
+            SocketFile::Unix(socket) => {
+                let received = socket.recv_msg(&mut buf, nonblock)?;
+                unsafe { write_sockaddr_un(msg.name, &mut name_len, &received.from)? };
+                if received.truncated {
+                    msg.flags |= MSG_TRUNC;
+                }
+                let (control_len, truncated) = unsafe {
+                    write_scm_rights(msg.control, msg.control_len as usize, received.rights)
+                };
+                msg.control_len = control_len as u32;
+                if truncated {
+                    msg.flags |= MSG_CTRUNC;
+                }
+                received.len
+            }
+        };
+        msg.name_len = name_len;
+
+        // 把数据依次写入各个iovec
+        let mut data = &buf[..len];
+        for iov in iovs {
+            if data.is_empty() {
+                break;
+            }
+            let n = iov.len.min(data.len());
+            unsafe { core::ptr::copy_nonoverlapping(data.as_ptr(), iov.base, n) };
+            data = &data[n..];
+        }
+        Ok(len)
+    });
+    syscall_ret("recvmsg", result)
+}
@@ -0,0 +1,297 @@
+//! 处理与文件就绪状态(poll/select/epoll)有关的系统调用
+
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use axfs_os::epoll::{EpollCtl, EpollEvent, EpollFile};
+use axfs_os::file_io::FileIO;
+use axfs_os::poll::{needs_periodic_check, poll_until, PollEvents};
+use axhal::time::current_time;
+use axprocess::process::current_process;
+use core::time::Duration;
+use log::debug;
+
+use crate::flags::{PollFd, TimeSecs};
+
+/// select支持的最大文件描述符数
+const FD_SETSIZE: usize = 1024;
+/// fd_set中每个字的位数
+const FD_BITS: usize = usize::BITS as usize;
+/// epoll_create1的标志: exec时关闭epoll实例
+const EPOLL_CLOEXEC: usize = 0x80000;
+
+/// 辅助函数：根据超时时间计算截止时间，超时时间为空指针时表示一直等待
+fn deadline_of(timeout: *const TimeSecs) -> Option<Duration> {
+    if timeout.is_null() {
+        return None;
+    }
+    let timeout = unsafe { *timeout };
+    Some(current_time() + Duration::new(timeout.tv_sec as u64, timeout.tv_nsec as u32))
+}
+
+/// 功能：等待一组文件描述符上的事件；
+/// 输入：
+///     - fds：pollfd数组，每一项指定要等待的文件描述符和事件，返回时填入发生的事件。
+///     - nfds：数组的长度。
+///     - timeout：超时时间。如为空指针，则一直等待；如为0，则立即返回。
+///     - sigmask：等待期间的信号掩码。目前没有信号机制，忽略。
+/// 返回值：成功执行，返回发生了事件的文件描述符个数，超时返回0。失败，返回-1。
+///
+/// 注意：fd为负数的项被忽略；fd未打开时，revents为POLLNVAL。
+pub fn syscall_ppoll(
+    fds: *mut PollFd,
+    nfds: usize,
+    timeout: *const TimeSecs,
+    _sigmask: usize,
+) -> isize {
+    debug!(
+        "Into syscall_ppoll. fds: {:?}, nfds: {}, timeout: {:?}",
+        fds as usize, nfds, timeout as usize
+    );
+    if nfds > FD_SETSIZE {
+        return -1;
+    }
+    let fds: &mut [PollFd] = if nfds == 0 {
+        &mut []
+    } else {
+        unsafe { core::slice::from_raw_parts_mut(fds, nfds) }
+    };
+    let files: Vec<Option<Arc<dyn FileIO>>> = {
+        let process = current_process();
+        let process_inner = process.inner.lock();
+        fds.iter()
+            .map(|pfd| {
+                if pfd.fd < 0 {
+                    None
+                } else {
+                    process_inner.fd_table.get(pfd.fd as usize).cloned().flatten()
+                }
+            })
+            .collect()
+    };
+    let periodic = files.iter().flatten().any(|file| needs_periodic_check(file.as_ref()));
+    let deadline = deadline_of(timeout);
+
+    let ready = poll_until(deadline, periodic, || {
+        let mut ready = 0;
+        for (pfd, file) in fds.iter_mut().zip(files.iter()) {
+            let revents = if pfd.fd < 0 {
+                PollEvents::empty()
+            } else if let Some(file) = file {
+                // 错误和挂断总是会被报告
+                let events = PollEvents::from_bits_truncate(pfd.events as u16)
+                    | PollEvents::ERR
+                    | PollEvents::HUP;
+                file.poll() & events
+            } else {
+                PollEvents::NVAL
+            };
+            pfd.revents = revents.bits() as i16;
+            if !revents.is_empty() {
+                ready += 1;
+            }
+        }
+        ready
+    });
+    debug!("ppoll ready: {}", ready);
+    ready as isize
+}
+
+/// 功能：等待一组文件描述符变为可读、可写或有异常；
+/// 输入：
+///     - nfds：最大的文件描述符加1。
+///     - readfds：等待可读的文件描述符集合，返回时只保留可读的文件描述符。可以为空指针。
+///     - writefds：等待可写的文件描述符集合，返回时只保留可写的文件描述符。可以为空指针。
+///     - exceptfds：等待异常的文件描述符集合，返回时只保留有异常的文件描述符。可以为空指针。
+///     - timeout：超时时间。如为空指针，则一直等待；如为0，则立即返回。
+///     - sigmask：等待期间的信号掩码。目前没有信号机制，忽略。
+/// 返回值：成功执行，返回三个集合中就绪的文件描述符总数，超时返回0。失败，返回-1。
+pub fn syscall_pselect6(
+    nfds: usize,
+    readfds: *mut usize,
+    writefds: *mut usize,
+    exceptfds: *mut usize,
+    timeout: *const TimeSecs,
+    _sigmask: usize,
+) -> isize {
+    debug!(
+        "Into syscall_pselect6. nfds: {}, readfds: {:?}, writefds: {:?}, exceptfds: {:?}, timeout: {:?}",
+        nfds, readfds as usize, writefds as usize, exceptfds as usize, timeout as usize
+    );
+    if nfds > FD_SETSIZE {
+        return -1;
+    }
+    let words = (nfds + FD_BITS - 1) / FD_BITS;
+    let set_ptrs = [readfds, writefds, exceptfds];
+    let sets: Vec<Vec<usize>> = set_ptrs
+        .iter()
+        .map(|&ptr| {
+            if ptr.is_null() {
+                vec![0; words]
+            } else {
+                unsafe { core::slice::from_raw_parts(ptr, words) }.to_vec()
+            }
+        })
+        .collect();
+    let is_set = |set: &[usize], fd: usize| set[fd / FD_BITS] & (1 << (fd % FD_BITS)) != 0;
+
+    let mut files: Vec<(usize, Arc<dyn FileIO>)> = Vec::new();
+    {
+        let process = current_process();
+        let process_inner = process.inner.lock();
+        for fd in 0..nfds {
+            if !sets.iter().any(|set| is_set(set, fd)) {
+                continue;
+            }
+            match process_inner.fd_table.get(fd).cloned().flatten() {
+                Some(file) => files.push((fd, file)),
+                None => {
+                    debug!("fd {} is not opened", fd);
+                    return -1;
+                }
+            }
+        }
+    }
+    let periodic = files.iter().any(|(_, file)| needs_periodic_check(file.as_ref()));
+    let deadline = deadline_of(timeout);
+
+    // 可读、可写、异常分别对应的事件, 与Linux一致
+    let conditions = [
+        PollEvents::IN | PollEvents::HUP | PollEvents::ERR,
+        PollEvents::OUT | PollEvents::ERR,
+        PollEvents::PRI,
+    ];
+    let mut results = vec![vec![0usize; words]; 3];
+    let ready = poll_until(deadline, periodic, || {
+        let mut ready = 0;
+        for result in results.iter_mut() {
+            result.fill(0);
+        }
+        for (fd, file) in files.iter() {
+            let events = file.poll();
+            for i in 0..3 {
+                if is_set(&sets[i], *fd) && events.intersects(conditions[i]) {
+                    results[i][fd / FD_BITS] |= 1 << (fd % FD_BITS);
+                    ready += 1;
+                }
+            }
+        }
+        ready
+    });
+
+    for (ptr, result) in set_ptrs.iter().zip(results.iter()) {
+        if !ptr.is_null() {
+            unsafe { core::ptr::copy_nonoverlapping(result.as_ptr(), *ptr, words) };
+        }
+    }
+    debug!("pselect6 ready: {}", ready);
+    ready as isize
+}
+
+/// 功能：创建一个epoll实例；
+/// 输入：
+///     - flags：目前只支持EPOLL_CLOEXEC。
+/// 返回值：成功执行，返回epoll实例的文件描述符。失败，返回-1。
+pub fn syscall_epoll_create1(flags: usize) -> isize {
+    debug!("Into syscall_epoll_create1. flags: {}", flags);
+    let process = current_process();
+    let mut process_inner = process.inner.lock();
+    let fd = process_inner.alloc_fd();
+    process_inner.fd_table[fd] = Some(Arc::new(EpollFile::new()));
+    process_inner.set_cloexec(fd, flags & EPOLL_CLOEXEC != 0);
+    fd as isize
+}
+
+/// 功能：修改epoll实例的兴趣列表；
+/// 输入：
+///     - epfd：epoll实例的文件描述符。
+///     - op：操作类型，EPOLL_CTL_ADD(1)、EPOLL_CTL_DEL(2)或EPOLL_CTL_MOD(3)。
+///     - fd：目标文件描述符。
+///     - event：关心的事件和用户数据。op为EPOLL_CTL_DEL时忽略。
+/// 返回值：成功执行，返回0。失败，返回-1。
+pub fn syscall_epoll_ctl(epfd: usize, op: usize, fd: usize, event: *const EpollEvent) -> isize {
+    debug!(
+        "Into syscall_epoll_ctl. epfd: {}, op: {}, fd: {}, event: {:?}",
+        epfd, op, fd, event as usize
+    );
+    let op = match EpollCtl::try_from(op) {
+        Ok(op) => op,
+        Err(_) => return -1,
+    };
+    let event = if op == EpollCtl::Del || event.is_null() {
+        EpollEvent::default()
+    } else {
+        unsafe { *event }
+    };
+    let (epoll, file) = {
+        let process = current_process();
+        let process_inner = process.inner.lock();
+        match (
+            process_inner.fd_table.get(epfd).cloned().flatten(),
+            process_inner.fd_table.get(fd).cloned().flatten(),
+        ) {
+            (Some(epoll), Some(file)) => (epoll, file),
+            _ => return -1,
+        }
+    };
+    let epoll = match epoll.as_ref().as_any().downcast_ref::<EpollFile>() {
+        Some(epoll) => epoll,
+        None => {
+            debug!("fd {} is not an epoll instance", epfd);
+            return -1;
+        }
+    };
+    match epoll.ctl(op, fd, file, event) {
+        Ok(()) => 0,
+        Err(e) => {
+            debug!("epoll_ctl failed: {:?}", e);
+            -1
+        }
+    }
+}
+
+/// 功能：等待epoll实例上的事件；
+/// 输入：
+///     - epfd：epoll实例的文件描述符。
+///     - events：用于保存就绪事件的数组。
+///     - maxevents：数组的长度，必须大于0。
+///     - timeout：超时时间(毫秒)。如为-1，则一直等待；如为0，则立即返回。
+///     - sigmask：等待期间的信号掩码。目前没有信号机制，忽略。
+/// 返回值：成功执行，返回就绪事件的个数，超时返回0。失败，返回-1。
+pub fn syscall_epoll_pwait(
+    epfd: usize,
+    events: *mut EpollEvent,
+    maxevents: usize,
+    timeout: i32,
+    _sigmask: usize,
+) -> isize {
+    debug!(
+        "Into syscall_epoll_pwait. epfd: {}, events: {:?}, maxevents: {}, timeout: {}",
+        epfd, events as usize, maxevents, timeout
+    );
+    if maxevents == 0 || maxevents > i32::MAX as usize {
+        return -1;
+    }
+    let epoll = {
+        let process = current_process();
+        let process_inner = process.inner.lock();
+        match process_inner.fd_table.get(epfd).cloned().flatten() {
+            Some(epoll) => epoll,
+            None => return -1,
+        }
+    };
+    let epoll = match epoll.as_ref().as_any().downcast_ref::<EpollFile>() {
+        Some(epoll) => epoll,
+        None => {
+            debug!("fd {} is not an epoll instance", epfd);
+            return -1;
+        }
+    };
+    let events = unsafe { core::slice::from_raw_parts_mut(events, maxevents) };
+    let timeout = if timeout < 0 {
+        None
+    } else {
+        Some(Duration::from_millis(timeout as u64))
+    };
+    epoll.wait(events, timeout) as isize
+}
@@ -0,0 +1,595 @@
+//! 用户进程的socket
+//!
+//! [`Socket`]把axnet的socket包装成文件, 放在进程的文件描述符表中, 可以像普通文件一样
+//! read/write/close/poll。socket地址与用户态`struct sockaddr`之间的转换也在这里完成。
+//!
+//! 目前支持IPv4和IPv6协议族的TCP和UDP socket, 以及`ping`使用的ICMP原始socket(SOCK_RAW)。IPv6 socket是双栈的, 也可以与IPv4地址通信,
+//! IPv4地址以`::ffff:a.b.c.d`的形式出现在它的地址中。阻塞的操作先检查socket的就绪状态, 未就绪时不持有socket的锁
+//! 睡眠在axnet的等待队列上, 这样一个线程阻塞在recv上时, 其他线程仍然可以对同一个socket进行send和poll。
+//!
+//! axnet的网络任务在收到数据包或定时器到期后更新所有socket的状态, 并报告状态发生变化的socket。
+//! 回调按socket在axnet中的标识[`SocketKey`]分开保存, 只有状态变化的socket上注册的回调会被调用。
+//!
+//! ICMP原始socket只收发回显请求和回显应答, 第一个发出的回显请求的标识符决定了它接收哪些报文。
+//! 网卡会自动应答发给本机的回显请求, 不需要用户进程处理。
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec;
+use core::mem::size_of;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axerrno::{ax_err, AxError, AxResult};
+use axfs_os::file_io::FileIO;
+use axfs_os::poll::{poll_notify, PollEvents, PollWaker, PollWakers};
+use axfs_os::types::{normal_file_mode, Kstat, StMode};
+use axio::PollState;
+use axnet::{IcmpSocket, IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketKey, TcpSocket, UdpSocket};
+use axsync::Mutex;
+
+use crate::net::{
+    IPPROTO_ICMP, IPPROTO_ICMPV6, SOCK_CLOEXEC, SOCK_DGRAM, SOCK_NONBLOCK, SOCK_RAW, SOCK_STREAM,
+};
+
+/// 网络socket上注册的回调, 以socket在axnet中的标识为键
+static INET_POLL_WAKERS: Mutex<BTreeMap<SocketKey, Arc<PollWakers>>> = Mutex::new(BTreeMap::new());
+
+/// IPv4协议族
+pub const AF_INET: usize = 2;
+/// IPv6协议族
+pub const AF_INET6: usize = 10;
+
+/// IPv4头部的长度, 不含选项
+const IPV4_HEADER_LEN: usize = 20;
+/// 构造的IPv4头部中的TTL
+const IPV4_DEFAULT_TTL: u8 = 64;
+
+/// 用户态的`struct sockaddr_in`
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SockAddrIn {
+    /// 协议族, 总是AF_INET
+    sin_family: u16,
+    /// 端口, 网络字节序
+    sin_port: u16,
+    /// IPv4地址, 网络字节序
+    sin_addr: [u8; 4],
+    sin_zero: [u8; 8],
+}
+
+/// 用户态的`struct sockaddr_in6`
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SockAddrIn6 {
+    /// 协议族, 总是AF_INET6
+    sin6_family: u16,
+    /// 端口, 网络字节序
+    sin6_port: u16,
+    /// 流标签, 忽略
+    sin6_flowinfo: u32,
+    /// IPv6地址, 网络字节序
+    sin6_addr: [u8; 16],
+    /// 链路本地地址所在的网卡, 忽略
+    sin6_scope_id: u32,
+}
+
+/// 如果`ip`是IPv4映射地址`::ffff:a.b.c.d`, 返回对应的IPv4地址
+fn to_ipv4_mapped(ip: &Ipv6Addr) -> Option<Ipv4Addr> {
+    match ip.0 {
+        [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, a, b, c, d] => Some(Ipv4Addr::new(a, b, c, d)),
+        _ => None,
+    }
+}
+
+/// 从用户态的`struct sockaddr`中读取socket地址
+///
+/// # Safety
+///
+/// `addr`必须指向至少`addrlen`字节的可读内存
+pub unsafe fn read_sockaddr(addr: *const u8, addrlen: usize) -> AxResult<SocketAddr> {
+    if addr.is_null() || addrlen < size_of::<u16>() {
+        return ax_err!(InvalidInput);
+    }
+    let family = (addr as *const u16).read_unaligned() as usize;
+    match family {
+        AF_INET if addrlen >= size_of::<SockAddrIn>() => {
+            let sockaddr = (addr as *const SockAddrIn).read_unaligned();
+            let ip = Ipv4Addr::from_bytes(&sockaddr.sin_addr);
+            Ok(SocketAddr::new(ip.into(), u16::from_be(sockaddr.sin_port)))
+        }
+        AF_INET6 if addrlen >= size_of::<SockAddrIn6>() => {
+            let sockaddr = (addr as *const SockAddrIn6).read_unaligned();
+            let ip = Ipv6Addr::from_bytes(&sockaddr.sin6_addr);
+            let ip = match to_ipv4_mapped(&ip) {
+                Some(v4) => IpAddr::Ipv4(v4),
+                None => IpAddr::Ipv6(ip),
+            };
+            Ok(SocketAddr::new(ip, u16::from_be(sockaddr.sin6_port)))
+        }
+        _ => ax_err!(InvalidInput),
+    }
+}
+
+/// 把socket地址写入用户态的`struct sockaddr`
+///
+/// `domain`为socket的协议族, AF_INET6的socket总是得到`struct sockaddr_in6`, IPv4地址被转换为
+/// IPv4映射地址。`*addrlen`为缓冲区的长度, 地址过长时被截断, 返回后`*addrlen`为地址的实际长度。
+/// `addr`为空指针时什么也不做。
+///
+/// # Safety
+///
+/// `addr`不为空时必须指向至少`*addrlen`字节的可写内存
+pub unsafe fn write_sockaddr(
+    addr: *mut u8,
+    addrlen: *mut u32,
+    sockaddr: SocketAddr,
+    domain: usize,
+) -> AxResult {
+    if addr.is_null() {
+        return Ok(());
+    }
+    if addrlen.is_null() {
+        return ax_err!(InvalidInput);
+    }
+    let ip = match sockaddr.addr {
+        IpAddr::Ipv4(ip) if domain == AF_INET6 => {
+            let mut bytes = [0; 16];
+            bytes[10..12].copy_from_slice(&[0xff, 0xff]);
+            bytes[12..].copy_from_slice(&ip.0);
+            IpAddr::Ipv6(Ipv6Addr(bytes))
+        }
+        ip => ip,
+    };
+    match ip {
+        IpAddr::Ipv4(ip) => copy_sockaddr(
+            addr,
+            addrlen,
+            SockAddrIn {
+                sin_family: AF_INET as u16,
+                sin_port: sockaddr.port.to_be(),
+                sin_addr: ip.0,
+                sin_zero: [0; 8],
+            },
+        ),
+        IpAddr::Ipv6(ip) => copy_sockaddr(
+            addr,
+            addrlen,
+            SockAddrIn6 {
+                sin6_family: AF_INET6 as u16,
+                sin6_port: sockaddr.port.to_be(),
+                sin6_flowinfo: 0,
+                sin6_addr: ip.0,
+                sin6_scope_id: 0,
+            },
+        ),
+    }
+    Ok(())
+}
+
+/// 把`raw`复制到用户态的缓冲区, 过长时截断
+unsafe fn copy_sockaddr<T>(addr: *mut u8, addrlen: *mut u32, raw: T) {
+    let len = (*addrlen as usize).min(size_of::<T>());
+    core::ptr::copy_nonoverlapping(&raw as *const T as *const u8, addr, len);
+    *addrlen = size_of::<T>() as u32;
+}
+
+/// socket使用的协议
+///
+/// TCP socket的连接、监听等操作需要可变引用, 用锁保护; UDP和ICMP socket的操作都只需要共享引用。
+enum SocketInner {
+    Tcp(Mutex<TcpSocket>),
+    Udp(UdpSocket),
+    Icmp(IcmpSocket),
+}
+
+/// 用户进程的socket
+pub struct Socket {
+    /// 协议族
+    domain: usize,
+    /// socket类型, 不含标志位
+    socket_type: usize,
+    inner: SocketInner,
+    /// 是否是非阻塞的
+    nonblock: AtomicBool,
+}
+
+impl Socket {
+    /// 创建一个socket, `socket_type`可以包含SOCK_NONBLOCK等标志位
+    ///
+    /// TCP和UDP socket忽略`protocol`; SOCK_RAW的socket只支持IPv4的IPPROTO_ICMP和IPv6的IPPROTO_ICMPV6。
+    pub fn new(domain: usize, socket_type: usize, protocol: usize) -> AxResult<Self> {
+        let nonblock = socket_type & SOCK_NONBLOCK != 0;
+        let socket_type = socket_type & !(SOCK_NONBLOCK | SOCK_CLOEXEC);
+        let inner = match (domain, socket_type) {
+            (AF_INET | AF_INET6, SOCK_STREAM) => SocketInner::Tcp(Mutex::new(TcpSocket::new())),
+            (AF_INET | AF_INET6, SOCK_DGRAM) => SocketInner::Udp(UdpSocket::new()),
+            (AF_INET, SOCK_RAW) if protocol == IPPROTO_ICMP => SocketInner::Icmp(IcmpSocket::new()),
+            (AF_INET6, SOCK_RAW) if protocol == IPPROTO_ICMPV6 => {
+                SocketInner::Icmp(IcmpSocket::new())
+            }
+            _ => return ax_err!(Unsupported),
+        };
+        // 重复设置是无害的
+        axnet::set_event_handler(wake_inet_poll_wakers);
+        let socket = Self {
+            domain,
+            socket_type,
+            inner,
+            nonblock: AtomicBool::new(false),
+        };
+        socket.set_nonblocking(nonblock);
+        Ok(socket)
+    }
+
+    /// 协议族
+    pub fn domain(&self) -> usize {
+        self.domain
+    }
+
+    /// socket类型, 不含标志位
+    pub fn socket_type(&self) -> usize {
+        self.socket_type
+    }
+
+    /// TCP socket, 用于读写TCP socket的选项; 其他socket返回`None`
+    pub fn tcp(&self) -> Option<&Mutex<TcpSocket>> {
+        match &self.inner {
+            SocketInner::Tcp(socket) => Some(socket),
+            _ => None,
+        }
+    }
+
+    /// ICMP socket, 用于读写ICMP socket的选项; 其他socket返回`None`
+    pub fn icmp(&self) -> Option<&IcmpSocket> {
+        match &self.inner {
+            SocketInner::Icmp(socket) => Some(socket),
+            _ => None,
+        }
+    }
+
+    /// 绑定本地地址, ICMP socket忽略本地地址
+    pub fn bind(&self, addr: SocketAddr) -> AxResult {
+        match &self.inner {
+            SocketInner::Tcp(socket) => socket.lock().bind(addr),
+            SocketInner::Udp(socket) => socket.bind(addr),
+            SocketInner::Icmp(_) => Ok(()),
+        }
+    }
+
+    /// 开始监听连接, 只有TCP socket支持
+    ///
+    /// 监听的TCP socket在axnet中的标识变为端口, 已注册的回调随之移动。
+    pub fn listen(&self) -> AxResult {
+        match &self.inner {
+            SocketInner::Tcp(socket) => {
+                let mut socket = socket.lock();
+                let old_key = socket.key();
+                socket.listen()?;
+                let mut wakers = INET_POLL_WAKERS.lock();
+                if let Some(old_wakers) = wakers.remove(&old_key) {
+                    wakers.insert(socket.key(), old_wakers);
+                }
+                Ok(())
+            }
+            _ => ax_err!(Unsupported),
+        }
+    }
+
+    /// 以socket在axnet中的标识调用`f`, TCP socket在此期间持有锁, 使标识不会因为[`Self::listen`]而改变
+    fn with_key<R>(&self, f: impl FnOnce(SocketKey) -> R) -> R {
+        match &self.inner {
+            SocketInner::Tcp(socket) => {
+                let socket = socket.lock();
+                f(socket.key())
+            }
+            SocketInner::Udp(socket) => f(socket.key()),
+            SocketInner::Icmp(socket) => f(socket.key()),
+        }
+    }
+
+    /// 连接到`addr`
+    ///
+    /// TCP socket在连接建立或失败后返回, 非阻塞或超时时发起连接后返回`InProgress`(EINPROGRESS), 连接建立后socket变为可写;
+    /// UDP socket只记录默认的对端地址; ICMP socket不支持。
+    ///
+    /// TCP socket只在发起连接时持有锁, 等待握手时不持有, 与[`Self::wait_ready`]一样,
+    /// 其它线程在此期间可以查询和设置这个socket。
+    pub fn connect(&self, addr: SocketAddr) -> AxResult {
+        match &self.inner {
+            SocketInner::Tcp(socket) => {
+                let (key, nonblock, timeout) = {
+                    let mut socket = socket.lock();
+                    socket.start_connect(addr)?;
+                    (socket.key(), socket.is_nonblocking(), socket.send_timeout())
+                };
+                // 非阻塞或超时的连接在后台继续进行
+                axnet::block_on_timeout(key, nonblock, timeout, || socket.lock().poll_connect())
+                    .map_err(|e| match e {
+                        AxError::WouldBlock => AxError::InProgress,
+                        e => e,
+                    })
+            }
+            SocketInner::Udp(socket) => socket.connect(addr),
+            SocketInner::Icmp(_) => ax_err!(Unsupported),
+        }
+    }
+
+    /// 接受一个连接, 新的socket是阻塞的
+    pub fn accept(&self) -> AxResult<Socket> {
+        let socket = match &self.inner {
+            SocketInner::Tcp(socket) => socket,
+            _ => return ax_err!(Unsupported),
+        };
+        self.wait_ready(true, self.is_nonblocking())?;
+        let new_socket = socket.lock().accept()?;
+        Ok(Self {
+            domain: self.domain,
+            socket_type: self.socket_type,
+            inner: SocketInner::Tcp(Mutex::new(new_socket)),
+            nonblock: AtomicBool::new(false),
+        })
+    }
+
+    /// 发送数据, `nonblock`为真时即使socket是阻塞的也不等待
+    ///
+    /// `to`为数据报的目标地址, 为`None`时发送给连接的对端。TCP socket忽略`to`; ICMP socket必须指定`to`,
+    /// 发送的数据为ICMP报文, 不含IP头部, 校验和由协议栈计算。
+    pub fn send_to(&self, buf: &[u8], to: Option<SocketAddr>, nonblock: bool) -> AxResult<usize> {
+        self.wait_ready(false, nonblock || self.is_nonblocking())?;
+        match &self.inner {
+            SocketInner::Tcp(socket) => socket.lock().send(buf),
+            SocketInner::Udp(socket) => match to {
+                Some(addr) => socket.send_to(buf, addr),
+                None => socket.send(buf),
+            },
+            SocketInner::Icmp(socket) => match to {
+                Some(addr) => socket.send_to(buf, addr.addr),
+                None => ax_err!(NotConnected),
+            },
+        }
+    }
+
+    /// 接收数据, 同时返回发送方的地址(如果知道), `nonblock`为真时即使socket是阻塞的也不等待
+    ///
+    /// 连接了对端的UDP socket只接收来自对端的数据报。与Linux一致, IPv4的ICMP socket接收的数据包含IPv4头部,
+    /// IPv6的ICMP socket接收的数据不含IP头部。
+    pub fn recv_from(
+        &self,
+        buf: &mut [u8],
+        nonblock: bool,
+    ) -> AxResult<(usize, Option<SocketAddr>)> {
+        self.wait_ready(true, nonblock || self.is_nonblocking())?;
+        match &self.inner {
+            SocketInner::Tcp(socket) => {
+                let socket = socket.lock();
+                Ok((socket.recv(buf)?, socket.peer_addr().ok()))
+            }
+            SocketInner::Udp(socket) => match socket.peer_addr() {
+                Ok(peer_addr) => Ok((socket.recv(buf)?, Some(peer_addr))),
+                Err(_) => socket.recv_from(buf).map(|(len, from)| (len, Some(from))),
+            },
+            SocketInner::Icmp(socket) if self.domain == AF_INET => {
+                let (len, from) = recv_icmp_with_ipv4_header(socket, buf)?;
+                Ok((len, Some(SocketAddr::new(from, 0))))
+            }
+            SocketInner::Icmp(socket) => {
+                let (len, from) = socket.recv_from(buf)?;
+                Ok((len, Some(SocketAddr::new(from, 0))))
+            }
+        }
+    }
+
+    /// 本地地址
+    pub fn local_addr(&self) -> AxResult<SocketAddr> {
+        match &self.inner {
+            SocketInner::Tcp(socket) => socket.lock().local_addr(),
+            SocketInner::Udp(socket) => socket.local_addr(),
+            SocketInner::Icmp(_) => {
+                let ip = match self.domain {
+                    AF_INET6 => IpAddr::Ipv6(Ipv6Addr::UNSPECIFIED),
+                    _ => IpAddr::Ipv4(Ipv4Addr::UNSPECIFIED),
+                };
+                Ok(SocketAddr::new(ip, 0))
+            }
+        }
+    }
+
+    /// 对端地址
+    pub fn peer_addr(&self) -> AxResult<SocketAddr> {
+        match &self.inner {
+            SocketInner::Tcp(socket) => socket.lock().peer_addr(),
+            SocketInner::Udp(socket) => socket.peer_addr(),
+            SocketInner::Icmp(_) => ax_err!(NotConnected),
+        }
+    }
+
+    /// 关闭连接的发送方向, 监听的socket停止监听
+    pub fn shutdown(&self) -> AxResult {
+        match &self.inner {
+            SocketInner::Tcp(socket) => socket.lock().shutdown(),
+            SocketInner::Udp(socket) => socket.shutdown(),
+            SocketInner::Icmp(_) => Ok(()),
+        }
+    }
+
+    fn poll_state(&self) -> AxResult<PollState> {
+        match &self.inner {
+            SocketInner::Tcp(socket) => socket.lock().poll(),
+            SocketInner::Udp(socket) => socket.poll(),
+            SocketInner::Icmp(socket) => socket.poll(),
+        }
+    }
+
+    /// 等待socket可读(`read`为真)或可写, `nonblock`为真时未就绪直接返回`WouldBlock`
+    ///
+    /// TCP socket设置了SO_RCVTIMEO或SO_SNDTIMEO时, ICMP socket设置了SO_RCVTIMEO时, 超时后也返回`WouldBlock`。
+    fn wait_ready(&self, read: bool, nonblock: bool) -> AxResult {
+        let timeout = match &self.inner {
+            SocketInner::Tcp(socket) if read => socket.lock().recv_timeout(),
+            SocketInner::Tcp(socket) => socket.lock().send_timeout(),
+            SocketInner::Icmp(socket) if read => socket.recv_timeout(),
+            _ => None,
+        };
+        // TCP socket开始监听时标识会改变, 此时在新的标识上重新等待
+        loop {
+            let key = self.with_key(|key| key);
+            let ready = axnet::block_on_timeout(key, nonblock, timeout, || {
+                if self.with_key(|new_key| new_key != key) {
+                    return Ok(false);
+                }
+                let state = self.poll_state()?;
+                if (read && state.readable) || (!read && state.writable) {
+                    Ok(true)
+                } else {
+                    Err(AxError::WouldBlock)
+                }
+            })?;
+            if ready {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// 接收一个ICMP报文, 在前面加上IPv4头部, 返回总长度和发送方的地址
+///
+/// smoltcp不保留收到的IP头部, 这里根据报文重新构造: TTL固定为[`IPV4_DEFAULT_TTL`], 目标地址为0.0.0.0。
+/// 超出`buf`的部分被丢弃。
+fn recv_icmp_with_ipv4_header(socket: &IcmpSocket, buf: &mut [u8]) -> AxResult<(usize, IpAddr)> {
+    let mut message = vec![0; buf.len().saturating_sub(IPV4_HEADER_LEN)];
+    let (len, from) = socket.recv_from(&mut message)?;
+    let src = match from {
+        IpAddr::Ipv4(ip) => ip,
+        IpAddr::Ipv6(_) => return ax_err!(InvalidData),
+    };
+    let mut header = [0; IPV4_HEADER_LEN];
+    // 版本4, 头部长度5个32位字
+    header[0] = 0x45;
+    header[2..4].copy_from_slice(&((IPV4_HEADER_LEN + len) as u16).to_be_bytes());
+    header[8] = IPV4_DEFAULT_TTL;
+    header[9] = IPPROTO_ICMP as u8;
+    header[12..16].copy_from_slice(&src.0);
+    let checksum = !header.chunks(2).fold(0u32, |sum, word| {
+        let sum = sum + u16::from_be_bytes([word[0], word[1]]) as u32;
+        (sum & 0xffff) + (sum >> 16)
+    }) as u16;
+    header[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+    let header_len = buf.len().min(IPV4_HEADER_LEN);
+    buf[..header_len].copy_from_slice(&header[..header_len]);
+    buf[header_len..header_len + len].copy_from_slice(&message[..len]);
+    Ok((header_len + len, from))
+}
+
+/// axnet报告`key`标识的socket的状态可能发生变化时调用
+fn wake_inet_poll_wakers(key: SocketKey) {
+    let wakers = INET_POLL_WAKERS.lock().get(&key).cloned();
+    match wakers {
+        Some(wakers) => wakers.wake(),
+        // 仍然要唤醒没有注册回调的poll/select等待者
+        None => poll_notify(),
+    }
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        // axnet的socket在此之后才释放, 标识仍然有效
+        self.with_key(|key| INET_POLL_WAKERS.lock().remove(&key));
+    }
+}
+
+impl FileIO for Socket {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, buf: &mut [u8]) -> AxResult<usize> {
+        self.recv_from(buf, false).map(|(len, _)| len)
+    }
+
+    fn write(&self, buf: &[u8]) -> AxResult<usize> {
+        self.send_to(buf, None, false)
+    }
+
+    fn get_type(&self) -> String {
+        "Socket".to_string()
+    }
+
+    fn is_nonblocking(&self) -> bool {
+        self.nonblock.load(Ordering::Acquire)
+    }
+
+    /// 同时设置axnet的socket, 使非阻塞的connect只发起连接而不等待连接建立。
+    fn set_nonblocking(&self, nonblock: bool) {
+        self.nonblock.store(nonblock, Ordering::Release);
+        match &self.inner {
+            SocketInner::Tcp(socket) => socket.lock().set_nonblocking(nonblock),
+            SocketInner::Udp(socket) => socket.set_nonblocking(nonblock),
+            SocketInner::Icmp(socket) => socket.set_nonblocking(nonblock),
+        }
+    }
+
+    fn get_stat(&self) -> AxResult<Kstat> {
+        Ok(Kstat {
+            st_dev: 1,
+            st_ino: 1,
+            st_mode: normal_file_mode(StMode::S_IFSOCK).bits(),
+            st_nlink: 1,
+            st_uid: 0,
+            st_gid: 0,
+            st_rdev: 0,
+            _pad0: 0,
+            st_size: 0,
+            st_blksize: 0,
+            _pad1: 0,
+            st_blocks: 0,
+            st_atime_sec: 0,
+            st_atime_nsec: 0,
+            st_mtime_sec: 0,
+            st_mtime_nsec: 0,
+            st_ctime_sec: 0,
+            st_ctime_nsec: 0,
+        })
+    }
+
+    /// 出错的socket报告ERR
+    fn poll(&self) -> PollEvents {
+        match self.poll_state() {
+            Ok(state) => {
+                let mut events = PollEvents::empty();
+                if state.readable {
+                    events |= PollEvents::IN;
+                }
+                if state.writable {
+                    events |= PollEvents::OUT;
+                }
+                events
+            }
+            Err(_) => PollEvents::ERR,
+        }
+    }
+
+    fn register_poll_waker(&self, waker: &Arc<dyn PollWaker>) -> bool {
+        self.with_key(|key| {
+            INET_POLL_WAKERS
+                .lock()
+                .entry(key)
+                .or_insert_with(|| Arc::new(PollWakers::new()))
+                .register(waker)
+        });
+        true
+    }
+
+    fn unregister_poll_waker(&self, waker: &Arc<dyn PollWaker>) {
+        self.with_key(|key| {
+            if let Some(wakers) = INET_POLL_WAKERS.lock().get(&key) {
+                wakers.unregister(waker);
+            }
+        });
+    }
+}
@@ -1,26 +1,62 @@
 // 文件系统
 pub const SYSCALL_GETCWD: usize = 17;
+pub const SYSCALL_EPOLL_CREATE1: usize = 20;
+pub const SYSCALL_EPOLL_CTL: usize = 21;
+pub const SYSCALL_EPOLL_PWAIT: usize = 22;
 pub const SYSCALL_DUP: usize = 23;
 pub const SYSCALL_DUP3: usize = 24;
+pub const SYSCALL_FCNTL: usize = 25;
+pub const SYSCALL_IOCTL: usize = 29;
+pub const SYSCALL_FLOCK: usize = 32;
 //?
+pub const SYSCALL_MKNODAT: usize = 33;
 pub const SYSCALL_MKDIRAT: usize = 34;
 pub const SYSCALL_UNLINKAT: usize = 35;
 pub const SYSCALL_LINKAT: usize = 37;
 pub const SYSCALL_UNMOUNT: usize = 39;
 pub const SYSCALL_MOUNT: usize = 40;
+pub const SYSCALL_TRUNCATE: usize = 45;
+pub const SYSCALL_FTRUNCATE: usize = 46;
 pub const SYSCALL_CHDIR: usize = 49;
+pub const SYSCALL_FCHMOD: usize = 52;
+pub const SYSCALL_FCHMODAT: usize = 53;
+pub const SYSCALL_FCHOWNAT: usize = 54;
+pub const SYSCALL_FCHOWN: usize = 55;
 pub const SYSCALL_OPENAT: usize = 56;
 pub const SYSCALL_CLOSE: usize = 57;
 pub const SYSCALL_PIPE2: usize = 59;
 pub const SYSCALL_GETDENTS64: usize = 61;
 pub const SYSCALL_READ: usize = 63;
 pub const SYSCALL_WRITE: usize = 64;
+pub const SYSCALL_PSELECT6: usize = 72;
+pub const SYSCALL_PPOLL: usize = 73;
 pub const SYSCALL_FSTAT: usize = 80;
+pub const SYSCALL_SYNC: usize = 81;
+pub const SYSCALL_FSYNC: usize = 82;
+pub const SYSCALL_FDATASYNC: usize = 83;
+pub const SYSCALL_UTIMENSAT: usize = 88;
+pub const SYSCALL_UMASK: usize = 166;
+pub const SYSCALL_SYNCFS: usize = 267;
+pub const SYSCALL_RENAMEAT2: usize = 276;
 
 // 进程管理
 pub const SYSCALL_EXIT: usize = 93;
+pub const SYSCALL_SETREGID: usize = 143;
+pub const SYSCALL_SETGID: usize = 144;
+pub const SYSCALL_SETREUID: usize = 145;
+pub const SYSCALL_SETUID: usize = 146;
+pub const SYSCALL_SETRESUID: usize = 147;
+pub const SYSCALL_GETRESUID: usize = 148;
+pub const SYSCALL_SETRESGID: usize = 149;
+pub const SYSCALL_GETRESGID: usize = 150;
+pub const SYSCALL_GETGROUPS: usize = 158;
+pub const SYSCALL_SETGROUPS: usize = 159;
 pub const SYSCALL_GETPID: usize = 172;
 pub const SYSCALL_GETPPID: usize = 173;
+pub const SYSCALL_GETUID: usize = 174;
+pub const SYSCALL_GETEUID: usize = 175;
+pub const SYSCALL_GETGID: usize = 176;
+pub const SYSCALL_GETEGID: usize = 177;
 pub const SYSCALL_CLONE: usize = 220;
 pub const SYSCALL_EXECVE: usize = 221;
 pub const SYSCALL_WAIT4: usize = 260;
@@ -29,6 +65,25 @@ pub const SYSCALL_WAIT4: usize = 260;
 pub const SYSCALL_BRK: usize = 214;
 pub const SYSCALL_MUNMAP: usize = 215;
 pub const SYSCALL_MMAP: usize = 222;
+pub const SYSCALL_MSYNC: usize = 227;
+
+// 网络
+pub const SYSCALL_SOCKET: usize = 198;
+pub const SYSCALL_SOCKETPAIR: usize = 199;
+pub const SYSCALL_BIND: usize = 200;
+pub const SYSCALL_LISTEN: usize = 201;
+pub const SYSCALL_ACCEPT: usize = 202;
+pub const SYSCALL_CONNECT: usize = 203;
+pub const SYSCALL_GETSOCKNAME: usize = 204;
+pub const SYSCALL_GETPEERNAME: usize = 205;
+pub const SYSCALL_SENDTO: usize = 206;
+pub const SYSCALL_RECVFROM: usize = 207;
+pub const SYSCALL_SETSOCKOPT: usize = 208;
+pub const SYSCALL_GETSOCKOPT: usize = 209;
+pub const SYSCALL_SHUTDOWN: usize = 210;
+pub const SYSCALL_SENDMSG: usize = 211;
+pub const SYSCALL_RECVMSG: usize = 212;
+pub const SYSCALL_ACCEPT4: usize = 242;
 
 // 其他
 pub const SYSCALL_NANO_SLEEP: usize = 101;
@@ -42,35 +97,88 @@ pub const SYSCALL_GETTIMEOFDAY: usize = 169;
 pub fn get_syscall_name(syscall_id: usize) -> &'static str {
     match syscall_id {
         SYSCALL_GETCWD => "getcwd",
+        SYSCALL_EPOLL_CREATE1 => "epoll_create1",
+        SYSCALL_EPOLL_CTL => "epoll_ctl",
+        SYSCALL_EPOLL_PWAIT => "epoll_pwait",
         SYSCALL_DUP => "dup",
         SYSCALL_DUP3 => "dup3",
+        SYSCALL_FCNTL => "fcntl",
+        SYSCALL_IOCTL => "ioctl",
+        SYSCALL_FLOCK => "flock",
+        SYSCALL_MKNODAT => "mknodat",
         SYSCALL_MKDIRAT => "mkdirat",
         SYSCALL_UNLINKAT => "unlinkat",
         SYSCALL_LINKAT => "linkat",
         SYSCALL_UNMOUNT => "unmount",
         SYSCALL_MOUNT => "mount",
+        SYSCALL_TRUNCATE => "truncate",
+        SYSCALL_FTRUNCATE => "ftruncate",
         SYSCALL_CHDIR => "chdir",
+        SYSCALL_FCHMOD => "fchmod",
+        SYSCALL_FCHMODAT => "fchmodat",
+        SYSCALL_FCHOWNAT => "fchownat",
+        SYSCALL_FCHOWN => "fchown",
         SYSCALL_OPENAT => "openat",
         SYSCALL_CLOSE => "close",
         SYSCALL_PIPE2 => "pipe2",
         SYSCALL_GETDENTS64 => "getdents64",
         SYSCALL_READ => "read",
         SYSCALL_WRITE => "write",
+        SYSCALL_PSELECT6 => "pselect6",
+        SYSCALL_PPOLL => "ppoll",
         SYSCALL_FSTAT => "fstat",
+        SYSCALL_SYNC => "sync",
+        SYSCALL_FSYNC => "fsync",
+        SYSCALL_FDATASYNC => "fdatasync",
+        SYSCALL_UTIMENSAT => "utimensat",
+        SYSCALL_UMASK => "umask",
+        SYSCALL_SYNCFS => "syncfs",
+        SYSCALL_RENAMEAT2 => "renameat2",
         SYSCALL_EXIT => "exit",
+        SYSCALL_SETREGID => "setregid",
+        SYSCALL_SETGID => "setgid",
+        SYSCALL_SETREUID => "setreuid",
+        SYSCALL_SETUID => "setuid",
+        SYSCALL_SETRESUID => "setresuid",
+        SYSCALL_GETRESUID => "getresuid",
+        SYSCALL_SETRESGID => "setresgid",
+        SYSCALL_GETRESGID => "getresgid",
+        SYSCALL_GETGROUPS => "getgroups",
+        SYSCALL_SETGROUPS => "setgroups",
         SYSCALL_GETPID => "getpid",
         SYSCALL_GETPPID => "getppid",
+        SYSCALL_GETUID => "getuid",
+        SYSCALL_GETEUID => "geteuid",
+        SYSCALL_GETGID => "getgid",
+        SYSCALL_GETEGID => "getegid",
         SYSCALL_CLONE => "clone",
         SYSCALL_EXECVE => "execve",
         SYSCALL_WAIT4 => "wait4",
         SYSCALL_BRK => "brk",
         SYSCALL_MUNMAP => "munmap",
         SYSCALL_MMAP => "mmap",
+        SYSCALL_MSYNC => "msync",
         SYSCALL_NANO_SLEEP => "nanosleep",
         SYSCALL_SCHED_YIELD => "sched_yield",
         SYSCALL_TIMES => "times",
         SYSCALL_UNAME => "uname",
         SYSCALL_GETTIMEOFDAY => "gettimeofday",
+        SYSCALL_SOCKET => "socket",
+        SYSCALL_SOCKETPAIR => "socketpair",
+        SYSCALL_BIND => "bind",
+        SYSCALL_LISTEN => "listen",
+        SYSCALL_ACCEPT => "accept",
+        SYSCALL_CONNECT => "connect",
+        SYSCALL_GETSOCKNAME => "getsockname",
+        SYSCALL_GETPEERNAME => "getpeername",
+        SYSCALL_SENDTO => "sendto",
+        SYSCALL_RECVFROM => "recvfrom",
+        SYSCALL_SETSOCKOPT => "setsockopt",
+        SYSCALL_GETSOCKOPT => "getsockopt",
+        SYSCALL_SHUTDOWN => "shutdown",
+        SYSCALL_SENDMSG => "sendmsg",
+        SYSCALL_RECVMSG => "recvmsg",
+        SYSCALL_ACCEPT4 => "accept4",
         _ => "unknown",
     }
-}
\ No newline at end of file
+}
\ No newline at end of file
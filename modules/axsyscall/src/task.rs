@@ -1,17 +1,21 @@
 use core::time::Duration;
 
+use axfs::api::{self, Permissions};
+use axfs::perm::{self, Cap};
 use axfs_os::read_file;
-use axhal::time::{current_time, current_time_nanos, nanos_to_ticks};
+use axhal::time::{current_time, current_time_nanos, nanos_to_ticks, wall_time_nanos};
 use axprocess::{
+    cred::{Credentials, NGROUPS_MAX},
     flags::{CloneFlags, WaitStatus},
     process::{current_process, current_task, sleep_now_task, wait_pid, yield_now_task},
     time_stat_output,
 };
 extern crate alloc;
 use alloc::vec::Vec;
-use log::info;
+use log::{debug, info};
 
 use crate::flags::{TimeSecs, TimeVal, UtsName, WaitFlags, TMS};
+use crate::fs::{close_file, current_identity};
 /// 处理与任务（线程）有关的系统调用
 
 pub fn syscall_exit(exit_code: i32) -> isize {
@@ -40,8 +44,33 @@ pub fn syscall_exec(path: *const u8, mut args: *const usize) -> isize {
         }
     }
     drop(inner);
+    // 执行文件需要有执行权限
+    if let Err(e) = perm::check_access(path.as_str(), &current_identity(), Cap::EXECUTE) {
+        debug!("exec {} denied: {:?}", path, e);
+        return -1;
+    }
+    // 程序文件设置了setuid/setgid位时, 以文件所有者/所属组的身份运行。FAT等文件系统不记录这两个位
+    let (set_uid, set_gid) = match api::metadata(path.as_str()) {
+        Ok(meta) => {
+            let perm = meta.permissions();
+            (
+                perm.contains(Permissions::SET_UID).then_some(meta.uid()),
+                perm.contains(Permissions::SET_GID).then_some(meta.gid()),
+            )
+        }
+        Err(_) => (None, None),
+    };
     let elf_data = read_file(path.as_str()).unwrap();
     let argc = args_vec.len();
+    let cloexec_files = {
+        let mut inner = curr_process.inner.lock();
+        inner.cred.exec(set_uid, set_gid);
+        inner.take_cloexec_files()
+    };
+    // 关闭设置了FD_CLOEXEC的文件描述符
+    for file in cloexec_files {
+        close_file(curr_process.pid, Some(file));
+    }
     curr_process.exec(elf_data.as_slice(), args_vec);
     argc as isize
 }
@@ -79,6 +108,199 @@ pub fn syscall_getppid() -> isize {
     parent_id as isize
 }
 
+/// 辅助函数：在持有进程锁时访问当前进程的身份凭证
+fn with_cred<T>(f: impl FnOnce(&mut Credentials) -> T) -> T {
+    let curr_process = current_process();
+    let mut inner = curr_process.inner.lock();
+    f(&mut inner.cred)
+}
+
+/// 辅助函数：将-1转换为None，表示不修改对应的ID
+fn optional_id(id: u32) -> Option<u32> {
+    (id != u32::MAX).then_some(id)
+}
+
+/// 功能：获取进程的实际用户ID；
+/// 返回值：总是成功，返回实际用户ID。
+pub fn syscall_getuid() -> isize {
+    with_cred(|cred| cred.uid) as isize
+}
+
+/// 功能：获取进程的有效用户ID；
+/// 返回值：总是成功，返回有效用户ID。
+pub fn syscall_geteuid() -> isize {
+    with_cred(|cred| cred.euid) as isize
+}
+
+/// 功能：获取进程的实际组ID；
+/// 返回值：总是成功，返回实际组ID。
+pub fn syscall_getgid() -> isize {
+    with_cred(|cred| cred.gid) as isize
+}
+
+/// 功能：获取进程的有效组ID；
+/// 返回值：总是成功，返回有效组ID。
+pub fn syscall_getegid() -> isize {
+    with_cred(|cred| cred.egid) as isize
+}
+
+/// 功能：设置进程的用户ID；
+/// 输入：
+///     - uid：新的用户ID。特权进程同时设置实际、有效和保存的用户ID，普通进程只能设置有效用户ID。
+/// 返回值：成功返回0，没有权限返回-1。
+pub fn syscall_setuid(uid: u32) -> isize {
+    debug!("Into syscall_setuid. uid: {}", uid);
+    if with_cred(|cred| cred.set_uid(uid)) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// 功能：设置进程的组ID；
+/// 输入：
+///     - gid：新的组ID，规则同setuid。
+/// 返回值：成功返回0，没有权限返回-1。
+pub fn syscall_setgid(gid: u32) -> isize {
+    debug!("Into syscall_setgid. gid: {}", gid);
+    if with_cred(|cred| cred.set_gid(gid)) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// 功能：设置进程的实际和有效用户ID；
+/// 输入：
+///     - ruid：新的实际用户ID，为-1时不修改。
+///     - euid：新的有效用户ID，为-1时不修改。
+/// 返回值：成功返回0，没有权限返回-1。
+pub fn syscall_setreuid(ruid: u32, euid: u32) -> isize {
+    debug!("Into syscall_setreuid. ruid: {}, euid: {}", ruid, euid);
+    if with_cred(|cred| cred.set_reuid(optional_id(ruid), optional_id(euid))) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// 功能：设置进程的实际和有效组ID；
+/// 输入：
+///     - rgid：新的实际组ID，为-1时不修改。
+///     - egid：新的有效组ID，为-1时不修改。
+/// 返回值：成功返回0，没有权限返回-1。
+pub fn syscall_setregid(rgid: u32, egid: u32) -> isize {
+    debug!("Into syscall_setregid. rgid: {}, egid: {}", rgid, egid);
+    if with_cred(|cred| cred.set_regid(optional_id(rgid), optional_id(egid))) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// 功能：设置进程的实际、有效和保存的用户ID；
+/// 输入：
+///     - ruid、euid、suid：新的实际、有效和保存的用户ID，为-1时不修改。
+/// 返回值：成功返回0，没有权限返回-1。
+pub fn syscall_setresuid(ruid: u32, euid: u32, suid: u32) -> isize {
+    debug!(
+        "Into syscall_setresuid. ruid: {}, euid: {}, suid: {}",
+        ruid, euid, suid
+    );
+    let (ruid, euid, suid) = (optional_id(ruid), optional_id(euid), optional_id(suid));
+    if with_cred(|cred| cred.set_resuid(ruid, euid, suid)) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// 功能：获取进程的实际、有效和保存的用户ID；
+/// 输入：
+///     - ruid、euid、suid：分别用于保存实际、有效和保存的用户ID的地址。
+/// 返回值：成功返回0。
+pub fn syscall_getresuid(ruid: *mut u32, euid: *mut u32, suid: *mut u32) -> isize {
+    let ids = with_cred(|cred| [cred.uid, cred.euid, cred.suid]);
+    for (ptr, id) in [ruid, euid, suid].into_iter().zip(ids) {
+        if ptr.is_null() {
+            return -1;
+        }
+        unsafe { *ptr = id };
+    }
+    0
+}
+
+/// 功能：设置进程的实际、有效和保存的组ID；
+/// 输入：
+///     - rgid、egid、sgid：新的实际、有效和保存的组ID，为-1时不修改。
+/// 返回值：成功返回0，没有权限返回-1。
+pub fn syscall_setresgid(rgid: u32, egid: u32, sgid: u32) -> isize {
+    debug!(
+        "Into syscall_setresgid. rgid: {}, egid: {}, sgid: {}",
+        rgid, egid, sgid
+    );
+    let (rgid, egid, sgid) = (optional_id(rgid), optional_id(egid), optional_id(sgid));
+    if with_cred(|cred| cred.set_resgid(rgid, egid, sgid)) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// 功能：获取进程的实际、有效和保存的组ID；
+/// 输入：
+///     - rgid、egid、sgid：分别用于保存实际、有效和保存的组ID的地址。
+/// 返回值：成功返回0。
+pub fn syscall_getresgid(rgid: *mut u32, egid: *mut u32, sgid: *mut u32) -> isize {
+    let ids = with_cred(|cred| [cred.gid, cred.egid, cred.sgid]);
+    for (ptr, id) in [rgid, egid, sgid].into_iter().zip(ids) {
+        if ptr.is_null() {
+            return -1;
+        }
+        unsafe { *ptr = id };
+    }
+    0
+}
+
+/// 功能：获取进程的附加组；
+/// 输入：
+///     - size：list能容纳的组ID个数。为0时只返回附加组的个数，不写入list。
+///     - list：用于保存附加组ID的数组。
+/// 返回值：成功返回附加组的个数。size小于附加组个数时返回-1。
+pub fn syscall_getgroups(size: usize, list: *mut u32) -> isize {
+    let groups = with_cred(|cred| cred.groups.clone());
+    if size == 0 {
+        return groups.len() as isize;
+    }
+    if size < groups.len() || list.is_null() {
+        return -1;
+    }
+    unsafe { core::ptr::copy_nonoverlapping(groups.as_ptr(), list, groups.len()) };
+    groups.len() as isize
+}
+
+/// 功能：设置进程的附加组，只有特权进程可以调用；
+/// 输入：
+///     - size：附加组的个数。
+///     - list：附加组ID的数组。
+/// 返回值：成功返回0，没有权限或个数过多返回-1。
+pub fn syscall_setgroups(size: usize, list: *const u32) -> isize {
+    debug!("Into syscall_setgroups. size: {}", size);
+    if size > NGROUPS_MAX || (size > 0 && list.is_null()) {
+        return -1;
+    }
+    let groups = if size == 0 {
+        Vec::new()
+    } else {
+        unsafe { core::slice::from_raw_parts(list, size) }.to_vec()
+    };
+    if with_cred(|cred| cred.set_groups(groups)) {
+        0
+    } else {
+        -1
+    }
+}
+
 /// 等待子进程完成任务，若子进程没有完成，则自身yield
 /// 当前仅支持WNOHANG选项，即若未完成时则不予等待，直接返回0
 pub fn syscall_wait4(pid: isize, exit_code_ptr: *mut i32, option: WaitFlags) -> isize {
@@ -160,7 +382,7 @@ pub fn syscall_time(tms: *mut TMS) -> isize {
 
 /// 获取当前系统时间并且存储在给定结构体中
 pub fn syscall_get_time_of_day(ts: *mut TimeVal) -> isize {
-    let current_us = current_time_nanos() as usize / 1000;
+    let current_us = wall_time_nanos() as usize / 1000;
     unsafe {
         *ts = TimeVal {
             sec: current_us / 1000_000,
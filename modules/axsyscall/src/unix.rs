@@ -0,0 +1,88 @@
+//! 用户态的`struct sockaddr_un`与Unix域socket地址之间的转换
+
+use alloc::string::String;
+use core::mem::size_of;
+
+use axerrno::{AxError, AxResult};
+use axfs_os::unix::UnixAddr;
+use axfs_os::FilePath;
+
+/// Unix域协议族
+pub const AF_UNIX: usize = 1;
+/// `sun_path`的长度
+const SUN_PATH_LEN: usize = 108;
+
+/// 用户态的`struct sockaddr_un`
+#[repr(C)]
+struct SockAddrUn {
+    /// 协议族, 总是AF_UNIX
+    sun_family: u16,
+    /// 路径, 以0结尾; 第一个字节为0时为抽象名字, 长度由addrlen决定
+    sun_path: [u8; SUN_PATH_LEN],
+}
+
+/// 从用户态的`struct sockaddr_un`中读取地址
+///
+/// 只有协议族字段时为未绑定的地址, bind时自动分配抽象名字。相对路径相对于当前工作目录。
+pub unsafe fn read_sockaddr_un(addr: *const u8, addrlen: usize) -> AxResult<UnixAddr> {
+    if addr.is_null() || addrlen < size_of::<u16>() || addrlen > size_of::<SockAddrUn>() {
+        return Err(AxError::InvalidInput);
+    }
+    if (addr as *const u16).read_unaligned() as usize != AF_UNIX {
+        return Err(AxError::InvalidInput);
+    }
+    let path = core::slice::from_raw_parts(addr.add(size_of::<u16>()), addrlen - size_of::<u16>());
+    match path.first() {
+        None => Ok(UnixAddr::Unnamed),
+        Some(0) => Ok(UnixAddr::Abstract(path[1..].to_vec())),
+        Some(_) => {
+            let len = path.iter().position(|&b| b == 0).unwrap_or(path.len());
+            let path =
+                String::from_utf8(path[..len].to_vec()).map_err(|_| AxError::InvalidInput)?;
+            Ok(UnixAddr::Path(FilePath::new(&path)))
+        }
+    }
+}
+
+/// 将地址写入用户态的`struct sockaddr_un`
+///
+/// `addrlen`输入时为缓冲区的长度, 地址超出缓冲区的部分被截断; 返回时为地址的实际长度。
+/// `addr`为空指针时什么也不做。
+pub unsafe fn write_sockaddr_un(
+    addr: *mut u8,
+    addrlen: *mut u32,
+    unix_addr: &UnixAddr,
+) -> AxResult {
+    if addr.is_null() {
+        return Ok(());
+    }
+    if addrlen.is_null() {
+        return Err(AxError::InvalidInput);
+    }
+    let mut sockaddr = SockAddrUn {
+        sun_family: AF_UNIX as u16,
+        sun_path: [0; SUN_PATH_LEN],
+    };
+    let path_len = match unix_addr {
+        UnixAddr::Unnamed => 0,
+        UnixAddr::Path(path) => {
+            // 保留结尾的0
+            let len = path.path().len().min(SUN_PATH_LEN - 1);
+            sockaddr.sun_path[..len].copy_from_slice(&path.path().as_bytes()[..len]);
+            len + 1
+        }
+        UnixAddr::Abstract(name) => {
+            let len = name.len().min(SUN_PATH_LEN - 1);
+            sockaddr.sun_path[1..=len].copy_from_slice(&name[..len]);
+            len + 1
+        }
+    };
+    let len = size_of::<u16>() + path_len;
+    core::ptr::copy_nonoverlapping(
+        &sockaddr as *const SockAddrUn as *const u8,
+        addr,
+        (*addrlen as usize).min(len),
+    );
+    *addrlen = len as u32;
+    Ok(())
+}
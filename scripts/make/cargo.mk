@@ -16,6 +16,11 @@ features-$(FS) += libax/fs
 features-$(NET) += libax/net
 features-$(GRAPHIC) += libax/display
 
+ifneq ($(INITRAMFS),)
+  features-y += libax/initramfs
+  export AX_INITRAMFS := $(abspath $(INITRAMFS))
+endif
+
 default_features := y
 
 ifeq ($(APP_LANG),c)
@@ -0,0 +1,252 @@
+// Unix域socket(AF_UNIX)的用户态测例, 全部通过时返回0, 否则打印失败的检查并返回1。
+//
+// 覆盖: socketpair的流和数据报收发, 绑定路径和抽象名字后connect/accept,
+// 数据报被截断时的MSG_TRUNC, 以及SCM_RIGHTS的控制缓冲区放不下所有文件时的MSG_CTRUNC。
+
+#include <errno.h>
+#include <fcntl.h>
+#include <stddef.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/socket.h>
+#include <sys/stat.h>
+#include <sys/uio.h>
+#include <sys/un.h>
+#include <unistd.h>
+
+#define SOCK_PATH "/unix_socket_test.sock"
+#define ABSTRACT_NAME "unix_socket_test"
+
+static int failed;
+
+#define CHECK(cond)                                                         \
+    do {                                                                    \
+        if (!(cond)) {                                                      \
+            printf("unix_socket: %s:%d: check failed: %s (errno %d)\n",     \
+                   __func__, __LINE__, #cond, errno);                       \
+            failed = 1;                                                     \
+            return;                                                         \
+        }                                                                   \
+    } while (0)
+
+// 在fd上发送msg, 再从peer上读回并比较
+static int round_trip(int fd, int peer, const char *msg)
+{
+    char buf[64];
+    size_t len = strlen(msg);
+    if (write(fd, msg, len) != (ssize_t)len)
+        return 0;
+    memset(buf, 0, sizeof(buf));
+    if (read(peer, buf, sizeof(buf)) != (ssize_t)len)
+        return 0;
+    return memcmp(buf, msg, len) == 0;
+}
+
+static void test_socketpair_stream(void)
+{
+    int sv[2];
+    char buf[16];
+    CHECK(socketpair(AF_UNIX, SOCK_STREAM, 0, sv) == 0);
+    CHECK(round_trip(sv[0], sv[1], "ping"));
+    CHECK(round_trip(sv[1], sv[0], "pong"));
+    // 对端关闭后读到文件尾
+    close(sv[1]);
+    CHECK(read(sv[0], buf, sizeof(buf)) == 0);
+    close(sv[0]);
+}
+
+static void test_socketpair_dgram(void)
+{
+    int sv[2];
+    char buf[16];
+    CHECK(socketpair(AF_UNIX, SOCK_DGRAM, 0, sv) == 0);
+    // 保留消息边界
+    CHECK(write(sv[0], "one", 3) == 3);
+    CHECK(write(sv[0], "two!", 4) == 4);
+    CHECK(read(sv[1], buf, sizeof(buf)) == 3 && memcmp(buf, "one", 3) == 0);
+    CHECK(read(sv[1], buf, sizeof(buf)) == 4 && memcmp(buf, "two!", 4) == 0);
+    CHECK(round_trip(sv[1], sv[0], "back"));
+    close(sv[0]);
+    close(sv[1]);
+}
+
+// 在addr上监听, 连接之后双向收发, 并检查accept和getpeername返回的地址
+static void connect_accept(const struct sockaddr_un *addr, socklen_t addrlen)
+{
+    struct sockaddr_un peer;
+    socklen_t peerlen;
+    int listener, client, server;
+
+    listener = socket(AF_UNIX, SOCK_STREAM, 0);
+    CHECK(listener >= 0);
+    CHECK(bind(listener, (const struct sockaddr *)addr, addrlen) == 0);
+    // 同一个地址不能被绑定两次
+    client = socket(AF_UNIX, SOCK_STREAM, 0);
+    CHECK(client >= 0);
+    CHECK(bind(client, (const struct sockaddr *)addr, addrlen) == -1);
+    CHECK(listen(listener, 4) == 0);
+
+    // 连接放入backlog后立即返回, 不需要另一个进程accept
+    CHECK(connect(client, (const struct sockaddr *)addr, addrlen) == 0);
+    peerlen = sizeof(peer);
+    server = accept(listener, (struct sockaddr *)&peer, &peerlen);
+    CHECK(server >= 0);
+    // 客户端没有绑定地址
+    CHECK(peerlen == offsetof(struct sockaddr_un, sun_path));
+
+    peerlen = sizeof(peer);
+    CHECK(getpeername(client, (struct sockaddr *)&peer, &peerlen) == 0);
+    CHECK(peerlen == addrlen);
+    CHECK(memcmp(&peer, addr, addrlen) == 0);
+
+    CHECK(round_trip(client, server, "hello server"));
+    CHECK(round_trip(server, client, "hello client"));
+    close(server);
+    close(client);
+    close(listener);
+}
+
+static void test_bind_path(void)
+{
+    struct sockaddr_un addr;
+    struct stat st;
+    memset(&addr, 0, sizeof(addr));
+    addr.sun_family = AF_UNIX;
+    strcpy(addr.sun_path, SOCK_PATH);
+    unlink(SOCK_PATH);
+    connect_accept(&addr, offsetof(struct sockaddr_un, sun_path) + strlen(SOCK_PATH) + 1);
+    // 绑定路径时创建了socket类型的文件
+    CHECK(stat(SOCK_PATH, &st) == 0 && S_ISSOCK(st.st_mode));
+    CHECK(unlink(SOCK_PATH) == 0);
+}
+
+static void test_bind_abstract(void)
+{
+    struct sockaddr_un addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sun_family = AF_UNIX;
+    // 抽象名字以0字节开头, 长度由addrlen决定
+    memcpy(addr.sun_path + 1, ABSTRACT_NAME, strlen(ABSTRACT_NAME));
+    connect_accept(&addr, offsetof(struct sockaddr_un, sun_path) + 1 + strlen(ABSTRACT_NAME));
+    // 抽象名字不在文件系统中
+    CHECK(access(ABSTRACT_NAME, F_OK) == -1);
+}
+
+static void test_dgram_truncation(void)
+{
+    int sv[2];
+    char buf[4];
+    char rest[16];
+    struct iovec iov = {.iov_base = buf, .iov_len = sizeof(buf)};
+    struct msghdr msg;
+    CHECK(socketpair(AF_UNIX, SOCK_DGRAM, 0, sv) == 0);
+    CHECK(write(sv[0], "0123456789", 10) == 10);
+    CHECK(write(sv[0], "next", 4) == 4);
+
+    memset(&msg, 0, sizeof(msg));
+    msg.msg_iov = &iov;
+    msg.msg_iovlen = 1;
+    CHECK(recvmsg(sv[1], &msg, 0) == (ssize_t)sizeof(buf));
+    CHECK(msg.msg_flags & MSG_TRUNC);
+    CHECK(memcmp(buf, "0123", 4) == 0);
+    // 被截断的部分被丢弃, 下一次读到的是下一个数据报
+    CHECK(read(sv[1], rest, sizeof(rest)) == 4 && memcmp(rest, "next", 4) == 0);
+
+    // 放得下时不设置MSG_TRUNC
+    CHECK(write(sv[0], "abc", 3) == 3);
+    memset(&msg, 0, sizeof(msg));
+    msg.msg_iov = &iov;
+    msg.msg_iovlen = 1;
+    CHECK(recvmsg(sv[1], &msg, 0) == 3);
+    CHECK(!(msg.msg_flags & MSG_TRUNC));
+    close(sv[0]);
+    close(sv[1]);
+}
+
+#define NFDS 3
+
+static void test_scm_rights_ctrunc(void)
+{
+    int sv[2];
+    int pipes[NFDS][2];
+    int fds[NFDS];
+    int received[NFDS];
+    int count, i;
+    char data = 'x', buf[8];
+    struct iovec iov = {.iov_base = &data, .iov_len = 1};
+    struct msghdr msg;
+    struct cmsghdr *cmsg;
+    // 发送端放得下所有文件, 接收端只放得下一个
+    union {
+        char buf[CMSG_SPACE(sizeof(fds))];
+        struct cmsghdr align;
+    } send_control;
+    union {
+        char buf[CMSG_LEN(sizeof(int))];
+        struct cmsghdr align;
+    } recv_control;
+
+    CHECK(socketpair(AF_UNIX, SOCK_DGRAM, 0, sv) == 0);
+    for (i = 0; i < NFDS; i++) {
+        CHECK(pipe(pipes[i]) == 0);
+        CHECK(fcntl(pipes[i][0], F_SETFL, O_NONBLOCK) == 0);
+        fds[i] = pipes[i][1];
+    }
+
+    memset(&msg, 0, sizeof(msg));
+    msg.msg_iov = &iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = send_control.buf;
+    msg.msg_controllen = sizeof(send_control.buf);
+    cmsg = CMSG_FIRSTHDR(&msg);
+    cmsg->cmsg_level = SOL_SOCKET;
+    cmsg->cmsg_type = SCM_RIGHTS;
+    cmsg->cmsg_len = CMSG_LEN(sizeof(fds));
+    memcpy(CMSG_DATA(cmsg), fds, sizeof(fds));
+    CHECK(sendmsg(sv[0], &msg, 0) == 1);
+    // 发送之后文件由消息持有, 关闭发送进程中的写端
+    for (i = 0; i < NFDS; i++)
+        close(pipes[i][1]);
+
+    memset(&msg, 0, sizeof(msg));
+    msg.msg_iov = &iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = recv_control.buf;
+    msg.msg_controllen = sizeof(recv_control.buf);
+    CHECK(recvmsg(sv[1], &msg, 0) == 1);
+    CHECK(msg.msg_flags & MSG_CTRUNC);
+    cmsg = CMSG_FIRSTHDR(&msg);
+    CHECK(cmsg != NULL);
+    CHECK(cmsg->cmsg_level == SOL_SOCKET && cmsg->cmsg_type == SCM_RIGHTS);
+    count = (cmsg->cmsg_len - CMSG_LEN(0)) / sizeof(int);
+    CHECK(count == 1);
+    memcpy(received, CMSG_DATA(cmsg), count * sizeof(int));
+
+    // 收到的文件是第一个管道的写端, 可以正常使用
+    CHECK(write(received[0], "y", 1) == 1);
+    CHECK(read(pipes[0][0], buf, sizeof(buf)) == 1 && buf[0] == 'y');
+    // 放不下的文件已被关闭, 对应管道的读端读到文件尾而不是EAGAIN
+    for (i = count; i < NFDS; i++)
+        CHECK(read(pipes[i][0], buf, sizeof(buf)) == 0);
+    close(received[0]);
+    CHECK(read(pipes[0][0], buf, sizeof(buf)) == 0);
+
+    for (i = 0; i < NFDS; i++)
+        close(pipes[i][0]);
+    close(sv[0]);
+    close(sv[1]);
+}
+
+int main(void)
+{
+    test_socketpair_stream();
+    test_socketpair_dgram();
+    test_bind_path();
+    test_bind_abstract();
+    test_dgram_truncation();
+    test_scm_rights_ctrunc();
+    if (failed)
+        return 1;
+    printf("unix_socket: all tests passed\n");
+    return 0;
+}
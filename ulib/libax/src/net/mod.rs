@@ -1,4 +1,10 @@
+mod ping;
+mod socket_addr;
 mod tcp;
+mod udp;
 
+pub use self::ping::{ping, Pinger};
+pub use self::socket_addr::{lookup_host, ToSocketAddrs};
 pub use self::tcp::{TcpListener, TcpStream};
-pub use axnet::{IpAddr, Ipv4Addr, SocketAddr};
+pub use self::udp::UdpSocket;
+pub use axnet::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
@@ -0,0 +1,79 @@
+use core::sync::atomic::{AtomicU16, Ordering};
+
+use crate::io;
+use crate::time::{Duration, Instant};
+
+use axerrno::{ax_err, AxError};
+use axnet::{IcmpSocket, IpAddr};
+
+/// The size of the payload of an echo request, as sent by `ping`.
+const PAYLOAD_LEN: usize = 56;
+const ICMP_HEADER_LEN: usize = 8;
+
+/// The identifier of the next pinger.
+static NEXT_IDENT: AtomicU16 = AtomicU16::new(1);
+
+/// Sends ICMP echo requests and waits for the replies.
+pub struct Pinger {
+    socket: IcmpSocket,
+    ident: u16,
+}
+
+impl Pinger {
+    pub fn new() -> io::Result<Self> {
+        let socket = IcmpSocket::new();
+        let ident = NEXT_IDENT.fetch_add(1, Ordering::Relaxed);
+        socket.bind(ident)?;
+        Ok(Self { socket, ident })
+    }
+
+    /// Sends an echo request with sequence number `seq` to `addr`, and returns
+    /// the round-trip time once the reply arrives.
+    ///
+    /// Returns a `WouldBlock` error if no reply arrives within `timeout`.
+    pub fn ping(&self, addr: IpAddr, seq: u16, timeout: Duration) -> io::Result<Duration> {
+        if timeout.is_zero() {
+            return ax_err!(InvalidInput, "zero timeout");
+        }
+        let (request, reply) = match addr {
+            IpAddr::Ipv4(_) => (8, 0),
+            IpAddr::Ipv6(_) => (128, 129),
+        };
+        let mut packet = [0; ICMP_HEADER_LEN + PAYLOAD_LEN];
+        packet[0] = request;
+        packet[4..6].copy_from_slice(&self.ident.to_be_bytes());
+        packet[6..8].copy_from_slice(&seq.to_be_bytes());
+        for (i, b) in packet[ICMP_HEADER_LEN..].iter_mut().enumerate() {
+            *b = i as u8;
+        }
+
+        let start = Instant::now();
+        self.socket.send_to(&packet, addr)?;
+        let mut buf = [0; ICMP_HEADER_LEN + PAYLOAD_LEN];
+        loop {
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                return Err(AxError::WouldBlock);
+            }
+            self.socket.set_recv_timeout(Some(timeout - elapsed));
+            let (len, from) = self.socket.recv_from(&mut buf)?;
+            // skip other messages, e.g. our own request when pinging ourselves
+            if from == addr
+                && len == packet.len()
+                && buf[0] == reply
+                && buf[4..8] == packet[4..8]
+                && buf[ICMP_HEADER_LEN..] == packet[ICMP_HEADER_LEN..]
+            {
+                return Ok(start.elapsed());
+            }
+        }
+    }
+}
+
+/// Sends one echo request to `addr`, and returns the round-trip time once the
+/// reply arrives.
+///
+/// Returns a `WouldBlock` error if no reply arrives within `timeout`.
+pub fn ping(addr: IpAddr, timeout: Duration) -> io::Result<Duration> {
+    Pinger::new()?.ping(addr, 0, timeout)
+}
@@ -0,0 +1,137 @@
+use alloc::string::String;
+use alloc::vec::{self, Vec};
+use core::{iter, option};
+
+use axerrno::{ax_err, ax_err_type};
+
+use super::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use crate::io;
+
+/// Resolves a host name to its IP addresses by DNS.
+///
+/// A literal IP address is returned as is. Results are cached by the kernel.
+pub fn lookup_host(host: &str) -> io::Result<Vec<IpAddr>> {
+    axnet::dns_query(host)
+}
+
+/// Objects that can be converted to one or more [`SocketAddr`]s, like
+/// `std::net::ToSocketAddrs`.
+///
+/// Strings in the form `"host:port"` and `(&str, u16)` pairs resolve the host
+/// name by DNS if it is not an IP address. IPv6 addresses in strings are
+/// written in brackets, as in `"[::1]:80"`.
+pub trait ToSocketAddrs {
+    /// Returned iterator over socket addresses.
+    type Iter: Iterator<Item = SocketAddr>;
+
+    /// Converts this object to an iterator of resolved [`SocketAddr`]s.
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter>;
+}
+
+impl ToSocketAddrs for SocketAddr {
+    type Iter = option::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        Ok(Some(*self).into_iter())
+    }
+}
+
+impl ToSocketAddrs for (IpAddr, u16) {
+    type Iter = option::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        SocketAddr::new(self.0, self.1).to_socket_addrs()
+    }
+}
+
+impl ToSocketAddrs for (Ipv4Addr, u16) {
+    type Iter = option::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        SocketAddr::new(self.0.into(), self.1).to_socket_addrs()
+    }
+}
+
+impl ToSocketAddrs for (Ipv6Addr, u16) {
+    type Iter = option::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        SocketAddr::new(self.0.into(), self.1).to_socket_addrs()
+    }
+}
+
+impl ToSocketAddrs for (&str, u16) {
+    type Iter = vec::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        let (host, port) = *self;
+        let addrs = lookup_host(host)?;
+        Ok(addrs
+            .into_iter()
+            .map(|addr| SocketAddr::new(addr, port))
+            .collect::<Vec<_>>()
+            .into_iter())
+    }
+}
+
+impl ToSocketAddrs for str {
+    type Iter = vec::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        let (host, port) = self
+            .rsplit_once(':')
+            .ok_or_else(|| ax_err_type!(InvalidInput, "invalid socket address"))?;
+        let port = port
+            .parse()
+            .map_err(|_| ax_err_type!(InvalidInput, "invalid port value"))?;
+        let host = host
+            .strip_prefix('[')
+            .and_then(|host| host.strip_suffix(']'))
+            .unwrap_or(host);
+        (host, port).to_socket_addrs()
+    }
+}
+
+impl ToSocketAddrs for String {
+    type Iter = vec::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        self.as_str().to_socket_addrs()
+    }
+}
+
+impl<'a> ToSocketAddrs for &'a [SocketAddr] {
+    type Iter = iter::Cloned<core::slice::Iter<'a, SocketAddr>>;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        Ok(self.iter().cloned())
+    }
+}
+
+impl<T: ToSocketAddrs + ?Sized> ToSocketAddrs for &T {
+    type Iter = T::Iter;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        (**self).to_socket_addrs()
+    }
+}
+
+/// Calls `f` on each address of `addr` until it succeeds, returning the last
+/// error if none does.
+pub(super) fn each_addr<A, F, T>(addr: A, mut f: F) -> io::Result<T>
+where
+    A: ToSocketAddrs,
+    F: FnMut(SocketAddr) -> io::Result<T>,
+{
+    let mut last_err = None;
+    for addr in addr.to_socket_addrs()? {
+        match f(addr) {
+            Ok(t) => return Ok(t),
+            Err(e) => last_err = Some(e),
+        }
+    }
+    match last_err {
+        Some(e) => Err(e),
+        None => ax_err!(InvalidInput, "could not resolve to any addresses"),
+    }
+}
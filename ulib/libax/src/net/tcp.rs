@@ -1,7 +1,11 @@
 use crate::io::{self, prelude::*};
+use crate::time::Duration;
 
+use axerrno::ax_err;
 use axnet::{SocketAddr, TcpSocket};
 
+use super::socket_addr::{each_addr, ToSocketAddrs};
+
 pub struct TcpStream {
     socket: TcpSocket,
 }
@@ -11,10 +15,13 @@ pub struct TcpListener {
 }
 
 impl TcpStream {
-    pub fn connect(addr: SocketAddr) -> io::Result<Self> {
-        let mut socket = TcpSocket::new();
-        socket.connect(addr)?;
-        Ok(Self { socket })
+    /// Opens a connection to `addr`, trying each resolved address in turn.
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        each_addr(addr, |addr| {
+            let mut socket = TcpSocket::new();
+            socket.connect(addr)?;
+            Ok(Self { socket })
+        })
     }
 
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
@@ -28,6 +35,65 @@ impl TcpStream {
     pub fn shutdown(&self) -> io::Result {
         self.socket.shutdown()
     }
+
+    /// Moves the stream into or out of nonblocking mode, in which reads and
+    /// writes return a `WouldBlock` error instead of waiting.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result {
+        self.socket.set_nonblocking(nonblocking);
+        Ok(())
+    }
+
+    /// Sets how long a read waits before returning a `WouldBlock` error, or
+    /// `None` to wait forever. A zero duration is invalid.
+    pub fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result {
+        check_timeout(dur)?;
+        self.socket.set_recv_timeout(dur);
+        Ok(())
+    }
+
+    pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        Ok(self.socket.recv_timeout())
+    }
+
+    /// Sets how long a write waits before returning a `WouldBlock` error, or
+    /// `None` to wait forever. A zero duration is invalid.
+    pub fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result {
+        check_timeout(dur)?;
+        self.socket.set_send_timeout(dur);
+        Ok(())
+    }
+
+    pub fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        Ok(self.socket.send_timeout())
+    }
+
+    /// Sets `TCP_NODELAY`, which sends small writes at once instead of
+    /// coalescing them.
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result {
+        self.socket.set_nodelay(nodelay)
+    }
+
+    pub fn nodelay(&self) -> io::Result<bool> {
+        Ok(self.socket.nodelay())
+    }
+
+    /// Sends keep-alive probes after the connection has been idle for
+    /// `interval`, or never if `None`.
+    pub fn set_keepalive(&self, interval: Option<Duration>) -> io::Result {
+        self.socket.set_keep_alive(interval)
+    }
+
+    pub fn keepalive(&self) -> io::Result<Option<Duration>> {
+        Ok(self.socket.keep_alive())
+    }
+
+    pub fn recv_buffer_size(&self) -> io::Result<usize> {
+        Ok(self.socket.recv_buffer_size())
+    }
+
+    pub fn send_buffer_size(&self) -> io::Result<usize> {
+        Ok(self.socket.send_buffer_size())
+    }
 }
 
 impl Read for TcpStream {
@@ -47,11 +113,13 @@ impl Write for TcpStream {
 }
 
 impl TcpListener {
-    pub fn bind(addr: SocketAddr) -> io::Result<Self> {
-        let mut socket = TcpSocket::new();
-        socket.bind(addr)?;
-        socket.listen()?;
-        Ok(Self { socket })
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        each_addr(addr, |addr| {
+            let mut socket = TcpSocket::new();
+            socket.bind(addr)?;
+            socket.listen()?;
+            Ok(Self { socket })
+        })
     }
 
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
@@ -63,4 +131,48 @@ impl TcpListener {
         let addr = socket.peer_addr()?;
         Ok((TcpStream { socket }, addr))
     }
+
+    /// Moves the listener into or out of nonblocking mode, in which `accept`
+    /// returns a `WouldBlock` error if no connection is waiting. The accepted
+    /// streams are always blocking.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result {
+        self.socket.set_nonblocking(nonblocking);
+        Ok(())
+    }
+
+    /// Sets how long `accept` waits before returning a `WouldBlock` error, or
+    /// `None` to wait forever. A zero duration is invalid.
+    pub fn set_accept_timeout(&self, dur: Option<Duration>) -> io::Result {
+        check_timeout(dur)?;
+        self.socket.set_recv_timeout(dur);
+        Ok(())
+    }
+
+    /// Sets `TCP_NODELAY` on the streams accepted from now on.
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result {
+        self.socket.set_nodelay(nodelay)
+    }
+
+    /// Sets the keep-alive interval of the streams accepted from now on, see
+    /// [`TcpStream::set_keepalive`].
+    pub fn set_keepalive(&self, interval: Option<Duration>) -> io::Result {
+        self.socket.set_keep_alive(interval)
+    }
+
+    /// Sets the receive buffer size of the streams accepted from now on.
+    pub fn set_recv_buffer_size(&self, size: usize) -> io::Result {
+        self.socket.set_recv_buffer_size(size)
+    }
+
+    /// Sets the send buffer size of the streams accepted from now on.
+    pub fn set_send_buffer_size(&self, size: usize) -> io::Result {
+        self.socket.set_send_buffer_size(size)
+    }
+}
+
+fn check_timeout(dur: Option<Duration>) -> io::Result {
+    match dur {
+        Some(dur) if dur.is_zero() => ax_err!(InvalidInput, "cannot set a 0 duration timeout"),
+        _ => Ok(()),
+    }
 }
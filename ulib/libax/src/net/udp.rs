@@ -0,0 +1,62 @@
+use crate::io;
+
+use axerrno::ax_err;
+use axnet::SocketAddr;
+
+use super::socket_addr::{each_addr, ToSocketAddrs};
+
+pub struct UdpSocket {
+    socket: axnet::UdpSocket,
+}
+
+impl UdpSocket {
+    /// Creates a UDP socket bound to `addr`. Use port 0 to get an ephemeral
+    /// port.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        each_addr(addr, |addr| {
+            let socket = axnet::UdpSocket::new();
+            socket.bind(addr)?;
+            Ok(Self { socket })
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.peer_addr()
+    }
+
+    /// Sends a datagram to the first address `addr` resolves to.
+    pub fn send_to<A: ToSocketAddrs>(&self, buf: &[u8], addr: A) -> io::Result<usize> {
+        match addr.to_socket_addrs()?.next() {
+            Some(addr) => self.socket.send_to(buf, addr),
+            None => ax_err!(InvalidInput, "no addresses to send data to"),
+        }
+    }
+
+    pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.socket.recv_from(buf)
+    }
+
+    /// Sets the peer used by `send` and `recv`.
+    pub fn connect<A: ToSocketAddrs>(&self, addr: A) -> io::Result {
+        each_addr(addr, |addr| self.socket.connect(addr))
+    }
+
+    pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        self.socket.send(buf)
+    }
+
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.socket.recv(buf)
+    }
+
+    /// Moves the socket into or out of nonblocking mode, in which `send` and
+    /// `recv` return a `WouldBlock` error instead of waiting.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result {
+        self.socket.set_nonblocking(nonblocking);
+        Ok(())
+    }
+}